@@ -1,11 +1,51 @@
+use crate::models::audit::AuditOutcome;
+use crate::models::journal::{JournalChange, JournalEntry};
+use crate::models::privacy::BackupCopy;
+use crate::models::revision::{FieldChange, RevisionOperation, field_changes};
 use crate::models::user::User;
-use crate::repositories::user_repository::UserRepositoryImpl;
-use crate::services::user_service::UserService;
+use crate::repositories::audit_repository::{AuditRepositoryImpl, hmac_key_from_env};
+use crate::repositories::backup_repository::{BackupRepository, BackupRepositoryImpl};
+use crate::repositories::encryption::{DataFileCipher, KeySource, rewrite_sidecar};
+use crate::repositories::event_sourced_repository::EventSourcedUserRepository;
+use crate::repositories::field_encryption::FieldCipher;
+use crate::repositories::history_repository::HistoryRepositoryImpl;
+use crate::repositories::journal_repository::JournalRepositoryImpl;
+use crate::repositories::receipt_repository::{ReceiptRepository, ReceiptRepositoryImpl};
+use crate::repositories::user_repository::{
+    StorageBackend, UserRepository, UserRepositoryImpl, data_file_path, erase_quarantined,
+    find_quarantined, sibling_path,
+};
+use crate::services::anonymizer::Anonymizer;
+use crate::services::duplicate_detector::DEFAULT_DUPLICATE_THRESHOLD;
+use crate::services::masking::{MaskStrategy, Masker};
+use crate::services::merge::{MergeChoice, MergeField, MergeOptions, MergeStrategy};
+use crate::services::seeder::Seeder;
+use crate::services::user_service::{AuditQuery, UserError, UserService, UserServiceConfig};
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
 
 /// コマンドライン操作を処理するコマンドハンドラ
 pub struct UserCommand {
     /// ユーザー操作のビジネスロジックを実装するサービス
-    service: UserService<UserRepositoryImpl>,
+    service: UserService<Box<dyn UserRepository>>,
+    /// ユーザーデータのファイルパス（補助ファイルのパスの基準）
+    data_file: String,
+    /// ユーザーデータの保存方式
+    backend: StorageBackend,
+    /// ユーザーデータのファイルの暗号化に使用する鍵の入手元
+    key_source: Option<KeySource>,
+    /// 変更履歴・監査ログ・操作ジャーナル・隔離ファイルのパス（暗号化の切り替えで書き直す）
+    sidecar_files: Vec<String>,
+    /// 表示するメールアドレス・電話番号の伏せ方
+    mask_strategy: MaskStrategy,
+    /// メールアドレス・電話番号を伏せずに表示するかどうか
+    reveal: bool,
+    /// 消去請求の受領証の署名に使用する鍵
+    signing_key: Option<Vec<u8>>,
+    /// データの匿名化に使用する仮名の生成器
+    anonymizer: Option<Anonymizer>,
 }
 
 impl Default for UserCommand {
@@ -17,15 +57,144 @@ impl Default for UserCommand {
 impl UserCommand {
     /// 新しいUserCommandインスタンスを作成します。
     ///
+    /// 保存方式は環境変数`USER_STORAGE_BACKEND`に従います（不正な値の場合はJSONファイル）。
+    /// 変更履歴と監査ログの保存先は、それぞれ環境変数`USER_HISTORY_FILE`、
+    /// `USER_AUDIT_FILE`で変更できます。
+    /// フィールドの暗号化の鍵は環境変数`USER_FIELD_KEY_FILE`から読み込みます
+    /// （読み込めない場合はフィールドを暗号化しません）。
+    ///
     /// # 戻り値
     /// * `Self` - 新しいUserCommandインスタンス
     ///
     /// # Errors
     /// このメソッドはエラーを返しません。
     pub fn new() -> Self {
-        let repository = UserRepositoryImpl::new();
-        let service = UserService::new(repository);
-        Self { service }
+        let data_file = data_file_path();
+        let backend = StorageBackend::from_env().unwrap_or_default();
+        let repository = backend.open(&data_file, DataFileCipher::from_env());
+        let history = HistoryRepositoryImpl::new();
+        let audit = AuditRepositoryImpl::new();
+        let journal = JournalRepositoryImpl::new();
+        let mut sidecar_files = vec![
+            history.file_path().to_string(),
+            audit.file_path().to_string(),
+            journal.file_path().to_string(),
+            sibling_path(&data_file, "quarantine.jsonl"),
+        ];
+        sidecar_files.extend(backend.storage_files(&data_file));
+        let service = UserService::with_config(repository, UserServiceConfig::from_env())
+            .with_history(history)
+            .with_audit(audit)
+            .with_journal(journal);
+        let service = match FieldCipher::from_env() {
+            Ok(Some(cipher)) => service.with_field_encryption(cipher),
+            _ => service,
+        };
+        Self {
+            service,
+            data_file,
+            backend,
+            key_source: KeySource::from_env(),
+            sidecar_files,
+            mask_strategy: MaskStrategy::from_env().unwrap_or_default(),
+            reveal: false,
+            signing_key: hmac_key_from_env(),
+            anonymizer: Anonymizer::from_env(),
+        }
+    }
+
+    /// ユーザーデータのファイルパスを指定してUserCommandインスタンスを作成します。
+    ///
+    /// 変更履歴などの補助ファイルは、ユーザーデータのファイルと同じ場所に作成されます。
+    ///
+    /// # 引数
+    /// * `data_file` - ユーザーデータを保存するJSONファイルのパス
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいUserCommandインスタンス
+    pub fn with_data_file(data_file: &str) -> Self {
+        Self::with_backend(data_file, StorageBackend::Json)
+    }
+
+    /// ユーザーデータのファイルパスと保存方式を指定してUserCommandインスタンスを作成します。
+    ///
+    /// # 引数
+    /// * `data_file` - ユーザーデータのファイルパス
+    /// * `backend` - ユーザーデータの保存方式
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいUserCommandインスタンス
+    pub fn with_backend(data_file: &str, backend: StorageBackend) -> Self {
+        let mut history =
+            HistoryRepositoryImpl::with_file_path(sibling_path(data_file, "history.jsonl"));
+        let mut audit = AuditRepositoryImpl::with_file_path(sibling_path(data_file, "audit.jsonl"))
+            .with_hmac_key(hmac_key_from_env());
+        let mut journal =
+            JournalRepositoryImpl::with_file_path(sibling_path(data_file, "journal.json"));
+        let mut sidecar_files = vec![
+            history.file_path().to_string(),
+            audit.file_path().to_string(),
+            journal.file_path().to_string(),
+            sibling_path(data_file, "quarantine.jsonl"),
+        ];
+        sidecar_files.extend(backend.storage_files(data_file));
+        let key_source = KeySource::from_env();
+        let cipher = key_source.clone().map(DataFileCipher::new);
+        if let Some(cipher) = &cipher {
+            history = history.with_cipher(cipher.clone());
+            audit = audit.with_cipher(cipher.clone());
+            journal = journal.with_cipher(cipher.clone());
+        }
+        let repository = backend.open(data_file, cipher);
+        let service = UserService::with_config(repository, UserServiceConfig::from_env())
+            .with_history(history)
+            .with_audit(audit)
+            .with_journal(journal);
+        let service = match FieldCipher::from_env() {
+            Ok(Some(cipher)) => service.with_field_encryption(cipher),
+            _ => service,
+        };
+        Self {
+            service,
+            data_file: data_file.to_string(),
+            backend,
+            key_source,
+            sidecar_files,
+            mask_strategy: MaskStrategy::from_env().unwrap_or_default(),
+            reveal: false,
+            signing_key: hmac_key_from_env(),
+            anonymizer: Anonymizer::from_env(),
+        }
+    }
+
+    /// 変更履歴と監査ログに記録する操作者を設定します。
+    ///
+    /// 環境変数`USER_ACTOR`やOSのユーザー名よりも優先されます。
+    ///
+    /// # 引数
+    /// * `actor` - 操作者の名前
+    ///
+    /// # 戻り値
+    /// * `Self` - 操作者を設定したUserCommandインスタンス
+    pub fn with_actor(self, actor: &str) -> Self {
+        Self {
+            service: self.service.with_actor(actor),
+            ..self
+        }
+    }
+
+    /// メールアドレス・電話番号を伏せずに表示するよう設定します。
+    ///
+    /// 設定しない場合、一覧を表示するコマンドと、標準出力が端末でない場合の出力では、
+    /// メールアドレス・電話番号を環境変数`USER_MASK_STRATEGY`の伏せ方で伏せ字にします。
+    ///
+    /// # 戻り値
+    /// * `Self` - 値を伏せずに表示するUserCommandインスタンス
+    pub fn with_reveal(self) -> Self {
+        Self {
+            reveal: true,
+            ..self
+        }
     }
 
     /// 新しいユーザーを作成します。
@@ -37,18 +206,21 @@ impl UserCommand {
     ///   * `phone` - 電話番号
     ///   * `age` - 年齢
     ///
+    ///   `--dry-run`を指定した場合は、書き込みを行わずに作成される内容を表示します。
+    ///
     /// # 戻り値
     /// * `Ok(())` - ユーザーの作成に成功した場合
     ///
     /// # Errors
     /// 以下の場合にエラーを返します：
-    /// * 引数の数が不正な場合（"Usage: create \<email\> \<username\> \<phone\> \<age\>"）
+    /// * 引数の数が不正な場合（"Usage: create [--dry-run] \<email\> \<username\> \<phone\> \<age\>"）
     /// * 年齢の形式が不正な場合（"Invalid age format"）
     /// * メールアドレス、ユーザー名、電話番号、年齢のバリデーションに失敗した場合
     /// * ユーザーの保存に失敗した場合
     pub fn create(&self, args: &[String]) -> Result<(), String> {
+        let (dry_run, args) = take_flag(args, "--dry-run");
         if args.len() != 4 {
-            return Err("Usage: create <email> <username> <phone> <age>".to_string());
+            return Err("Usage: create [--dry-run] <email> <username> <phone> <age>".to_string());
         }
 
         let email = &args[0];
@@ -56,13 +228,16 @@ impl UserCommand {
         let phone = &args[2];
         let age = args[3].parse::<u32>().map_err(|_| "Invalid age format")?;
 
-        match self.service.create_user(
-            email.to_string(),
-            username.to_string(),
-            phone.to_string(),
-            age,
-        ) {
-            Ok(user) => {
+        match self.run(dry_run, |service| {
+            service.create_user(
+                email.to_string(),
+                username.to_string(),
+                phone.to_string(),
+                age,
+            )
+        }) {
+            Ok(None) => Ok(()),
+            Ok(Some(user)) => {
                 println!("User created successfully:");
                 self.print_user(&user);
                 Ok(())
@@ -80,33 +255,48 @@ impl UserCommand {
     ///   * `phone` - 新しい電話番号
     ///   * `age` - 新しい年齢
     ///
+    ///   `--dry-run`を指定した場合は、書き込みを行わずに変更される内容を表示します。
+    ///   `--expected-version <version>`を指定した場合は、レコードのバージョンが
+    ///   異なれば（他の操作者が変更していれば）更新せずに失敗します。
+    ///
     /// # 戻り値
     /// * `Ok(())` - ユーザーの更新に成功した場合
     ///
     /// # Errors
     /// 以下の場合にエラーを返します：
-    /// * 引数の数が不正な場合（"Usage: update \<email\> \<username\> \<phone\> \<age\>"）
-    /// * 年齢の形式が不正な場合（"Invalid age format"）
+    /// * 引数の数が不正な場合（"Usage: update [--dry-run] [--expected-version \<version\>] ..."）
+    /// * 年齢またはバージョンの形式が不正な場合（"Invalid age format"、"Invalid version"）
     /// * 指定されたメールアドレスのユーザーが存在しない場合
+    /// * レコードのバージョンが指定されたものと異なる場合（"Failed to update user: Conflict(...)"）
     /// * メールアドレス、ユーザー名、電話番号、年齢のバリデーションに失敗した場合
     /// * ユーザーの保存に失敗した場合
     pub fn update(&self, args: &[String]) -> Result<(), String> {
+        let (dry_run, args) = take_flag(args, "--dry-run");
+        let (expected_version, args) = take_option(&args, "--expected-version")?;
         if args.len() != 4 {
-            return Err("Usage: update <email> <username> <phone> <age>".to_string());
+            return Err("Usage: update [--dry-run] [--expected-version <version>] \
+                        <email> <username> <phone> <age>"
+                .to_string());
         }
+        let expected_version = parse_version(expected_version)?;
 
         let email = &args[0];
         let username = &args[1];
         let phone = &args[2];
         let age = args[3].parse::<u32>().map_err(|_| "Invalid age format")?;
 
-        match self.service.update_user(
-            email.to_string(),
-            username.to_string(),
-            phone.to_string(),
-            age,
-        ) {
-            Ok(user) => {
+        match self.run(dry_run, |service| {
+            let (email, username, phone) =
+                (email.to_string(), username.to_string(), phone.to_string());
+            match expected_version {
+                Some(version) => {
+                    service.update_user_if_version(email, username, phone, age, version)
+                }
+                None => service.update_user(email, username, phone, age),
+            }
+        }) {
+            Ok(None) => Ok(()),
+            Ok(Some(user)) => {
                 println!("User updated successfully:");
                 self.print_user(&user);
                 Ok(())
@@ -117,20 +307,59 @@ impl UserCommand {
 
     /// 全てのユーザーの一覧を表示します。
     ///
+    /// 正規化後のメールアドレスが重複しているレコードがある場合は、
+    /// 一覧の後に警告を表示します。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス。以下のオプションを受け付けます：
+    ///   * `--redacted` - 暗号化されたフィールドを、鍵を使わずに伏せ字で表示する
+    ///
     /// # 戻り値
     /// * `Ok(())` - ユーザー一覧の表示に成功した場合
     ///
     /// # Errors
     /// 以下の場合にエラーを返します：
+    /// * 引数が不正な場合（"Usage: list [--redacted]"）
     /// * ユーザー一覧の取得に失敗した場合（"Failed to list users: ..."）
-    pub fn list(&self) -> Result<(), String> {
-        match self.service.list_users() {
+    pub fn list(&self, args: &[String]) -> Result<(), String> {
+        let (redacted, rest) = take_flag(args, "--redacted");
+        if !rest.is_empty() {
+            return Err("Usage: list [--redacted]".to_string());
+        }
+
+        let masker = self.masker(true);
+        let view = self.service.redacted();
+        let (users, duplicates) = if redacted {
+            (view.list_users(), view.find_email_duplicates())
+        } else {
+            (
+                self.service.list_users(),
+                self.service.find_email_duplicates(),
+            )
+        };
+        match users {
             Ok(users) => {
                 println!("User list:");
                 println!("Email\t\tUsername");
                 println!("------------------------");
                 for user in users {
-                    println!("{}\t{}", user.email, user.username);
+                    println!("{}\t{}", masker.email(&user.email), user.username);
+                }
+            }
+            Err(e) => return Err(format!("Failed to list users: {:?}", e)),
+        }
+
+        match duplicates {
+            Ok(duplicates) => {
+                for (email, users) in duplicates {
+                    let emails: Vec<String> =
+                        users.iter().map(|u| masker.email(&u.email)).collect();
+                    eprintln!(
+                        "Warning: {} records normalize to {}: {}",
+                        users.len(),
+                        masker.email(&email),
+                        emails.join(", ")
+                    );
                 }
                 Ok(())
             }
@@ -141,24 +370,36 @@ impl UserCommand {
     /// 指定されたメールアドレスのユーザー情報を表示します。
     ///
     /// # 引数
-    /// * `args` - コマンドライン引数のスライス。1つの要素が必要です：
+    /// * `args` - コマンドライン引数のスライス。1つの要素と任意のオプションを受け付けます：
     ///   * `email` - 検索するユーザーのメールアドレス
+    ///   * `--at <timestamp>` - 指定した日時の状態を表示する（RFC 3339形式、
+    ///     または`YYYY-MM-DD`形式でその日の終わり（UTC）を指定）
+    ///   * `--redacted` - 暗号化されたフィールドを、鍵を使わずに伏せ字で表示する
+    ///     （`--at`とは同時に指定できない）
     ///
     /// # 戻り値
     /// * `Ok(())` - ユーザー情報の表示に成功した場合
     ///
     /// # Errors
     /// 以下の場合にエラーを返します：
-    /// * 引数の数が不正な場合（"Usage: get \<email\>"）
+    /// * 引数の数が不正な場合（"Usage: get \<email\> [--at \<timestamp\> | --redacted]"）
+    /// * 日時の形式が不正な場合
     /// * 指定されたメールアドレスのユーザーが存在しない場合
     /// * ユーザー情報の取得に失敗した場合（"Failed to get user: ..."）
     pub fn get(&self, args: &[String]) -> Result<(), String> {
-        if args.len() != 1 {
-            return Err("Usage: get <email>".to_string());
+        let (redacted, args) = take_flag(args, "--redacted");
+        let (at, rest) = take_option(&args, "--at")?;
+        if rest.len() != 1 || (redacted && at.is_some()) {
+            return Err("Usage: get <email> [--at <timestamp> | --redacted]".to_string());
         }
 
-        let email = &args[0];
-        match self.service.get_user(email) {
+        let email = &rest[0];
+        let result = match at {
+            Some(at) => self.service.get_user_at(email, parse_timestamp(&at, true)?),
+            None if redacted => self.service.redacted().get_user(email),
+            None => self.service.get_user(email),
+        };
+        match result {
             Ok(user) => {
                 self.print_user(&user);
                 Ok(())
@@ -167,130 +408,2599 @@ impl UserCommand {
         }
     }
 
-    /// 指定されたメールアドレスのユーザーを削除します。
+    /// ユーザー名・メールアドレスからユーザーを検索し、一致度の高い順に表示します。
+    ///
+    /// 全角・半角、大文字・小文字、カタカナ・ひらがなの違いは無視されます。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス。1つの要素が必要です：
+    ///   * `term` - 検索語
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 検索結果の表示に成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数の数が不正な場合、または検索語が空の場合（"Usage: search \<term\>"）
+    /// * ユーザー情報の取得に失敗した場合（"Failed to search users: ..."）
+    pub fn search(&self, args: &[String]) -> Result<(), String> {
+        if args.len() != 1 || args[0].trim().is_empty() {
+            return Err("Usage: search <term>".to_string());
+        }
+
+        let masker = self.masker(true);
+        match self.service.search_users(&args[0]) {
+            Ok(results) => {
+                println!("Search results:");
+                println!("Score\tEmail\t\tUsername");
+                println!("----------------------------------------");
+                for result in results {
+                    println!(
+                        "{}\t{}\t{}",
+                        result.score,
+                        masker.email(&result.user.email),
+                        result.user.username
+                    );
+                }
+                Ok(())
+            }
+            Err(e) => Err(format!("Failed to search users: {:?}", e)),
+        }
+    }
+
+    /// 同一人物が重複して登録されている可能性があるユーザーのまとまりを表示します。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス。以下のオプションを指定できます：
+    ///   * `--threshold <score>` - 重複候補とみなすスコアの下限（0.0〜1.0、既定値0.6）
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 重複候補の表示に成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数が不正な場合（"Usage: duplicates \[--threshold \<score\>\]"）
+    /// * ユーザー情報の取得に失敗した場合（"Failed to find duplicates: ..."）
+    pub fn duplicates(&self, args: &[String]) -> Result<(), String> {
+        const USAGE: &str = "Usage: duplicates [--threshold <score>]";
+        let (threshold, rest) = take_option(args, "--threshold")?;
+        if !rest.is_empty() {
+            return Err(USAGE.to_string());
+        }
+        let threshold = match threshold {
+            Some(value) => value
+                .parse::<f64>()
+                .ok()
+                .filter(|t| (0.0..=1.0).contains(t))
+                .ok_or_else(|| USAGE.to_string())?,
+            None => DEFAULT_DUPLICATE_THRESHOLD,
+        };
+
+        let masker = self.masker(true);
+        match self.service.find_probable_duplicates(threshold) {
+            Ok(clusters) if clusters.is_empty() => {
+                println!("No probable duplicates found");
+                Ok(())
+            }
+            Ok(clusters) => {
+                for (index, cluster) in clusters.iter().enumerate() {
+                    println!(
+                        "Cluster {} (max score {:.2}):",
+                        index + 1,
+                        cluster.max_score()
+                    );
+                    for user in &cluster.users {
+                        println!(
+                            "  {}\t{}\t{}",
+                            masker.email(&user.email),
+                            user.username,
+                            masker.phone(&self.service.display_phone(&user.phone))
+                        );
+                    }
+                    for pair in &cluster.pairs {
+                        println!(
+                            "  {:.2}  {} <-> {} (username {:.2}, phone {}, email {:.2})",
+                            pair.score,
+                            masker.email(&pair.first),
+                            masker.email(&pair.second),
+                            pair.username_similarity,
+                            if pair.same_phone { "same" } else { "different" },
+                            pair.email_local_similarity
+                        );
+                    }
+                }
+                Ok(())
+            }
+            Err(e) => Err(format!("Failed to find duplicates: {:?}", e)),
+        }
+    }
+
+    /// 2人のユーザーを1つのレコードに統合します。
+    ///
+    /// 取り込まれる側のユーザーは削除され、そのメールアドレスは統合後のユーザーの
+    /// 別名として記録されます。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス。2つの要素が必要です：
+    ///   * `keep-email` - 残す側のユーザーのメールアドレス
+    ///   * `drop-email` - 取り込まれて削除される側のユーザーのメールアドレス
+    ///
+    ///   以下のオプションで統合戦略（`keep`、`newest`、`ask`）を指定できます：
+    ///   * `--strategy <strategy>` - 全フィールドの既定の戦略（既定値`keep`）
+    ///   * `--username <strategy>` / `--phone <strategy>` / `--age <strategy>` - フィールドごとの戦略
+    ///
+    ///   `--dry-run`を指定した場合は、書き込みを行わずに変更される内容を表示します。
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - ユーザーの統合に成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数の数や戦略の指定が不正な場合
+    /// * どちらかのユーザーが存在しない場合、または同じユーザーを指している場合
+    /// * ユーザーの保存に失敗した場合（"Failed to merge users: ..."）
+    pub fn merge(&self, args: &[String]) -> Result<(), String> {
+        const USAGE: &str = "Usage: merge [--dry-run] <keep-email> <drop-email> \
+                             [--strategy keep|newest|ask] \
+                             [--username <strategy>] [--phone <strategy>] [--age <strategy>]";
+        let (dry_run, args) = take_flag(args, "--dry-run");
+        let (strategy, rest) = take_option(&args, "--strategy")?;
+        let (username, rest) = take_option(&rest, "--username")?;
+        let (phone, rest) = take_option(&rest, "--phone")?;
+        let (age, rest) = take_option(&rest, "--age")?;
+        if rest.len() != 2 {
+            return Err(USAGE.to_string());
+        }
+
+        let parse = |value: Option<String>, default: MergeStrategy| {
+            value.map_or(Ok(default), |v| v.parse::<MergeStrategy>())
+        };
+        let default = parse(strategy, MergeStrategy::default())?;
+        let options = MergeOptions {
+            username: parse(username, default)?,
+            phone: parse(phone, default)?,
+            age: parse(age, default)?,
+        };
+
+        let mut ask = |field: MergeField, keep: &str, drop: &str| {
+            let (keep, drop) = match field {
+                MergeField::Phone => (
+                    self.service.display_phone(keep),
+                    self.service.display_phone(drop),
+                ),
+                _ => (keep.to_string(), drop.to_string()),
+            };
+            prompt_merge_choice(field, &keep, &drop)
+        };
+        match self.run(dry_run, |service| {
+            service.merge_users(&rest[0], &rest[1], &options, &mut ask)
+        }) {
+            Ok(None) => Ok(()),
+            Ok(Some(user)) => {
+                println!("Users merged successfully:");
+                self.print_user(&user);
+                Ok(())
+            }
+            Err(e) => Err(format!("Failed to merge users: {:?}", e)),
+        }
+    }
+
+    /// 指定されたメールアドレスのユーザーをゴミ箱に移動します。
+    ///
+    /// ゴミ箱に移動したユーザーは`restore`で元に戻せます。
     ///
     /// # 引数
     /// * `args` - コマンドライン引数のスライス。1つの要素が必要です：
     ///   * `email` - 削除するユーザーのメールアドレス
     ///
+    ///   `--dry-run`を指定した場合は、書き込みを行わずに変更される内容を表示します。
+    ///   `--expected-version <version>`を指定した場合は、レコードのバージョンが
+    ///   異なれば（他の操作者が変更していれば）削除せずに失敗します。
+    ///
     /// # 戻り値
     /// * `Ok(())` - ユーザーの削除に成功した場合
     ///
     /// # Errors
     /// 以下の場合にエラーを返します：
-    /// * 引数の数が不正な場合（"Usage: delete \<email\>"）
+    /// * 引数の数が不正な場合（"Usage: delete [--dry-run] [--expected-version \<version\>] \<email\>"）
+    /// * バージョンの形式が不正な場合（"Invalid version"）
     /// * 指定されたメールアドレスのユーザーが存在しない場合
+    /// * レコードのバージョンが指定されたものと異なる場合（"Failed to delete user: Conflict(...)"）
     /// * ユーザーの削除に失敗した場合（"Failed to delete user: ..."）
     pub fn delete(&self, args: &[String]) -> Result<(), String> {
+        let (dry_run, args) = take_flag(args, "--dry-run");
+        let (expected_version, args) = take_option(&args, "--expected-version")?;
         if args.len() != 1 {
-            return Err("Usage: delete <email>".to_string());
+            return Err(
+                "Usage: delete [--dry-run] [--expected-version <version>] <email>".to_string(),
+            );
         }
+        let expected_version = parse_version(expected_version)?;
 
         let email = &args[0];
-        match self.service.delete_user(email) {
-            Ok(()) => {
-                println!("User deleted successfully");
+        match self.run(dry_run, |service| match expected_version {
+            Some(version) => service.delete_user_if_version(email, version),
+            None => service.delete_user(email),
+        }) {
+            Ok(None) => Ok(()),
+            Ok(Some(())) => {
+                println!(
+                    "User moved to trash (use `restore {}` to undo)",
+                    self.masker(false).email(email)
+                );
                 Ok(())
             }
             Err(e) => Err(format!("Failed to delete user: {:?}", e)),
         }
     }
 
-    /// ユーザー情報を標準出力に整形して表示します。
+    /// ゴミ箱に関する操作を行います。
     ///
     /// # 引数
-    /// * `user` - 表示するユーザー情報
-    ///   ユーザー情報を標準出力に整形して表示します。
+    /// * `args` - コマンドライン引数のスライス。1つの要素が必要です：
+    ///   * `list` - ゴミ箱にあるユーザーを削除日時とともに表示する
     ///
-    /// # 引数
-    /// * `user` - 表示するユーザー情報
+    /// # 戻り値
+    /// * `Ok(())` - 操作に成功した場合
     ///
-    /// 以下の形式で表示されます：
-    /// ```text
-    /// Email: user@example.com
-    /// Username: username
-    /// Phone: 1234567890
-    /// Age: 25
-    /// ```
-    fn print_user(&self, user: &User) {
-        println!("Email: {}", user.email);
-        println!("Username: {}", user.username);
-        println!("Phone: {}", user.phone);
-        println!("Age: {}", user.age);
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数が不正な場合（"Usage: trash list"）
+    /// * ユーザー情報の取得に失敗した場合（"Failed to list trash: ..."）
+    pub fn trash(&self, args: &[String]) -> Result<(), String> {
+        if args.len() != 1 || args[0] != "list" {
+            return Err("Usage: trash list".to_string());
+        }
+
+        let masker = self.masker(true);
+        match self.service.list_trashed_users() {
+            Ok(users) => {
+                println!("Trash:");
+                println!("Deleted at\t\t\tEmail\t\tUsername");
+                println!("----------------------------------------");
+                for user in users {
+                    let deleted_at = user
+                        .deleted_at
+                        .map(|at| at.to_rfc3339())
+                        .unwrap_or_default();
+                    println!(
+                        "{}\t{}\t{}",
+                        deleted_at,
+                        masker.email(&user.email),
+                        user.username
+                    );
+                }
+                Ok(())
+            }
+            Err(e) => Err(format!("Failed to list trash: {:?}", e)),
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::env;
-    use tempfile::NamedTempFile;
+    /// ゴミ箱にあるユーザーを元に戻します。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス。1つの要素が必要です：
+    ///   * `email` - 元に戻すユーザーのメールアドレス
+    ///
+    ///   `--dry-run`を指定した場合は、書き込みを行わずに変更される内容を表示します。
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - ユーザーを元に戻せた場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数の数が不正な場合（"Usage: restore [--dry-run] \<email\>"）
+    /// * 指定されたメールアドレスのユーザーがゴミ箱にない場合
+    /// * ユーザーの保存に失敗した場合（"Failed to restore user: ..."）
+    pub fn restore(&self, args: &[String]) -> Result<(), String> {
+        let (dry_run, args) = take_flag(args, "--dry-run");
+        if args.len() != 1 {
+            return Err("Usage: restore [--dry-run] <email>".to_string());
+        }
 
-    fn setup() -> UserCommand {
-        let temp_file = NamedTempFile::new().unwrap();
-        unsafe {
-            env::set_var("USER_DATA_FILE", temp_file.path().to_str().unwrap());
+        match self.run(dry_run, |service| service.restore_user(&args[0])) {
+            Ok(None) => Ok(()),
+            Ok(Some(user)) => {
+                println!("User restored successfully:");
+                self.print_user(&user);
+                Ok(())
+            }
+            Err(e) => Err(format!("Failed to restore user: {:?}", e)),
         }
-        UserCommand::new()
     }
 
-    #[test]
-    fn test_create_user_command() {
-        let command = setup();
-        let args = vec![
-            "test@example.com".to_string(),
-            "testuser".to_string(),
-            "1234567890".to_string(),
-            "25".to_string(),
-        ];
+    /// ゴミ箱にあるユーザーのうち、削除から指定期間以上経過したものを完全に削除します。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス。以下のオプションが必要です：
+    ///   * `--older-than <duration>` - 経過期間（例: `30d`、`12h`、`45m`）
+    ///
+    ///   `--dry-run`を指定した場合は、書き込みを行わずに削除される内容を表示します。
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 完全な削除に成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数が不正な場合（"Usage: purge [--dry-run] --older-than \<duration\>"）
+    /// * ユーザーの削除に失敗した場合（"Failed to purge trash: ..."）
+    pub fn purge(&self, args: &[String]) -> Result<(), String> {
+        const USAGE: &str = "Usage: purge [--dry-run] --older-than <duration>";
+        let (dry_run, args) = take_flag(args, "--dry-run");
+        let (older_than, rest) = take_option(&args, "--older-than")?;
+        let (Some(older_than), true) = (older_than, rest.is_empty()) else {
+            return Err(USAGE.to_string());
+        };
+        let older_than = parse_duration(&older_than)?;
 
-        let result = command.create(&args);
-        assert!(result.is_ok());
+        match self.run(dry_run, |service| service.purge_trash(older_than)) {
+            Ok(None) => Ok(()),
+            Ok(Some(users)) => {
+                let masker = self.masker(false);
+                for user in &users {
+                    println!("Purged {}", masker.email(&user.email));
+                }
+                println!("{} user(s) purged", users.len());
+                Ok(())
+            }
+            Err(e) => Err(format!("Failed to purge trash: {:?}", e)),
+        }
     }
 
-    #[test]
-    fn test_create_user_invalid_args() {
-        let command = setup();
-        let args = vec!["test@example.com".to_string()];
+    /// ユーザーの変更履歴を、リビジョンごとの変更点とともに表示します。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス。1つの要素が必要です：
+    ///   * `email` - ユーザーのメールアドレス
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 変更履歴の表示に成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数の数が不正な場合（"Usage: history \<email\>"）
+    /// * 変更履歴が存在しない場合
+    /// * 変更履歴の取得に失敗した場合（"Failed to get history: ..."）
+    pub fn history(&self, args: &[String]) -> Result<(), String> {
+        if args.len() != 1 {
+            return Err("Usage: history <email>".to_string());
+        }
 
-        let result = command.create(&args);
-        assert!(result.is_err());
+        let masker = self.masker(false);
+        match self.service.user_history(&args[0]) {
+            Ok(revisions) => {
+                let mut previous = None;
+                for revision in &revisions {
+                    println!(
+                        "#{} {} {} by {}",
+                        revision.revision,
+                        revision.timestamp.to_rfc3339(),
+                        revision.operation,
+                        revision.actor
+                    );
+                    for change in revision.changes_from(previous) {
+                        print_field_change(&change, &masker);
+                    }
+                    previous = Some(revision);
+                }
+                Ok(())
+            }
+            Err(e) => Err(format!("Failed to get history: {:?}", e)),
+        }
     }
 
-    #[test]
-    fn test_update_user_command() {
-        let command = setup();
-        let create_args = vec![
-            "test@example.com".to_string(),
-            "testuser".to_string(),
-            "1234567890".to_string(),
-            "25".to_string(),
-        ];
-        command.create(&create_args).unwrap();
+    /// ユーザー名・電話番号・年齢を過去のリビジョンの値に戻します。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス。2つの要素が必要です：
+    ///   * `email` - ユーザーのメールアドレス
+    ///   * `revision` - 戻す先のリビジョン番号（`history`で確認できます）
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 巻き戻しに成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数の数が不正な場合（"Usage: revert \<email\> \<revision\>"）
+    /// * リビジョン番号の形式が不正な場合（"Invalid revision number"）
+    /// * ユーザーまたはリビジョンが存在しない場合
+    /// * ユーザーの保存に失敗した場合（"Failed to revert user: ..."）
+    pub fn revert(&self, args: &[String]) -> Result<(), String> {
+        if args.len() != 2 {
+            return Err("Usage: revert <email> <revision>".to_string());
+        }
 
-        let update_args = vec![
-            "test@example.com".to_string(),
-            "newuser".to_string(),
-            "0987654321".to_string(),
-            "30".to_string(),
-        ];
-        let result = command.update(&update_args);
-        assert!(result.is_ok());
+        let revision = args[1]
+            .trim_start_matches('#')
+            .parse::<u64>()
+            .map_err(|_| "Invalid revision number")?;
+        match self.service.revert_user(&args[0], revision) {
+            Ok(user) => {
+                println!("User reverted to revision {}:", revision);
+                self.print_user(&user);
+                Ok(())
+            }
+            Err(e) => Err(format!("Failed to revert user: {:?}", e)),
+        }
     }
 
-    #[test]
-    fn test_delete_user_command() {
-        let command = setup();
-        let create_args = vec![
-            "test@example.com".to_string(),
-            "testuser".to_string(),
-            "1234567890".to_string(),
-            "25".to_string(),
-        ];
-        command.create(&create_args).unwrap();
+    /// 直前の操作を新しいものから順に取り消します。
+    ///
+    /// 操作の後に対象のレコードが変更されている場合は、その操作の取り消しを拒否します。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス。以下の要素を受け付けます：
+    ///   * `N` - 取り消す操作の数（省略時は1）
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 取り消しに成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数が不正な場合（"Usage: undo [N]"）
+    /// * 取り消せる操作がない場合、または対象のレコードが変更されている場合
+    ///   （"Failed to undo: ..."）
+    pub fn undo(&self, args: &[String]) -> Result<(), String> {
+        let count = parse_count(args, "Usage: undo [N]")?;
+        let masker = self.masker(false);
+        match self.service.undo(count) {
+            Ok(entries) => {
+                for entry in &entries {
+                    println!("Undone: {}", describe_journal_entry(entry, &masker));
+                }
+                Ok(())
+            }
+            Err(e) => Err(format!("Failed to undo: {:?}", e)),
+        }
+    }
 
-        let delete_args = vec!["test@example.com".to_string()];
-        let result = command.delete(&delete_args);
-        assert!(result.is_ok());
+    /// 取り消した操作を、最後に取り消したものから順にやり直します。
+    ///
+    /// 取り消しの後に対象のレコードが変更されている場合は、その操作のやり直しを拒否します。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス。以下の要素を受け付けます：
+    ///   * `N` - やり直す操作の数（省略時は1）
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - やり直しに成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数が不正な場合（"Usage: redo [N]"）
+    /// * やり直せる操作がない場合、または対象のレコードが変更されている場合
+    ///   （"Failed to redo: ..."）
+    pub fn redo(&self, args: &[String]) -> Result<(), String> {
+        let count = parse_count(args, "Usage: redo [N]")?;
+        let masker = self.masker(false);
+        match self.service.redo(count) {
+            Ok(entries) => {
+                for entry in &entries {
+                    println!("Redone: {}", describe_journal_entry(entry, &masker));
+                }
+                Ok(())
+            }
+            Err(e) => Err(format!("Failed to redo: {:?}", e)),
+        }
+    }
+
+    /// 監査ログから条件に一致する記録を表示します。
+    ///
+    /// 最初の引数が`verify`の場合は、代わりに監査ログの改ざんを検証します。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス。`verify`、または以下のオプションを受け付けます：
+    ///   * `--user <email>` - 対象ユーザーのメールアドレス
+    ///   * `--actor <name>` - 操作者の名前
+    ///   * `--since <timestamp>` - この日時以降の記録に限定する（日付のみの場合はその日の始まり）
+    ///   * `--until <timestamp>` - この日時以前の記録に限定する（日付のみの場合はその日の終わり）
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 記録の表示に成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数が不正な場合（"Usage: audit [--user \<email\>] ..."）
+    /// * 日時の形式が不正な場合
+    /// * 監査ログの取得に失敗した場合（"Failed to read audit log: ..."）
+    /// * 監査ログのハッシュチェーンが途切れている場合（"Audit log is broken at line ..."）
+    pub fn audit(&self, args: &[String]) -> Result<(), String> {
+        if args.first().is_some_and(|arg| arg == "verify") {
+            if args.len() != 1 {
+                return Err("Usage: audit verify".to_string());
+            }
+            return self.verify_audit();
+        }
+
+        const USAGE: &str = "Usage: audit [--user <email>] [--actor <name>] \
+                             [--since <timestamp>] [--until <timestamp>]";
+        let (email, rest) = take_option(args, "--user")?;
+        let (actor, rest) = take_option(&rest, "--actor")?;
+        let (since, rest) = take_option(&rest, "--since")?;
+        let (until, rest) = take_option(&rest, "--until")?;
+        if !rest.is_empty() {
+            return Err(USAGE.to_string());
+        }
+        let query = AuditQuery {
+            email,
+            actor,
+            since: since.map(|at| parse_timestamp(&at, false)).transpose()?,
+            until: until.map(|at| parse_timestamp(&at, true)).transpose()?,
+        };
+
+        let masker = self.masker(true);
+        match self.service.audit_log(&query) {
+            Ok(entries) => {
+                for entry in &entries {
+                    let email = entry.email.as_deref().unwrap_or("");
+                    let outcome = match &entry.outcome {
+                        AuditOutcome::Success => match &entry.details {
+                            Some(details) => format!("ok ({})", details),
+                            None => "ok".to_string(),
+                        },
+                        AuditOutcome::Failure(error) => {
+                            format!("failed: {}", masker.text(error, email))
+                        }
+                    };
+                    println!(
+                        "{}\t{}\t{}\t{}\t{}",
+                        entry.timestamp.to_rfc3339(),
+                        entry.actor,
+                        entry.operation,
+                        entry
+                            .email
+                            .as_ref()
+                            .map_or("-".to_string(), |email| masker.email(email)),
+                        outcome
+                    );
+                }
+                println!("{} entry(ies)", entries.len());
+                Ok(())
+            }
+            Err(e) => Err(format!("Failed to read audit log: {:?}", e)),
+        }
+    }
+
+    /// 監査ログのハッシュチェーンとHMACを検証し、結果を表示します。
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 改ざんが見つからなかった場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 監査ログのハッシュチェーンが途切れている場合（"Audit log is broken at line ..."）
+    /// * 監査ログの取得に失敗した場合（"Failed to verify audit log: ..."）
+    fn verify_audit(&self) -> Result<(), String> {
+        let verification = self
+            .service
+            .verify_audit_log()
+            .map_err(|e| format!("Failed to verify audit log: {:?}", e))?;
+        if let Some(broken) = verification.broken {
+            return Err(format!(
+                "Audit log is broken at line {}: {}",
+                broken.line, broken.reason
+            ));
+        }
+
+        println!("Audit log OK: {} entry(ies) verified", verification.entries);
+        if verification.unchained_entries > 0 {
+            println!(
+                "Note: the first {} entry(ies) predate hash chaining and cannot be verified",
+                verification.unchained_entries
+            );
+        }
+        if let Some(last_hash) = verification.last_hash {
+            println!("Last hash: {}", last_hash);
+        }
+        Ok(())
+    }
+
+    /// イベントログの全てのイベントを再生して、ユーザーデータの射影とスナップショットを作り直します。
+    ///
+    /// 保存方式がイベントソーシング（`USER_STORAGE_BACKEND=event`）の場合に使用します。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス。引数は取りません
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 再構築に成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数が指定された場合（"Usage: rebuild"）
+    /// * イベントログが存在しない、または読み込めない場合（"Failed to rebuild: ..."）
+    pub fn rebuild(&self, args: &[String]) -> Result<(), String> {
+        if !args.is_empty() {
+            return Err("Usage: rebuild".to_string());
+        }
+
+        let repository = EventSourcedUserRepository::with_data_file(&self.data_file);
+        let repository = match self.data_cipher() {
+            Some(cipher) => repository.with_cipher(cipher),
+            None => repository,
+        };
+        match repository.rebuild() {
+            Ok(summary) => {
+                println!(
+                    "Rebuilt projection from {} event(s): {} user(s)",
+                    summary.events, summary.users
+                );
+                Ok(())
+            }
+            Err(e) => Err(format!("Failed to rebuild: {}", e)),
+        }
+    }
+
+    /// ユーザーデータのファイルのバックアップに関する操作を行います。
+    ///
+    /// バックアップは保存方式がJSONファイルの場合に、書き込みのたびに自動で作成されます。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス。以下のいずれかを受け付けます：
+    ///   * `create` - 現在のファイルのバックアップを作成する
+    ///   * `list` - バックアップを古い順に表示する
+    ///   * `restore <id>` - 内容を確認した上で、バックアップをファイルに書き戻す
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 操作に成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数が不正な場合（"Usage: backup create|list|restore \<id\>"）
+    /// * バックアップするファイルがない、またはバックアップが無効な場合（"Nothing to back up ..."）
+    /// * 指定されたバックアップが存在しない、またはユーザーデータとして読み込めない場合
+    /// * ファイルの読み書きに失敗した場合（"Failed to ... backup: ..."）
+    pub fn backup(&self, args: &[String]) -> Result<(), String> {
+        const USAGE: &str = "Usage: backup create|list|restore <id>";
+        let backups = self.backup_repository();
+        match args {
+            [action] if action == "create" => match backups.create() {
+                Ok(Some(backup)) => {
+                    println!("Backup created: {} ({} bytes)", backup.id, backup.size);
+                    Ok(())
+                }
+                Ok(None) => {
+                    Err("Nothing to back up (no data file, or USER_BACKUP_COUNT is 0)".to_string())
+                }
+                Err(e) => Err(format!("Failed to create backup: {}", e)),
+            },
+            [action] if action == "list" => match backups.list() {
+                Ok(list) => {
+                    println!("Backups:");
+                    println!("ID\t\t\t\tCreated at\t\t\tSize");
+                    println!("----------------------------------------");
+                    for backup in list {
+                        println!(
+                            "{}\t{}\t{}",
+                            backup.id,
+                            backup.created_at.to_rfc3339(),
+                            backup.size
+                        );
+                    }
+                    Ok(())
+                }
+                Err(e) => Err(format!("Failed to list backups: {}", e)),
+            },
+            [action, id] if action == "restore" => match backups.restore(id) {
+                Ok(backup) => {
+                    println!(
+                        "Restored backup {} (created at {})",
+                        backup.id,
+                        backup.created_at.to_rfc3339()
+                    );
+                    Ok(())
+                }
+                Err(e) => Err(format!("Failed to restore backup: {}", e)),
+            },
+            _ => Err(USAGE.to_string()),
+        }
+    }
+
+    /// 壊れたユーザーデータのファイルを修復します。
+    ///
+    /// ユーザーを1件ずつ解析して読み込めたものだけでファイルを書き直し、読み込めなかった
+    /// 断片は隔離ファイル（例: `userdata.quarantine.jsonl`）に移します。修復の後、
+    /// 読み込めることを確認できた最新のバックアップがあれば、その書き戻し方を案内します。
+    /// 保存方式がJSONファイルの場合に使用します。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス。以下のオプションを受け付けます：
+    ///   * `--from-backup` - 修復の代わりに、読み込めることを確認できた最新のバックアップを書き戻す
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 修復または書き戻しに成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数が不正な場合（"Usage: repair [--from-backup]"）
+    /// * 読み込めるバックアップがない場合（"No valid backup to restore"）
+    /// * ファイルの読み書きに失敗した場合（"Failed to repair: ..."）
+    pub fn repair(&self, args: &[String]) -> Result<(), String> {
+        let (from_backup, rest) = take_flag(args, "--from-backup");
+        if !rest.is_empty() {
+            return Err("Usage: repair [--from-backup]".to_string());
+        }
+
+        let backups = self.backup_repository();
+        let latest_backup = backups
+            .list()
+            .map_err(|e| format!("Failed to repair: {}", e))?
+            .into_iter()
+            .rev()
+            .find_map(|backup| {
+                let users = backups.verify(&backup.id).ok()?;
+                Some((backup, users))
+            });
+
+        if from_backup {
+            let Some((backup, users)) = latest_backup else {
+                return Err("No valid backup to restore".to_string());
+            };
+            backups
+                .restore(&backup.id)
+                .map_err(|e| format!("Failed to repair: {}", e))?;
+            println!(
+                "Restored backup {} (created at {}): {} user(s)",
+                backup.id,
+                backup.created_at.to_rfc3339(),
+                users
+            );
+            return Ok(());
+        }
+
+        let quarantine_path = sibling_path(&self.data_file, "quarantine.jsonl");
+        let report = self
+            .file_repository()
+            .repair(&quarantine_path)
+            .map_err(|e| format!("Failed to repair: {}", e))?;
+        if !report.rewritten {
+            println!("No corruption found: {} user(s)", report.recovered);
+            return Ok(());
+        }
+
+        println!("Recovered {} user(s)", report.recovered);
+        println!(
+            "Quarantined {} fragment(s) to {}",
+            report.quarantined.len(),
+            quarantine_path
+        );
+        for fragment in &report.quarantined {
+            println!("  {}", fragment);
+        }
+        if let Some((backup, users)) = latest_backup {
+            println!(
+                "Latest valid backup: {} (created at {}, {} user(s)); run `repair --from-backup` to restore it instead",
+                backup.id,
+                backup.created_at.to_rfc3339(),
+                users
+            );
+        }
+        Ok(())
+    }
+
+    /// ユーザーデータのファイルを現在のファイル形式に変換して書き直します。
+    ///
+    /// 古い形式のファイルも読み込み時に変換されるため、このコマンドは必須ではありませんが、
+    /// 変換した内容を保存しておくことで、以後の読み込みで変換が不要になります。
+    /// 保存方式がJSONファイルの場合に使用します。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス。以下のオプションを受け付けます：
+    ///   * `--dry-run` - 書き込みを行わずに、適用する移行を表示する
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 変換に成功した場合、または既に現在の形式の場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数が不正な場合（"Usage: migrate [--dry-run]"）
+    /// * ファイルの読み書き、または変換に失敗した場合（"Failed to migrate: ..."）
+    pub fn migrate(&self, args: &[String]) -> Result<(), String> {
+        let (dry_run, rest) = take_flag(args, "--dry-run");
+        if !rest.is_empty() {
+            return Err("Usage: migrate [--dry-run]".to_string());
+        }
+
+        let report = self
+            .file_repository()
+            .migrate(dry_run)
+            .map_err(|e| format!("Failed to migrate: {}", e))?;
+        if report.migrations.is_empty() {
+            println!(
+                "Data file is already at schema version {}",
+                report.to_version
+            );
+            return Ok(());
+        }
+
+        if dry_run {
+            println!("Dry run: no changes were written.");
+        }
+        for (step, description) in (report.from_version..).zip(&report.migrations) {
+            println!("{} -> {}: {}", step, step + 1, description);
+        }
+        println!(
+            "Migrated from schema version {} to {}",
+            report.from_version, report.to_version
+        );
+        Ok(())
+    }
+
+    /// 保存されている全てのレコードを現在の規則に照らして検査し、見つかった問題を表示します。
+    ///
+    /// キーとメールアドレスの不一致、規則に違反する値、正規化されていない値、
+    /// 正規化後のメールアドレス・電話番号の重複を報告します。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス。以下のオプションを受け付けます：
+    ///   * `--fix` - 正規化されていない値とキーの不一致を修正する
+    ///   * `--dry-run` - `--fix`と合わせて指定し、書き込みを行わずに修正内容を表示する
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 問題が見つからなかった、または全て修正した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数が不正な場合（"Usage: check [--fix] [--dry-run]"）
+    /// * 修正されていない問題が残っている場合（"... issue(s) remain"）
+    /// * データの取得・保存に失敗した場合（"Failed to check: ..."）
+    pub fn check(&self, args: &[String]) -> Result<(), String> {
+        const USAGE: &str = "Usage: check [--fix] [--dry-run]";
+        let (fix, args) = take_flag(args, "--fix");
+        let (dry_run, rest) = take_flag(&args, "--dry-run");
+        if !rest.is_empty() || (dry_run && !fix) {
+            return Err(USAGE.to_string());
+        }
+
+        let report = match self.run(dry_run, |service| service.check_integrity(fix)) {
+            Ok(None) => return Ok(()),
+            Ok(Some(report)) => report,
+            Err(e) => return Err(format!("Failed to check: {:?}", e)),
+        };
+        let masker = self.masker(false);
+        for issue in &report.issues {
+            let status = if issue.fixed { " (fixed)" } else { "" };
+            println!(
+                "{}: {}{}",
+                masker.email(&issue.key),
+                masker.text(&issue.problem.to_string(), &issue.key),
+                status
+            );
+        }
+        println!(
+            "Checked {} record(s): {} issue(s) found, {} fixed",
+            report.checked,
+            report.issues.len(),
+            report.issues.len() - report.remaining()
+        );
+        match report.remaining() {
+            0 => Ok(()),
+            remaining => Err(format!(
+                "{} issue(s) remain; use `check --fix` for normalization and key mismatches, \
+                 `merge` for duplicates, and `update` for invalid values",
+                remaining
+            )),
+        }
+    }
+
+    /// ユーザーデータのファイルを暗号化します。
+    ///
+    /// 鍵は環境変数`USER_DATA_PASSPHRASE`（パスフレーズ）または`USER_DATA_KEY_FILE`
+    /// （鍵ファイル）で指定します。暗号化した後は、同じ環境変数を設定している間だけ
+    /// ファイルを読み書きできます。変更履歴・監査ログ・操作ジャーナル・隔離ファイルも
+    /// 同じ鍵で暗号化します。暗号化する前に作成された平文のバックアップも、同じ鍵で
+    /// 暗号化して書き直します。保存方式がJSONファイルの場合に使用します。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス（引数は受け付けません）
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 暗号化に成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数が不正な場合（"Usage: encrypt"）
+    /// * 鍵が設定されていない場合（"No encryption key configured; ..."）
+    /// * ファイルが既に暗号化されている場合（"Data file is already encrypted; ..."）
+    /// * ファイルの読み書き、または暗号化に失敗した場合（"Failed to encrypt: ..."）
+    pub fn encrypt(&self, args: &[String]) -> Result<(), String> {
+        if !args.is_empty() {
+            return Err("Usage: encrypt".to_string());
+        }
+        let Some(source) = &self.key_source else {
+            return Err(
+                "No encryption key configured; set USER_DATA_PASSPHRASE or USER_DATA_KEY_FILE"
+                    .to_string(),
+            );
+        };
+
+        let repository = self.file_repository();
+        if repository
+            .is_encrypted()
+            .map_err(|e| format!("Failed to encrypt: {}", e))?
+        {
+            return Err(
+                "Data file is already encrypted; use `rekey` to change the key".to_string(),
+            );
+        }
+        let cipher = DataFileCipher::new(source.clone());
+        let users = repository
+            .set_encryption(Some(&cipher))
+            .map_err(|e| format!("Failed to encrypt: {}", e))?;
+        println!("Encrypted {} user(s) in {}", users, self.data_file);
+        let sidecars = self
+            .rewrite_sidecars(Some(&cipher), Some(&cipher))
+            .map_err(|e| format!("Failed to encrypt: {}", e))?;
+        if sidecars > 0 {
+            println!(
+                "Encrypted {} history, audit, journal or quarantine file(s)",
+                sidecars
+            );
+        }
+
+        let backups = self
+            .backup_repository()
+            .encrypt_all()
+            .map_err(|e| format!("Failed to encrypt: {}", e))?;
+        if !backups.is_empty() {
+            println!("Encrypted {} backup(s)", backups.len());
+        }
+        Ok(())
+    }
+
+    /// 暗号化されたユーザーデータのファイルを復号し、平文で書き直します。
+    ///
+    /// 復号には、暗号化したときと同じ環境変数の鍵を使用します。書き直す前のファイルは
+    /// 暗号化されたままバックアップされます。変更履歴などの補助ファイルも平文に戻します。
+    /// 保存方式がJSONファイルの場合に使用します。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス（引数は受け付けません）
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 復号に成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数が不正な場合（"Usage: decrypt"）
+    /// * ファイルが暗号化されていない場合（"Data file is not encrypted"）
+    /// * 鍵が設定されていない、または鍵が誤っている場合
+    /// * ファイルの読み書きに失敗した場合（"Failed to decrypt: ..."）
+    pub fn decrypt(&self, args: &[String]) -> Result<(), String> {
+        if !args.is_empty() {
+            return Err("Usage: decrypt".to_string());
+        }
+
+        let repository = self.file_repository();
+        if !repository
+            .is_encrypted()
+            .map_err(|e| format!("Failed to decrypt: {}", e))?
+        {
+            return Err("Data file is not encrypted".to_string());
+        }
+        let users = repository
+            .set_encryption(None)
+            .map_err(|e| format!("Failed to decrypt: {}", e))?;
+        println!("Decrypted {} user(s) in {}", users, self.data_file);
+        let sidecars = self
+            .rewrite_sidecars(self.data_cipher().as_ref(), None)
+            .map_err(|e| format!("Failed to decrypt: {}", e))?;
+        if sidecars > 0 {
+            println!(
+                "Decrypted {} history, audit, journal or quarantine file(s)",
+                sidecars
+            );
+        }
+        println!(
+            "Unset USER_DATA_PASSPHRASE and USER_DATA_KEY_FILE, or the next write will encrypt the file again"
+        );
+        Ok(())
+    }
+
+    /// 暗号化されたユーザーデータのファイルを新しい鍵で暗号化し直します。
+    ///
+    /// 現在のファイルは環境変数の鍵で復号し、引数で指定した新しい鍵で暗号化します。
+    /// 書き直す前のファイルは古い鍵で暗号化されたままバックアップされます。
+    /// 変更履歴などの補助ファイルも新しい鍵で暗号化し直します。
+    /// 保存方式がJSONファイルの場合に使用します。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス。以下のいずれかを受け付けます：
+    ///   * `--key-file <path>` - 新しい鍵を鍵ファイルから読み込む
+    ///   * `--passphrase-env <name>` - 新しい鍵を、指定した環境変数のパスフレーズから導出する
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 暗号化し直すことに成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数が不正な場合（"Usage: rekey --key-file \<path\> | --passphrase-env \<name\>"）
+    /// * 指定した環境変数にパスフレーズが設定されていない場合
+    /// * ファイルが暗号化されていない場合（"Data file is not encrypted; ..."）
+    /// * 鍵が設定されていない、または鍵が誤っている場合
+    /// * ファイルの読み書き、または暗号化に失敗した場合（"Failed to rekey: ..."）
+    pub fn rekey(&self, args: &[String]) -> Result<(), String> {
+        const USAGE: &str = "Usage: rekey --key-file <path> | --passphrase-env <name>";
+        let (key_file, args) = take_option(args, "--key-file")?;
+        let (passphrase_env, rest) = take_option(&args, "--passphrase-env")?;
+        let source = match (key_file, passphrase_env) {
+            (Some(path), None) if rest.is_empty() => KeySource::KeyFile(path.into()),
+            (None, Some(name)) if rest.is_empty() => match std::env::var(&name) {
+                Ok(passphrase) if !passphrase.is_empty() => KeySource::Passphrase(passphrase),
+                _ => return Err(format!("Environment variable {} is not set", name)),
+            },
+            _ => return Err(USAGE.to_string()),
+        };
+
+        let repository = self.file_repository();
+        if !repository
+            .is_encrypted()
+            .map_err(|e| format!("Failed to rekey: {}", e))?
+        {
+            return Err("Data file is not encrypted; use `encrypt` first".to_string());
+        }
+        let cipher = DataFileCipher::new(source.clone());
+        let users = repository
+            .set_encryption(Some(&cipher))
+            .map_err(|e| format!("Failed to rekey: {}", e))?;
+        println!("Re-encrypted {} user(s) in {}", users, self.data_file);
+        let sidecars = self
+            .rewrite_sidecars(self.data_cipher().as_ref(), Some(&cipher))
+            .map_err(|e| format!("Failed to rekey: {}", e))?;
+        if sidecars > 0 {
+            println!(
+                "Re-encrypted {} history, audit, journal or quarantine file(s)",
+                sidecars
+            );
+        }
+        match source {
+            KeySource::KeyFile(path) => println!(
+                "Set USER_DATA_KEY_FILE to {} to read the data file from now on",
+                path.display()
+            ),
+            KeySource::Passphrase(_) => println!(
+                "Set USER_DATA_PASSPHRASE to the new passphrase (and unset USER_DATA_KEY_FILE) to read the data file from now on"
+            ),
+        }
+        Ok(())
+    }
+
+    /// 全てのユーザーを書き直し、フィールドの暗号化の設定を保存されている値に反映します。
+    ///
+    /// 環境変数`USER_ENCRYPTED_FIELDS`に指定されたフィールドは暗号化し、指定から外された
+    /// フィールドは復号して保存します。鍵は環境変数`USER_FIELD_KEY_FILE`で指定します。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス（引数は受け付けません）
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 書き直しに成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数が不正な場合（"Usage: encrypt-fields"）
+    /// * 鍵が設定されていない場合
+    /// * データの取得・保存、または復号に失敗した場合（"Failed to encrypt fields: ..."）
+    pub fn encrypt_fields(&self, args: &[String]) -> Result<(), String> {
+        if !args.is_empty() {
+            return Err("Usage: encrypt-fields".to_string());
+        }
+        match self.service.rewrite_fields() {
+            Ok(count) => {
+                println!(
+                    "Rewrote {} user(s) with the current field encryption",
+                    count
+                );
+                Ok(())
+            }
+            Err(e) => Err(format!("Failed to encrypt fields: {:?}", e)),
+        }
+    }
+
+    /// 本人からの開示請求・消去請求に応じます。
+    ///
+    /// `export`は、現在のレコード・変更履歴・監査ログ・操作ジャーナル・バックアップ・
+    /// 隔離ファイルから対象のユーザーに関するデータを集め、JSONとして出力します
+    /// （請求への回答であるため、値は伏せ字にしません）。
+    /// `erase`は、レコードを完全に削除した上で、変更履歴・操作ジャーナル・バックアップ・
+    /// 隔離ファイルからユーザーを取り除き、監査ログからユーザーを特定できる値を消去します。
+    /// 消去した内容は環境変数`USER_AUDIT_HMAC_KEY`の鍵で署名した受領証として出力し、
+    /// 受領証ファイル（例: `userdata.erasures.jsonl`）に追記します。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス。以下のいずれかを受け付けます：
+    ///   * `export <email> [--output <path>]` - データを標準出力（または指定したファイル）に書き出す
+    ///   * `erase <email>` - データを消去し、署名した受領証を出力する
+    ///   * `receipts [<email>]` - 記録された受領証と、署名の検証結果を表示する
+    ///     （メールアドレスを指定した場合は、そのユーザーの受領証だけを表示する）
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 操作に成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数が不正な場合（"Usage: privacy export \<email\> [--output \<path\>] | erase \<email\> | receipts [\<email\>]"）
+    /// * メールアドレスを指定した`receipts`で、署名の鍵が設定されていない場合
+    /// * 受領証に署名する鍵が設定されていない場合（"No signing key configured; ..."）
+    /// * 保存方式がイベントログの場合の`erase`（"Cannot erase users stored in the event log; ..."）
+    /// * 消去するデータがない場合（"No data stored about ..."）
+    /// * データの読み書きに失敗した場合（"Failed to export: ..."、"Failed to erase: ..."）
+    pub fn privacy(&self, args: &[String]) -> Result<(), String> {
+        const USAGE: &str =
+            "Usage: privacy export <email> [--output <path>] | erase <email> | receipts [<email>]";
+        let (output, args) = take_option(args, "--output")?;
+        match args.as_slice() {
+            [action, email] if action == "export" => self.export_subject(email, output),
+            [action, email] if action == "erase" && output.is_none() => self.erase_subject(email),
+            [action] if action == "receipts" && output.is_none() => self.print_receipts(None),
+            [action, email] if action == "receipts" && output.is_none() => {
+                self.print_receipts(Some(email))
+            }
+            _ => Err(USAGE.to_string()),
+        }
+    }
+
+    /// 対象のユーザーに関するデータを集めてJSONとして書き出します。
+    ///
+    /// # 引数
+    /// * `email` - 対象のユーザーのメールアドレス
+    /// * `output` - 書き出すファイルのパス（`None`の場合は標準出力）
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 書き出しに成功した場合
+    ///
+    /// # エラー
+    /// * データの取得、または書き出しに失敗した場合（"Failed to export: ..."）
+    fn export_subject(&self, email: &str, output: Option<String>) -> Result<(), String> {
+        let mut export = self
+            .service
+            .export_subject(email)
+            .map_err(|e| format!("Failed to export: {:?}", e))?;
+        export.backups = self
+            .backup_repository()
+            .find_user(&export.emails)
+            .map_err(|e| format!("Failed to export: {}", e))?
+            .into_iter()
+            .map(|(backup, user)| BackupCopy {
+                backup_id: backup.id,
+                created_at: backup.created_at,
+                user,
+            })
+            .collect();
+        export.quarantine = find_quarantined(
+            &sibling_path(&self.data_file, "quarantine.jsonl"),
+            &export.emails,
+            self.data_cipher().as_ref(),
+        )
+        .map_err(|e| format!("Failed to export: {}", e))?;
+
+        let content = serde_json::to_string_pretty(&export)
+            .map_err(|e| format!("Failed to export: {}", e))?;
+        match output {
+            None => println!("{}", content),
+            Some(path) => {
+                fs::write(&path, content).map_err(|e| format!("Failed to export: {}", e))?;
+                println!(
+                    "Exported data about {} to {}",
+                    self.masker(false).email(email),
+                    path
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// 対象のユーザーのデータを消去し、署名した受領証を出力・記録します。
+    ///
+    /// # 引数
+    /// * `email` - 対象のユーザーのメールアドレス
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 消去に成功した場合
+    ///
+    /// # エラー
+    /// * 署名する鍵が設定されていない、または保存方式がイベントログの場合
+    /// * 消去するデータがない場合
+    /// * データの読み書きに失敗した場合（"Failed to erase: ..."）
+    fn erase_subject(&self, email: &str) -> Result<(), String> {
+        let Some(key) = &self.signing_key else {
+            return Err(
+                "No signing key configured; set USER_AUDIT_HMAC_KEY to sign erasure receipts"
+                    .to_string(),
+            );
+        };
+        if self.backend == StorageBackend::EventSourced {
+            return Err("Cannot erase users stored in the event log; \
+                 the event-sourced backend is append-only"
+                .to_string());
+        }
+
+        let emails = self
+            .service
+            .subject_emails(email)
+            .map_err(|e| format!("Failed to erase: {:?}", e))?;
+        // レコードの削除で作られるバックアップからも取り除けるよう、バックアップは最後に消去する
+        let mut receipt = self
+            .service
+            .erase_subject(email, key)
+            .map_err(|e| format!("Failed to erase: {:?}", e))?;
+        receipt.backups = self
+            .backup_repository()
+            .erase_user(&emails)
+            .map_err(|e| format!("Failed to erase: {}", e))?;
+        receipt.quarantine_fragments = erase_quarantined(
+            &sibling_path(&self.data_file, "quarantine.jsonl"),
+            &emails,
+            self.data_cipher().as_ref(),
+        )
+        .map_err(|e| format!("Failed to erase: {}", e))?;
+        if receipt.is_empty() {
+            return Err(format!("No data stored about {}", email));
+        }
+
+        receipt.sign(key)?;
+        self.receipt_repository()
+            .append(&receipt)
+            .map_err(|e| format!("Failed to erase: {}", e))?;
+        println!("Erased data about {}", self.masker(false).email(email));
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&receipt)
+                .map_err(|e| format!("Failed to erase: {}", e))?
+        );
+        Ok(())
+    }
+
+    /// 記録された受領証を、署名の検証結果とともに表示します。
+    ///
+    /// 受領証の`subject`は署名の鍵によるHMACのため、メールアドレスで絞り込むには鍵が必要です。
+    ///
+    /// # 引数
+    /// * `email` - 受領証を表示するユーザーのメールアドレス（`None`の場合は全て表示する）
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 表示に成功した場合
+    ///
+    /// # エラー
+    /// * メールアドレスが指定されたが、署名の鍵が設定されていない場合
+    /// * メールアドレスの形式が不正な場合（"Failed to read erasure receipts: ..."）
+    /// * 受領証の読み込みに失敗した場合
+    fn print_receipts(&self, email: Option<&str>) -> Result<(), String> {
+        let subject = match (email, &self.signing_key) {
+            (None, _) => None,
+            (Some(_), None) => {
+                return Err(
+                    "No signing key configured; set USER_AUDIT_HMAC_KEY to find receipts by email"
+                        .to_string(),
+                );
+            }
+            (Some(email), Some(key)) => Some(
+                self.service
+                    .erasure_subject(email, key)
+                    .map_err(|e| format!("Failed to read erasure receipts: {:?}", e))?,
+            ),
+        };
+        let receipts: Vec<_> = self
+            .receipt_repository()
+            .find_all()
+            .map_err(|e| format!("Failed to read erasure receipts: {}", e))?
+            .into_iter()
+            .filter(|receipt| subject.as_ref().is_none_or(|s| receipt.subject == *s))
+            .collect();
+        println!("Erasure receipts:");
+        println!("Erased at\t\t\tActor\tSignature\tSubject");
+        println!("----------------------------------------");
+        for receipt in &receipts {
+            let signature = match &self.signing_key {
+                None => "unverified",
+                Some(key) if receipt.verify(key)? => "valid",
+                Some(_) => "INVALID",
+            };
+            println!(
+                "{}\t{}\t{}\t{}",
+                receipt.erased_at.to_rfc3339(),
+                receipt.actor,
+                signature,
+                receipt.subject
+            );
+        }
+        Ok(())
+    }
+
+    /// ユーザーデータのファイルを読み込み、個人を特定できる値を仮名に置き換えたファイルを作成します。
+    ///
+    /// メールアドレス・別名・ユーザー名・電話番号を、環境変数`USER_ANONYMIZE_SALT`のソルトから
+    /// 決定的に作る仮名に置き換えます。同じ入力とソルトからは常に同じファイルが作られます。
+    /// 入力ファイルは環境変数の鍵で復号して読み込み、出力ファイルは平文で書き込みます。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス。2つの要素が必要です：
+    ///   * `input` - 読み込むユーザーデータのファイルのパス
+    ///   * `output` - 作成するファイルのパス（既に存在する場合はエラー）
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - ファイルの作成に成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数が不正な場合（"Usage: anonymize \<input\> \<output\>"）
+    /// * ソルトが設定されていない場合（"No salt configured; ..."）
+    /// * 入力ファイルが存在しない場合（"Input file not found: ..."）
+    /// * 出力ファイルが既に存在する場合（"Output file already exists: ..."）
+    /// * データの読み書き、または仮名の作成に失敗した場合（"Failed to anonymize: ..."）
+    pub fn anonymize(&self, args: &[String]) -> Result<(), String> {
+        let [input, output] = args else {
+            return Err("Usage: anonymize <input> <output>".to_string());
+        };
+        let Some(anonymizer) = &self.anonymizer else {
+            return Err(
+                "No salt configured; set USER_ANONYMIZE_SALT to anonymize data".to_string(),
+            );
+        };
+        if !Path::new(input).exists() {
+            return Err(format!("Input file not found: {}", input));
+        }
+        if Path::new(output).exists() {
+            return Err(format!("Output file already exists: {}", output));
+        }
+
+        let source = UserRepositoryImpl::with_file_path(input);
+        let source = match &self.key_source {
+            Some(key) => source.with_cipher(DataFileCipher::new(key.clone())),
+            None => source,
+        };
+        let service = UserService::with_config(source, UserServiceConfig::from_env());
+        let service = match FieldCipher::from_env() {
+            Ok(Some(cipher)) => service.with_field_encryption(cipher),
+            _ => service,
+        };
+        match service.anonymize_into(anonymizer, &UserRepositoryImpl::with_file_path(output)) {
+            Ok(count) => {
+                println!("Anonymized {} user(s) from {} to {}", count, input, output);
+                Ok(())
+            }
+            Err(e) => Err(format!("Failed to anonymize: {:?}", e)),
+        }
+    }
+
+    /// 架空のユーザーをシードから決定的に生成し、まとめて作成します。
+    ///
+    /// 同じシードと件数からは常に同じユーザーが生成されます。作成は1回の書き込みで行い、
+    /// 既存のユーザーとメールアドレスが重複する場合は何も作成しません。
+    /// 監査ログには件数とシードを1件の記録として残し、操作ジャーナルに記録するため`undo`で取り消せます。
+    /// ユーザーごとの変更履歴は記録しません。使用したシードは成功時に表示します。
+    ///
+    /// # 引数
+    /// * `args` - コマンドライン引数のスライス。以下のオプションを受け付けます：
+    ///   * `--count <N>` - 生成するユーザーの件数（1以上、必須）
+    ///   * `--seed <S>` - 乱数のシード（0以上の整数、省略時は0）
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - ユーザーの作成に成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 引数が不正な場合（"Usage: seed --count \<N\> [--seed \<S\>] ..."）
+    /// * 生成したユーザーが既存のユーザーと重複する、または規則に違反する場合
+    /// * データの読み書きに失敗した場合（"Failed to seed users: ..."）
+    pub fn seed(&self, args: &[String]) -> Result<(), String> {
+        const USAGE: &str =
+            "Usage: seed --count <N> [--seed <S>] (the seed defaults to 0 and is printed)";
+        let (count, args) = take_option(args, "--count")?;
+        let (seed, rest) = take_option(&args, "--seed")?;
+        if !rest.is_empty() {
+            return Err(USAGE.to_string());
+        }
+        let count = count
+            .and_then(|count| count.parse::<usize>().ok())
+            .filter(|&count| count > 0)
+            .ok_or_else(|| USAGE.to_string())?;
+        let seed = match seed {
+            Some(seed) => seed.parse::<u64>().map_err(|_| USAGE.to_string())?,
+            None => 0,
+        };
+
+        let users = Seeder::new(seed).generate(count);
+        let details = format!("count={} seed={}", count, seed);
+        match self.service.insert_users(&users, &details) {
+            Ok(count) => {
+                println!("Seeded {} user(s) with seed {}", count, seed);
+                Ok(())
+            }
+            Err(e) => Err(format!("Failed to seed users: {:?}", e)),
+        }
+    }
+
+    /// 消去請求の受領証を記録するリポジトリを作成します。
+    ///
+    /// # 戻り値
+    /// * `ReceiptRepositoryImpl` - ユーザーデータのファイルと同じ場所の受領証ファイルを扱うリポジトリ
+    fn receipt_repository(&self) -> ReceiptRepositoryImpl {
+        ReceiptRepositoryImpl::with_file_path(sibling_path(&self.data_file, "erasures.jsonl"))
+    }
+
+    /// 設定された鍵の入手元から、ファイルの暗号化に使用するインスタンスを作成します。
+    ///
+    /// # 戻り値
+    /// * `Option<DataFileCipher>` - 鍵が設定されていない場合は`None`
+    fn data_cipher(&self) -> Option<DataFileCipher> {
+        self.key_source.clone().map(DataFileCipher::new)
+    }
+
+    /// 変更履歴・監査ログ・操作ジャーナル・隔離ファイルを、別の鍵で（または平文で）書き直します。
+    ///
+    /// # 引数
+    /// * `current` - 現在のファイルの復号に使用する鍵
+    /// * `cipher` - 書き直すファイルの暗号化に使用する鍵（`None`の場合は平文）
+    ///
+    /// # 戻り値
+    /// * `Ok(usize)` - 書き直したファイルの数
+    ///
+    /// # エラー
+    /// * ファイルの読み書き、または復号・暗号化に失敗した場合
+    fn rewrite_sidecars(
+        &self,
+        current: Option<&DataFileCipher>,
+        cipher: Option<&DataFileCipher>,
+    ) -> Result<usize, String> {
+        let mut rewritten = 0;
+        for path in &self.sidecar_files {
+            if rewrite_sidecar(path, current, cipher).map_err(|e| format!("{}: {}", path, e))? {
+                rewritten += 1;
+            }
+        }
+        Ok(rewritten)
+    }
+
+    /// ユーザーデータのファイルのバックアップを扱うリポジトリを作成します。
+    ///
+    /// # 戻り値
+    /// * `BackupRepositoryImpl` - 設定された鍵でバックアップを読み込むリポジトリ
+    fn backup_repository(&self) -> BackupRepositoryImpl {
+        let backups = BackupRepositoryImpl::new(&self.data_file);
+        match &self.key_source {
+            Some(source) => backups.with_cipher(DataFileCipher::new(source.clone())),
+            None => backups,
+        }
+    }
+
+    /// ユーザーデータのファイルを直接扱うリポジトリを作成します。
+    ///
+    /// 修復や暗号化など、サービスを経由せずにファイル全体を書き直す操作に使用します。
+    ///
+    /// # 戻り値
+    /// * `UserRepositoryImpl` - 設定された鍵で読み書きし、書き込みの前にバックアップするリポジトリ
+    fn file_repository(&self) -> UserRepositoryImpl {
+        let repository = UserRepositoryImpl::with_file_path(&self.data_file)
+            .with_backups(self.backup_repository());
+        match &self.key_source {
+            Some(source) => repository.with_cipher(DataFileCipher::new(source.clone())),
+            None => repository,
+        }
+    }
+
+    /// ユーザーを変更する操作を実行します。
+    ///
+    /// `dry_run`が`true`の場合は書き込みを行わずに操作を実行し、
+    /// 行われるはずだった変更を差分として表示します。
+    ///
+    /// # 引数
+    /// * `dry_run` - 書き込みを行わずに変更内容を表示するかどうか
+    /// * `action` - サービスを受け取って実行する操作
+    ///
+    /// # 戻り値
+    /// * `Ok(Some(R))` - 操作を実行した場合の結果
+    /// * `Ok(None)` - 変更内容を表示した場合
+    ///
+    /// # エラー
+    /// * 操作が返したエラー
+    fn run<R>(
+        &self,
+        dry_run: bool,
+        action: impl FnOnce(&UserService<Box<dyn UserRepository + '_>>) -> Result<R, UserError>,
+    ) -> Result<Option<R>, UserError> {
+        if !dry_run {
+            return action(&self.service).map(Some);
+        }
+
+        let (_, changes) = self.service.preview(action)?;
+        let masker = self.masker(false);
+        println!("Dry run: no changes were written.");
+        if changes.is_empty() {
+            println!("No changes.");
+        }
+        for change in &changes {
+            print_planned_change(change, &masker);
+        }
+        Ok(None)
+    }
+
+    /// ユーザー情報を標準出力に整形して表示します。
+    ///
+    /// # 引数
+    /// * `user` - 表示するユーザー情報
+    ///   ユーザー情報を標準出力に整形して表示します。
+    ///
+    /// # 引数
+    /// * `user` - 表示するユーザー情報
+    ///
+    /// 以下の形式で表示されます：
+    /// ```text
+    /// Email: user@example.com
+    /// Username: username
+    /// Phone: 090-1234-5678
+    /// Age: 25
+    /// Version: 1
+    /// ```
+    ///
+    /// 電話番号は既定の国の番号であれば国内形式で表示されます。
+    /// 統合によって記録された別名がある場合は`Aliases:`行が`Version:`行の前に追加されます。
+    /// 標準出力が端末でない場合、メールアドレス・電話番号・別名は伏せ字で表示されます。
+    fn print_user(&self, user: &User) {
+        let masker = self.masker(false);
+        println!("Email: {}", masker.email(&user.email));
+        println!("Username: {}", user.username);
+        println!(
+            "Phone: {}",
+            masker.phone(&self.service.display_phone(&user.phone))
+        );
+        println!("Age: {}", user.age);
+        if !user.aliases.is_empty() {
+            println!(
+                "Aliases: {}",
+                masker.field("aliases", &user.aliases.join(", "))
+            );
+        }
+        println!("Version: {}", user.version);
+    }
+
+    /// 表示するメールアドレス・電話番号を伏せ字にするインスタンスを返します。
+    ///
+    /// `--reveal`が指定された場合は伏せません。それ以外の場合、`always`が`true`
+    /// （一覧の表示）であるか、標準出力が端末でない場合に伏せます。
+    ///
+    /// # 引数
+    /// * `always` - 標準出力が端末の場合も伏せるかどうか
+    ///
+    /// # 戻り値
+    /// * `Masker` - 表示する値を伏せ字にするインスタンス
+    fn masker(&self, always: bool) -> Masker {
+        if self.reveal || (!always && io::stdout().is_terminal()) {
+            Masker::reveal()
+        } else {
+            Masker::new(self.mask_strategy)
+        }
+    }
+}
+
+/// 統合時に値が異なるフィールドについて、どちらの値を採用するかを標準入力から尋ねます。
+///
+/// # 引数
+/// * `field` - 対象のフィールド
+/// * `keep` - 残す側のユーザーの値
+/// * `drop` - 取り込む側のユーザーの値
+///
+/// # 戻り値
+/// * `MergeChoice` - `2`が入力された場合は`Drop`、それ以外は`Keep`
+fn prompt_merge_choice(field: MergeField, keep: &str, drop: &str) -> MergeChoice {
+    print!("{}: [1] {} (keep) / [2] {} (drop) > ", field, keep, drop);
+    let _ = io::stdout().flush();
+    let mut line = String::new();
+    let _ = io::stdin().lock().read_line(&mut line);
+    if line.trim() == "2" {
+        MergeChoice::Drop
+    } else {
+        MergeChoice::Keep
+    }
+}
+
+/// `30d`、`12h`、`45m`、`10s`のような期間の指定を解析します。
+///
+/// # 引数
+/// * `value` - 数値と単位（`d`、`h`、`m`、`s`）からなる期間
+///
+/// # 戻り値
+/// * `Ok(TimeDelta)` - 解析した期間
+///
+/// # エラー
+/// * 形式が不正な場合
+fn parse_duration(value: &str) -> Result<TimeDelta, String> {
+    let invalid = || format!("Invalid duration: {} (e.g. 30d, 12h, 45m)", value);
+    let (unit_index, _) = value.char_indices().next_back().ok_or_else(invalid)?;
+    let (amount, unit) = value.split_at(unit_index);
+    let amount = amount.parse::<i64>().map_err(|_| invalid())?;
+    let duration = match unit {
+        "d" => TimeDelta::try_days(amount),
+        "h" => TimeDelta::try_hours(amount),
+        "m" => TimeDelta::try_minutes(amount),
+        "s" => TimeDelta::try_seconds(amount),
+        _ => None,
+    };
+    duration
+        .filter(|d| *d >= TimeDelta::zero())
+        .ok_or_else(invalid)
+}
+
+/// `undo`・`redo`の省略可能な回数の引数を解析します。
+///
+/// # 引数
+/// * `args` - コマンドライン引数のスライス
+/// * `usage` - 引数が不正な場合に返す使い方の説明
+///
+/// # 戻り値
+/// * `Ok(usize)` - 回数（省略時は1）
+///
+/// # エラー
+/// * 引数が多すぎる場合、または1以上の整数でない場合（`usage`）
+fn parse_count(args: &[String], usage: &str) -> Result<usize, String> {
+    match args {
+        [] => Ok(1),
+        [count] => count
+            .parse::<usize>()
+            .ok()
+            .filter(|&count| count > 0)
+            .ok_or_else(|| usage.to_string()),
+        _ => Err(usage.to_string()),
+    }
+}
+
+/// 操作ジャーナルの記録を1行で表す文字列を作成します。
+///
+/// # 引数
+/// * `entry` - 操作ジャーナルの記録
+///
+/// * `masker` - 対象のメールアドレスを伏せ字にするインスタンス
+///
+/// # 戻り値
+/// * `String` - 操作の種類・対象（一括作成では件数）・日時・操作者を含む文字列
+fn describe_journal_entry(entry: &JournalEntry, masker: &Masker) -> String {
+    let target = if entry.operation == RevisionOperation::Seed {
+        format!("{} user(s)", entry.changes.len())
+    } else {
+        let emails: Vec<String> = entry
+            .changes
+            .iter()
+            .map(|change| masker.email(change.email()))
+            .collect();
+        emails.join(", ")
+    };
+    format!(
+        "{} {} ({} by {})",
+        entry.operation,
+        target,
+        entry.timestamp.to_rfc3339(),
+        entry.actor
+    )
+}
+
+/// `get --at`や`audit --since`に指定された日時を解析します。
+///
+/// # 引数
+/// * `value` - RFC 3339形式の日時、または`YYYY-MM-DD`形式の日付
+/// * `end_of_day` - 日付のみが指定された場合に、その日の終わりとするかどうか
+///   （`false`の場合はその日の始まり）
+///
+/// # 戻り値
+/// * `Ok(DateTime<Utc>)` - 解析した日時（日付のみの場合はUTCでのその日の始まりまたは終わり）
+///
+/// # エラー
+/// * 形式が不正な場合
+fn parse_timestamp(value: &str, end_of_day: bool) -> Result<DateTime<Utc>, String> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Ok(at.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| {
+            if end_of_day {
+                date.and_hms_nano_opt(23, 59, 59, 999_999_999)
+            } else {
+                date.and_hms_opt(0, 0, 0)
+            }
+        })
+        .map(|at| at.and_utc())
+        .ok_or_else(|| {
+            format!(
+                "Invalid timestamp: {} (e.g. 2025-01-31 or 2025-01-31T09:00:00+09:00)",
+                value
+            )
+        })
+}
+
+/// `--expected-version`に指定されたバージョンを解析します。
+///
+/// # 引数
+/// * `value` - オプションの値（指定されていない場合は`None`）
+///
+/// # 戻り値
+/// * `Ok(Option<u64>)` - 解析したバージョン
+///
+/// # エラー
+/// * 0以上の整数でない場合（"Invalid version"）
+fn parse_version(value: Option<String>) -> Result<Option<u64>, String> {
+    value
+        .map(|v| v.parse::<u64>().map_err(|_| "Invalid version".to_string()))
+        .transpose()
+}
+
+/// 書き込みが行われるはずだった1件のレコードの変更を差分として表示します。
+///
+/// 作成されるレコードは`+`、削除されるレコードは`-`、変更されるレコードは`~`を
+/// 先頭に付けて表示し、続けて変化するフィールドを表示します。
+///
+/// # 引数
+/// * `change` - レコードの変更
+/// * `masker` - メールアドレス・電話番号を伏せ字にするインスタンス
+fn print_planned_change(change: &JournalChange, masker: &Masker) {
+    let marker = match (&change.before, &change.after) {
+        (None, _) => "+",
+        (_, None) => "-",
+        _ => "~",
+    };
+    println!("{} {}", marker, masker.email(change.email()));
+    for field in field_changes(change.before.as_ref(), change.after.as_ref()) {
+        print_field_change(&field, masker);
+    }
+}
+
+/// 1つのフィールドの変更を`フィールド: 変更前 -> 変更後`の形式で表示します。
+///
+/// # 引数
+/// * `change` - フィールドの変更
+/// * `masker` - メールアドレス・電話番号を伏せ字にするインスタンス
+fn print_field_change(change: &FieldChange, masker: &Masker) {
+    let show = |value: &Option<String>| {
+        value
+            .as_deref()
+            .map_or("-".to_string(), |value| masker.field(change.field, value))
+    };
+    println!(
+        "    {}: {} -> {}",
+        change.field,
+        show(&change.before),
+        show(&change.after)
+    );
+}
+
+/// コマンドライン引数から値を取らないフラグを取り出します。
+///
+/// # 引数
+/// * `args` - コマンドライン引数のスライス
+/// * `name` - フラグ名（例: "--dry-run"）
+///
+/// # 戻り値
+/// * `(bool, Vec<String>)` - フラグが指定されたかどうかと、残りの引数
+fn take_flag(args: &[String], name: &str) -> (bool, Vec<String>) {
+    let rest: Vec<String> = args.iter().filter(|arg| *arg != name).cloned().collect();
+    (rest.len() != args.len(), rest)
+}
+
+/// コマンドライン引数から値を取るオプションを取り出します。
+///
+/// # 引数
+/// * `args` - コマンドライン引数のスライス
+/// * `name` - オプション名（例: "--threshold"）
+///
+/// # 戻り値
+/// * `Ok((Option<String>, Vec<String>))` - オプションの値と、残りの引数
+///
+/// # エラー
+/// * オプションに値が指定されていない場合
+fn take_option(args: &[String], name: &str) -> Result<(Option<String>, Vec<String>), String> {
+    let mut value = None;
+    let mut rest = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == name {
+            value = Some(
+                iter.next()
+                    .ok_or_else(|| format!("Missing value for {}", name))?
+                    .clone(),
+            );
+        } else {
+            rest.push(arg.clone());
+        }
+    }
+    Ok((value, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::field_encryption::EncryptedField;
+    use sha2::{Digest, Sha256};
+    use tempfile::TempDir;
+
+    fn setup_in_temp_dir() -> (TempDir, UserCommand) {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_file = temp_dir.path().join("userdata.json");
+        let command = UserCommand::with_data_file(data_file.to_str().unwrap());
+        (temp_dir, command)
+    }
+
+    #[test]
+    fn test_create_user_command() {
+        let (_temp_dir, command) = setup_in_temp_dir();
+        let args = vec![
+            "test@example.com".to_string(),
+            "testuser".to_string(),
+            "1234567890".to_string(),
+            "25".to_string(),
+        ];
+
+        let result = command.create(&args);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_create_user_invalid_args() {
+        let (_temp_dir, command) = setup_in_temp_dir();
+        let args = vec!["test@example.com".to_string()];
+
+        let result = command.create(&args);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_update_user_command() {
+        let (_temp_dir, command) = setup_in_temp_dir();
+        let create_args = vec![
+            "test@example.com".to_string(),
+            "testuser".to_string(),
+            "1234567890".to_string(),
+            "25".to_string(),
+        ];
+        command.create(&create_args).unwrap();
+
+        let update_args = vec![
+            "test@example.com".to_string(),
+            "newuser".to_string(),
+            "0987654321".to_string(),
+            "30".to_string(),
+        ];
+        let result = command.update(&update_args);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_delete_user_command() {
+        let (_temp_dir, command) = setup_in_temp_dir();
+        let create_args = vec![
+            "test@example.com".to_string(),
+            "testuser".to_string(),
+            "1234567890".to_string(),
+            "25".to_string(),
+        ];
+        command.create(&create_args).unwrap();
+
+        let delete_args = vec!["test@example.com".to_string()];
+        let result = command.delete(&delete_args);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_search_user_command() {
+        let (_temp_dir, command) = setup_in_temp_dir();
+        let create_args = vec![
+            "tanaka@example.com".to_string(),
+            "タナカ".to_string(),
+            "09012345678".to_string(),
+            "25".to_string(),
+        ];
+        command.create(&create_args).unwrap();
+
+        assert!(command.search(&["ﾀﾅｶ".to_string()]).is_ok());
+        assert!(command.search(&[" ".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_duplicates_command() {
+        let (_temp_dir, command) = setup_in_temp_dir();
+        assert!(command.duplicates(&[]).is_ok());
+        assert!(
+            command
+                .duplicates(&["--threshold".to_string(), "0.8".to_string()])
+                .is_ok()
+        );
+        assert!(
+            command
+                .duplicates(&["--threshold".to_string(), "2".to_string()])
+                .is_err()
+        );
+        assert!(command.duplicates(&["--threshold".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_merge_user_command() {
+        let (_temp_dir, command) = setup_in_temp_dir();
+        for (email, username) in [
+            ("keep@example.com", "keepuser"),
+            ("drop@example.com", "dropuser"),
+        ] {
+            let args = vec![
+                email.to_string(),
+                username.to_string(),
+                "09012345678".to_string(),
+                "25".to_string(),
+            ];
+            command.create(&args).unwrap();
+        }
+
+        let args = vec![
+            "keep@example.com".to_string(),
+            "drop@example.com".to_string(),
+            "--username".to_string(),
+            "newest".to_string(),
+        ];
+        assert!(command.merge(&args).is_ok());
+        assert!(command.get(&["drop@example.com".to_string()]).is_ok());
+        assert!(command.merge(&args).is_err());
+    }
+
+    #[test]
+    fn test_trash_restore_and_purge_commands() {
+        let (_temp_dir, command) = setup_in_temp_dir();
+        let create_args = vec![
+            "test@example.com".to_string(),
+            "testuser".to_string(),
+            "1234567890".to_string(),
+            "25".to_string(),
+        ];
+        command.create(&create_args).unwrap();
+
+        let email = vec!["test@example.com".to_string()];
+        command.delete(&email).unwrap();
+        assert!(command.get(&email).is_err());
+        assert!(command.create(&create_args).is_err());
+        assert!(command.trash(&["list".to_string()]).is_ok());
+
+        command.restore(&email).unwrap();
+        assert!(command.get(&email).is_ok());
+        assert!(command.restore(&email).is_err());
+
+        command.delete(&email).unwrap();
+        let purge_args = vec!["--older-than".to_string(), "0d".to_string()];
+        command.purge(&purge_args).unwrap();
+        assert!(command.restore(&email).is_err());
+        assert!(command.create(&create_args).is_ok());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30d"), Ok(TimeDelta::days(30)));
+        assert_eq!(parse_duration("12h"), Ok(TimeDelta::hours(12)));
+        assert!(parse_duration("30").is_err());
+        assert!(parse_duration("-1d").is_err());
+        assert!(parse_duration("").is_err());
+        // 末尾が複数バイトの文字でもエラーとして扱う
+        assert!(parse_duration("30日").is_err());
+        assert!(parse_duration("5ｄ").is_err());
+        assert!(parse_duration("日").is_err());
+    }
+
+    #[test]
+    fn test_history_get_at_and_revert_commands() {
+        let (_temp_dir, command) = setup_in_temp_dir();
+        let create_args = vec![
+            "test@example.com".to_string(),
+            "testuser".to_string(),
+            "09012345678".to_string(),
+            "25".to_string(),
+        ];
+        command.create(&create_args).unwrap();
+        let update_args = vec![
+            "test@example.com".to_string(),
+            "newuser".to_string(),
+            "08012345678".to_string(),
+            "30".to_string(),
+        ];
+        command.update(&update_args).unwrap();
+
+        let email = "test@example.com".to_string();
+        assert!(command.history(std::slice::from_ref(&email)).is_ok());
+        let now = Utc::now().to_rfc3339();
+        assert!(
+            command
+                .get(&[email.clone(), "--at".to_string(), now])
+                .is_ok()
+        );
+        assert!(
+            command
+                .get(&[email.clone(), "--at".to_string(), "2000-01-01".to_string()])
+                .is_err()
+        );
+        assert!(command.revert(&[email.clone(), "1".to_string()]).is_ok());
+        assert!(command.revert(&[email.clone(), "x".to_string()]).is_err());
+        assert!(
+            command
+                .history(&["nobody@example.com".to_string()])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(
+            parse_timestamp("2025-01-31", true).unwrap().to_rfc3339(),
+            "2025-01-31T23:59:59.999999999+00:00"
+        );
+        assert_eq!(
+            parse_timestamp("2025-01-31", false).unwrap().to_rfc3339(),
+            "2025-01-31T00:00:00+00:00"
+        );
+        assert_eq!(
+            parse_timestamp("2025-01-31T09:00:00+09:00", true)
+                .unwrap()
+                .to_rfc3339(),
+            "2025-01-31T00:00:00+00:00"
+        );
+        assert!(parse_timestamp("last month", true).is_err());
+    }
+
+    #[test]
+    fn test_audit_command_records_failures() {
+        let (_temp_dir, command) = setup_in_temp_dir();
+        let command = command.with_actor("alice");
+        let create_args = vec![
+            "test@example.com".to_string(),
+            "testuser".to_string(),
+            "09012345678".to_string(),
+            "25".to_string(),
+        ];
+        command.create(&create_args).unwrap();
+        assert!(command.create(&create_args).is_err());
+
+        let entries = command
+            .service
+            .audit_log(&AuditQuery {
+                actor: Some("alice".to_string()),
+                ..AuditQuery::default()
+            })
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].outcome, AuditOutcome::Success);
+        assert!(matches!(entries[1].outcome, AuditOutcome::Failure(_)));
+
+        let args = vec![
+            "--user".to_string(),
+            "Test@Example.com".to_string(),
+            "--since".to_string(),
+            "2000-01-01".to_string(),
+        ];
+        assert!(command.audit(&args).is_ok());
+        assert!(
+            command
+                .audit(&["--since".to_string(), "yesterday".to_string()])
+                .is_err()
+        );
+        assert!(command.audit(&["extra".to_string()]).is_err());
+        assert!(command.audit(&["verify".to_string()]).is_ok());
+        assert!(
+            command
+                .audit(&["verify".to_string(), "extra".to_string()])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_event_sourced_backend_and_rebuild_command() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_file = temp_dir.path().join("userdata.json");
+        let data_file = data_file.to_str().unwrap();
+        let command = UserCommand::with_backend(data_file, StorageBackend::EventSourced);
+        assert!(command.rebuild(&[]).is_err());
+
+        let create_args = vec![
+            "test@example.com".to_string(),
+            "testuser".to_string(),
+            "09012345678".to_string(),
+            "25".to_string(),
+        ];
+        command.create(&create_args).unwrap();
+        command.delete(&["test@example.com".to_string()]).unwrap();
+        assert!(!temp_dir.path().join("userdata.json").exists());
+
+        let command = UserCommand::with_backend(data_file, StorageBackend::EventSourced);
+        assert!(command.rebuild(&[]).is_ok());
+        assert!(command.rebuild(&["extra".to_string()]).is_err());
+        assert!(command.restore(&["test@example.com".to_string()]).is_ok());
+    }
+
+    #[test]
+    fn test_undo_and_redo_commands() {
+        let (_temp_dir, command) = setup_in_temp_dir();
+        assert!(command.undo(&[]).is_err());
+
+        let create_args = vec![
+            "test@example.com".to_string(),
+            "testuser".to_string(),
+            "09012345678".to_string(),
+            "25".to_string(),
+        ];
+        command.create(&create_args).unwrap();
+        command.delete(&["test@example.com".to_string()]).unwrap();
+
+        assert!(command.undo(&["0".to_string()]).is_err());
+        assert!(command.undo(&["2".to_string()]).is_ok());
+        assert!(command.get(&["test@example.com".to_string()]).is_err());
+        assert!(command.redo(&[]).is_ok());
+        assert!(command.get(&["test@example.com".to_string()]).is_ok());
+        assert!(command.redo(&["1".to_string(), "2".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_dry_run_does_not_write() {
+        let (_temp_dir, command) = setup_in_temp_dir();
+        let create_args = vec![
+            "--dry-run".to_string(),
+            "test@example.com".to_string(),
+            "testuser".to_string(),
+            "09012345678".to_string(),
+            "25".to_string(),
+        ];
+        assert!(command.create(&create_args).is_ok());
+        assert!(command.get(&["test@example.com".to_string()]).is_err());
+
+        command.create(&create_args[1..]).unwrap();
+        let delete_args = vec!["test@example.com".to_string(), "--dry-run".to_string()];
+        assert!(command.delete(&delete_args).is_ok());
+        assert!(command.get(&["test@example.com".to_string()]).is_ok());
+        assert!(
+            command
+                .delete(&["--dry-run".to_string(), "nobody@example.com".to_string()])
+                .is_err()
+        );
+        assert!(command.create(&create_args).is_err());
+    }
+
+    #[test]
+    fn test_expected_version_option() {
+        let (_temp_dir, command) = setup_in_temp_dir();
+        let create_args = vec![
+            "test@example.com".to_string(),
+            "testuser".to_string(),
+            "09012345678".to_string(),
+            "25".to_string(),
+        ];
+        command.create(&create_args).unwrap();
+
+        let update_args = |version: &str| {
+            let mut args = create_args.clone();
+            args.extend(["--expected-version".to_string(), version.to_string()]);
+            args
+        };
+        assert!(command.update(&update_args("2")).is_err());
+        assert!(command.update(&update_args("one")).is_err());
+        assert!(command.update(&update_args("1")).is_ok());
+        assert!(
+            command
+                .delete(&[
+                    "--expected-version".to_string(),
+                    "1".to_string(),
+                    "test@example.com".to_string()
+                ])
+                .is_err()
+        );
+        assert!(
+            command
+                .delete(&[
+                    "--expected-version".to_string(),
+                    "2".to_string(),
+                    "test@example.com".to_string()
+                ])
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_repair_command() {
+        let (temp_dir, command) = setup_in_temp_dir();
+        assert!(
+            command
+                .create(&[
+                    "a@example.com".to_string(),
+                    "user_a".to_string(),
+                    "09012345678".to_string(),
+                    "25".to_string(),
+                ])
+                .is_ok()
+        );
+        assert!(command.backup(&["create".to_string()]).is_ok());
+        let content = std::fs::read_to_string(&command.data_file).unwrap();
+        std::fs::write(
+            &command.data_file,
+            content.replacen(
+                "\"a@example.com\": {",
+                "\"b@example.com\": {\"email\",\n    \"a@example.com\": {",
+                1,
+            ),
+        )
+        .unwrap();
+        assert!(command.list(&[]).is_err());
+
+        assert!(command.repair(&[]).is_ok());
+        assert!(command.get(&["a@example.com".to_string()]).is_ok());
+        assert!(temp_dir.path().join("userdata.quarantine.jsonl").exists());
+        assert!(command.repair(&["--from-backup".to_string()]).is_ok());
+        assert!(command.list(&[]).is_ok());
+        assert!(command.repair(&["extra".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_check_command() {
+        let (_temp_dir, command) = setup_in_temp_dir();
+        assert!(command.check(&[]).is_ok());
+
+        let user = User {
+            email: "Test@Example.com".to_string(),
+            username: "testuser".to_string(),
+            phone: "09012345678".to_string(),
+            age: 25,
+            ..Default::default()
+        };
+        let users = std::collections::HashMap::from([("test@example.com".to_string(), user)]);
+        std::fs::write(&command.data_file, serde_json::to_string(&users).unwrap()).unwrap();
+
+        assert!(command.check(&[]).is_err());
+        assert!(
+            command
+                .check(&["--fix".to_string(), "--dry-run".to_string()])
+                .is_ok()
+        );
+        assert!(command.check(&[]).is_err());
+        assert!(command.check(&["--fix".to_string()]).is_ok());
+        assert!(command.check(&[]).is_ok());
+        assert_eq!(
+            command.service.get_user("test@example.com").unwrap().phone,
+            "+819012345678"
+        );
+        assert!(command.check(&["--dry-run".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_migrate_command() {
+        let (_temp_dir, command) = setup_in_temp_dir();
+        assert!(command.migrate(&[]).is_ok());
+
+        let fixture = include_str!("../../tests/fixtures/userdata_v1.json");
+        std::fs::write(&command.data_file, fixture).unwrap();
+        assert!(command.get(&["legacy@example.com".to_string()]).is_ok());
+        assert!(command.migrate(&["--dry-run".to_string()]).is_ok());
+        assert_eq!(
+            std::fs::read_to_string(&command.data_file).unwrap(),
+            fixture
+        );
+        assert!(command.migrate(&[]).is_ok());
+        assert!(
+            std::fs::read_to_string(&command.data_file)
+                .unwrap()
+                .contains("schema_version")
+        );
+        assert!(command.migrate(&["extra".to_string()]).is_err());
+
+        std::fs::write(&command.data_file, r#"{"schema_version": 99}"#).unwrap();
+        assert!(command.migrate(&[]).is_err());
+        assert!(command.list(&[]).is_err());
+    }
+
+    #[test]
+    fn test_encrypt_leaves_no_plaintext_backup() {
+        let (temp_dir, mut command) = setup_in_temp_dir();
+        for email in ["a@example.com", "b@example.com"] {
+            command
+                .create(&[
+                    email.to_string(),
+                    "user_a".to_string(),
+                    "09012345678".to_string(),
+                    "25".to_string(),
+                ])
+                .unwrap();
+        }
+        let backups = command.backup_repository().list().unwrap();
+        assert!(!backups.is_empty());
+
+        let key = temp_dir.path().join("data.key");
+        std::fs::write(&key, "11".repeat(32)).unwrap();
+        command.key_source = Some(KeySource::KeyFile(key));
+        command.encrypt(&[]).unwrap();
+
+        let backups = command.backup_repository().list().unwrap();
+        let directory = temp_dir.path().join("userdata.backups");
+        for entry in std::fs::read_dir(directory).unwrap() {
+            let content = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+            assert!(!content.contains("example.com"));
+        }
+        for backup in &backups {
+            assert!(command.backup_repository().verify(&backup.id).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_and_rekey_commands() {
+        let (temp_dir, mut command) = setup_in_temp_dir();
+        command.key_source = None;
+        command
+            .create(&[
+                "a@example.com".to_string(),
+                "user_a".to_string(),
+                "09012345678".to_string(),
+                "25".to_string(),
+            ])
+            .unwrap();
+        assert!(command.encrypt(&[]).is_err());
+
+        let old_key = temp_dir.path().join("old.key");
+        let new_key = temp_dir.path().join("new.key");
+        std::fs::write(&old_key, "11".repeat(32)).unwrap();
+        std::fs::write(&new_key, "22".repeat(32)).unwrap();
+        command.key_source = Some(KeySource::KeyFile(old_key.clone()));
+        assert!(command.decrypt(&[]).is_err());
+        assert!(command.encrypt(&[]).is_ok());
+        let content = std::fs::read_to_string(&command.data_file).unwrap();
+        assert!(!content.contains("a@example.com"));
+        // 変更履歴・監査ログ・操作ジャーナルも暗号化される
+        let sidecars = ["history.jsonl", "audit.jsonl", "journal.json"]
+            .map(|suffix| sibling_path(&command.data_file, suffix));
+        for path in &sidecars {
+            let content = std::fs::read_to_string(path).unwrap();
+            assert!(!content.contains("+819012345678"), "{}", path);
+        }
+        assert!(command.encrypt(&[]).is_err());
+        // サービスのリポジトリには鍵が設定されていないため、読み込めない
+        assert!(command.list(&[]).is_err());
+
+        let rekey_args = vec![
+            "--key-file".to_string(),
+            new_key.to_str().unwrap().to_string(),
+        ];
+        assert!(command.rekey(&[]).is_err());
+        assert!(command.rekey(&rekey_args).is_ok());
+        assert!(command.decrypt(&[]).is_err());
+
+        command.key_source = Some(KeySource::KeyFile(new_key));
+        assert!(command.decrypt(&[]).is_ok());
+        assert!(command.list(&[]).is_ok());
+        assert!(
+            std::fs::read_to_string(&sidecars[0])
+                .unwrap()
+                .contains("+819012345678")
+        );
+        assert!(command.rekey(&rekey_args).is_err());
+        assert!(command.decrypt(&["extra".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_redacted_view_of_encrypted_fields() {
+        let (_temp_dir, command) = setup_in_temp_dir();
+        let command = UserCommand {
+            service: command
+                .service
+                .with_field_encryption(FieldCipher::new([5; 32], vec![EncryptedField::Phone])),
+            ..command
+        };
+        command
+            .create(&[
+                "a@example.com".to_string(),
+                "user_a".to_string(),
+                "09012345678".to_string(),
+                "25".to_string(),
+            ])
+            .unwrap();
+        assert!(command.get(&["a@example.com".to_string()]).is_ok());
+
+        let without_key = UserCommand::with_data_file(&command.data_file);
+        assert!(without_key.get(&["a@example.com".to_string()]).is_err());
+        let redacted = vec!["a@example.com".to_string(), "--redacted".to_string()];
+        assert!(without_key.get(&redacted).is_ok());
+        assert!(without_key.list(&["--redacted".to_string()]).is_ok());
+        assert!(without_key.list(&["extra".to_string()]).is_err());
+        assert!(without_key.encrypt_fields(&[]).is_err());
+        assert!(command.encrypt_fields(&[]).is_ok());
+    }
+
+    #[test]
+    fn test_masking_is_on_by_default_for_lists() {
+        let (_temp_dir, command) = setup_in_temp_dir();
+        assert_eq!(
+            command.masker(true).email("john@example.com"),
+            "j***@example.com"
+        );
+
+        let command = command.with_reveal();
+        assert_eq!(
+            command.masker(true).email("john@example.com"),
+            "john@example.com"
+        );
+        assert_eq!(
+            command.masker(false).phone("090-1234-5678"),
+            "090-1234-5678"
+        );
+    }
+
+    #[test]
+    fn test_backup_command() {
+        let (temp_dir, command) = setup_in_temp_dir();
+        assert!(command.backup(&["create".to_string()]).is_err());
+
+        let create_args = vec![
+            "test@example.com".to_string(),
+            "testuser".to_string(),
+            "09012345678".to_string(),
+            "25".to_string(),
+        ];
+        command.create(&create_args).unwrap();
+        command.delete(&["test@example.com".to_string()]).unwrap();
+        assert!(command.backup(&["create".to_string()]).is_ok());
+        assert!(command.backup(&["list".to_string()]).is_ok());
+
+        // 削除する前の状態（作成直後）のバックアップを書き戻す
+        let backups = BackupRepositoryImpl::with_directory(
+            &command.data_file,
+            temp_dir.path().join("userdata.backups"),
+        );
+        let id = backups.list().unwrap()[0].id.clone();
+        assert!(command.backup(&["restore".to_string(), id]).is_ok());
+        assert!(command.get(&["test@example.com".to_string()]).is_ok());
+
+        assert!(
+            command
+                .backup(&["restore".to_string(), "missing".to_string()])
+                .is_err()
+        );
+        assert!(command.backup(&[]).is_err());
+    }
+
+    #[test]
+    fn test_privacy_export_and_erase_commands() {
+        let (temp_dir, mut command) = setup_in_temp_dir();
+        command.signing_key = Some(b"secret".to_vec());
+        for email in ["a@example.com", "b@example.com"] {
+            command
+                .create(&[
+                    email.to_string(),
+                    "testuser".to_string(),
+                    "09012345678".to_string(),
+                    "25".to_string(),
+                ])
+                .unwrap();
+        }
+        let output = temp_dir.path().join("export.json");
+        let export_args = vec![
+            "export".to_string(),
+            "a@example.com".to_string(),
+            "--output".to_string(),
+            output.to_str().unwrap().to_string(),
+        ];
+        command.privacy(&export_args).unwrap();
+        let export: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+        assert_eq!(export["user"]["email"], "a@example.com");
+        assert_eq!(export["history"].as_array().unwrap().len(), 1);
+        // 2人目の作成前に作られたバックアップに含まれている
+        assert_eq!(export["backups"].as_array().unwrap().len(), 1);
+
+        command
+            .privacy(&["erase".to_string(), "a@example.com".to_string()])
+            .unwrap();
+        assert!(command.get(&["a@example.com".to_string()]).is_err());
+        assert!(command.get(&["b@example.com".to_string()]).is_ok());
+        command.privacy(&export_args).unwrap();
+        let export: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+        assert!(export["user"].is_null());
+        assert!(export["backups"].as_array().unwrap().is_empty());
+        assert!(export["audit"].as_array().unwrap().is_empty());
+
+        let receipts = command.receipt_repository().find_all().unwrap();
+        assert_eq!(receipts.len(), 1);
+        assert!(receipts[0].verify(b"secret").unwrap());
+        assert!(receipts[0].record_deleted);
+        // 受領証の対象は鍵なしのハッシュからは特定できない
+        assert_ne!(
+            receipts[0].subject,
+            hex::encode(Sha256::digest("a@example.com".as_bytes()))
+        );
+        assert!(command.privacy(&["receipts".to_string()]).is_ok());
+        assert!(
+            command
+                .privacy(&["receipts".to_string(), "A@example.com".to_string()])
+                .is_ok()
+        );
+
+        // 2回目は消去するデータがない
+        assert!(
+            command
+                .privacy(&["erase".to_string(), "a@example.com".to_string()])
+                .is_err()
+        );
+        command.signing_key = None;
+        assert!(
+            command
+                .privacy(&["erase".to_string(), "b@example.com".to_string()])
+                .is_err()
+        );
+        assert!(command.privacy(&["erase".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_anonymize_command() {
+        let (temp_dir, mut command) = setup_in_temp_dir();
+        command.anonymizer = None;
+        command
+            .create(&[
+                "a@example.com".to_string(),
+                "user_a".to_string(),
+                "09012345678".to_string(),
+                "25".to_string(),
+            ])
+            .unwrap();
+        let output = temp_dir.path().join("anonymized.json");
+        let args = vec![
+            command.data_file.clone(),
+            output.to_str().unwrap().to_string(),
+        ];
+        assert!(command.anonymize(&args).is_err());
+
+        command.anonymizer = Some(Anonymizer::new("salt"));
+        assert!(command.anonymize(&args).is_ok());
+        let content = fs::read_to_string(&output).unwrap();
+        assert!(!content.contains("a@example.com"));
+        assert!(!content.contains("user_a"));
+        let anonymized = UserCommand::with_data_file(output.to_str().unwrap());
+        assert_eq!(anonymized.service.list_users().unwrap().len(), 1);
+
+        // 出力先が既に存在する場合は上書きしない
+        assert!(command.anonymize(&args).is_err());
+        assert!(
+            command
+                .anonymize(&[
+                    temp_dir
+                        .path()
+                        .join("missing.json")
+                        .to_str()
+                        .unwrap()
+                        .to_string(),
+                    temp_dir
+                        .path()
+                        .join("other.json")
+                        .to_str()
+                        .unwrap()
+                        .to_string(),
+                ])
+                .is_err()
+        );
+        assert!(command.anonymize(&args[..1]).is_err());
+    }
+
+    #[test]
+    fn test_seed_command() {
+        let (_temp_dir, command) = setup_in_temp_dir();
+        let args = |count: &str, seed: &str| {
+            vec![
+                "--count".to_string(),
+                count.to_string(),
+                "--seed".to_string(),
+                seed.to_string(),
+            ]
+        };
+        assert!(command.seed(&args("20", "42")).is_ok());
+        let users = command.service.list_users().unwrap();
+        assert_eq!(users.len(), 20);
+        let expected = Seeder::new(42).generate(20);
+        assert!(users.iter().any(|user| user.email == expected[0].email));
+        // 件数とシードは1件の記録として監査ログに残り、取り消すことができる
+        let entries = command.service.audit_log(&AuditQuery::default()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].details.as_deref(), Some("count=20 seed=42"));
+        assert!(command.undo(&[]).is_ok());
+        assert!(command.service.list_users().unwrap().is_empty());
+        assert!(command.redo(&[]).is_ok());
+
+        // 同じシードでは同じメールアドレスになるため、重複して失敗する
+        assert!(command.seed(&args("20", "42")).is_err());
+        assert_eq!(command.service.list_users().unwrap().len(), 20);
+        assert!(command.seed(&args("0", "1")).is_err());
+        assert!(command.seed(&args("5", "abc")).is_err());
+        assert!(command.seed(&[]).is_err());
     }
 }
@@ -7,35 +7,152 @@
 //! - ユーザー情報の更新
 //! - ユーザー一覧の表示
 //! - 特定ユーザーの情報表示
-//! - ユーザーの削除
+//! - ユーザーの検索
+//! - 重複登録の可能性があるユーザーの検出
+//! - ユーザーの統合
+//! - ユーザーの削除（ゴミ箱への移動）
+//! - ゴミ箱の一覧表示、ユーザーの復元と完全な削除
+//! - 変更履歴の参照、過去の状態の表示と巻き戻し
+//! - 直前の操作の取り消しとやり直し
+//! - 書き込みを行わずに変更内容を確認するドライラン
+//! - 監査ログの検索と改ざんの検証
+//! - イベントログからのユーザーデータの再構築
+//! - ユーザーデータのファイルの自動バックアップと復元
+//! - 壊れたユーザーデータのファイルの修復
+//! - 保存されているレコードの整合性の検査と修正
+//! - ユーザーデータのファイル形式の移行
+//! - ユーザーデータのファイルの暗号化、復号と鍵の変更
+//! - 電話番号などの一部のフィールドの暗号化と伏せ字での表示
+//! - 一覧や端末以外への出力でのメールアドレス・電話番号の伏せ字表示
+//! - 本人からの開示請求に応じたデータの書き出しと、消去請求に応じた消去
+//! - 個人を特定できる値を仮名に置き換えた、共有用のユーザーデータの作成
+//! - 負荷試験やデモのための架空のユーザーの一括作成
 
 use rust_learn::commands::user_command::UserCommand;
+use rust_learn::repositories::field_encryption::FieldCipher;
+use rust_learn::repositories::user_repository::StorageBackend;
+use rust_learn::services::masking::{MaskStrategy, Masker};
 use std::env;
 
 /// コマンドの使用方法を標準出力に表示します。
 fn print_usage() {
-    println!("Usage:");
-    println!("  create <email> <username> <phone> <age>");
-    println!("  update <email> <username> <phone> <age>");
-    println!("  list");
-    println!("  get <email>");
-    println!("  delete <email>");
+    println!("Usage: rust-learn [--actor <name>] [--reveal] <command> [args...]");
+    println!();
+    println!("Commands:");
+    println!("  create [--dry-run] <email> <username> <phone> <age>");
+    println!(
+        "  update [--dry-run] [--expected-version <version>] <email> <username> <phone> <age>"
+    );
+    println!("  list [--redacted]");
+    println!("  get <email> [--at <timestamp> | --redacted]");
+    println!("  search <term>");
+    println!("  duplicates [--threshold <score>]");
+    println!(
+        "  merge [--dry-run] <keep-email> <drop-email> [--strategy keep|newest|ask] \
+         [--username <strategy>] [--phone <strategy>] [--age <strategy>]"
+    );
+    println!("  delete [--dry-run] [--expected-version <version>] <email>");
+    println!("  trash list");
+    println!("  restore [--dry-run] <email>");
+    println!("  purge [--dry-run] --older-than <duration>");
+    println!("  history <email>");
+    println!("  revert <email> <revision>");
+    println!("  undo [N]");
+    println!("  redo [N]");
+    println!(
+        "  audit [--user <email>] [--actor <name>] [--since <timestamp>] [--until <timestamp>]"
+    );
+    println!("  audit verify");
+    println!("  rebuild");
+    println!("  backup create|list|restore <id>");
+    println!("  repair [--from-backup]");
+    println!("  check [--fix] [--dry-run]");
+    println!("  migrate [--dry-run]");
+    println!("  encrypt");
+    println!("  decrypt");
+    println!("  rekey --key-file <path> | --passphrase-env <name>");
+    println!("  encrypt-fields");
+    println!("  privacy export <email> [--output <path>] | erase <email> | receipts [<email>]");
+    println!("  anonymize <input> <output>");
+    println!("  seed --count <N> [--seed <S>]   (the seed defaults to 0 and is printed)");
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+
+    // `--reveal`はどの位置に指定しても、メールアドレス・電話番号を伏せずに表示する指定とする
+    let reveal = args.iter().any(|arg| arg == "--reveal");
+    args.retain(|arg| arg != "--reveal");
+
+    // コマンド名より前に指定された`--actor`は、変更履歴と監査ログに記録する操作者とする
+    let mut actor = None;
+    if args.get(1).is_some_and(|arg| arg == "--actor") {
+        if args.len() < 3 {
+            eprintln!("Error: Missing value for --actor");
+            return;
+        }
+        actor = Some(args.remove(2));
+        args.remove(1);
+    }
     if args.len() < 2 {
         print_usage();
         return;
     }
 
-    let command = UserCommand::new();
+    let strategy = match MaskStrategy::from_env() {
+        Ok(strategy) => strategy,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return;
+        }
+    };
+    // エラーメッセージも、ログに残る場合はメールアドレス・電話番号を伏せて表示する
+    let errors = Masker::for_errors(strategy, reveal);
+    if let Err(e) = StorageBackend::from_env() {
+        eprintln!("Error: {}", errors.message(&e));
+        return;
+    }
+    if let Err(e) = FieldCipher::from_env() {
+        eprintln!("Error: {}", errors.message(&e));
+        return;
+    }
+
+    let mut command = UserCommand::new();
+    if let Some(actor) = actor {
+        command = command.with_actor(&actor);
+    }
+    if reveal {
+        command = command.with_reveal();
+    }
     let result = match args[1].as_str() {
         "create" => command.create(&args[2..]),
         "update" => command.update(&args[2..]),
-        "list" => command.list(),
+        "list" => command.list(&args[2..]),
         "get" => command.get(&args[2..]),
+        "search" => command.search(&args[2..]),
+        "duplicates" => command.duplicates(&args[2..]),
+        "merge" => command.merge(&args[2..]),
         "delete" => command.delete(&args[2..]),
+        "trash" => command.trash(&args[2..]),
+        "restore" => command.restore(&args[2..]),
+        "purge" => command.purge(&args[2..]),
+        "history" => command.history(&args[2..]),
+        "revert" => command.revert(&args[2..]),
+        "undo" => command.undo(&args[2..]),
+        "redo" => command.redo(&args[2..]),
+        "audit" => command.audit(&args[2..]),
+        "rebuild" => command.rebuild(&args[2..]),
+        "backup" => command.backup(&args[2..]),
+        "repair" => command.repair(&args[2..]),
+        "check" => command.check(&args[2..]),
+        "migrate" => command.migrate(&args[2..]),
+        "encrypt" => command.encrypt(&args[2..]),
+        "decrypt" => command.decrypt(&args[2..]),
+        "rekey" => command.rekey(&args[2..]),
+        "encrypt-fields" => command.encrypt_fields(&args[2..]),
+        "privacy" => command.privacy(&args[2..]),
+        "anonymize" => command.anonymize(&args[2..]),
+        "seed" => command.seed(&args[2..]),
         _ => {
             print_usage();
             Ok(())
@@ -43,6 +160,6 @@ fn main() {
     };
 
     if let Err(e) = result {
-        eprintln!("Error: {}", e);
+        eprintln!("Error: {}", errors.message(&e));
     }
 }
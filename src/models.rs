@@ -1 +1,9 @@
+pub mod audit;
+pub mod backup;
+pub mod data_file;
+pub mod event;
+pub mod integrity;
+pub mod journal;
+pub mod privacy;
+pub mod revision;
 pub mod user;
@@ -0,0 +1,118 @@
+//! 監査ログの記録を表す構造体の定義
+
+use crate::models::revision::RevisionOperation;
+use crate::models::user::User;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// 操作の結果
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "result", content = "error", rename_all = "lowercase")]
+pub enum AuditOutcome {
+    /// 操作に成功した
+    Success,
+    /// 操作に失敗した（エラーの内容を保持する）
+    Failure(String),
+}
+
+/// 監査ログの1件の記録を表す構造体
+///
+/// ユーザーを変更する操作のたびに、成功・失敗を問わず記録されます。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditEntry {
+    /// 操作が行われた日時
+    pub timestamp: DateTime<Utc>,
+    /// 操作を行った操作者
+    pub actor: String,
+    /// 操作の種類
+    pub operation: RevisionOperation,
+    /// 対象ユーザーのメールアドレス
+    ///
+    /// 成功した操作では正規化後のメールアドレス、失敗した操作では入力されたままの値です。
+    /// 複数のユーザーを対象とする操作が失敗した場合は`None`になります。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// 操作前のユーザーの状態
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<User>,
+    /// 操作後のユーザーの状態
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<User>,
+    /// 複数のユーザーへの変更をまとめて1件で記録した場合の内容（例: `count=100 seed=42`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+    /// 操作の結果
+    #[serde(flatten)]
+    pub outcome: AuditOutcome,
+    /// 直前の記録（JSON Linesの1行）のSHA-256ハッシュ（16進数）
+    ///
+    /// 最初の記録では0が64個並んだ値になります。追記時にリポジトリが設定し、
+    /// ハッシュチェーンの導入前に記録されたものでは`None`になります。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prev_hash: Option<String>,
+    /// `hmac`以外の項目をシリアライズした値に対するHMAC-SHA256（16進数）
+    ///
+    /// HMACの鍵が設定されている場合に、追記時にリポジトリが設定します。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hmac: Option<String>,
+}
+
+/// 監査ログのハッシュチェーンが途切れている箇所
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokenLink {
+    /// 問題が見つかった行番号（1から始まる）
+    pub line: usize,
+    /// 問題の内容
+    pub reason: String,
+}
+
+/// 監査ログの検証結果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditVerification {
+    /// 検証した記録の件数
+    pub entries: usize,
+    /// ハッシュチェーンの導入前に記録された、先頭の検証できない記録の件数
+    pub unchained_entries: usize,
+    /// 最後の記録のハッシュ（記録がない場合は`None`）
+    ///
+    /// 末尾の記録が削除されていないことを後で確認できるよう、控えておくことができます。
+    pub last_hash: Option<String>,
+    /// 最初に見つかったチェーンの途切れ（問題がない場合は`None`）
+    pub broken: Option<BrokenLink>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_entry_serialization() {
+        let entry = AuditEntry {
+            timestamp: Utc::now(),
+            actor: "tester".to_string(),
+            operation: RevisionOperation::Update,
+            email: Some("test@example.com".to_string()),
+            before: None,
+            after: None,
+            details: None,
+            outcome: AuditOutcome::Failure("User not found".to_string()),
+            prev_hash: None,
+            hmac: None,
+        };
+        let json = serde_json::to_string(&entry).unwrap();
+
+        assert!(json.contains(r#""result":"failure","error":"User not found""#));
+        assert!(!json.contains("before"));
+        assert!(!json.contains("prev_hash"));
+        assert_eq!(serde_json::from_str::<AuditEntry>(&json).unwrap(), entry);
+
+        let success = AuditEntry {
+            details: None,
+            outcome: AuditOutcome::Success,
+            ..entry
+        };
+        let json = serde_json::to_string(&success).unwrap();
+        assert!(json.contains(r#""result":"success""#));
+        assert_eq!(serde_json::from_str::<AuditEntry>(&json).unwrap(), success);
+    }
+}
@@ -0,0 +1,14 @@
+//! ユーザーデータのバックアップを表す構造体の定義
+
+use chrono::{DateTime, Utc};
+
+/// ユーザーデータのファイルのバックアップ
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backup {
+    /// バックアップを識別するID（作成日時から作られ、時刻順に並ぶ）
+    pub id: String,
+    /// バックアップが作成された日時
+    pub created_at: DateTime<Utc>,
+    /// バックアップのファイルサイズ（バイト）
+    pub size: u64,
+}
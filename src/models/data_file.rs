@@ -0,0 +1,34 @@
+//! ユーザーデータのファイル全体を表す構造体の定義
+
+use crate::models::user::User;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// ユーザーデータのファイル全体
+///
+/// ユーザーの一覧を、ファイル形式のバージョンとメタデータで包んで保存します。
+/// バージョン1の形式ではユーザーの一覧（メールアドレスをキーとするオブジェクト）が
+/// そのまま保存されていました。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DataFile {
+    /// ファイル形式のバージョン
+    pub schema_version: u32,
+    /// ファイルに関する情報
+    #[serde(default)]
+    pub metadata: DataFileMetadata,
+    /// メールアドレスをキーとするユーザーの一覧
+    #[serde(default)]
+    pub users: HashMap<String, User>,
+}
+
+/// ユーザーデータのファイルに関する情報
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DataFileMetadata {
+    /// ファイルを書き込んだアプリケーションの名前とバージョン（例: "rust-learn 0.1.0"）
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub generator: String,
+    /// ファイルが最後に書き込まれた日時
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
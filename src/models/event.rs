@@ -0,0 +1,58 @@
+//! イベントソーシング方式の保存で使用するイベントの定義
+
+use crate::models::user::User;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// ユーザーデータに対して発生したイベント
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type")]
+pub enum UserEvent {
+    /// ユーザーが作成された
+    UserCreated {
+        /// 作成されたユーザー
+        user: User,
+    },
+    /// ユーザーが更新された
+    UserUpdated {
+        /// 更新後のユーザー
+        user: User,
+    },
+    /// ユーザーが削除された
+    UserDeleted {
+        /// 削除されたユーザーのメールアドレス
+        email: String,
+    },
+}
+
+/// イベントログに記録されたイベント
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StoredEvent {
+    /// 1から始まる通し番号
+    pub sequence: u64,
+    /// イベントが記録された日時
+    pub timestamp: DateTime<Utc>,
+    /// イベントの内容
+    #[serde(flatten)]
+    pub event: UserEvent,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stored_event_serialization() {
+        let stored = StoredEvent {
+            sequence: 3,
+            timestamp: Utc::now(),
+            event: UserEvent::UserDeleted {
+                email: "test@example.com".to_string(),
+            },
+        };
+        let json = serde_json::to_string(&stored).unwrap();
+
+        assert!(json.contains(r#""type":"UserDeleted","email":"test@example.com""#));
+        assert_eq!(serde_json::from_str::<StoredEvent>(&json).unwrap(), stored);
+    }
+}
@@ -0,0 +1,132 @@
+//! 保存されているユーザーデータの整合性の検査結果を表す構造体の定義
+
+use std::fmt;
+
+/// 1件のレコードで見つかった整合性の問題の種類
+#[derive(Debug, Clone, PartialEq)]
+pub enum IntegrityProblem {
+    /// 保存時のキーとレコードのメールアドレスが一致しない
+    KeyMismatch {
+        /// レコードに記録されているメールアドレス
+        email: String,
+    },
+    /// フィールドの値が現在の規則に違反している
+    InvalidField {
+        /// フィールド名
+        field: &'static str,
+        /// 違反の内容
+        reason: String,
+    },
+    /// フィールドの値が正規化されていない
+    NotNormalized {
+        /// フィールド名
+        field: &'static str,
+        /// 保存されている値
+        value: String,
+        /// 正規化後の値
+        normalized: String,
+    },
+    /// 正規化後のメールアドレスが他のレコードと重複している
+    DuplicateEmail {
+        /// 重複している他のレコードのキー
+        others: Vec<String>,
+    },
+    /// 正規化後の電話番号が他のレコードと重複している
+    DuplicatePhone {
+        /// 正規化後の電話番号
+        phone: String,
+        /// 重複している他のレコードのキー
+        others: Vec<String>,
+    },
+}
+
+impl fmt::Display for IntegrityProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegrityProblem::KeyMismatch { email } => {
+                write!(f, "key does not match email {}", email)
+            }
+            IntegrityProblem::InvalidField { field, reason } => {
+                write!(f, "invalid {}: {}", field, reason)
+            }
+            IntegrityProblem::NotNormalized {
+                field,
+                value,
+                normalized,
+            } => write!(f, "{} {} is not normalized ({})", field, value, normalized),
+            IntegrityProblem::DuplicateEmail { others } => {
+                write!(f, "same email as {}", others.join(", "))
+            }
+            IntegrityProblem::DuplicatePhone { phone, others } => {
+                write!(f, "same phone {} as {}", phone, others.join(", "))
+            }
+        }
+    }
+}
+
+/// 1件のレコードで見つかった整合性の問題
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntegrityIssue {
+    /// 問題のあるレコードの保存時のキー
+    pub key: String,
+    /// 問題の種類
+    pub problem: IntegrityProblem,
+    /// 修正したかどうか
+    pub fixed: bool,
+}
+
+/// ユーザーデータ全体の整合性の検査結果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IntegrityReport {
+    /// 検査したレコードの件数
+    pub checked: usize,
+    /// 見つかった問題（キーの昇順）
+    pub issues: Vec<IntegrityIssue>,
+}
+
+impl IntegrityReport {
+    /// 修正されずに残っている問題の件数を返します。
+    ///
+    /// # 戻り値
+    /// * `usize` - 修正されていない問題の件数
+    pub fn remaining(&self) -> usize {
+        self.issues.iter().filter(|issue| !issue.fixed).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_problem_display_and_remaining() {
+        let report = IntegrityReport {
+            checked: 2,
+            issues: vec![
+                IntegrityIssue {
+                    key: "A@example.com".to_string(),
+                    problem: IntegrityProblem::NotNormalized {
+                        field: "email",
+                        value: "A@example.com".to_string(),
+                        normalized: "a@example.com".to_string(),
+                    },
+                    fixed: true,
+                },
+                IntegrityIssue {
+                    key: "b@example.com".to_string(),
+                    problem: IntegrityProblem::InvalidField {
+                        field: "age",
+                        reason: "Age must be between 0 and 150".to_string(),
+                    },
+                    fixed: false,
+                },
+            ],
+        };
+
+        assert_eq!(
+            report.issues[0].problem.to_string(),
+            "email A@example.com is not normalized (a@example.com)"
+        );
+        assert_eq!(report.remaining(), 1);
+    }
+}
@@ -0,0 +1,108 @@
+//! 操作の取り消し・やり直しに使用する操作ジャーナルの定義
+
+use crate::models::revision::RevisionOperation;
+use crate::models::user::User;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// 1件のレコードに対する変更
+///
+/// 変更前と変更後の状態を両方持つため、取り消し（変更後から変更前へ）にも
+/// やり直し（変更前から変更後へ）にも使用できます。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JournalChange {
+    /// 変更前のレコード（作成された場合は`None`）
+    pub before: Option<User>,
+    /// 変更後のレコード（完全に削除された場合は`None`）
+    pub after: Option<User>,
+}
+
+impl JournalChange {
+    /// 変更の対象となったレコードのメールアドレスを返します。
+    ///
+    /// # 戻り値
+    /// * `&str` - 変更後のレコードのメールアドレス（ない場合は変更前のもの）
+    pub fn email(&self) -> &str {
+        self.after
+            .as_ref()
+            .or(self.before.as_ref())
+            .map_or("", |user| user.email.as_str())
+    }
+}
+
+/// 1回の操作で行われた変更のまとまり
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JournalEntry {
+    /// 操作が行われた日時
+    pub timestamp: DateTime<Utc>,
+    /// 操作を行った操作者
+    pub actor: String,
+    /// 操作の種類
+    pub operation: RevisionOperation,
+    /// 操作によるレコードごとの変更（行われた順）
+    pub changes: Vec<JournalChange>,
+}
+
+/// 取り消し・やり直しのための操作ジャーナル
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Journal {
+    /// 取り消しできる操作（新しいものが末尾）
+    #[serde(default)]
+    pub undo: Vec<JournalEntry>,
+    /// やり直しできる操作（最後に取り消したものが末尾）
+    #[serde(default)]
+    pub redo: Vec<JournalEntry>,
+}
+
+impl Journal {
+    /// 新しい操作を記録します。
+    ///
+    /// 新しい操作を記録するとやり直しできる操作は破棄され、
+    /// 取り消しできる操作が`limit`件を超えた場合は古いものから破棄されます。
+    ///
+    /// # 引数
+    /// * `entry` - 記録する操作
+    /// * `limit` - 保持する取り消しできる操作の上限
+    pub fn record(&mut self, entry: JournalEntry, limit: usize) {
+        self.redo.clear();
+        self.undo.push(entry);
+        if self.undo.len() > limit {
+            let excess = self.undo.len() - limit;
+            self.undo.drain(..excess);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(operation: RevisionOperation) -> JournalEntry {
+        JournalEntry {
+            timestamp: Utc::now(),
+            actor: "tester".to_string(),
+            operation,
+            changes: vec![JournalChange {
+                before: None,
+                after: Some(User {
+                    email: "test@example.com".to_string(),
+                    ..Default::default()
+                }),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_record_clears_redo_and_keeps_limit() {
+        let mut journal = Journal {
+            undo: vec![entry(RevisionOperation::Create)],
+            redo: vec![entry(RevisionOperation::Update)],
+        };
+        journal.record(entry(RevisionOperation::Delete), 1);
+
+        assert!(journal.redo.is_empty());
+        assert_eq!(journal.undo.len(), 1);
+        assert_eq!(journal.undo[0].operation, RevisionOperation::Delete);
+        assert_eq!(journal.undo[0].changes[0].email(), "test@example.com");
+    }
+}
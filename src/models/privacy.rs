@@ -0,0 +1,208 @@
+//! 本人からの開示請求・消去請求に使用する構造体の定義
+
+use crate::models::audit::AuditEntry;
+use crate::models::journal::JournalEntry;
+use crate::models::revision::Revision;
+use crate::models::user::User;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// 消去した値の代わりに記録する文字列
+pub const ERASED_VALUE: &str = "[erased]";
+
+/// バックアップに含まれていたユーザーの状態
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BackupCopy {
+    /// バックアップのID
+    pub backup_id: String,
+    /// バックアップが作成された日時
+    pub created_at: DateTime<Utc>,
+    /// バックアップに含まれていたユーザー（暗号化したフィールドは保存されたままの値）
+    pub user: User,
+}
+
+/// 1人のユーザーについて保存されている全てのデータ
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SubjectExport {
+    /// 書き出した日時
+    pub exported_at: DateTime<Utc>,
+    /// 対象として扱ったメールアドレス（正規化後のアドレスと別名）
+    pub emails: Vec<String>,
+    /// 現在のレコード（ゴミ箱にあるものを含む。存在しない場合は`None`）
+    pub user: Option<User>,
+    /// 変更履歴のリビジョン
+    pub history: Vec<Revision>,
+    /// 監査ログの記録
+    pub audit: Vec<AuditEntry>,
+    /// 操作ジャーナルに残っている操作
+    pub journal: Vec<JournalEntry>,
+    /// バックアップに含まれていた状態
+    pub backups: Vec<BackupCopy>,
+    /// 修復時に隔離したファイルの断片
+    pub quarantine: Vec<String>,
+}
+
+/// ユーザーのデータを消去したことを示す受領証
+///
+/// メールアドレスそのものは含めず、署名と同じ鍵によるHMAC-SHA256で対象を示します。
+/// 鍵を知らない者は、ありそうなメールアドレスを総当たりしても対象を特定できません。
+/// `signature`は`signature`以外の項目をシリアライズした値に対するHMAC-SHA256です。
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ErasureReceipt {
+    /// 対象のメールアドレス（正規化後）の、署名の鍵によるHMAC-SHA256（16進数）
+    pub subject: String,
+    /// 消去した日時
+    pub erased_at: DateTime<Utc>,
+    /// 消去を行った操作者
+    pub actor: String,
+    /// 現在のレコードを削除したかどうか
+    pub record_deleted: bool,
+    /// 削除した変更履歴のリビジョンの件数
+    pub history_revisions: usize,
+    /// 対象の値を消去した監査ログの記録の件数
+    pub audit_entries: usize,
+    /// 監査ログの最後の記録のハッシュ（消去前）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit_head_before: Option<String>,
+    /// 監査ログの最後の記録のハッシュ（消去後）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit_head_after: Option<String>,
+    /// 削除した操作ジャーナルの操作の件数
+    pub journal_entries: usize,
+    /// 対象のユーザーを取り除いたバックアップのID
+    pub backups: Vec<String>,
+    /// 削除した隔離ファイルの断片の件数
+    pub quarantine_fragments: usize,
+    /// 受領証の署名（16進数）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl ErasureReceipt {
+    /// メールアドレスから受領証の`subject`に記録する値を求めます。
+    ///
+    /// # 引数
+    /// * `email` - 正規化後のメールアドレス
+    /// * `key` - 受領証の署名に使用する鍵
+    ///
+    /// # 戻り値
+    /// * `Ok(String)` - HMAC-SHA256（16進数）
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 鍵をHMACの鍵として使用できない場合
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_learn::models::privacy::ErasureReceipt;
+    ///
+    /// let subject = ErasureReceipt::subject_of("john@example.com", b"secret").unwrap();
+    /// assert_eq!(subject.len(), 64);
+    /// assert_eq!(subject, ErasureReceipt::subject_of("john@example.com", b"secret").unwrap());
+    /// assert_ne!(subject, ErasureReceipt::subject_of("john@example.com", b"other").unwrap());
+    /// ```
+    pub fn subject_of(email: &str, key: &[u8]) -> Result<String, String> {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key)
+            .map_err(|e| format!("Invalid signing key: {}", e))?;
+        mac.update(email.as_bytes());
+        Ok(hex::encode(mac.finalize().into_bytes()))
+    }
+
+    /// 何も消去していないかどうかを返します。
+    ///
+    /// # 戻り値
+    /// * `bool` - 消去したデータが1件もない場合は`true`
+    pub fn is_empty(&self) -> bool {
+        !self.record_deleted
+            && self.history_revisions == 0
+            && self.audit_entries == 0
+            && self.journal_entries == 0
+            && self.backups.is_empty()
+            && self.quarantine_fragments == 0
+    }
+
+    /// 受領証に署名します。
+    ///
+    /// # 引数
+    /// * `key` - 署名に使用する鍵
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 署名に成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * JSONのシリアライズに失敗した場合
+    pub fn sign(&mut self, key: &[u8]) -> Result<(), String> {
+        let mac = self.mac(key)?;
+        self.signature = Some(hex::encode(mac.finalize().into_bytes()));
+        Ok(())
+    }
+
+    /// 受領証の署名を検証します。
+    ///
+    /// # 引数
+    /// * `key` - 署名に使用した鍵
+    ///
+    /// # 戻り値
+    /// * `Ok(bool)` - 署名があり、内容と一致する場合は`true`
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * JSONのシリアライズに失敗した場合
+    pub fn verify(&self, key: &[u8]) -> Result<bool, String> {
+        let Some(signature) = &self.signature else {
+            return Ok(false);
+        };
+        let mac = self.mac(key)?;
+        Ok(hex::decode(signature).is_ok_and(|expected| mac.verify_slice(&expected).is_ok()))
+    }
+
+    /// 受領証の内容を入力済みのHMACを作成します。
+    ///
+    /// # 引数
+    /// * `key` - HMACの鍵
+    ///
+    /// # 戻り値
+    /// * `Ok(Hmac<Sha256>)` - `signature`以外の項目を入力したHMAC-SHA256
+    ///
+    /// # エラー
+    /// * JSONのシリアライズに失敗した場合
+    fn mac(&self, key: &[u8]) -> Result<Hmac<Sha256>, String> {
+        let unsigned = ErasureReceipt {
+            signature: None,
+            ..self.clone()
+        };
+        let content = serde_json::to_string(&unsigned)
+            .map_err(|e| format!("Failed to serialize erasure receipt: {}", e))?;
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key)
+            .map_err(|e| format!("Invalid signing key: {}", e))?;
+        mac.update(content.as_bytes());
+        Ok(mac)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify_receipt() {
+        let mut receipt = ErasureReceipt {
+            subject: ErasureReceipt::subject_of("a@example.com", b"secret").unwrap(),
+            actor: "admin".to_string(),
+            record_deleted: true,
+            history_revisions: 2,
+            ..Default::default()
+        };
+        assert!(!receipt.verify(b"secret").unwrap());
+
+        receipt.sign(b"secret").unwrap();
+        assert!(receipt.verify(b"secret").unwrap());
+        assert!(!receipt.verify(b"other").unwrap());
+
+        receipt.history_revisions = 1;
+        assert!(!receipt.verify(b"secret").unwrap());
+    }
+}
@@ -0,0 +1,203 @@
+//! ユーザーの変更履歴（リビジョン）を表す構造体の定義
+
+use crate::models::user::User;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// リビジョンを記録した操作の種類
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RevisionOperation {
+    /// 履歴の記録が始まる前から存在していたユーザーの状態
+    Baseline,
+    /// ユーザーの作成
+    Create,
+    /// ユーザー情報の更新
+    Update,
+    /// ゴミ箱への移動
+    Delete,
+    /// ゴミ箱からの復元
+    Restore,
+    /// 別のユーザーとの統合
+    Merge,
+    /// ゴミ箱からの完全な削除
+    Purge,
+    /// 過去のリビジョンへの巻き戻し
+    Revert,
+    /// 直前の操作の取り消し
+    Undo,
+    /// 取り消した操作のやり直し
+    Redo,
+    /// 整合性の検査による修正
+    Fix,
+    /// 生成したユーザーの一括作成
+    Seed,
+}
+
+impl fmt::Display for RevisionOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RevisionOperation::Baseline => "baseline",
+            RevisionOperation::Create => "create",
+            RevisionOperation::Update => "update",
+            RevisionOperation::Delete => "delete",
+            RevisionOperation::Restore => "restore",
+            RevisionOperation::Merge => "merge",
+            RevisionOperation::Purge => "purge",
+            RevisionOperation::Revert => "revert",
+            RevisionOperation::Undo => "undo",
+            RevisionOperation::Redo => "redo",
+            RevisionOperation::Fix => "fix",
+            RevisionOperation::Seed => "seed",
+        };
+        f.write_str(name)
+    }
+}
+
+/// ユーザーの1つのリビジョンを表す構造体
+///
+/// 変更のたびに、変更後のユーザーの状態全体を記録します。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Revision {
+    /// 対象ユーザーのメールアドレス（保存時のキー）
+    pub email: String,
+    /// ユーザーごとに1から始まるリビジョン番号
+    pub revision: u64,
+    /// 変更が行われた日時
+    pub timestamp: DateTime<Utc>,
+    /// 変更を行った操作者
+    pub actor: String,
+    /// 変更を行った操作の種類
+    pub operation: RevisionOperation,
+    /// 変更後のユーザーの状態（完全に削除された場合は`None`）
+    pub user: Option<User>,
+}
+
+/// 差分の対象とするフィールドの名前と、ユーザーから表示用の値を取り出す関数
+type FieldAccessor = (&'static str, fn(&User) -> Option<String>);
+
+/// 2つのリビジョンの間で変化したフィールド
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    /// フィールド名
+    pub field: &'static str,
+    /// 変更前の値（存在しなかった場合は`None`）
+    pub before: Option<String>,
+    /// 変更後の値（存在しなくなった場合は`None`）
+    pub after: Option<String>,
+}
+
+impl Revision {
+    /// 直前のリビジョンからの変更点を返します。
+    ///
+    /// # 引数
+    /// * `previous` - 直前のリビジョン（最初のリビジョンの場合は`None`）
+    ///
+    /// # 戻り値
+    /// * `Vec<FieldChange>` - 値が変化したフィールドの一覧
+    pub fn changes_from(&self, previous: Option<&Revision>) -> Vec<FieldChange> {
+        field_changes(
+            previous.and_then(|revision| revision.user.as_ref()),
+            self.user.as_ref(),
+        )
+    }
+}
+
+/// 2つのユーザーの状態の間で変化したフィールドを返します。
+///
+/// # 引数
+/// * `before` - 変更前のユーザーの状態（存在しなかった場合は`None`）
+/// * `after` - 変更後のユーザーの状態（存在しなくなった場合は`None`）
+///
+/// # 戻り値
+/// * `Vec<FieldChange>` - 値が変化したフィールドの一覧
+pub fn field_changes(before: Option<&User>, after: Option<&User>) -> Vec<FieldChange> {
+    let fields: [FieldAccessor; 5] = [
+        ("username", |u| Some(u.username.clone())),
+        ("phone", |u| Some(u.phone.clone())),
+        ("age", |u| Some(u.age.to_string())),
+        ("aliases", |u| {
+            (!u.aliases.is_empty()).then(|| u.aliases.join(", "))
+        }),
+        ("deleted_at", |u| u.deleted_at.map(|at| at.to_rfc3339())),
+    ];
+
+    fields
+        .iter()
+        .filter_map(|(field, value)| {
+            let before = before.and_then(value);
+            let after = after.and_then(value);
+            (before != after).then_some(FieldChange {
+                field,
+                before,
+                after,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn revision(number: u64, user: Option<User>) -> Revision {
+        Revision {
+            email: "test@example.com".to_string(),
+            revision: number,
+            timestamp: Utc::now(),
+            actor: "tester".to_string(),
+            operation: RevisionOperation::Update,
+            user,
+        }
+    }
+
+    fn user(phone: &str) -> User {
+        User {
+            email: "test@example.com".to_string(),
+            username: "testuser".to_string(),
+            phone: phone.to_string(),
+            age: 25,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_changes_from_previous_revision() {
+        let first = revision(1, Some(user("+819012345678")));
+        let second = revision(2, Some(user("+818012345678")));
+
+        let changes = second.changes_from(Some(&first));
+        assert_eq!(
+            changes,
+            vec![FieldChange {
+                field: "phone",
+                before: Some("+819012345678".to_string()),
+                after: Some("+818012345678".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_changes_of_first_and_purged_revision() {
+        let first = revision(1, Some(user("+819012345678")));
+        let purged = revision(2, None);
+
+        assert_eq!(first.changes_from(None).len(), 3);
+        assert!(
+            purged
+                .changes_from(Some(&first))
+                .iter()
+                .all(|change| change.after.is_none())
+        );
+    }
+
+    #[test]
+    fn test_revision_serialization() {
+        let revision = revision(1, Some(user("+819012345678")));
+        let json = serde_json::to_string(&revision).unwrap();
+
+        assert!(json.contains(r#""operation":"update""#));
+        assert_eq!(serde_json::from_str::<Revision>(&json).unwrap(), revision);
+    }
+}
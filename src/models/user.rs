@@ -1,11 +1,12 @@
 //! ユーザデータを表す構造体の定義
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 /// ユーザデータを表す構造体
 ///
 /// この構造体はユーザの基本情報を保持し、JSONとの相互変換が可能です。
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct User {
     /// ユーザのメールアドレス（一意な識別子として使用）
     ///
@@ -24,20 +25,61 @@ pub struct User {
 
     /// ユーザの表示名
     ///
-    /// 3文字以上の長さが必要です。
+    /// Unicode NFKC正規化された値が保存されます。
+    /// 3文字以上（書記素クラスタ数）の長さが必要です。
     /// 空文字列は許可されません。
     pub username: String,
 
     /// ユーザの電話番号
     ///
-    /// 10桁以上の数字である必要があります。
-    /// ハイフンなどの区切り文字は使用できません。
+    /// E.164形式（例: "+819012345678"）で保存されます。
+    /// 正規化が導入される前に保存されたデータでは、区切り文字のない数字のみの場合があります。
     pub phone: String,
 
     /// ユーザの年齢
     ///
     /// 0から150までの整数である必要があります。
     pub age: u32,
+
+    /// 最後に作成・更新された日時
+    ///
+    /// 日時の記録が導入される前に保存されたデータでは`None`になります。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+
+    /// 統合によって取り込まれた別のメールアドレス
+    ///
+    /// `merge`で統合されたユーザーのメールアドレスが記録され、
+    /// このアドレスでの検索もこのユーザーに解決されます。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+
+    /// ゴミ箱に移動された日時
+    ///
+    /// `None`の場合は有効なユーザーです。値がある場合、`list`や`get`には表示されず、
+    /// `restore`で元に戻すか`purge`で完全に削除できます。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+
+    /// レコードのバージョン
+    ///
+    /// 作成時に1となり、レコードが変更されるたびに1ずつ増えます。
+    /// 更新・削除の際に想定するバージョンを指定すると、その間に他の操作者が
+    /// 変更していた場合に上書きせずに失敗させることができます。
+    /// バージョンの導入前に保存されたデータでは0になります（0の場合は保存されません）。
+    #[serde(default, skip_serializing_if = "is_unversioned")]
+    pub version: u64,
+}
+
+/// バージョンの導入前に保存されたレコードかどうかを判定します。
+///
+/// # 引数
+/// * `version` - レコードのバージョン
+///
+/// # 戻り値
+/// * `bool` - バージョンが0の場合は`true`
+fn is_unversioned(version: &u64) -> bool {
+    *version == 0
 }
 
 #[cfg(test)]
@@ -51,6 +93,7 @@ mod tests {
             username: "testuser".to_string(),
             phone: "1234567890".to_string(),
             age: 25,
+            ..Default::default()
         };
 
         assert_eq!(user.email, "test@example.com");
@@ -66,6 +109,7 @@ mod tests {
             username: "testuser".to_string(),
             phone: "1234567890".to_string(),
             age: 25,
+            ..Default::default()
         };
 
         let serialized = serde_json::to_string(&user).unwrap();
@@ -73,4 +117,16 @@ mod tests {
 
         assert_eq!(user, deserialized);
     }
+
+    #[test]
+    fn test_user_deserialization_without_optional_fields() {
+        let json =
+            r#"{"email":"test@example.com","username":"testuser","phone":"1234567890","age":25}"#;
+        let user: User = serde_json::from_str(json).unwrap();
+
+        assert_eq!(user.updated_at, None);
+        assert!(user.aliases.is_empty());
+        assert_eq!(user.version, 0);
+        assert_eq!(serde_json::to_string(&user).unwrap(), json);
+    }
 }
@@ -3,5 +3,35 @@
 //! このモジュールは、データの保存、読み込み、更新、削除などの
 //! データ永続化操作を実装します。
 
+/// 監査ログの永続化を担当するモジュール
+pub mod audit_repository;
+
+/// ユーザーデータのファイルのバックアップを担当するモジュール
+pub mod backup_repository;
+
+/// 書き込みを行わずに変更内容を確認するためのリポジトリを提供するモジュール
+pub mod dry_run_repository;
+
+/// ユーザーデータのファイルの暗号化を担当するモジュール
+pub mod encryption;
+
+/// ユーザーの一部のフィールドの暗号化を担当するモジュール
+pub mod field_encryption;
+
+/// イベントログを正とするユーザーデータの永続化を担当するモジュール
+pub mod event_sourced_repository;
+
+/// ユーザーの変更履歴の永続化を担当するモジュール
+pub mod history_repository;
+
+/// 操作ジャーナルの永続化を担当するモジュール
+pub mod journal_repository;
+
+/// 消去請求の受領証の永続化を担当するモジュール
+pub mod receipt_repository;
+
+/// ユーザーデータのファイル形式のバージョンと移行を担当するモジュール
+pub mod schema;
+
 /// ユーザーデータの永続化を担当するモジュール
 pub mod user_repository;
@@ -0,0 +1,520 @@
+//! 監査ログの永続化を担うモジュール
+//!
+//! 記録を1行1件のJSON Lines形式で追記していきます。既存の行を書き換えるのは、
+//! 消去請求に応じて記録からユーザーの値を消去する場合（`erase`）だけです。
+//! 各記録には直前の行のSHA-256ハッシュを含め、記録の改ざんや削除を検出できるようにします。
+//! 環境変数`USER_AUDIT_HMAC_KEY`が設定されている場合は、その鍵によるHMAC-SHA256も付加します。
+//! 保存先のファイルパスは環境変数`USER_AUDIT_FILE`で指定できます。
+//! 指定がない場合は、ユーザーデータのファイル名から`.json`を除いて
+//! `.audit.jsonl`を付けたパス（例: `userdata.audit.jsonl`）を使用します。
+//! 暗号化の鍵が設定されている場合は、ユーザーデータのファイルと同じ鍵でファイル全体を暗号化します。
+
+use crate::models::audit::{AuditEntry, AuditOutcome, AuditVerification, BrokenLink};
+use crate::models::privacy::ERASED_VALUE;
+use crate::repositories::encryption::{
+    DataFileCipher, append_sidecar, read_sidecar, write_sidecar,
+};
+use crate::repositories::user_repository::{data_file_path, sibling_path};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::env;
+
+#[cfg(test)]
+use mockall::automock;
+
+/// 最初の記録の`prev_hash`に使用する値
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// 監査ログの永続化操作を定義するトレイト
+#[cfg_attr(test, automock)]
+pub trait AuditRepository {
+    /// 記録を追記します。
+    ///
+    /// 渡された記録の`prev_hash`と`hmac`は無視され、リポジトリが設定した値で保存されます。
+    ///
+    /// # 引数
+    /// * `entry` - 追記する記録
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 追記に成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * ファイルの書き込みに失敗した場合
+    /// * JSONのシリアライズに失敗した場合
+    fn append(&self, entry: &AuditEntry) -> Result<(), String>;
+
+    /// 全ての記録を記録された順に取得します。
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<AuditEntry>)` - 記録された順の全ての記録
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * ファイルの読み込みに失敗した場合
+    /// * JSONのデシリアライズに失敗した場合
+    fn find_all(&self) -> Result<Vec<AuditEntry>, String>;
+
+    /// ハッシュチェーンとHMACを先頭からたどり、記録が改ざんされていないかを検証します。
+    ///
+    /// # 戻り値
+    /// * `Ok(AuditVerification)` - 検証結果（チェーンの途切れは`broken`に設定されます）
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * ファイルの読み込みに失敗した場合
+    fn verify(&self) -> Result<AuditVerification, String>;
+
+    /// 指定された位置の記録から、ユーザーを特定できる値を消去します。
+    ///
+    /// 対象の記録のメールアドレスを`[erased]`に、変更前後の状態を`None`に、
+    /// 失敗した操作のエラーの内容を`[erased]`に置き換えます。
+    /// 書き換えた記録以降のハッシュチェーンとHMACは計算し直します。
+    ///
+    /// # 引数
+    /// * `positions` - `find_all`が返す順で数えた、値を消去する記録の位置（0から始まる）
+    ///
+    /// # 戻り値
+    /// * `Ok(usize)` - 値を消去した記録の件数
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * ファイルの読み書きに失敗した場合
+    /// * JSONのシリアライズ/デシリアライズに失敗した場合
+    fn erase(&self, positions: &[usize]) -> Result<usize, String>;
+}
+
+/// JSON Linesファイルベースの監査ログリポジトリの実装
+pub struct AuditRepositoryImpl {
+    /// 記録を保存するJSON Linesファイルのパス
+    file_path: String,
+    /// HMACの計算に使用する鍵（`None`の場合はHMACを付加しない）
+    hmac_key: Option<Vec<u8>>,
+    /// ファイルの暗号化・復号に使用する鍵（`None`の場合は平文）
+    cipher: Option<DataFileCipher>,
+}
+
+impl Default for AuditRepositoryImpl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuditRepositoryImpl {
+    /// 新しいAuditRepositoryインスタンスを作成します。
+    ///
+    /// 環境変数`USER_AUDIT_FILE`が設定されている場合はその値を、設定されていない場合は
+    /// 環境変数`USER_DATA_FILE`（既定値"userdata.json"）から導いたパスを使用します。
+    /// HMACの鍵は環境変数`USER_AUDIT_HMAC_KEY`から読み込みます。
+    /// 暗号化の鍵が環境変数で設定されている場合は、その鍵でファイルを暗号化します。
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいAuditRepositoryインスタンス
+    pub fn new() -> Self {
+        let file_path = env::var("USER_AUDIT_FILE")
+            .unwrap_or_else(|_| sibling_path(&data_file_path(), "audit.jsonl"));
+        let repository = Self::with_file_path(file_path).with_hmac_key(hmac_key_from_env());
+        match DataFileCipher::from_env() {
+            Some(cipher) => repository.with_cipher(cipher),
+            None => repository,
+        }
+    }
+
+    /// 保存先のファイルパスを指定してAuditRepositoryインスタンスを作成します。
+    ///
+    /// # 引数
+    /// * `file_path` - 記録を保存するJSON Linesファイルのパス
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいAuditRepositoryインスタンス
+    pub fn with_file_path(file_path: impl Into<String>) -> Self {
+        Self {
+            file_path: file_path.into(),
+            hmac_key: None,
+            cipher: None,
+        }
+    }
+
+    /// 保存先のファイルパスを返します。
+    ///
+    /// # 戻り値
+    /// * `&str` - 保存先のファイルパス
+    pub fn file_path(&self) -> &str {
+        &self.file_path
+    }
+
+    /// ファイルの暗号化・復号に使用する鍵を設定します。
+    ///
+    /// # 引数
+    /// * `cipher` - 暗号化・復号に使用する鍵
+    ///
+    /// # 戻り値
+    /// * `Self` - 鍵を設定したAuditRepositoryインスタンス
+    pub fn with_cipher(mut self, cipher: DataFileCipher) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// HMACの計算に使用する鍵を設定します。
+    ///
+    /// # 引数
+    /// * `key` - HMACの鍵（`None`の場合はHMACを付加・検証しない）
+    ///
+    /// # 戻り値
+    /// * `Self` - 鍵を設定したAuditRepositoryインスタンス
+    pub fn with_hmac_key(mut self, key: Option<Vec<u8>>) -> Self {
+        self.hmac_key = key;
+        self
+    }
+
+    /// ファイルの内容を行ごとに読み込みます。
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<String>)` - 空行を除く全ての行（ファイルが存在しない場合は空）
+    ///
+    /// # エラー
+    /// * ファイルの読み込み、または復号に失敗した場合
+    fn read_lines(&self) -> Result<Vec<String>, String> {
+        let content = read_sidecar(&self.file_path, self.cipher.as_ref())
+            .map_err(|e| format!("Failed to read audit log: {}", e))?;
+        Ok(content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// 記録の内容を入力済みのHMACを作成します。
+    ///
+    /// # 引数
+    /// * `key` - HMACの鍵
+    /// * `entry` - 対象の記録（`hmac`の値は計算に含めません）
+    ///
+    /// # 戻り値
+    /// * `Ok(Hmac<Sha256>)` - 記録の内容を入力したHMAC-SHA256
+    ///
+    /// # エラー
+    /// * JSONのシリアライズに失敗した場合
+    fn entry_mac(key: &[u8], entry: &AuditEntry) -> Result<Hmac<Sha256>, String> {
+        let unsigned = AuditEntry {
+            hmac: None,
+            ..entry.clone()
+        };
+        let content = serde_json::to_string(&unsigned)
+            .map_err(|e| format!("Failed to serialize audit entry: {}", e))?;
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(key).map_err(|e| format!("Invalid HMAC key: {}", e))?;
+        mac.update(content.as_bytes());
+        Ok(mac)
+    }
+}
+
+/// 記録（JSON Linesの1行）のSHA-256ハッシュを計算します。
+///
+/// # 引数
+/// * `line` - 記録の行（改行を含まない）
+///
+/// # 戻り値
+/// * `String` - 16進数で表したハッシュ
+pub fn hash_line(line: &str) -> String {
+    hex::encode(Sha256::digest(line.as_bytes()))
+}
+
+/// 環境変数`USER_AUDIT_HMAC_KEY`からHMACの鍵を読み込みます。
+///
+/// # 戻り値
+/// * `Option<Vec<u8>>` - 鍵（設定されていない、または空の場合は`None`）
+pub fn hmac_key_from_env() -> Option<Vec<u8>> {
+    env::var("USER_AUDIT_HMAC_KEY")
+        .ok()
+        .filter(|key| !key.is_empty())
+        .map(String::into_bytes)
+}
+
+impl AuditRepository for AuditRepositoryImpl {
+    fn append(&self, entry: &AuditEntry) -> Result<(), String> {
+        let prev_hash = self
+            .read_lines()?
+            .last()
+            .map_or_else(|| GENESIS_HASH.to_string(), |line| hash_line(line));
+        let mut entry = AuditEntry {
+            prev_hash: Some(prev_hash),
+            hmac: None,
+            ..entry.clone()
+        };
+        if let Some(key) = &self.hmac_key {
+            let mac = Self::entry_mac(key, &entry)?;
+            entry.hmac = Some(hex::encode(mac.finalize().into_bytes()));
+        }
+        let line = serde_json::to_string(&entry)
+            .map_err(|e| format!("Failed to serialize audit entry: {}", e))?;
+        append_sidecar(&self.file_path, &line, self.cipher.as_ref())
+            .map_err(|e| format!("Failed to write audit log: {}", e))
+    }
+
+    fn find_all(&self) -> Result<Vec<AuditEntry>, String> {
+        self.read_lines()?
+            .iter()
+            .map(|line| {
+                serde_json::from_str(line)
+                    .map_err(|e| format!("Failed to parse audit entry: {}", e))
+            })
+            .collect()
+    }
+
+    fn verify(&self) -> Result<AuditVerification, String> {
+        let lines = self.read_lines()?;
+        let mut verification = AuditVerification {
+            entries: lines.len(),
+            ..AuditVerification::default()
+        };
+        let mut chained = false;
+        let mut previous: Option<&str> = None;
+
+        for (index, line) in lines.iter().enumerate() {
+            let broken = |reason: String| BrokenLink {
+                line: index + 1,
+                reason,
+            };
+            let entry: AuditEntry = match serde_json::from_str(line) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    verification.broken = Some(broken(format!("unparsable entry: {}", e)));
+                    return Ok(verification);
+                }
+            };
+
+            match &entry.prev_hash {
+                // ハッシュチェーンの導入前の記録は先頭に連続している場合のみ許容する
+                None if !chained => verification.unchained_entries += 1,
+                None => {
+                    verification.broken = Some(broken("missing previous hash".to_string()));
+                    return Ok(verification);
+                }
+                Some(prev_hash) => {
+                    let expected = previous.map_or_else(|| GENESIS_HASH.to_string(), hash_line);
+                    if *prev_hash != expected {
+                        verification.broken = Some(broken(
+                            "previous hash does not match; an entry before it was edited or removed"
+                                .to_string(),
+                        ));
+                        return Ok(verification);
+                    }
+                    chained = true;
+                }
+            }
+
+            if let (true, Some(key)) = (chained, &self.hmac_key) {
+                let reason = match &entry.hmac {
+                    None => Some("missing HMAC".to_string()),
+                    Some(hmac) => {
+                        let mac = Self::entry_mac(key, &entry)?;
+                        let valid = hex::decode(hmac)
+                            .is_ok_and(|expected| mac.verify_slice(&expected).is_ok());
+                        (!valid).then(|| "HMAC does not match; the entry was edited".to_string())
+                    }
+                };
+                if let Some(reason) = reason {
+                    verification.broken = Some(broken(reason));
+                    return Ok(verification);
+                }
+            }
+            previous = Some(line);
+        }
+
+        verification.last_hash = previous.map(hash_line);
+        Ok(verification)
+    }
+
+    fn erase(&self, positions: &[usize]) -> Result<usize, String> {
+        let positions: HashSet<usize> = positions.iter().copied().collect();
+        let lines = self.read_lines()?;
+        let mut rewritten = Vec::with_capacity(lines.len());
+        let mut erased = 0;
+        let mut changed = false;
+
+        for (index, line) in lines.iter().enumerate() {
+            let erase = positions.contains(&index);
+            // 書き換えた記録より前の行は、元の内容のまま残す
+            if !erase && !changed {
+                rewritten.push(line.clone());
+                continue;
+            }
+
+            let mut entry: AuditEntry = serde_json::from_str(line)
+                .map_err(|e| format!("Failed to parse audit entry: {}", e))?;
+            if erase {
+                entry.email = entry.email.map(|_| ERASED_VALUE.to_string());
+                entry.before = None;
+                entry.after = None;
+                if let AuditOutcome::Failure(_) = entry.outcome {
+                    entry.outcome = AuditOutcome::Failure(ERASED_VALUE.to_string());
+                }
+                erased += 1;
+            }
+            // ハッシュチェーンの導入前の記録には、ハッシュもHMACも付けない
+            if entry.prev_hash.is_some() {
+                entry.prev_hash = Some(
+                    rewritten
+                        .last()
+                        .map_or_else(|| GENESIS_HASH.to_string(), |line| hash_line(line)),
+                );
+                entry.hmac = None;
+                if let Some(key) = &self.hmac_key {
+                    let mac = Self::entry_mac(key, &entry)?;
+                    entry.hmac = Some(hex::encode(mac.finalize().into_bytes()));
+                }
+            }
+            rewritten.push(
+                serde_json::to_string(&entry)
+                    .map_err(|e| format!("Failed to serialize audit entry: {}", e))?,
+            );
+            changed = true;
+        }
+        if erased == 0 {
+            return Ok(0);
+        }
+
+        let mut content = rewritten.join("\n");
+        content.push('\n');
+        write_sidecar(&self.file_path, &content, self.cipher.as_ref())
+            .map_err(|e| format!("Failed to write audit log: {}", e))?;
+        Ok(erased)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::audit::AuditOutcome;
+    use crate::models::revision::RevisionOperation;
+    use chrono::Utc;
+    use std::fs;
+
+    #[test]
+    fn test_append_and_find_entries() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = AuditRepositoryImpl::with_file_path(
+            temp_dir.path().join("audit.jsonl").to_str().unwrap(),
+        );
+        let entry = AuditEntry {
+            timestamp: Utc::now(),
+            actor: "tester".to_string(),
+            operation: RevisionOperation::Delete,
+            email: Some("test@example.com".to_string()),
+            before: None,
+            after: None,
+            details: None,
+            outcome: AuditOutcome::Success,
+            prev_hash: None,
+            hmac: None,
+        };
+
+        assert!(repo.find_all().unwrap().is_empty());
+        repo.append(&entry).unwrap();
+        repo.append(&entry).unwrap();
+
+        let entries = repo.find_all().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].prev_hash.as_deref(), Some(GENESIS_HASH));
+        assert_eq!(entries[1].actor, entry.actor);
+        assert_ne!(entries[1].prev_hash, entries[0].prev_hash);
+    }
+
+    fn entry(email: &str) -> AuditEntry {
+        AuditEntry {
+            timestamp: Utc::now(),
+            actor: "tester".to_string(),
+            operation: RevisionOperation::Update,
+            email: Some(email.to_string()),
+            before: None,
+            after: None,
+            details: None,
+            outcome: AuditOutcome::Success,
+            prev_hash: None,
+            hmac: None,
+        }
+    }
+
+    fn signed_repo(temp_dir: &tempfile::TempDir) -> AuditRepositoryImpl {
+        AuditRepositoryImpl::with_file_path(temp_dir.path().join("audit.jsonl").to_str().unwrap())
+            .with_hmac_key(Some(b"secret".to_vec()))
+    }
+
+    #[test]
+    fn test_verify_intact_chain() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = signed_repo(&temp_dir);
+        for email in ["a@example.com", "b@example.com", "c@example.com"] {
+            repo.append(&entry(email)).unwrap();
+        }
+
+        let verification = repo.verify().unwrap();
+        assert_eq!(verification.entries, 3);
+        assert_eq!(verification.broken, None);
+        assert!(verification.last_hash.is_some());
+    }
+
+    #[test]
+    fn test_verify_detects_removed_and_edited_entries() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = signed_repo(&temp_dir);
+        for email in ["a@example.com", "b@example.com", "c@example.com"] {
+            repo.append(&entry(email)).unwrap();
+        }
+        let path = temp_dir.path().join("audit.jsonl");
+        let original = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = original.lines().collect();
+
+        // 2行目を削除すると3行目で途切れる
+        fs::write(&path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        assert_eq!(repo.verify().unwrap().broken.unwrap().line, 2);
+
+        // 最後の行を書き換えると、HMACが一致しなくなる
+        let edited = lines[2].replace("c@example.com", "x@example.com");
+        fs::write(&path, format!("{}\n{}\n{}\n", lines[0], lines[1], edited)).unwrap();
+        let broken = repo.verify().unwrap().broken.unwrap();
+        assert_eq!(broken.line, 3);
+        assert!(broken.reason.contains("HMAC"));
+    }
+
+    #[test]
+    fn test_verify_allows_unchained_prefix_only() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("audit.jsonl");
+        let legacy = serde_json::to_string(&entry("legacy@example.com")).unwrap();
+        fs::write(&path, format!("{}\n", legacy)).unwrap();
+        let repo = AuditRepositoryImpl::with_file_path(path.to_str().unwrap());
+        repo.append(&entry("a@example.com")).unwrap();
+
+        let verification = repo.verify().unwrap();
+        assert_eq!(verification.unchained_entries, 1);
+        assert_eq!(verification.broken, None);
+
+        let content = fs::read_to_string(&path).unwrap();
+        fs::write(&path, format!("{}{}\n", content, legacy)).unwrap();
+        assert_eq!(repo.verify().unwrap().broken.unwrap().line, 3);
+    }
+
+    #[test]
+    fn test_erase_redacts_entries_and_rechains() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = signed_repo(&temp_dir);
+        for email in ["a@example.com", "b@example.com", "a@example.com"] {
+            repo.append(&entry(email)).unwrap();
+        }
+        let head = repo.find_all().unwrap()[0].clone();
+
+        assert_eq!(repo.erase(&[1]).unwrap(), 1);
+        let entries = repo.find_all().unwrap();
+        assert_eq!(entries[0], head);
+        assert_eq!(entries[1].email.as_deref(), Some(ERASED_VALUE));
+        assert_eq!(entries[2].email.as_deref(), Some("a@example.com"));
+        // 書き換えた後もチェーンとHMACは検証できる
+        let verification = repo.verify().unwrap();
+        assert_eq!(verification.entries, 3);
+        assert_eq!(verification.broken, None);
+        assert_eq!(repo.erase(&[]).unwrap(), 0);
+    }
+}
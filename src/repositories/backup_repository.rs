@@ -0,0 +1,557 @@
+//! ユーザーデータのファイルのバックアップを管理するモジュール
+//!
+//! バックアップは、ユーザーデータのファイル名から`.json`を除いて`.backups`を付けた
+//! ディレクトリ（例: `userdata.backups/`）に、作成日時をIDとしたファイルとして保存されます。
+//! 保持する件数は環境変数`USER_BACKUP_COUNT`（既定値10、0の場合はバックアップしない）、
+//! 保持する期間は環境変数`USER_BACKUP_MAX_AGE_DAYS`（既定では無期限）で指定できます。
+
+use crate::models::backup::Backup;
+use crate::models::data_file::DataFile;
+use crate::models::user::User;
+use crate::repositories::encryption::{DataFileCipher, decrypt_if_encrypted, is_encrypted};
+use crate::repositories::schema::parse_data_file;
+use crate::repositories::user_repository::sibling_path;
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+use mockall::automock;
+
+/// 既定で保持するバックアップの件数
+pub const DEFAULT_BACKUP_COUNT: usize = 10;
+
+/// バックアップのIDに使用する日時の形式
+const ID_FORMAT: &str = "%Y%m%dT%H%M%S%.9fZ";
+
+/// バックアップの作成・一覧・復元を定義するトレイト
+#[cfg_attr(test, automock)]
+pub trait BackupRepository {
+    /// 現在のユーザーデータのファイルのバックアップを作成し、保持期間を過ぎたものを削除します。
+    ///
+    /// # 戻り値
+    /// * `Ok(Some(Backup))` - 作成したバックアップ
+    /// * `Ok(None)` - ユーザーデータのファイルがまだない、またはバックアップが無効な場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * ファイルの読み書きに失敗した場合
+    fn create(&self) -> Result<Option<Backup>, String>;
+
+    /// バックアップの一覧を古い順に取得します。
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<Backup>)` - 作成日時の昇順に並んだバックアップ
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * ディレクトリの読み込みに失敗した場合
+    fn list(&self) -> Result<Vec<Backup>, String>;
+
+    /// バックアップの内容がユーザーデータとして読み込めることを確認します。
+    ///
+    /// # 引数
+    /// * `id` - 確認するバックアップのID
+    ///
+    /// # 戻り値
+    /// * `Ok(usize)` - バックアップに含まれるユーザーの件数
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 指定されたIDのバックアップが存在しない場合
+    /// * バックアップの内容がユーザーデータとして読み込めない場合
+    fn verify(&self, id: &str) -> Result<usize, String>;
+
+    /// バックアップをユーザーデータのファイルに書き戻します。
+    ///
+    /// 書き戻す前にバックアップの内容がユーザーデータとして読み込めることを確認し、
+    /// 現在のファイルもバックアップしてから置き換えます。
+    ///
+    /// # 引数
+    /// * `id` - 書き戻すバックアップのID
+    ///
+    /// # 戻り値
+    /// * `Ok(Backup)` - 書き戻したバックアップ
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 指定されたIDのバックアップが存在しない場合
+    /// * バックアップの内容がユーザーデータとして読み込めない場合
+    /// * ファイルの読み書きに失敗した場合
+    fn restore(&self, id: &str) -> Result<Backup, String>;
+
+    /// 指定されたメールアドレスのユーザーを含むバックアップを探します。
+    ///
+    /// キー・メールアドレス・別名のいずれかが一致するユーザーを対象とします（大文字・小文字は区別しません）。
+    ///
+    /// # 引数
+    /// * `emails` - 対象のメールアドレス
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<(Backup, User)>)` - バックアップと、そこに含まれていたユーザー（古い順）
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * バックアップの読み込み、または復号に失敗した場合
+    fn find_user(&self, emails: &[String]) -> Result<Vec<(Backup, User)>, String>;
+
+    /// 全てのバックアップから、指定されたメールアドレスのユーザーを取り除きます。
+    ///
+    /// 対象のユーザーを含むバックアップだけを書き直します。暗号化されたバックアップは
+    /// 同じ鍵で暗号化し直します。
+    ///
+    /// # 引数
+    /// * `emails` - 対象のメールアドレス
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<String>)` - 書き直したバックアップのID
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * バックアップの読み書き、または暗号化・復号に失敗した場合
+    fn erase_user(&self, emails: &[String]) -> Result<Vec<String>, String>;
+
+    /// 平文のバックアップを全て、設定された鍵で暗号化して書き直します。
+    ///
+    /// 既に暗号化されているバックアップはそのまま残します。
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<String>)` - 暗号化したバックアップのID
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 鍵が設定されていない場合
+    /// * バックアップの読み書き、または暗号化に失敗した場合
+    fn encrypt_all(&self) -> Result<Vec<String>, String>;
+}
+
+/// ファイルのコピーによるバックアップの実装
+pub struct BackupRepositoryImpl {
+    /// バックアップするユーザーデータのファイルのパス
+    data_file: String,
+    /// バックアップを保存するディレクトリ
+    directory: PathBuf,
+    /// 保持するバックアップの件数（0の場合はバックアップしない）
+    max_count: usize,
+    /// バックアップを保持する期間（`None`の場合は無期限）
+    max_age: Option<TimeDelta>,
+    /// 暗号化されたバックアップの確認に使用する鍵
+    cipher: Option<DataFileCipher>,
+}
+
+impl BackupRepositoryImpl {
+    /// 新しいBackupRepositoryインスタンスを作成します。
+    ///
+    /// 保持する件数と期間は環境変数`USER_BACKUP_COUNT`・`USER_BACKUP_MAX_AGE_DAYS`から、
+    /// 暗号化されたバックアップの確認に使用する鍵は`USER_DATA_PASSPHRASE`・`USER_DATA_KEY_FILE`から読み込みます。
+    ///
+    /// # 引数
+    /// * `data_file` - バックアップするユーザーデータのファイルのパス
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいBackupRepositoryインスタンス
+    pub fn new(data_file: &str) -> Self {
+        let max_count = env::var("USER_BACKUP_COUNT")
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+            .unwrap_or(DEFAULT_BACKUP_COUNT);
+        let max_age = env::var("USER_BACKUP_MAX_AGE_DAYS")
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+            .and_then(TimeDelta::try_days);
+        let backups = Self::with_directory(data_file, sibling_path(data_file, "backups"))
+            .with_retention(max_count, max_age);
+        match DataFileCipher::from_env() {
+            Some(cipher) => backups.with_cipher(cipher),
+            None => backups,
+        }
+    }
+
+    /// バックアップを保存するディレクトリを指定してBackupRepositoryインスタンスを作成します。
+    ///
+    /// # 引数
+    /// * `data_file` - バックアップするユーザーデータのファイルのパス
+    /// * `directory` - バックアップを保存するディレクトリ
+    ///
+    /// # 戻り値
+    /// * `Self` - 既定の件数を無期限に保持するBackupRepositoryインスタンス
+    pub fn with_directory(data_file: &str, directory: impl Into<PathBuf>) -> Self {
+        Self {
+            data_file: data_file.to_string(),
+            directory: directory.into(),
+            max_count: DEFAULT_BACKUP_COUNT,
+            max_age: None,
+            cipher: None,
+        }
+    }
+
+    /// 暗号化されたバックアップの確認に使用する鍵を設定します。
+    ///
+    /// # 引数
+    /// * `cipher` - 復号に使用する鍵
+    ///
+    /// # 戻り値
+    /// * `Self` - 設定を変更したBackupRepositoryインスタンス
+    pub fn with_cipher(mut self, cipher: DataFileCipher) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// 保持するバックアップの件数と期間を設定します。
+    ///
+    /// # 引数
+    /// * `max_count` - 保持する件数（0の場合はバックアップしない）
+    /// * `max_age` - 保持する期間（`None`の場合は無期限）
+    ///
+    /// # 戻り値
+    /// * `Self` - 設定を変更したBackupRepositoryインスタンス
+    pub fn with_retention(mut self, max_count: usize, max_age: Option<TimeDelta>) -> Self {
+        self.max_count = max_count;
+        self.max_age = max_age;
+        self
+    }
+
+    /// バックアップのファイルのパスを返します。
+    ///
+    /// # 引数
+    /// * `id` - バックアップのID
+    ///
+    /// # 戻り値
+    /// * `PathBuf` - バックアップのファイルのパス
+    fn backup_path(&self, id: &str) -> PathBuf {
+        self.directory.join(format!("{}.json", id))
+    }
+
+    /// バックアップを読み込みます。
+    ///
+    /// # 引数
+    /// * `id` - バックアップのID
+    ///
+    /// # 戻り値
+    /// * `Ok((DataFile, bool))` - バックアップの内容と、暗号化されていたかどうか
+    ///
+    /// # エラー
+    /// * ファイルの読み込み、または復号に失敗した場合
+    /// * 内容がユーザーデータとして読み込めない場合
+    fn read_backup(&self, id: &str) -> Result<(DataFile, bool), String> {
+        let content = fs::read_to_string(self.backup_path(id))
+            .map_err(|e| format!("Failed to read backup {}: {}", id, e))?;
+        decrypt_if_encrypted(&content, self.cipher.as_ref())
+            .and_then(|plain| parse_data_file(&plain))
+            .map(|(data_file, _)| (data_file, is_encrypted(&content)))
+            .map_err(|e| format!("Backup {} is not valid user data: {}", id, e))
+    }
+
+    /// バックアップを一時ファイル経由で書き直します。
+    ///
+    /// # 引数
+    /// * `id` - バックアップのID
+    /// * `content` - 書き込む内容
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 書き込みに成功した場合
+    ///
+    /// # エラー
+    /// * ファイルの書き込みに失敗した場合
+    fn write_backup(&self, id: &str, content: &str) -> Result<(), String> {
+        let path = self.backup_path(id);
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, content)
+            .map_err(|e| format!("Failed to write backup {}: {}", id, e))?;
+        fs::rename(&temp_path, &path).map_err(|e| format!("Failed to write backup {}: {}", id, e))
+    }
+
+    /// 件数または期間の上限を超えた古いバックアップを削除します。
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 削除に成功した場合
+    ///
+    /// # エラー
+    /// * ディレクトリの読み込みやファイルの削除に失敗した場合
+    fn prune(&self) -> Result<(), String> {
+        let backups = self.list()?;
+        let excess = backups.len().saturating_sub(self.max_count);
+        let cutoff = self.max_age.map(|age| Utc::now() - age);
+        for (index, backup) in backups.iter().enumerate() {
+            let expired = cutoff.is_some_and(|cutoff| backup.created_at < cutoff);
+            if index < excess || expired {
+                fs::remove_file(self.backup_path(&backup.id))
+                    .map_err(|e| format!("Failed to remove backup {}: {}", backup.id, e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BackupRepository for BackupRepositoryImpl {
+    fn create(&self) -> Result<Option<Backup>, String> {
+        if self.max_count == 0 || !Path::new(&self.data_file).exists() {
+            return Ok(None);
+        }
+
+        fs::create_dir_all(&self.directory)
+            .map_err(|e| format!("Failed to create backup directory: {}", e))?;
+        let created_at = Utc::now();
+        let id = created_at.format(ID_FORMAT).to_string();
+        let size = fs::copy(&self.data_file, self.backup_path(&id))
+            .map_err(|e| format!("Failed to create backup: {}", e))?;
+        self.prune()?;
+        Ok(Some(Backup {
+            id,
+            created_at,
+            size,
+        }))
+    }
+
+    fn list(&self) -> Result<Vec<Backup>, String> {
+        if !self.directory.exists() {
+            return Ok(Vec::new());
+        }
+
+        let entries = fs::read_dir(&self.directory)
+            .map_err(|e| format!("Failed to read backup directory: {}", e))?;
+        let mut backups = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("Failed to read backup directory: {}", e))?;
+            let path = entry.path();
+            // IDの形式でないファイルはバックアップとして扱わない
+            let Some(id) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".json"))
+            else {
+                continue;
+            };
+            let Ok(created_at) = NaiveDateTime::parse_from_str(id, ID_FORMAT) else {
+                continue;
+            };
+            let size = entry
+                .metadata()
+                .map_err(|e| format!("Failed to read backup {}: {}", id, e))?
+                .len();
+            backups.push(Backup {
+                id: id.to_string(),
+                created_at: DateTime::from_naive_utc_and_offset(created_at, Utc),
+                size,
+            });
+        }
+        backups.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(backups)
+    }
+
+    fn verify(&self, id: &str) -> Result<usize, String> {
+        self.read_backup(id)
+            .map(|(data_file, _)| data_file.users.len())
+    }
+
+    fn restore(&self, id: &str) -> Result<Backup, String> {
+        let backup = self
+            .list()?
+            .into_iter()
+            .find(|backup| backup.id == id)
+            .ok_or_else(|| format!("Backup not found: {}", id))?;
+
+        self.verify(id)?;
+        let content = fs::read_to_string(self.backup_path(id))
+            .map_err(|e| format!("Failed to read backup {}: {}", id, e))?;
+
+        // 書き戻しも取り消せるよう、現在のファイルを先にバックアップする
+        self.create()?;
+        let temp_path = format!("{}.tmp", self.data_file);
+        fs::write(&temp_path, content).map_err(|e| format!("Failed to restore backup: {}", e))?;
+        fs::rename(&temp_path, &self.data_file)
+            .map_err(|e| format!("Failed to restore backup: {}", e))?;
+        Ok(backup)
+    }
+
+    fn find_user(&self, emails: &[String]) -> Result<Vec<(Backup, User)>, String> {
+        let mut found = Vec::new();
+        for backup in self.list()? {
+            let (data_file, _) = self.read_backup(&backup.id)?;
+            let mut users: Vec<(String, User)> = data_file.users.into_iter().collect();
+            users.sort_by(|a, b| a.0.cmp(&b.0));
+            for (key, user) in users {
+                if refers_to(&key, &user, emails) {
+                    found.push((backup.clone(), user));
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    fn erase_user(&self, emails: &[String]) -> Result<Vec<String>, String> {
+        let mut rewritten = Vec::new();
+        for backup in self.list()? {
+            let (mut data_file, encrypted) = self.read_backup(&backup.id)?;
+            let count = data_file.users.len();
+            data_file
+                .users
+                .retain(|key, user| !refers_to(key, user, emails));
+            if data_file.users.len() == count {
+                continue;
+            }
+
+            let mut content = serde_json::to_string_pretty(&data_file)
+                .map_err(|e| format!("Failed to serialize backup {}: {}", backup.id, e))?;
+            if encrypted && let Some(cipher) = &self.cipher {
+                content = cipher.encrypt(&content)?;
+            }
+            self.write_backup(&backup.id, &content)?;
+            rewritten.push(backup.id);
+        }
+        Ok(rewritten)
+    }
+
+    fn encrypt_all(&self) -> Result<Vec<String>, String> {
+        let Some(cipher) = &self.cipher else {
+            return Err("No encryption key configured for backups".to_string());
+        };
+
+        let mut encrypted = Vec::new();
+        for backup in self.list()? {
+            let content = fs::read_to_string(self.backup_path(&backup.id))
+                .map_err(|e| format!("Failed to read backup {}: {}", backup.id, e))?;
+            if is_encrypted(&content) {
+                continue;
+            }
+            self.write_backup(&backup.id, &cipher.encrypt(&content)?)?;
+            encrypted.push(backup.id);
+        }
+        Ok(encrypted)
+    }
+}
+
+/// ユーザーが指定されたメールアドレスのいずれかに該当するかを判定します。
+///
+/// # 引数
+/// * `key` - ユーザーを保存しているキー
+/// * `user` - ユーザー
+/// * `emails` - 対象のメールアドレス
+///
+/// # 戻り値
+/// * `bool` - キー・メールアドレス・別名のいずれかが一致する場合は`true`
+fn refers_to(key: &str, user: &User, emails: &[String]) -> bool {
+    std::iter::once(key)
+        .chain(std::iter::once(user.email.as_str()))
+        .chain(user.aliases.iter().map(String::as_str))
+        .any(|email| {
+            let email = email.trim().to_lowercase();
+            emails.iter().any(|target| target.to_lowercase() == email)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::user_repository::{UserRepository, UserRepositoryImpl};
+    use tempfile::TempDir;
+
+    fn user(email: &str) -> User {
+        User {
+            email: email.to_string(),
+            username: "testuser".to_string(),
+            phone: "+819012345678".to_string(),
+            age: 25,
+            ..Default::default()
+        }
+    }
+
+    fn setup(temp_dir: &TempDir, max_count: usize) -> (String, UserRepositoryImpl) {
+        let data_file = temp_dir
+            .path()
+            .join("userdata.json")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let backups = BackupRepositoryImpl::with_directory(
+            &data_file,
+            temp_dir.path().join("userdata.backups"),
+        )
+        .with_retention(max_count, None);
+        let repository = UserRepositoryImpl::with_file_path(&data_file).with_backups(backups);
+        (data_file, repository)
+    }
+
+    #[test]
+    fn test_backups_are_rotated_before_each_write() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let (data_file, repository) = setup(&temp_dir, 2);
+        for email in [
+            "a@example.com",
+            "b@example.com",
+            "c@example.com",
+            "d@example.com",
+        ] {
+            repository.save(&user(email)).unwrap();
+        }
+
+        let backups = BackupRepositoryImpl::with_directory(
+            &data_file,
+            temp_dir.path().join("userdata.backups"),
+        );
+        let list = backups.list().unwrap();
+        // 最初の書き込みの前にはファイルがないため、バックアップは3回作られ、古い1件が削除される
+        assert_eq!(list.len(), 2);
+        assert!(list[0].id < list[1].id);
+    }
+
+    #[test]
+    fn test_restore_backup() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let (data_file, repository) = setup(&temp_dir, 10);
+        repository.save(&user("a@example.com")).unwrap();
+        repository.save(&user("b@example.com")).unwrap();
+        let backups = BackupRepositoryImpl::with_directory(
+            &data_file,
+            temp_dir.path().join("userdata.backups"),
+        );
+        let id = backups.list().unwrap()[0].id.clone();
+
+        backups.restore(&id).unwrap();
+        assert_eq!(repository.find_all().unwrap(), vec![user("a@example.com")]);
+        // 書き戻す前の状態もバックアップされている
+        assert_eq!(backups.list().unwrap().len(), 2);
+        assert!(backups.restore("missing").is_err());
+    }
+
+    #[test]
+    fn test_restore_rejects_invalid_backup() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let (data_file, repository) = setup(&temp_dir, 10);
+        repository.save(&user("a@example.com")).unwrap();
+        repository.save(&user("b@example.com")).unwrap();
+        let backups = BackupRepositoryImpl::with_directory(
+            &data_file,
+            temp_dir.path().join("userdata.backups"),
+        );
+        let id = backups.list().unwrap()[0].id.clone();
+        fs::write(backups.backup_path(&id), "{ broken").unwrap();
+
+        assert!(backups.verify(&id).is_err());
+        assert!(backups.restore(&id).is_err());
+        assert_eq!(repository.find_all().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_find_and_erase_user_in_backups() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let (data_file, repository) = setup(&temp_dir, 10);
+        repository.save(&user("a@example.com")).unwrap();
+        repository.save(&user("b@example.com")).unwrap();
+        repository.save(&user("c@example.com")).unwrap();
+        let backups = BackupRepositoryImpl::with_directory(
+            &data_file,
+            temp_dir.path().join("userdata.backups"),
+        );
+        let emails = vec!["a@example.com".to_string()];
+
+        assert_eq!(backups.find_user(&emails).unwrap().len(), 2);
+        assert_eq!(backups.erase_user(&emails).unwrap().len(), 2);
+        assert!(backups.find_user(&emails).unwrap().is_empty());
+        // 他のユーザーはバックアップに残る
+        let others = vec!["b@example.com".to_string()];
+        assert_eq!(backups.find_user(&others).unwrap().len(), 1);
+        assert!(backups.erase_user(&emails).unwrap().is_empty());
+    }
+}
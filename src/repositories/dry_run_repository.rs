@@ -0,0 +1,174 @@
+//! 書き込みを行わずに変更内容を確認するためのリポジトリ
+//!
+//! 読み込みは元のリポジトリから行い、書き込みはメモリ上に保持します。
+//! 操作を実行した後に`planned_changes`で、実際に書き込まれるはずだった変更を取得できます。
+
+use crate::models::journal::JournalChange;
+use crate::models::user::User;
+use crate::repositories::user_repository::{BatchOperation, UserRepository};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+/// 書き込みをメモリ上に保持し、元のリポジトリを変更しないリポジトリ
+pub struct DryRunUserRepository<R: UserRepository> {
+    /// 読み込み元のリポジトリ
+    inner: R,
+    /// 書き込まれた内容（キーはメールアドレス、削除された場合は`None`）
+    overlay: RefCell<BTreeMap<String, Option<User>>>,
+}
+
+impl<R: UserRepository> DryRunUserRepository<R> {
+    /// 新しいDryRunUserRepositoryインスタンスを作成します。
+    ///
+    /// # 引数
+    /// * `inner` - 読み込み元のリポジトリ
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいDryRunUserRepositoryインスタンス
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            overlay: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    /// 元のリポジトリに書き込まれるはずだった変更を返します。
+    ///
+    /// 書き込まれた後に元の状態へ戻ったレコードは含みません。
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<JournalChange>)` - メールアドレス順に並んだレコードごとの変更
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 元のリポジトリからの読み込みに失敗した場合
+    pub fn planned_changes(&self) -> Result<Vec<JournalChange>, String> {
+        let mut changes = Vec::new();
+        for (email, after) in self.overlay.borrow().iter() {
+            let before = self.inner.find_by_email(email)?;
+            if &before != after {
+                changes.push(JournalChange {
+                    before,
+                    after: after.clone(),
+                });
+            }
+        }
+        Ok(changes)
+    }
+}
+
+impl<R: UserRepository> UserRepository for DryRunUserRepository<R> {
+    fn save(&self, user: &User) -> Result<(), String> {
+        self.overlay
+            .borrow_mut()
+            .insert(user.email.clone(), Some(user.clone()));
+        Ok(())
+    }
+
+    fn find_by_email(&self, email: &str) -> Result<Option<User>, String> {
+        match self.overlay.borrow().get(email) {
+            Some(user) => Ok(user.clone()),
+            None => self.inner.find_by_email(email),
+        }
+    }
+
+    fn find_all(&self) -> Result<Vec<User>, String> {
+        let overlay = self.overlay.borrow();
+        let mut users: Vec<User> = self
+            .inner
+            .find_all()?
+            .into_iter()
+            .filter_map(|user| match overlay.get(&user.email) {
+                Some(written) => written.clone(),
+                None => Some(user),
+            })
+            .collect();
+        for user in overlay.values().flatten() {
+            if !users.iter().any(|existing| existing.email == user.email) {
+                users.push(user.clone());
+            }
+        }
+        Ok(users)
+    }
+
+    fn find_all_entries(&self) -> Result<Vec<(String, User)>, String> {
+        let overlay = self.overlay.borrow();
+        let mut entries: Vec<(String, User)> = self
+            .inner
+            .find_all_entries()?
+            .into_iter()
+            .filter(|(key, _)| !overlay.contains_key(key))
+            .collect();
+        for (key, user) in overlay.iter() {
+            if let Some(user) = user {
+                entries.push((key.clone(), user.clone()));
+            }
+        }
+        Ok(entries)
+    }
+
+    fn delete(&self, email: &str) -> Result<bool, String> {
+        let existed = self.find_by_email(email)?.is_some();
+        self.overlay.borrow_mut().insert(email.to_string(), None);
+        Ok(existed)
+    }
+
+    fn apply_batch(&self, operations: &[BatchOperation]) -> Result<(), String> {
+        let mut overlay = self.overlay.borrow_mut();
+        for operation in operations {
+            match operation {
+                BatchOperation::Put(user) => overlay.insert(user.email.clone(), Some(user.clone())),
+                BatchOperation::Delete(email) => overlay.insert(email.clone(), None),
+            };
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::user_repository::UserRepositoryImpl;
+
+    fn user(email: &str, age: u32) -> User {
+        User {
+            email: email.to_string(),
+            username: "testuser".to_string(),
+            phone: "+819012345678".to_string(),
+            age,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_writes_are_kept_in_memory() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let inner = UserRepositoryImpl::with_file_path(
+            temp_dir.path().join("userdata.json").to_str().unwrap(),
+        );
+        inner.save(&user("a@example.com", 20)).unwrap();
+        inner.save(&user("b@example.com", 30)).unwrap();
+
+        let repo = DryRunUserRepository::new(&inner);
+        repo.save(&user("a@example.com", 21)).unwrap();
+        assert!(repo.delete("b@example.com").unwrap());
+        repo.save(&user("c@example.com", 40)).unwrap();
+
+        assert_eq!(
+            repo.find_by_email("a@example.com").unwrap().unwrap().age,
+            21
+        );
+        assert!(repo.find_by_email("b@example.com").unwrap().is_none());
+        assert_eq!(repo.find_all().unwrap().len(), 2);
+        assert_eq!(
+            inner.find_by_email("a@example.com").unwrap().unwrap().age,
+            20
+        );
+        assert_eq!(inner.find_all().unwrap().len(), 2);
+
+        let changes = repo.planned_changes().unwrap();
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[1].after, None);
+        assert_eq!(changes[2].before, None);
+    }
+}
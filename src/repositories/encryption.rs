@@ -0,0 +1,627 @@
+//! ユーザーデータのファイルの暗号化を扱うモジュール
+//!
+//! 暗号化したファイルは、暗号化の方式を記録したヘッダーと、XChaCha20-Poly1305で
+//! 暗号化した`DataFile`のJSONを持つJSONとして保存されます。ヘッダーも認証の対象に
+//! 含めるため、ヘッダーと本文のどちらが書き換えられても復号に失敗します。
+//!
+//! 鍵は、環境変数`USER_DATA_PASSPHRASE`のパスフレーズからArgon2idで導出するか、
+//! 環境変数`USER_DATA_KEY_FILE`で指定した鍵ファイル（256ビットの鍵を64文字の
+//! 16進数で記述したもの）から読み込みます。両方が設定されている場合は鍵ファイルを使用します。
+//!
+//! 変更履歴・監査ログ・操作ジャーナル・隔離ファイルなどの補助ファイルも、鍵が設定されて
+//! いれば同じ形式でファイル全体を暗号化します（`read_sidecar`・`write_sidecar`・`append_sidecar`）。
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// 暗号化の方式の名前
+const CIPHER_NAME: &str = "xchacha20poly1305";
+
+/// パスフレーズから鍵を導出する際のソルトのバイト数
+const SALT_LENGTH: usize = 16;
+
+/// 鍵のバイト数
+const KEY_LENGTH: usize = 32;
+
+/// ファイルのヘッダーで受け付けるArgon2idのメモリ量の上限（KiB）
+const MAX_MEMORY_KIB: u32 = 256 * 1024;
+
+/// ファイルのヘッダーで受け付けるArgon2idの反復回数の上限
+const MAX_ITERATIONS: u32 = 16;
+
+/// ファイルのヘッダーで受け付けるArgon2idの並列度の上限
+const MAX_PARALLELISM: u32 = 16;
+
+/// 鍵の導出方法と、それによって求めた鍵
+type DerivedKey = (KeyDerivation, [u8; KEY_LENGTH]);
+
+/// 暗号化の鍵の入手元
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeySource {
+    /// パスフレーズからArgon2idで鍵を導出する
+    Passphrase(String),
+    /// 鍵ファイルから鍵を読み込む
+    KeyFile(PathBuf),
+}
+
+impl KeySource {
+    /// 環境変数から鍵の入手元を決定します。
+    ///
+    /// `USER_DATA_KEY_FILE`が設定されていれば鍵ファイルを、そうでなければ
+    /// `USER_DATA_PASSPHRASE`のパスフレーズを使用します。
+    ///
+    /// # 戻り値
+    /// * `Some(KeySource)` - 鍵の入手元
+    /// * `None` - どちらの環境変数も設定されていない場合
+    pub fn from_env() -> Option<Self> {
+        if let Some(path) = env::var_os("USER_DATA_KEY_FILE").filter(|path| !path.is_empty()) {
+            return Some(KeySource::KeyFile(PathBuf::from(path)));
+        }
+        env::var("USER_DATA_PASSPHRASE")
+            .ok()
+            .filter(|passphrase| !passphrase.is_empty())
+            .map(KeySource::Passphrase)
+    }
+}
+
+/// 鍵の導出方法（暗号化したファイルのヘッダーに記録する）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "algorithm", rename_all = "kebab-case")]
+enum KeyDerivation {
+    /// パスフレーズからArgon2idで導出した鍵
+    Argon2id {
+        /// ソルト（Base64）
+        salt: String,
+        /// 使用するメモリ量（KiB）
+        memory_kib: u32,
+        /// 反復回数
+        iterations: u32,
+        /// 並列度
+        parallelism: u32,
+    },
+    /// 鍵ファイルから読み込んだ鍵
+    KeyFile,
+}
+
+/// 暗号化したファイルのヘッダー
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct EncryptionHeader {
+    /// 暗号化の方式
+    cipher: String,
+    /// 鍵の導出方法
+    kdf: KeyDerivation,
+    /// ナンス（Base64）
+    nonce: String,
+}
+
+/// 暗号化したファイル全体
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedFile {
+    /// 暗号化の方式
+    encryption: EncryptionHeader,
+    /// 暗号文（Base64）
+    ciphertext: String,
+}
+
+/// ユーザーデータのファイルを暗号化・復号する
+///
+/// パスフレーズから導出した鍵は、同じソルトで再び導出しないよう保持します。
+/// 書き込みのたびにナンスは新しく生成し、ソルトは鍵を導出したときのものを使い続けます。
+/// 複製したインスタンスは保持した鍵を共有するため、補助ファイルごとに鍵を導出し直しません。
+#[derive(Clone)]
+pub struct DataFileCipher {
+    /// 鍵の入手元
+    source: KeySource,
+    /// 最後に使用した鍵の導出方法と鍵
+    key: Rc<RefCell<Option<DerivedKey>>>,
+}
+
+impl DataFileCipher {
+    /// 鍵の入手元を指定してインスタンスを作成します。
+    ///
+    /// # 引数
+    /// * `source` - 鍵の入手元
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいDataFileCipherインスタンス
+    pub fn new(source: KeySource) -> Self {
+        Self {
+            source,
+            key: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// 環境変数で鍵が設定されている場合にインスタンスを作成します。
+    ///
+    /// # 戻り値
+    /// * `Some(Self)` - 鍵が設定されている場合
+    /// * `None` - 鍵が設定されていない場合
+    pub fn from_env() -> Option<Self> {
+        KeySource::from_env().map(Self::new)
+    }
+
+    /// ファイルの内容を暗号化します。
+    ///
+    /// # 引数
+    /// * `plaintext` - 暗号化するファイルの内容
+    ///
+    /// # 戻り値
+    /// * `Ok(String)` - 暗号化したファイルの内容（JSON）
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 鍵ファイルを読み込めない、または鍵の形式が不正な場合
+    /// * 鍵の導出・暗号化に失敗した場合
+    pub fn encrypt(&self, plaintext: &str) -> Result<String, String> {
+        let cached = self.key.borrow().clone();
+        let (kdf, key) = match cached {
+            Some(cached) => cached,
+            None => {
+                let kdf = match &self.source {
+                    KeySource::Passphrase(_) => {
+                        let mut salt = [0u8; SALT_LENGTH];
+                        OsRng.fill_bytes(&mut salt);
+                        KeyDerivation::Argon2id {
+                            salt: BASE64.encode(salt),
+                            memory_kib: Params::DEFAULT_M_COST,
+                            iterations: Params::DEFAULT_T_COST,
+                            parallelism: Params::DEFAULT_P_COST,
+                        }
+                    }
+                    KeySource::KeyFile(_) => KeyDerivation::KeyFile,
+                };
+                self.key_for(&kdf)?
+            }
+        };
+
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let header = EncryptionHeader {
+            cipher: CIPHER_NAME.to_string(),
+            kdf,
+            nonce: BASE64.encode(nonce),
+        };
+        let aad = serde_json::to_vec(&header)
+            .map_err(|e| format!("Failed to serialize encryption header: {}", e))?;
+        let ciphertext = XChaCha20Poly1305::new(&key.into())
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext.as_bytes(),
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| "Failed to encrypt data file".to_string())?;
+
+        serde_json::to_string_pretty(&EncryptedFile {
+            encryption: header,
+            ciphertext: BASE64.encode(ciphertext),
+        })
+        .map_err(|e| format!("Failed to serialize JSON: {}", e))
+    }
+
+    /// 暗号化したファイルの内容を復号します。
+    ///
+    /// # 引数
+    /// * `content` - 暗号化したファイルの内容
+    ///
+    /// # 戻り値
+    /// * `Ok(String)` - 復号したファイルの内容
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 暗号化したファイルとして解析できない場合
+    /// * 鍵の入手元の種類がファイルの暗号化に使われたものと異なる場合
+    /// * 鍵が誤っている、またはファイルが書き換えられている場合
+    pub fn decrypt(&self, content: &str) -> Result<String, String> {
+        let file: EncryptedFile = serde_json::from_str(content)
+            .map_err(|e| format!("Failed to parse encrypted data file: {}", e))?;
+        let header = file.encryption;
+        if header.cipher != CIPHER_NAME {
+            return Err(format!("Unsupported cipher: {}", header.cipher));
+        }
+        let nonce = decode_base64(&header.nonce, "nonce")?;
+        if nonce.len() != 24 {
+            return Err("Invalid nonce in encrypted data file".to_string());
+        }
+        let ciphertext = decode_base64(&file.ciphertext, "ciphertext")?;
+
+        let (_, key) = self.key_for(&header.kdf)?;
+        let aad = serde_json::to_vec(&header)
+            .map_err(|e| format!("Failed to serialize encryption header: {}", e))?;
+        let plaintext = XChaCha20Poly1305::new(&key.into())
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| {
+                "Failed to decrypt data file: the passphrase or key file is wrong, \
+                 or the file has been modified"
+                    .to_string()
+            })?;
+        String::from_utf8(plaintext).map_err(|e| format!("Failed to decrypt data file: {}", e))
+    }
+
+    /// 鍵の導出方法に対応する鍵を求め、以後の暗号化のために保持します。
+    ///
+    /// # 引数
+    /// * `kdf` - 鍵の導出方法
+    ///
+    /// # 戻り値
+    /// * `Ok((KeyDerivation, [u8; KEY_LENGTH]))` - 鍵の導出方法と鍵
+    ///
+    /// # エラー
+    /// * 鍵の入手元の種類が導出方法と異なる場合
+    /// * Argon2idのパラメーターが上限を超えている場合
+    /// * 鍵ファイルを読み込めない、または鍵の形式が不正な場合
+    /// * 鍵の導出に失敗した場合
+    fn key_for(&self, kdf: &KeyDerivation) -> Result<DerivedKey, String> {
+        if let Some((cached_kdf, key)) = self.key.borrow().as_ref()
+            && cached_kdf == kdf
+        {
+            return Ok((kdf.clone(), *key));
+        }
+
+        let mut key = [0u8; KEY_LENGTH];
+        match (&self.source, kdf) {
+            (
+                KeySource::Passphrase(passphrase),
+                KeyDerivation::Argon2id {
+                    salt,
+                    memory_kib,
+                    iterations,
+                    parallelism,
+                },
+            ) => {
+                // ヘッダーは認証前に読むため、巨大なパラメーターで鍵の導出を長引かせられないようにする
+                if *memory_kib > MAX_MEMORY_KIB
+                    || *iterations > MAX_ITERATIONS
+                    || *parallelism > MAX_PARALLELISM
+                {
+                    return Err(format!(
+                        "Key derivation parameters exceed the supported maximum \
+                         (memory {} KiB, {} iterations, parallelism {})",
+                        MAX_MEMORY_KIB, MAX_ITERATIONS, MAX_PARALLELISM
+                    ));
+                }
+                let salt = decode_base64(salt, "salt")?;
+                let params = Params::new(*memory_kib, *iterations, *parallelism, Some(KEY_LENGTH))
+                    .map_err(|e| format!("Invalid key derivation parameters: {}", e))?;
+                Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+                    .map_err(|e| format!("Failed to derive key: {}", e))?;
+            }
+            (KeySource::KeyFile(path), KeyDerivation::KeyFile) => {
+                key = read_key_file(path)?;
+            }
+            (KeySource::Passphrase(_), KeyDerivation::KeyFile) => {
+                return Err(
+                    "Data file is encrypted with a key file, but a passphrase was given"
+                        .to_string(),
+                );
+            }
+            (KeySource::KeyFile(_), KeyDerivation::Argon2id { .. }) => {
+                return Err(
+                    "Data file is encrypted with a passphrase, but a key file was given"
+                        .to_string(),
+                );
+            }
+        }
+        *self.key.borrow_mut() = Some((kdf.clone(), key));
+        Ok((kdf.clone(), key))
+    }
+}
+
+/// ファイルの内容が暗号化されているかどうかを判定します。
+///
+/// # 引数
+/// * `content` - ファイルの内容
+///
+/// # 戻り値
+/// * `bool` - 暗号化したファイルの場合は`true`
+pub fn is_encrypted(content: &str) -> bool {
+    serde_json::from_str::<EncryptedFile>(content).is_ok()
+}
+
+/// ファイルの内容が暗号化されている場合は復号し、そうでなければそのまま返します。
+///
+/// # 引数
+/// * `content` - ファイルの内容
+/// * `cipher` - 復号に使用するインスタンス（鍵が設定されていない場合は`None`）
+///
+/// # 戻り値
+/// * `Ok(String)` - 平文のファイルの内容
+///
+/// # Errors
+/// 以下の場合にエラーを返します：
+/// * ファイルが暗号化されているが、鍵が設定されていない場合
+/// * 復号に失敗した場合
+pub fn decrypt_if_encrypted(
+    content: &str,
+    cipher: Option<&DataFileCipher>,
+) -> Result<String, String> {
+    if !is_encrypted(content) {
+        return Ok(content.to_string());
+    }
+    match cipher {
+        Some(cipher) => cipher.decrypt(content),
+        None => Err(
+            "Data file is encrypted; set USER_DATA_PASSPHRASE or USER_DATA_KEY_FILE to read it"
+                .to_string(),
+        ),
+    }
+}
+
+/// 補助ファイルの内容を読み込み、暗号化されている場合は復号します。
+///
+/// # 引数
+/// * `path` - 補助ファイルのパス
+/// * `cipher` - 復号に使用するインスタンス（鍵が設定されていない場合は`None`）
+///
+/// # 戻り値
+/// * `Ok(String)` - 平文のファイルの内容（ファイルが存在しない場合は空文字列）
+///
+/// # Errors
+/// 以下の場合にエラーを返します：
+/// * ファイルの読み込みに失敗した場合
+/// * ファイルが暗号化されているが、鍵が設定されていない、または鍵が誤っている場合
+pub fn read_sidecar(path: &str, cipher: Option<&DataFileCipher>) -> Result<String, String> {
+    if !Path::new(path).exists() {
+        return Ok(String::new());
+    }
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    decrypt_if_encrypted(&content, cipher)
+}
+
+/// 補助ファイルに内容を一時ファイル経由で書き込みます。
+///
+/// # 引数
+/// * `path` - 補助ファイルのパス
+/// * `content` - 書き込む平文の内容
+/// * `cipher` - 暗号化に使用するインスタンス（`None`の場合は平文で書き込む）
+///
+/// # 戻り値
+/// * `Ok(())` - 書き込みに成功した場合
+///
+/// # Errors
+/// 以下の場合にエラーを返します：
+/// * 暗号化に失敗した場合
+/// * ファイルの書き込みに失敗した場合
+pub fn write_sidecar(
+    path: &str,
+    content: &str,
+    cipher: Option<&DataFileCipher>,
+) -> Result<(), String> {
+    let content = match cipher {
+        Some(cipher) => cipher.encrypt(content)?,
+        None => content.to_string(),
+    };
+    let temp_path = format!("{}.tmp", path);
+    fs::write(&temp_path, content).map_err(|e| e.to_string())?;
+    fs::rename(&temp_path, path).map_err(|e| e.to_string())
+}
+
+/// JSON Linesの補助ファイルに行を追記します。
+///
+/// 鍵が設定されていない平文のファイルにはそのまま追記し、鍵が設定されている場合は
+/// ファイル全体を暗号化して書き直します（平文のファイルはこのときに暗号化されます）。
+///
+/// # 引数
+/// * `path` - 補助ファイルのパス
+/// * `line` - 追記する行（改行を含まない）
+/// * `cipher` - 暗号化・復号に使用するインスタンス（鍵が設定されていない場合は`None`）
+///
+/// # 戻り値
+/// * `Ok(())` - 追記に成功した場合
+///
+/// # Errors
+/// 以下の場合にエラーを返します：
+/// * ファイルの読み書きに失敗した場合
+/// * ファイルが暗号化されているが、鍵が設定されていない、または鍵が誤っている場合
+/// * 暗号化に失敗した場合
+pub fn append_sidecar(
+    path: &str,
+    line: &str,
+    cipher: Option<&DataFileCipher>,
+) -> Result<(), String> {
+    let mut content = read_sidecar(path, cipher)?;
+    if cipher.is_some() {
+        content.push_str(line);
+        content.push('\n');
+        return write_sidecar(path, &content, cipher);
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+    writeln!(file, "{}", line).map_err(|e| e.to_string())
+}
+
+/// 補助ファイルを読み込み、別の鍵で（または平文で）書き直します。
+///
+/// # 引数
+/// * `path` - 補助ファイルのパス
+/// * `current` - 現在のファイルの復号に使用するインスタンス
+/// * `cipher` - 書き直すファイルの暗号化に使用するインスタンス（`None`の場合は平文）
+///
+/// # 戻り値
+/// * `Ok(true)` - ファイルを書き直した場合
+/// * `Ok(false)` - ファイルが存在しない場合
+///
+/// # Errors
+/// 以下の場合にエラーを返します：
+/// * ファイルの読み書きに失敗した場合
+/// * 復号・暗号化に失敗した場合
+pub fn rewrite_sidecar(
+    path: &str,
+    current: Option<&DataFileCipher>,
+    cipher: Option<&DataFileCipher>,
+) -> Result<bool, String> {
+    if !Path::new(path).exists() {
+        return Ok(false);
+    }
+    let content = read_sidecar(path, current)?;
+    write_sidecar(path, &content, cipher)?;
+    Ok(true)
+}
+
+/// 鍵ファイルから256ビットの鍵を読み込みます。
+///
+/// # 引数
+/// * `path` - 鍵を64文字の16進数で記述した鍵ファイルのパス
+///
+/// # 戻り値
+/// * `Ok([u8; 32])` - 読み込んだ鍵
+///
+/// # Errors
+/// 以下の場合にエラーを返します：
+/// * 鍵ファイルを読み込めない場合
+/// * 鍵ファイルの内容が64文字の16進数でない場合
+pub fn read_key_file(path: &Path) -> Result<[u8; KEY_LENGTH], String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read key file {}: {}", path.display(), e))?;
+    hex::decode(content.trim())
+        .ok()
+        .and_then(|bytes| <[u8; KEY_LENGTH]>::try_from(bytes).ok())
+        .ok_or_else(|| {
+            format!(
+                "Key file {} must contain a 256-bit key as 64 hexadecimal characters",
+                path.display()
+            )
+        })
+}
+
+/// Base64の文字列をデコードします。
+///
+/// # 引数
+/// * `value` - Base64の文字列
+/// * `field` - エラーメッセージに使用するフィールド名
+///
+/// # 戻り値
+/// * `Ok(Vec<u8>)` - デコードしたバイト列
+///
+/// # エラー
+/// * Base64としてデコードできない場合
+fn decode_base64(value: &str, field: &str) -> Result<Vec<u8>, String> {
+    BASE64
+        .decode(value)
+        .map_err(|e| format!("Invalid {} in encrypted data file: {}", field, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_and_decrypt_with_passphrase() {
+        let cipher = DataFileCipher::new(KeySource::Passphrase("secret".to_string()));
+        let encrypted = cipher.encrypt(r#"{"users":{}}"#).unwrap();
+
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.contains("users"));
+        // 別のインスタンスでも、同じパスフレーズなら復号できる
+        let other = DataFileCipher::new(KeySource::Passphrase("secret".to_string()));
+        assert_eq!(other.decrypt(&encrypted).unwrap(), r#"{"users":{}}"#);
+
+        let wrong = DataFileCipher::new(KeySource::Passphrase("wrong".to_string()));
+        assert!(wrong.decrypt(&encrypted).unwrap_err().contains("wrong"));
+        assert!(decrypt_if_encrypted(&encrypted, None).is_err());
+        assert_eq!(decrypt_if_encrypted("{}", None).unwrap(), "{}");
+    }
+
+    #[test]
+    fn test_encrypt_and_decrypt_with_key_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let key_file = temp_dir.path().join("userdata.key");
+        fs::write(&key_file, format!("{}\n", "ab".repeat(32))).unwrap();
+        let cipher = DataFileCipher::new(KeySource::KeyFile(key_file.clone()));
+        let encrypted = cipher.encrypt("plaintext").unwrap();
+
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), "plaintext");
+        let passphrase = DataFileCipher::new(KeySource::Passphrase("secret".to_string()));
+        assert!(
+            passphrase
+                .decrypt(&encrypted)
+                .unwrap_err()
+                .contains("key file")
+        );
+
+        fs::write(&key_file, "too short").unwrap();
+        let cipher = DataFileCipher::new(KeySource::KeyFile(key_file));
+        assert!(
+            cipher
+                .encrypt("plaintext")
+                .unwrap_err()
+                .contains("64 hexadecimal")
+        );
+    }
+
+    #[test]
+    fn test_tampered_header_is_rejected() {
+        let cipher = DataFileCipher::new(KeySource::Passphrase("secret".to_string()));
+        let encrypted = cipher.encrypt("plaintext").unwrap();
+        let tampered = encrypted.replace(
+            &format!(r#""iterations": {}"#, Params::DEFAULT_T_COST),
+            r#""iterations": 3"#,
+        );
+
+        assert_ne!(tampered, encrypted);
+        assert!(cipher.decrypt(&tampered).is_err());
+    }
+
+    #[test]
+    fn test_oversized_key_derivation_parameters_are_rejected() {
+        let cipher = DataFileCipher::new(KeySource::Passphrase("secret".to_string()));
+        let encrypted = cipher.encrypt("plaintext").unwrap();
+        let oversized = encrypted.replace(
+            &format!(r#""memory_kib": {}"#, Params::DEFAULT_M_COST),
+            r#""memory_kib": 4194304"#,
+        );
+
+        assert_ne!(oversized, encrypted);
+        let other = DataFileCipher::new(KeySource::Passphrase("secret".to_string()));
+        assert!(
+            other
+                .decrypt(&oversized)
+                .unwrap_err()
+                .contains("supported maximum")
+        );
+    }
+
+    #[test]
+    fn test_sidecar_is_encrypted_when_a_key_is_set() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("history.jsonl");
+        let path = path.to_str().unwrap();
+        append_sidecar(path, r#"{"phone":"+819012345678"}"#, None).unwrap();
+
+        let cipher = DataFileCipher::new(KeySource::Passphrase("secret".to_string()));
+        append_sidecar(path, r#"{"phone":"+819087654321"}"#, Some(&cipher)).unwrap();
+        let raw = fs::read_to_string(path).unwrap();
+        assert!(is_encrypted(&raw));
+        assert!(!raw.contains("+8190"));
+        assert_eq!(
+            read_sidecar(path, Some(&cipher)).unwrap().lines().count(),
+            2
+        );
+        // 鍵がない場合、暗号化されたファイルに平文を追記しない
+        assert!(append_sidecar(path, "{}", None).is_err());
+
+        assert!(rewrite_sidecar(path, Some(&cipher), None).unwrap());
+        assert!(fs::read_to_string(path).unwrap().contains("+819087654321"));
+    }
+}
@@ -0,0 +1,660 @@
+//! イベントソーシング方式のユーザーリポジトリ
+//!
+//! ユーザーの作成・更新・削除をイベントとしてJSON Lines形式のイベントログに追記し、
+//! これを唯一の正とします。読み込みはイベントを再生して作ったメモリ上の
+//! 射影（プロジェクション）から行います。
+//!
+//! 起動を速くするため、一定数のイベントごとに射影をスナップショットとして保存し、
+//! 起動時はスナップショットを読み込んだ上で、それ以降のイベントだけを再生します。
+//!
+//! ファイルはユーザーデータのファイル名から`.json`を除いたパスに、次の拡張子を付けて作成します。
+//! * `.events.jsonl` - イベントログ
+//! * `.snapshot.json` - スナップショット
+//!
+//! 暗号化の鍵が設定されている場合は、どちらもユーザーデータのファイルと同じ鍵でファイル全体を暗号化します。
+
+use crate::models::event::{StoredEvent, UserEvent};
+use crate::models::user::User;
+use crate::repositories::encryption::{
+    DataFileCipher, append_sidecar, read_sidecar, write_sidecar,
+};
+use crate::repositories::user_repository::{
+    BatchOperation, UserRepository, UserRepositoryImpl, data_file_path, sibling_path,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::cell::{RefCell, RefMut};
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+
+/// スナップショットを保存する既定のイベント間隔
+pub const DEFAULT_SNAPSHOT_INTERVAL: u64 = 100;
+
+/// イベントを再生して得られる現在の状態
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+struct Projection {
+    /// 最後に反映したイベントの通し番号
+    sequence: u64,
+    /// メールアドレスをキーとするユーザーのマップ
+    users: HashMap<String, User>,
+}
+
+impl Projection {
+    /// イベントを射影に反映します。
+    ///
+    /// # 引数
+    /// * `stored` - 反映するイベント
+    fn apply(&mut self, stored: &StoredEvent) {
+        match &stored.event {
+            UserEvent::UserCreated { user } | UserEvent::UserUpdated { user } => {
+                self.users.insert(user.email.clone(), user.clone());
+            }
+            UserEvent::UserDeleted { email } => {
+                self.users.remove(email);
+            }
+        }
+        self.sequence = stored.sequence;
+    }
+}
+
+/// 再構築の結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RebuildSummary {
+    /// 再生したイベントの件数
+    pub events: u64,
+    /// 再構築後のユーザー数
+    pub users: usize,
+}
+
+/// イベントログを正とするユーザーリポジトリの実装
+pub struct EventSourcedUserRepository {
+    /// イベントログ（JSON Lines）のパス
+    log_path: String,
+    /// スナップショットのパス
+    snapshot_path: String,
+    /// イベントログがまだない場合に取り込む、従来形式のユーザーデータのパス
+    legacy_data_path: String,
+    /// スナップショットを保存するイベント間隔
+    snapshot_interval: u64,
+    /// ファイルの暗号化・復号に使用する鍵（`None`の場合は平文）
+    cipher: Option<DataFileCipher>,
+    /// メモリ上の射影（最初に使用されるまで読み込まない）
+    projection: RefCell<Option<Projection>>,
+}
+
+impl Default for EventSourcedUserRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventSourcedUserRepository {
+    /// 新しいEventSourcedUserRepositoryインスタンスを作成します。
+    ///
+    /// 環境変数`USER_DATA_FILE`（既定値"userdata.json"）から各ファイルのパスを導きます。
+    /// スナップショットの間隔は環境変数`USER_SNAPSHOT_INTERVAL`で指定できます。
+    /// 暗号化の鍵は環境変数`USER_DATA_PASSPHRASE`または`USER_DATA_KEY_FILE`から読み込みます。
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいEventSourcedUserRepositoryインスタンス
+    pub fn new() -> Self {
+        let mut repository = Self::with_data_file(&data_file_path());
+        if let Some(interval) = snapshot_interval_from_env() {
+            repository = repository.with_snapshot_interval(interval);
+        }
+        match DataFileCipher::from_env() {
+            Some(cipher) => repository.with_cipher(cipher),
+            None => repository,
+        }
+    }
+
+    /// ユーザーデータのファイルパスを指定してEventSourcedUserRepositoryインスタンスを作成します。
+    ///
+    /// # 引数
+    /// * `data_file` - ユーザーデータのファイルパス（各ファイルのパスの基準）
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいEventSourcedUserRepositoryインスタンス
+    pub fn with_data_file(data_file: &str) -> Self {
+        Self {
+            log_path: sibling_path(data_file, "events.jsonl"),
+            snapshot_path: sibling_path(data_file, "snapshot.json"),
+            legacy_data_path: data_file.to_string(),
+            snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+            cipher: None,
+            projection: RefCell::new(None),
+        }
+    }
+
+    /// スナップショットを保存するイベント間隔を設定します。
+    ///
+    /// # 引数
+    /// * `interval` - イベント間隔（0の場合は自動では保存しない）
+    ///
+    /// # 戻り値
+    /// * `Self` - 間隔を設定したEventSourcedUserRepositoryインスタンス
+    pub fn with_snapshot_interval(mut self, interval: u64) -> Self {
+        self.snapshot_interval = interval;
+        self
+    }
+
+    /// ファイルの暗号化・復号に使用する鍵を設定します。
+    ///
+    /// イベントログ・スナップショットの読み書きと、従来形式のユーザーデータの取り込みに使用します。
+    ///
+    /// # 引数
+    /// * `cipher` - 暗号化・復号に使用するインスタンス
+    ///
+    /// # 戻り値
+    /// * `Self` - ファイルを暗号化して書き込むEventSourcedUserRepositoryインスタンス
+    pub fn with_cipher(mut self, cipher: DataFileCipher) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// イベントログのパスを返します。
+    ///
+    /// # 戻り値
+    /// * `&str` - イベントログのパス
+    pub fn log_path(&self) -> &str {
+        &self.log_path
+    }
+
+    /// スナップショットのパスを返します。
+    ///
+    /// # 戻り値
+    /// * `&str` - スナップショットのパス
+    pub fn snapshot_path(&self) -> &str {
+        &self.snapshot_path
+    }
+
+    /// スナップショットを使わずにイベントログの全てのイベントを再生し、射影を作り直します。
+    ///
+    /// 作り直した射影は新しいスナップショットとして保存されます。
+    ///
+    /// # 戻り値
+    /// * `Ok(RebuildSummary)` - 再生したイベントの件数と再構築後のユーザー数
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * イベントログが存在しない場合
+    /// * ファイルの読み書きに失敗した場合
+    /// * JSONのシリアライズ/デシリアライズに失敗した場合
+    pub fn rebuild(&self) -> Result<RebuildSummary, String> {
+        if !Path::new(&self.log_path).exists() {
+            return Err(format!("Event log not found: {}", self.log_path));
+        }
+
+        let mut projection = Projection::default();
+        let events = self.read_events(0)?;
+        for stored in &events {
+            projection.apply(stored);
+        }
+        self.write_snapshot(&projection)?;
+
+        let summary = RebuildSummary {
+            events: events.len() as u64,
+            users: projection.users.len(),
+        };
+        *self.projection.borrow_mut() = Some(projection);
+        Ok(summary)
+    }
+
+    /// 射影を返します。まだ読み込んでいない場合は、スナップショットとイベントログから読み込みます。
+    ///
+    /// イベントログもスナップショットもなく、従来形式のユーザーデータがある場合は、
+    /// その内容を`UserCreated`イベントとして取り込みます。
+    ///
+    /// # 戻り値
+    /// * `Ok(RefMut<Projection>)` - 読み込み済みの射影
+    ///
+    /// # エラー
+    /// * ファイルの読み書きに失敗した場合
+    /// * JSONのシリアライズ/デシリアライズに失敗した場合
+    fn projection(&self) -> Result<RefMut<'_, Projection>, String> {
+        let mut slot = self.projection.borrow_mut();
+        if slot.is_none() {
+            *slot = Some(self.load()?);
+        }
+        Ok(RefMut::map(slot, |projection| {
+            projection.get_or_insert_with(Projection::default)
+        }))
+    }
+
+    /// スナップショットとそれ以降のイベントから射影を読み込みます。
+    ///
+    /// # 戻り値
+    /// * `Ok(Projection)` - 読み込んだ射影
+    ///
+    /// # エラー
+    /// * ファイルの読み書きに失敗した場合
+    /// * JSONのシリアライズ/デシリアライズに失敗した場合
+    fn load(&self) -> Result<Projection, String> {
+        if !Path::new(&self.log_path).exists() && !Path::new(&self.snapshot_path).exists() {
+            return self.import_legacy_data();
+        }
+
+        let mut projection = self.read_snapshot()?.unwrap_or_default();
+        for stored in self.read_events(projection.sequence)? {
+            projection.apply(&stored);
+        }
+        Ok(projection)
+    }
+
+    /// 従来形式のユーザーデータを`UserCreated`イベントとしてイベントログに取り込みます。
+    ///
+    /// # 戻り値
+    /// * `Ok(Projection)` - 取り込んだ後の射影
+    ///
+    /// # エラー
+    /// * ファイルの読み書きに失敗した場合
+    /// * JSONのシリアライズ/デシリアライズに失敗した場合
+    fn import_legacy_data(&self) -> Result<Projection, String> {
+        let mut projection = Projection::default();
+        let legacy = UserRepositoryImpl::with_file_path(&self.legacy_data_path);
+        let legacy = match &self.cipher {
+            Some(cipher) => legacy.with_cipher(cipher.clone()),
+            None => legacy,
+        };
+        let mut users = legacy.find_all()?;
+        if users.is_empty() {
+            return Ok(projection);
+        }
+
+        users.sort_by(|a, b| a.email.cmp(&b.email));
+        let events: Vec<UserEvent> = users
+            .into_iter()
+            .map(|user| UserEvent::UserCreated { user })
+            .collect();
+        let stored = self.write_events(&projection, &events)?;
+        for event in &stored {
+            projection.apply(event);
+        }
+        self.write_snapshot(&projection)?;
+        Ok(projection)
+    }
+
+    /// 指定された通し番号より後のイベントをイベントログから読み込みます。
+    ///
+    /// # 引数
+    /// * `after` - この通し番号以前のイベントは読み飛ばす
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<StoredEvent>)` - 記録された順のイベント
+    ///
+    /// # エラー
+    /// * ファイルの読み込み、または復号に失敗した場合
+    /// * JSONのデシリアライズに失敗した場合
+    fn read_events(&self, after: u64) -> Result<Vec<StoredEvent>, String> {
+        let content = read_sidecar(&self.log_path, self.cipher.as_ref())
+            .map_err(|e| format!("Failed to read event log: {}", e))?;
+        let mut events = Vec::new();
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            let stored: StoredEvent =
+                serde_json::from_str(line).map_err(|e| format!("Failed to parse event: {}", e))?;
+            if stored.sequence > after {
+                events.push(stored);
+            }
+        }
+        Ok(events)
+    }
+
+    /// イベントに通し番号を振り、1回の書き込みでイベントログに追記します。
+    ///
+    /// # 引数
+    /// * `projection` - 現在の射影（通し番号の基準）
+    /// * `events` - 追記するイベント
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<StoredEvent>)` - 通し番号を振って追記したイベント
+    ///
+    /// # エラー
+    /// * ファイルの読み書き、または暗号化に失敗した場合
+    /// * JSONのシリアライズに失敗した場合
+    fn write_events(
+        &self,
+        projection: &Projection,
+        events: &[UserEvent],
+    ) -> Result<Vec<StoredEvent>, String> {
+        let timestamp = Utc::now();
+        let stored: Vec<StoredEvent> = events
+            .iter()
+            .zip(projection.sequence + 1..)
+            .map(|(event, sequence)| StoredEvent {
+                sequence,
+                timestamp,
+                event: event.clone(),
+            })
+            .collect();
+
+        let lines = stored
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(|e| format!("Failed to serialize event: {}", e))?;
+        append_sidecar(&self.log_path, &lines.join("\n"), self.cipher.as_ref())
+            .map_err(|e| format!("Failed to write event log: {}", e))?;
+        Ok(stored)
+    }
+
+    /// イベントを追記して射影に反映し、必要であればスナップショットを保存します。
+    ///
+    /// # 引数
+    /// * `events` - 追記するイベント
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 追記に成功した場合
+    ///
+    /// # エラー
+    /// * ファイルの読み書きに失敗した場合
+    /// * JSONのシリアライズ/デシリアライズに失敗した場合
+    fn append(&self, events: &[UserEvent]) -> Result<(), String> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let mut projection = self.projection()?;
+        let before = projection.sequence;
+        for stored in self.write_events(&projection, events)? {
+            projection.apply(&stored);
+        }
+
+        let interval = self.snapshot_interval;
+        if interval > 0 && projection.sequence / interval > before / interval {
+            self.write_snapshot(&projection)?;
+        }
+        Ok(())
+    }
+
+    /// スナップショットを読み込みます。
+    ///
+    /// # 戻り値
+    /// * `Ok(Some(Projection))` - スナップショットがある場合
+    /// * `Ok(None)` - スナップショットがない場合
+    ///
+    /// # エラー
+    /// * ファイルの読み込み、または復号に失敗した場合
+    /// * JSONのデシリアライズに失敗した場合
+    fn read_snapshot(&self) -> Result<Option<Projection>, String> {
+        if !Path::new(&self.snapshot_path).exists() {
+            return Ok(None);
+        }
+
+        let content = read_sidecar(&self.snapshot_path, self.cipher.as_ref())
+            .map_err(|e| format!("Failed to read snapshot: {}", e))?;
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse snapshot: {}", e))
+    }
+
+    /// 射影をスナップショットとして保存します。
+    ///
+    /// 書き込み途中で中断しても古いスナップショットが壊れないよう、
+    /// 一時ファイルに書き込んでから置き換えます。
+    ///
+    /// # 引数
+    /// * `projection` - 保存する射影
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 保存に成功した場合
+    ///
+    /// # エラー
+    /// * JSONのシリアライズに失敗した場合
+    /// * ファイルの書き込み、または暗号化に失敗した場合
+    fn write_snapshot(&self, projection: &Projection) -> Result<(), String> {
+        let content = serde_json::to_string(projection)
+            .map_err(|e| format!("Failed to serialize snapshot: {}", e))?;
+        write_sidecar(&self.snapshot_path, &content, self.cipher.as_ref())
+            .map_err(|e| format!("Failed to write snapshot: {}", e))
+    }
+}
+
+/// 環境変数`USER_SNAPSHOT_INTERVAL`からスナップショットの間隔を読み込みます。
+///
+/// # 戻り値
+/// * `Option<u64>` - 間隔（設定されていない、または数値でない場合は`None`）
+pub fn snapshot_interval_from_env() -> Option<u64> {
+    env::var("USER_SNAPSHOT_INTERVAL")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+}
+
+impl UserRepository for EventSourcedUserRepository {
+    fn save(&self, user: &User) -> Result<(), String> {
+        let exists = self.projection()?.users.contains_key(&user.email);
+        let user = user.clone();
+        let event = if exists {
+            UserEvent::UserUpdated { user }
+        } else {
+            UserEvent::UserCreated { user }
+        };
+        self.append(&[event])
+    }
+
+    fn find_by_email(&self, email: &str) -> Result<Option<User>, String> {
+        Ok(self.projection()?.users.get(email).cloned())
+    }
+
+    fn find_all(&self) -> Result<Vec<User>, String> {
+        Ok(self.projection()?.users.values().cloned().collect())
+    }
+
+    fn delete(&self, email: &str) -> Result<bool, String> {
+        if !self.projection()?.users.contains_key(email) {
+            return Ok(false);
+        }
+        self.append(&[UserEvent::UserDeleted {
+            email: email.to_string(),
+        }])?;
+        Ok(true)
+    }
+
+    fn apply_batch(&self, operations: &[BatchOperation]) -> Result<(), String> {
+        // 途中の操作による存在状態の変化を反映しながら、1回の追記で書き込むイベントを組み立てる
+        let projection = self.projection()?;
+        let mut exists: HashMap<&str, bool> = HashMap::new();
+        let mut events = Vec::new();
+        for operation in operations {
+            match operation {
+                BatchOperation::Put(user) => {
+                    let existed = exists
+                        .insert(&user.email, true)
+                        .unwrap_or_else(|| projection.users.contains_key(&user.email));
+                    let user = user.clone();
+                    events.push(if existed {
+                        UserEvent::UserUpdated { user }
+                    } else {
+                        UserEvent::UserCreated { user }
+                    });
+                }
+                BatchOperation::Delete(email) => {
+                    let existed = exists
+                        .insert(email, false)
+                        .unwrap_or_else(|| projection.users.contains_key(email));
+                    if existed {
+                        events.push(UserEvent::UserDeleted {
+                            email: email.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        drop(projection);
+        self.append(&events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::encryption::KeySource;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn user(email: &str, age: u32) -> User {
+        User {
+            email: email.to_string(),
+            username: "testuser".to_string(),
+            phone: "+819012345678".to_string(),
+            age,
+            ..Default::default()
+        }
+    }
+
+    fn data_file(temp_dir: &TempDir) -> String {
+        temp_dir
+            .path()
+            .join("userdata.json")
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_apply_batch_appends_events_once() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = EventSourcedUserRepository::with_data_file(&data_file(&temp_dir));
+        repo.save(&user("a@example.com", 20)).unwrap();
+
+        repo.apply_batch(&[
+            BatchOperation::Delete("a@example.com".to_string()),
+            BatchOperation::Put(user("b@example.com", 30)),
+            BatchOperation::Put(user("b@example.com", 31)),
+            BatchOperation::Delete("missing@example.com".to_string()),
+        ])
+        .unwrap();
+
+        let reopened = EventSourcedUserRepository::with_data_file(&data_file(&temp_dir));
+        assert_eq!(
+            reopened.find_all().unwrap(),
+            vec![user("b@example.com", 31)]
+        );
+        let log = fs::read_to_string(temp_dir.path().join("userdata.events.jsonl")).unwrap();
+        let events: Vec<StoredEvent> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 4);
+        assert!(matches!(events[2].event, UserEvent::UserCreated { .. }));
+        assert!(matches!(events[3].event, UserEvent::UserUpdated { .. }));
+        assert_eq!(events[1].timestamp, events[3].timestamp);
+    }
+
+    #[test]
+    fn test_events_are_replayed_on_startup() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = EventSourcedUserRepository::with_data_file(&data_file(&temp_dir));
+        repo.save(&user("a@example.com", 20)).unwrap();
+        repo.save(&user("a@example.com", 21)).unwrap();
+        repo.save(&user("b@example.com", 30)).unwrap();
+        assert!(repo.delete("b@example.com").unwrap());
+        assert!(!repo.delete("b@example.com").unwrap());
+
+        let reopened = EventSourcedUserRepository::with_data_file(&data_file(&temp_dir));
+        assert_eq!(
+            reopened.find_all().unwrap(),
+            vec![user("a@example.com", 21)]
+        );
+
+        let log = fs::read_to_string(temp_dir.path().join("userdata.events.jsonl")).unwrap();
+        let types: Vec<&str> = log
+            .lines()
+            .map(|line| {
+                ["UserCreated", "UserUpdated", "UserDeleted"]
+                    .into_iter()
+                    .find(|t| line.contains(t))
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(
+            types,
+            vec!["UserCreated", "UserUpdated", "UserCreated", "UserDeleted"]
+        );
+    }
+
+    #[test]
+    fn test_snapshot_is_taken_periodically_and_used_on_startup() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = EventSourcedUserRepository::with_data_file(&data_file(&temp_dir))
+            .with_snapshot_interval(2);
+        repo.save(&user("a@example.com", 20)).unwrap();
+        let snapshot_path = temp_dir.path().join("userdata.snapshot.json");
+        assert!(!snapshot_path.exists());
+        repo.save(&user("b@example.com", 30)).unwrap();
+        assert!(snapshot_path.exists());
+        repo.save(&user("c@example.com", 40)).unwrap();
+
+        let reopened = EventSourcedUserRepository::with_data_file(&data_file(&temp_dir));
+        assert_eq!(reopened.find_all().unwrap().len(), 3);
+        assert_eq!(reopened.projection().unwrap().sequence, 3);
+    }
+
+    #[test]
+    fn test_rebuild_replays_all_events() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = EventSourcedUserRepository::with_data_file(&data_file(&temp_dir))
+            .with_snapshot_interval(1);
+        repo.save(&user("a@example.com", 20)).unwrap();
+        repo.replace(&user("b@example.com", 30), "a@example.com")
+            .unwrap();
+
+        // 壊れたスナップショットは再構築で置き換えられる
+        fs::write(temp_dir.path().join("userdata.snapshot.json"), "{}").unwrap();
+        let rebuilt = EventSourcedUserRepository::with_data_file(&data_file(&temp_dir));
+        let summary = rebuilt.rebuild().unwrap();
+
+        assert_eq!(
+            summary,
+            RebuildSummary {
+                events: 3,
+                users: 1
+            }
+        );
+        assert_eq!(rebuilt.find_all().unwrap(), vec![user("b@example.com", 30)]);
+    }
+
+    #[test]
+    fn test_legacy_data_is_imported() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let legacy = UserRepositoryImpl::with_file_path(data_file(&temp_dir));
+        legacy.save(&user("a@example.com", 20)).unwrap();
+
+        let repo = EventSourcedUserRepository::with_data_file(&data_file(&temp_dir));
+        assert_eq!(repo.find_all().unwrap(), vec![user("a@example.com", 20)]);
+        assert!(temp_dir.path().join("userdata.events.jsonl").exists());
+        assert!(
+            EventSourcedUserRepository::with_data_file("/nonexistent/userdata.json")
+                .rebuild()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_events_snapshot_and_legacy_import_are_encrypted() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cipher = DataFileCipher::new(KeySource::Passphrase("secret".to_string()));
+        let legacy =
+            UserRepositoryImpl::with_file_path(data_file(&temp_dir)).with_cipher(cipher.clone());
+        legacy.save(&user("a@example.com", 20)).unwrap();
+
+        let repo = EventSourcedUserRepository::with_data_file(&data_file(&temp_dir))
+            .with_cipher(cipher.clone())
+            .with_snapshot_interval(2);
+        assert_eq!(repo.find_all().unwrap(), vec![user("a@example.com", 20)]);
+        repo.save(&user("b@example.com", 30)).unwrap();
+
+        for name in ["userdata.events.jsonl", "userdata.snapshot.json"] {
+            let content = fs::read_to_string(temp_dir.path().join(name)).unwrap();
+            assert!(!content.contains("example.com"), "{} is plaintext", name);
+        }
+        let reopened =
+            EventSourcedUserRepository::with_data_file(&data_file(&temp_dir)).with_cipher(cipher);
+        assert_eq!(reopened.rebuild().unwrap().users, 2);
+        assert!(
+            EventSourcedUserRepository::with_data_file(&data_file(&temp_dir))
+                .find_all()
+                .is_err()
+        );
+    }
+}
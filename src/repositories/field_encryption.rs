@@ -0,0 +1,537 @@
+//! ユーザーの一部のフィールドを暗号化して保存するためのモジュール
+//!
+//! ファイル全体を暗号化する`encryption`と異なり、指定したフィールドの値だけを
+//! `enc:v1:`で始まる文字列に置き換えるため、ファイルの他の部分は読める形のまま残ります。
+//! 値はXChaCha20-Poly1305で暗号化し、フィールド名とメールアドレスを認証の対象に含めるため、
+//! 暗号化した値を別のユーザーや別のフィールドに移すと復号に失敗します。
+//!
+//! ナンスは鍵・フィールド名・メールアドレス・値から決まるHMAC-SHA256で生成します。
+//! 同じ値は同じ暗号文になるため、変更していないユーザーは書き込みの前後で差分が出ません。
+//!
+//! 鍵は環境変数`USER_FIELD_KEY_FILE`で指定した鍵ファイル（256ビットの鍵を64文字の
+//! 16進数で記述したもの）から、暗号化するフィールドは環境変数`USER_ENCRYPTED_FIELDS`
+//! （カンマ区切り、既定値は`phone`）から読み込みます。
+
+use crate::models::user::User;
+use crate::repositories::encryption::read_key_file;
+use crate::repositories::user_repository::{BatchOperation, UserRepository};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::env;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// 暗号化した値の先頭に付ける文字列
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+
+/// 伏せ字で表示する場合に暗号化した値の代わりに使用する文字列
+pub const REDACTED_VALUE: &str = "********";
+
+/// ナンスのバイト数
+const NONCE_LENGTH: usize = 24;
+
+/// 暗号化できるフィールド
+///
+/// メールアドレスはレコードのキーとして検索に使用するため、暗号化できません。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptedField {
+    /// ユーザー名
+    Username,
+    /// 電話番号
+    Phone,
+}
+
+impl EncryptedField {
+    /// 全ての暗号化できるフィールド
+    const ALL: [EncryptedField; 2] = [EncryptedField::Username, EncryptedField::Phone];
+
+    /// ユーザーのフィールドの値への参照を返します。
+    ///
+    /// # 引数
+    /// * `user` - 対象のユーザー
+    ///
+    /// # 戻り値
+    /// * `&mut String` - フィールドの値
+    fn value_mut(self, user: &mut User) -> &mut String {
+        match self {
+            EncryptedField::Username => &mut user.username,
+            EncryptedField::Phone => &mut user.phone,
+        }
+    }
+}
+
+impl fmt::Display for EncryptedField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncryptedField::Username => write!(f, "username"),
+            EncryptedField::Phone => write!(f, "phone"),
+        }
+    }
+}
+
+impl FromStr for EncryptedField {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "username" => Ok(EncryptedField::Username),
+            "phone" => Ok(EncryptedField::Phone),
+            "email" => Err(
+                "Field email cannot be encrypted because it is used as the record key".to_string(),
+            ),
+            _ => Err(format!(
+                "Invalid encrypted field: {} (expected username or phone)",
+                value
+            )),
+        }
+    }
+}
+
+/// フィールドの値を暗号化・復号する
+pub struct FieldCipher {
+    /// 書き込み時に暗号化するフィールド
+    fields: Vec<EncryptedField>,
+    /// 値の暗号化に使用する鍵
+    encryption_key: [u8; 32],
+    /// ナンスの生成に使用する鍵
+    nonce_key: [u8; 32],
+}
+
+impl FieldCipher {
+    /// 鍵と暗号化するフィールドを指定してインスタンスを作成します。
+    ///
+    /// 暗号化とナンスの生成には、指定した鍵からそれぞれ導出した別の鍵を使用します。
+    ///
+    /// # 引数
+    /// * `key` - 256ビットの鍵
+    /// * `fields` - 書き込み時に暗号化するフィールド
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいFieldCipherインスタンス
+    pub fn new(key: [u8; 32], fields: Vec<EncryptedField>) -> Self {
+        Self {
+            fields,
+            encryption_key: derive_key(&key, "user-field-encryption"),
+            nonce_key: derive_key(&key, "user-field-nonce"),
+        }
+    }
+
+    /// 環境変数で鍵ファイルが設定されている場合にインスタンスを作成します。
+    ///
+    /// # 戻り値
+    /// * `Ok(Some(Self))` - 環境変数`USER_FIELD_KEY_FILE`が設定されている場合
+    /// * `Ok(None)` - 設定されていない場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 鍵ファイルを読み込めない、または鍵の形式が不正な場合
+    /// * `USER_ENCRYPTED_FIELDS`に暗号化できないフィールドが含まれている場合
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Some(path) = env::var_os("USER_FIELD_KEY_FILE").filter(|path| !path.is_empty()) else {
+            return Ok(None);
+        };
+        let fields = match env::var("USER_ENCRYPTED_FIELDS") {
+            Ok(fields) => parse_fields(&fields)?,
+            Err(_) => vec![EncryptedField::Phone],
+        };
+        let key = read_key_file(Path::new(&path))?;
+        Ok(Some(Self::new(key, fields)))
+    }
+
+    /// 書き込み時に暗号化するフィールドを返します。
+    ///
+    /// # 戻り値
+    /// * `&[EncryptedField]` - 暗号化するフィールド
+    pub fn fields(&self) -> &[EncryptedField] {
+        &self.fields
+    }
+
+    /// フィールドの値を暗号化します。
+    ///
+    /// # 引数
+    /// * `field` - 対象のフィールド
+    /// * `email` - 値を持つユーザーのメールアドレス
+    /// * `value` - 暗号化する値
+    ///
+    /// # 戻り値
+    /// * `Ok(String)` - `enc:v1:`で始まる暗号化した値
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 暗号化に失敗した場合
+    pub fn encrypt(
+        &self,
+        field: EncryptedField,
+        email: &str,
+        value: &str,
+    ) -> Result<String, String> {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.nonce_key)
+            .map_err(|e| format!("Invalid field key: {}", e))?;
+        mac.update(associated_data(field, email).as_bytes());
+        mac.update(b"\0");
+        mac.update(value.as_bytes());
+        let digest = mac.finalize().into_bytes();
+        let nonce = XNonce::from_slice(&digest[..NONCE_LENGTH]);
+
+        let ciphertext = XChaCha20Poly1305::new(&self.encryption_key.into())
+            .encrypt(
+                nonce,
+                Payload {
+                    msg: value.as_bytes(),
+                    aad: associated_data(field, email).as_bytes(),
+                },
+            )
+            .map_err(|_| format!("Failed to encrypt {} of {}", field, email))?;
+        let mut bytes = nonce.to_vec();
+        bytes.extend(ciphertext);
+        Ok(format!("{}{}", ENCRYPTED_PREFIX, BASE64.encode(bytes)))
+    }
+
+    /// 暗号化した値を復号します。
+    ///
+    /// # 引数
+    /// * `field` - 対象のフィールド
+    /// * `email` - 値を持つユーザーのメールアドレス
+    /// * `value` - `enc:v1:`で始まる暗号化した値
+    ///
+    /// # 戻り値
+    /// * `Ok(String)` - 復号した値
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 暗号化した値として解析できない場合
+    /// * 鍵が誤っている、または値が書き換えられている（別のユーザーから移された場合を含む）場合
+    pub fn decrypt(
+        &self,
+        field: EncryptedField,
+        email: &str,
+        value: &str,
+    ) -> Result<String, String> {
+        let bytes = value
+            .strip_prefix(ENCRYPTED_PREFIX)
+            .and_then(|encoded| BASE64.decode(encoded).ok())
+            .filter(|bytes| bytes.len() > NONCE_LENGTH)
+            .ok_or_else(|| format!("Invalid encrypted {} of {}", field, email))?;
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LENGTH);
+        let plaintext = XChaCha20Poly1305::new(&self.encryption_key.into())
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: associated_data(field, email).as_bytes(),
+                },
+            )
+            .map_err(|_| {
+                format!(
+                    "Failed to decrypt {} of {}: the field key is wrong, or the value has been modified",
+                    field, email
+                )
+            })?;
+        String::from_utf8(plaintext).map_err(|e| format!("Failed to decrypt {}: {}", field, e))
+    }
+}
+
+/// 暗号化したフィールドの読み込み方
+pub enum FieldAccess<'a> {
+    /// 鍵がない（暗号化した値を読み込むとエラーにする）
+    NoKey,
+    /// 鍵で復号し、書き込み時に暗号化する
+    Key(&'a FieldCipher),
+    /// 暗号化した値を伏せ字に置き換える（書き込みはできない）
+    Redacted,
+}
+
+/// 指定したフィールドを暗号化して元のリポジトリに保存するリポジトリ
+///
+/// 読み込んだユーザーは復号（または伏せ字に置き換え）した状態で返し、
+/// 書き込むユーザーは`FieldCipher`で指定したフィールドを暗号化してから保存します。
+pub struct FieldEncryptedUserRepository<'a, R: UserRepository> {
+    /// 暗号化した値を保存するリポジトリ
+    inner: R,
+    /// 暗号化したフィールドの読み込み方
+    access: FieldAccess<'a>,
+}
+
+impl<'a, R: UserRepository> FieldEncryptedUserRepository<'a, R> {
+    /// 新しいFieldEncryptedUserRepositoryインスタンスを作成します。
+    ///
+    /// # 引数
+    /// * `inner` - 暗号化した値を保存するリポジトリ
+    /// * `access` - 暗号化したフィールドの読み込み方
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいFieldEncryptedUserRepositoryインスタンス
+    pub fn new(inner: R, access: FieldAccess<'a>) -> Self {
+        Self { inner, access }
+    }
+
+    /// 保存されているユーザーの暗号化したフィールドを、読み込み方に応じて置き換えます。
+    ///
+    /// 変更履歴などに記録された状態も、このメソッドで同じように読み込めます。
+    ///
+    /// # 引数
+    /// * `user` - 保存されているユーザー
+    ///
+    /// # 戻り値
+    /// * `Ok(User)` - 復号した、または伏せ字に置き換えたユーザー
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 鍵がないのに暗号化した値がある場合
+    /// * 復号に失敗した場合
+    pub fn open(&self, mut user: User) -> Result<User, String> {
+        let email = user.email.clone();
+        for field in EncryptedField::ALL {
+            let value = field.value_mut(&mut user);
+            if !value.starts_with(ENCRYPTED_PREFIX) {
+                continue;
+            }
+            *value = match &self.access {
+                FieldAccess::Key(cipher) => cipher.decrypt(field, &email, value)?,
+                FieldAccess::Redacted => REDACTED_VALUE.to_string(),
+                FieldAccess::NoKey => {
+                    return Err(format!(
+                        "User {} has encrypted fields; set USER_FIELD_KEY_FILE to read them, \
+                         or use --redacted to show them masked",
+                        email
+                    ));
+                }
+            };
+        }
+        Ok(user)
+    }
+
+    /// 書き込むユーザーの指定されたフィールドを暗号化します。
+    ///
+    /// 変更履歴などに記録する状態も、このメソッドで暗号化してから書き込みます。
+    ///
+    /// # 引数
+    /// * `user` - 書き込むユーザー
+    ///
+    /// # 戻り値
+    /// * `Ok(User)` - 暗号化したユーザー（鍵がない場合はそのまま）
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 伏せ字で読み込んでいる場合
+    /// * 暗号化に失敗した場合
+    pub fn seal(&self, user: &User) -> Result<User, String> {
+        let mut user = user.clone();
+        match &self.access {
+            FieldAccess::NoKey => {}
+            FieldAccess::Redacted => {
+                return Err("Cannot write users while encrypted fields are redacted".to_string());
+            }
+            FieldAccess::Key(cipher) => {
+                let email = user.email.clone();
+                for field in cipher.fields() {
+                    let value = field.value_mut(&mut user);
+                    if !value.starts_with(ENCRYPTED_PREFIX) {
+                        *value = cipher.encrypt(*field, &email, value)?;
+                    }
+                }
+            }
+        }
+        Ok(user)
+    }
+}
+
+impl<R: UserRepository> UserRepository for FieldEncryptedUserRepository<'_, R> {
+    fn save(&self, user: &User) -> Result<(), String> {
+        self.inner.save(&self.seal(user)?)
+    }
+
+    fn find_by_email(&self, email: &str) -> Result<Option<User>, String> {
+        self.inner
+            .find_by_email(email)?
+            .map(|user| self.open(user))
+            .transpose()
+    }
+
+    fn find_all(&self) -> Result<Vec<User>, String> {
+        self.inner
+            .find_all()?
+            .into_iter()
+            .map(|user| self.open(user))
+            .collect()
+    }
+
+    fn find_all_entries(&self) -> Result<Vec<(String, User)>, String> {
+        self.inner
+            .find_all_entries()?
+            .into_iter()
+            .map(|(key, user)| Ok((key, self.open(user)?)))
+            .collect()
+    }
+
+    fn delete(&self, email: &str) -> Result<bool, String> {
+        self.inner.delete(email)
+    }
+
+    fn replace(&self, user: &User, removed_email: &str) -> Result<(), String> {
+        self.inner.replace(&self.seal(user)?, removed_email)
+    }
+
+    fn apply_batch(&self, operations: &[BatchOperation]) -> Result<(), String> {
+        let operations = operations
+            .iter()
+            .map(|operation| match operation {
+                BatchOperation::Put(user) => Ok(BatchOperation::Put(self.seal(user)?)),
+                BatchOperation::Delete(email) => Ok(BatchOperation::Delete(email.clone())),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        self.inner.apply_batch(&operations)
+    }
+}
+
+/// カンマ区切りのフィールド名を解析します。
+///
+/// # 引数
+/// * `value` - カンマ区切りのフィールド名（例: "phone,username"）
+///
+/// # 戻り値
+/// * `Ok(Vec<EncryptedField>)` - 重複を除いたフィールドの一覧
+///
+/// # Errors
+/// 以下の場合にエラーを返します：
+/// * 暗号化できないフィールドが含まれている場合
+pub fn parse_fields(value: &str) -> Result<Vec<EncryptedField>, String> {
+    let mut fields = Vec::new();
+    for name in value.split(',').filter(|name| !name.trim().is_empty()) {
+        let field = name.parse()?;
+        if !fields.contains(&field) {
+            fields.push(field);
+        }
+    }
+    Ok(fields)
+}
+
+/// 鍵から用途ごとの鍵を導出します。
+///
+/// # 引数
+/// * `key` - 元の鍵
+/// * `label` - 用途を表す文字列
+///
+/// # 戻り値
+/// * `[u8; 32]` - 導出した鍵
+fn derive_key(key: &[u8; 32], label: &str) -> [u8; 32] {
+    let mut mac =
+        <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(label.as_bytes());
+    mac.finalize().into_bytes().into()
+}
+
+/// 暗号化した値とともに認証するデータ（フィールド名とメールアドレス）を作成します。
+///
+/// # 引数
+/// * `field` - 対象のフィールド
+/// * `email` - 値を持つユーザーのメールアドレス
+///
+/// # 戻り値
+/// * `String` - 認証するデータ
+fn associated_data(field: EncryptedField, email: &str) -> String {
+    format!("{}:{}", field, email)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::user_repository::UserRepositoryImpl;
+
+    fn user(email: &str) -> User {
+        User {
+            email: email.to_string(),
+            username: "testuser".to_string(),
+            phone: "+819012345678".to_string(),
+            age: 25,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_values_are_bound_to_field_and_user() {
+        let cipher = FieldCipher::new([7; 32], vec![EncryptedField::Phone]);
+        let encrypted = cipher
+            .encrypt(EncryptedField::Phone, "a@example.com", "+819012345678")
+            .unwrap();
+
+        assert!(encrypted.starts_with(ENCRYPTED_PREFIX));
+        // 同じ値は同じ暗号文になる
+        assert_eq!(
+            cipher
+                .encrypt(EncryptedField::Phone, "a@example.com", "+819012345678")
+                .unwrap(),
+            encrypted
+        );
+        assert_eq!(
+            cipher
+                .decrypt(EncryptedField::Phone, "a@example.com", &encrypted)
+                .unwrap(),
+            "+819012345678"
+        );
+        assert!(
+            cipher
+                .decrypt(EncryptedField::Phone, "b@example.com", &encrypted)
+                .is_err()
+        );
+        assert!(
+            cipher
+                .decrypt(EncryptedField::Username, "a@example.com", &encrypted)
+                .is_err()
+        );
+        let wrong = FieldCipher::new([8; 32], vec![EncryptedField::Phone]);
+        assert!(
+            wrong
+                .decrypt(EncryptedField::Phone, "a@example.com", &encrypted)
+                .unwrap_err()
+                .contains("the field key is wrong")
+        );
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        assert_eq!(
+            parse_fields("phone, username,phone").unwrap(),
+            vec![EncryptedField::Phone, EncryptedField::Username]
+        );
+        assert!(parse_fields("email").unwrap_err().contains("record key"));
+        assert!(parse_fields("age").is_err());
+    }
+
+    #[test]
+    fn test_repository_encrypts_on_write_and_redacts_without_key() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("userdata.json");
+        let inner = UserRepositoryImpl::with_file_path(path.to_str().unwrap());
+        let cipher = FieldCipher::new([7; 32], vec![EncryptedField::Phone]);
+        let repo = FieldEncryptedUserRepository::new(&inner, FieldAccess::Key(&cipher));
+
+        repo.save(&user("a@example.com")).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("+819012345678"));
+        assert!(content.contains("testuser"));
+        assert_eq!(
+            repo.find_by_email("a@example.com").unwrap().unwrap(),
+            user("a@example.com")
+        );
+
+        let no_key = FieldEncryptedUserRepository::new(&inner, FieldAccess::NoKey);
+        assert!(
+            no_key
+                .find_all()
+                .unwrap_err()
+                .contains("USER_FIELD_KEY_FILE")
+        );
+
+        let redacted = FieldEncryptedUserRepository::new(&inner, FieldAccess::Redacted);
+        let shown = redacted.find_all().unwrap();
+        assert_eq!(shown[0].phone, REDACTED_VALUE);
+        assert_eq!(shown[0].username, "testuser");
+        assert!(redacted.save(&user("b@example.com")).is_err());
+    }
+}
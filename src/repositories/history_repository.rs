@@ -0,0 +1,250 @@
+//! ユーザーの変更履歴の永続化を担うモジュール
+//!
+//! リビジョンを1行1件のJSON Lines形式で追記していきます。
+//! 保存先のファイルパスは環境変数`USER_HISTORY_FILE`で指定できます。
+//! 指定がない場合は、ユーザーデータのファイル名から`.json`を除いて
+//! `.history.jsonl`を付けたパス（例: `userdata.history.jsonl`）を使用します。
+//! 暗号化の鍵が設定されている場合は、ユーザーデータのファイルと同じ鍵でファイル全体を暗号化します。
+
+use crate::models::revision::Revision;
+use crate::repositories::encryption::{
+    DataFileCipher, append_sidecar, read_sidecar, write_sidecar,
+};
+use crate::repositories::user_repository::{data_file_path, sibling_path};
+use std::env;
+
+#[cfg(test)]
+use mockall::automock;
+
+/// 変更履歴の永続化操作を定義するトレイト
+#[cfg_attr(test, automock)]
+pub trait HistoryRepository {
+    /// リビジョンを追記します。
+    ///
+    /// # 引数
+    /// * `revision` - 追記するリビジョン
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 追記に成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * ファイルの書き込みに失敗した場合
+    /// * JSONのシリアライズに失敗した場合
+    fn append(&self, revision: &Revision) -> Result<(), String>;
+
+    /// 指定されたメールアドレスのユーザーのリビジョンを古い順に取得します。
+    ///
+    /// # 引数
+    /// * `email` - ユーザーのメールアドレス
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<Revision>)` - リビジョン番号の昇順に並んだリビジョン
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * ファイルの読み込みに失敗した場合
+    /// * JSONのデシリアライズに失敗した場合
+    fn find_by_email(&self, email: &str) -> Result<Vec<Revision>, String>;
+
+    /// 指定されたメールアドレスのユーザーのリビジョンを全て削除します。
+    ///
+    /// # 引数
+    /// * `email` - ユーザーのメールアドレス
+    ///
+    /// # 戻り値
+    /// * `Ok(usize)` - 削除したリビジョンの件数
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * ファイルの読み書きに失敗した場合
+    /// * JSONのシリアライズ/デシリアライズに失敗した場合
+    fn erase(&self, email: &str) -> Result<usize, String>;
+}
+
+/// JSON Linesファイルベースの変更履歴リポジトリの実装
+pub struct HistoryRepositoryImpl {
+    /// リビジョンを保存するJSON Linesファイルのパス
+    file_path: String,
+    /// ファイルの暗号化・復号に使用する鍵（`None`の場合は平文）
+    cipher: Option<DataFileCipher>,
+}
+
+impl Default for HistoryRepositoryImpl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HistoryRepositoryImpl {
+    /// 新しいHistoryRepositoryインスタンスを作成します。
+    ///
+    /// 環境変数`USER_HISTORY_FILE`が設定されている場合はその値を、設定されていない場合は
+    /// 環境変数`USER_DATA_FILE`（既定値"userdata.json"）から導いたパスを使用します。
+    /// 暗号化の鍵が環境変数で設定されている場合は、その鍵でファイルを暗号化します。
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいHistoryRepositoryインスタンス
+    pub fn new() -> Self {
+        let file_path = env::var("USER_HISTORY_FILE")
+            .unwrap_or_else(|_| sibling_path(&data_file_path(), "history.jsonl"));
+        let repository = Self::with_file_path(file_path);
+        match DataFileCipher::from_env() {
+            Some(cipher) => repository.with_cipher(cipher),
+            None => repository,
+        }
+    }
+
+    /// 保存先のファイルパスを指定してHistoryRepositoryインスタンスを作成します。
+    ///
+    /// # 引数
+    /// * `file_path` - リビジョンを保存するJSON Linesファイルのパス
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいHistoryRepositoryインスタンス
+    pub fn with_file_path(file_path: impl Into<String>) -> Self {
+        Self {
+            file_path: file_path.into(),
+            cipher: None,
+        }
+    }
+
+    /// 保存先のファイルパスを返します。
+    ///
+    /// # 戻り値
+    /// * `&str` - 保存先のファイルパス
+    pub fn file_path(&self) -> &str {
+        &self.file_path
+    }
+
+    /// ファイルの暗号化・復号に使用する鍵を設定します。
+    ///
+    /// # 引数
+    /// * `cipher` - 暗号化・復号に使用する鍵
+    ///
+    /// # 戻り値
+    /// * `Self` - 鍵を設定したHistoryRepositoryインスタンス
+    pub fn with_cipher(mut self, cipher: DataFileCipher) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// 全てのリビジョンを読み込みます。
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<Revision>)` - 記録された順のリビジョン
+    ///
+    /// # エラー
+    /// * ファイルの読み込み、または復号に失敗した場合
+    /// * JSONのデシリアライズに失敗した場合
+    fn read_revisions(&self) -> Result<Vec<Revision>, String> {
+        let content = read_sidecar(&self.file_path, self.cipher.as_ref())
+            .map_err(|e| format!("Failed to read history file: {}", e))?;
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line)
+                    .map_err(|e| format!("Failed to parse history entry: {}", e))
+            })
+            .collect()
+    }
+}
+
+impl HistoryRepository for HistoryRepositoryImpl {
+    fn append(&self, revision: &Revision) -> Result<(), String> {
+        let line = serde_json::to_string(revision)
+            .map_err(|e| format!("Failed to serialize history entry: {}", e))?;
+        append_sidecar(&self.file_path, &line, self.cipher.as_ref())
+            .map_err(|e| format!("Failed to write history file: {}", e))
+    }
+
+    fn find_by_email(&self, email: &str) -> Result<Vec<Revision>, String> {
+        let mut revisions: Vec<Revision> = self
+            .read_revisions()?
+            .into_iter()
+            .filter(|revision| revision.email == email)
+            .collect();
+        revisions.sort_by_key(|revision| revision.revision);
+        Ok(revisions)
+    }
+
+    fn erase(&self, email: &str) -> Result<usize, String> {
+        let (erased, kept): (Vec<Revision>, Vec<Revision>) = self
+            .read_revisions()?
+            .into_iter()
+            .partition(|revision| revision.email == email);
+        if erased.is_empty() {
+            return Ok(0);
+        }
+
+        let mut content = String::new();
+        for revision in &kept {
+            let line = serde_json::to_string(revision)
+                .map_err(|e| format!("Failed to serialize history entry: {}", e))?;
+            content.push_str(&line);
+            content.push('\n');
+        }
+        write_sidecar(&self.file_path, &content, self.cipher.as_ref())
+            .map_err(|e| format!("Failed to write history file: {}", e))?;
+        Ok(erased.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::revision::RevisionOperation;
+    use crate::models::user::User;
+    use chrono::Utc;
+
+    fn revision(email: &str, number: u64) -> Revision {
+        Revision {
+            email: email.to_string(),
+            revision: number,
+            timestamp: Utc::now(),
+            actor: "tester".to_string(),
+            operation: RevisionOperation::Create,
+            user: Some(User {
+                email: email.to_string(),
+                username: "testuser".to_string(),
+                phone: "+819012345678".to_string(),
+                age: 25,
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn test_append_and_find_revisions() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = HistoryRepositoryImpl::with_file_path(
+            temp_dir.path().join("history.jsonl").to_str().unwrap(),
+        );
+
+        repo.append(&revision("a@example.com", 1)).unwrap();
+        repo.append(&revision("b@example.com", 1)).unwrap();
+        repo.append(&revision("a@example.com", 2)).unwrap();
+
+        let revisions = repo.find_by_email("a@example.com").unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(revisions[1].revision, 2);
+        assert!(repo.find_by_email("c@example.com").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_erase_revisions() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = HistoryRepositoryImpl::with_file_path(
+            temp_dir.path().join("history.jsonl").to_str().unwrap(),
+        );
+        repo.append(&revision("a@example.com", 1)).unwrap();
+        repo.append(&revision("b@example.com", 1)).unwrap();
+        repo.append(&revision("a@example.com", 2)).unwrap();
+
+        assert_eq!(repo.erase("a@example.com").unwrap(), 2);
+        assert!(repo.find_by_email("a@example.com").unwrap().is_empty());
+        assert_eq!(repo.find_by_email("b@example.com").unwrap().len(), 1);
+        assert_eq!(repo.erase("a@example.com").unwrap(), 0);
+    }
+}
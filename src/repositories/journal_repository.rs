@@ -0,0 +1,162 @@
+//! 操作ジャーナルの永続化を担うモジュール
+//!
+//! 保存先のファイルパスは環境変数`USER_JOURNAL_FILE`で指定できます。
+//! 指定がない場合は、ユーザーデータのファイル名から`.json`を除いて
+//! `.journal.json`を付けたパス（例: `userdata.journal.json`）を使用します。
+//! 暗号化の鍵が設定されている場合は、ユーザーデータのファイルと同じ鍵でファイル全体を暗号化します。
+
+use crate::models::journal::Journal;
+use crate::repositories::encryption::{DataFileCipher, read_sidecar, write_sidecar};
+use crate::repositories::user_repository::{data_file_path, sibling_path};
+use std::env;
+
+#[cfg(test)]
+use mockall::automock;
+
+/// 操作ジャーナルの永続化操作を定義するトレイト
+#[cfg_attr(test, automock)]
+pub trait JournalRepository {
+    /// 操作ジャーナルを読み込みます。
+    ///
+    /// # 戻り値
+    /// * `Ok(Journal)` - 保存されている操作ジャーナル（まだない場合は空）
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * ファイルの読み込みに失敗した場合
+    /// * JSONのデシリアライズに失敗した場合
+    fn load(&self) -> Result<Journal, String>;
+
+    /// 操作ジャーナルを保存します。
+    ///
+    /// # 引数
+    /// * `journal` - 保存する操作ジャーナル
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 保存に成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * ファイルの書き込みに失敗した場合
+    /// * JSONのシリアライズに失敗した場合
+    fn store(&self, journal: &Journal) -> Result<(), String>;
+}
+
+/// JSONファイルベースの操作ジャーナルリポジトリの実装
+pub struct JournalRepositoryImpl {
+    /// 操作ジャーナルを保存するJSONファイルのパス
+    file_path: String,
+    /// ファイルの暗号化・復号に使用する鍵（`None`の場合は平文）
+    cipher: Option<DataFileCipher>,
+}
+
+impl Default for JournalRepositoryImpl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JournalRepositoryImpl {
+    /// 新しいJournalRepositoryインスタンスを作成します。
+    ///
+    /// 環境変数`USER_JOURNAL_FILE`が設定されている場合はその値を、設定されていない場合は
+    /// 環境変数`USER_DATA_FILE`（既定値"userdata.json"）から導いたパスを使用します。
+    /// 暗号化の鍵が環境変数で設定されている場合は、その鍵でファイルを暗号化します。
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいJournalRepositoryインスタンス
+    pub fn new() -> Self {
+        let file_path = env::var("USER_JOURNAL_FILE")
+            .unwrap_or_else(|_| sibling_path(&data_file_path(), "journal.json"));
+        let repository = Self::with_file_path(file_path);
+        match DataFileCipher::from_env() {
+            Some(cipher) => repository.with_cipher(cipher),
+            None => repository,
+        }
+    }
+
+    /// 保存先のファイルパスを指定してJournalRepositoryインスタンスを作成します。
+    ///
+    /// # 引数
+    /// * `file_path` - 操作ジャーナルを保存するJSONファイルのパス
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいJournalRepositoryインスタンス
+    pub fn with_file_path(file_path: impl Into<String>) -> Self {
+        Self {
+            file_path: file_path.into(),
+            cipher: None,
+        }
+    }
+
+    /// 保存先のファイルパスを返します。
+    ///
+    /// # 戻り値
+    /// * `&str` - 保存先のファイルパス
+    pub fn file_path(&self) -> &str {
+        &self.file_path
+    }
+
+    /// ファイルの暗号化・復号に使用する鍵を設定します。
+    ///
+    /// # 引数
+    /// * `cipher` - 暗号化・復号に使用する鍵
+    ///
+    /// # 戻り値
+    /// * `Self` - 鍵を設定したJournalRepositoryインスタンス
+    pub fn with_cipher(mut self, cipher: DataFileCipher) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+}
+
+impl JournalRepository for JournalRepositoryImpl {
+    fn load(&self) -> Result<Journal, String> {
+        let content = read_sidecar(&self.file_path, self.cipher.as_ref())
+            .map_err(|e| format!("Failed to read journal: {}", e))?;
+        if content.is_empty() {
+            return Ok(Journal::default());
+        }
+        serde_json::from_str(&content).map_err(|e| format!("Failed to parse journal: {}", e))
+    }
+
+    fn store(&self, journal: &Journal) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(journal)
+            .map_err(|e| format!("Failed to serialize journal: {}", e))?;
+        write_sidecar(&self.file_path, &content, self.cipher.as_ref())
+            .map_err(|e| format!("Failed to write journal: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::journal::{JournalChange, JournalEntry};
+    use crate::models::revision::RevisionOperation;
+    use chrono::Utc;
+
+    #[test]
+    fn test_store_and_load_journal() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = JournalRepositoryImpl::with_file_path(
+            temp_dir.path().join("journal.json").to_str().unwrap(),
+        );
+        assert_eq!(repo.load().unwrap(), Journal::default());
+
+        let journal = Journal {
+            undo: vec![JournalEntry {
+                timestamp: Utc::now(),
+                actor: "tester".to_string(),
+                operation: RevisionOperation::Delete,
+                changes: vec![JournalChange {
+                    before: None,
+                    after: None,
+                }],
+            }],
+            redo: Vec::new(),
+        };
+        repo.store(&journal).unwrap();
+
+        assert_eq!(repo.load().unwrap(), journal);
+    }
+}
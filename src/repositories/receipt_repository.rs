@@ -0,0 +1,93 @@
+//! 消去請求の受領証の永続化を担うモジュール
+//!
+//! 受領証を1行1件のJSON Lines形式で追記していきます。
+//! 保存先は、ユーザーデータのファイル名から`.json`を除いて`.erasures.jsonl`を付けた
+//! パス（例: `userdata.erasures.jsonl`）です。
+
+use crate::models::privacy::ErasureReceipt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+#[cfg(test)]
+use mockall::automock;
+
+/// 受領証の永続化操作を定義するトレイト
+#[cfg_attr(test, automock)]
+pub trait ReceiptRepository {
+    /// 受領証を追記します。
+    ///
+    /// # 引数
+    /// * `receipt` - 追記する受領証
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 追記に成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * ファイルの書き込みに失敗した場合
+    /// * JSONのシリアライズに失敗した場合
+    fn append(&self, receipt: &ErasureReceipt) -> Result<(), String>;
+
+    /// 全ての受領証を記録された順に取得します。
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<ErasureReceipt>)` - 記録された順の受領証
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * ファイルの読み込みに失敗した場合
+    /// * JSONのデシリアライズに失敗した場合
+    fn find_all(&self) -> Result<Vec<ErasureReceipt>, String>;
+}
+
+/// JSON Linesファイルベースの受領証リポジトリの実装
+pub struct ReceiptRepositoryImpl {
+    /// 受領証を保存するJSON Linesファイルのパス
+    file_path: String,
+}
+
+impl ReceiptRepositoryImpl {
+    /// 保存先のファイルパスを指定してReceiptRepositoryインスタンスを作成します。
+    ///
+    /// # 引数
+    /// * `file_path` - 受領証を保存するJSON Linesファイルのパス
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいReceiptRepositoryインスタンス
+    pub fn with_file_path(file_path: impl Into<String>) -> Self {
+        Self {
+            file_path: file_path.into(),
+        }
+    }
+}
+
+impl ReceiptRepository for ReceiptRepositoryImpl {
+    fn append(&self, receipt: &ErasureReceipt) -> Result<(), String> {
+        let line = serde_json::to_string(receipt)
+            .map_err(|e| format!("Failed to serialize erasure receipt: {}", e))?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file_path)
+            .map_err(|e| format!("Failed to open erasure receipts: {}", e))?;
+        writeln!(file, "{}", line).map_err(|e| format!("Failed to write erasure receipts: {}", e))
+    }
+
+    fn find_all(&self) -> Result<Vec<ErasureReceipt>, String> {
+        if !Path::new(&self.file_path).exists() {
+            return Ok(Vec::new());
+        }
+
+        let content = fs::read_to_string(&self.file_path)
+            .map_err(|e| format!("Failed to read erasure receipts: {}", e))?;
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line)
+                    .map_err(|e| format!("Failed to parse erasure receipt: {}", e))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,217 @@
+//! ユーザーデータのファイル形式のバージョンと移行を扱うモジュール
+//!
+//! ファイルは`schema_version`とメタデータを持つ`DataFile`として保存されます。
+//! 古い形式のファイルは、読み込み時に`MIGRATIONS`の移行を1つずつ適用して
+//! 現在の形式に変換されます（変換した内容は次の書き込みで保存されます）。
+//! 新しい形式を追加する場合は、`CURRENT_SCHEMA_VERSION`を上げ、1つ前の
+//! バージョンから変換する移行を`MIGRATIONS`の末尾に追加します。
+
+use crate::models::data_file::{DataFile, DataFileMetadata};
+use crate::models::user::User;
+use chrono::Utc;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// 現在のファイル形式のバージョン
+pub const CURRENT_SCHEMA_VERSION: u32 = 2;
+
+/// あるバージョンのファイルを次のバージョンに変換する移行
+pub struct Migration {
+    /// 変換元のバージョン（変換後は`from + 1`になる）
+    pub from: u32,
+    /// 移行の内容の説明
+    pub description: &'static str,
+    /// 変換を行う関数
+    apply: fn(Value) -> Result<Value, String>,
+}
+
+/// 古いバージョンから順に並んだ移行の一覧
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: 1,
+    description: "wrap the user map in an envelope with schema_version and metadata",
+    apply: wrap_in_envelope,
+}];
+
+/// ファイルの内容からファイル形式のバージョンを判定します。
+///
+/// `schema_version`を持たないオブジェクトは、バージョン1（ユーザーの一覧をそのまま
+/// 保存した形式）とみなします。
+///
+/// # 引数
+/// * `value` - ファイルの内容
+///
+/// # 戻り値
+/// * `Ok(u32)` - ファイル形式のバージョン
+///
+/// # Errors
+/// 以下の場合にエラーを返します：
+/// * 内容がオブジェクトでない、または`schema_version`が正の整数でない場合
+pub fn schema_version(value: &Value) -> Result<u32, String> {
+    let Some(object) = value.as_object() else {
+        return Err("Data file must contain a JSON object".to_string());
+    };
+    match object.get("schema_version") {
+        None => Ok(1),
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .filter(|version| *version >= 1)
+            .ok_or_else(|| format!("Invalid schema_version: {}", version)),
+    }
+}
+
+/// ファイルの内容を現在の形式に変換します。
+///
+/// # 引数
+/// * `value` - ファイルの内容
+///
+/// # 戻り値
+/// * `Ok((Value, Vec<&Migration>))` - 現在の形式に変換した内容と、適用した移行
+///
+/// # Errors
+/// 以下の場合にエラーを返します：
+/// * ファイル形式のバージョンを判定できない場合
+/// * ファイル形式がこのアプリケーションより新しい場合
+/// * 移行に失敗した場合
+pub fn migrate(mut value: Value) -> Result<(Value, Vec<&'static Migration>), String> {
+    let version = schema_version(&value)?;
+    if version > CURRENT_SCHEMA_VERSION {
+        return Err(format!(
+            "Data file schema version {} is newer than the supported version {}; \
+             upgrade the application to read it",
+            version, CURRENT_SCHEMA_VERSION
+        ));
+    }
+
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|migration| migration.from >= version)
+    {
+        value = (migration.apply)(value).map_err(|e| {
+            format!(
+                "Failed to migrate data file from schema version {}: {}",
+                migration.from, e
+            )
+        })?;
+        applied.push(migration);
+    }
+    Ok((value, applied))
+}
+
+/// ファイルの内容を解析し、必要に応じて現在の形式に変換します。
+///
+/// # 引数
+/// * `content` - ファイルの内容（空の場合はユーザーのいないファイルとして扱う）
+///
+/// # 戻り値
+/// * `Ok((DataFile, u32))` - 現在の形式のファイルと、変換前のファイル形式のバージョン
+///
+/// # Errors
+/// 以下の場合にエラーを返します：
+/// * JSONとして解析できない、またはユーザーデータとして読み込めない場合
+/// * ファイル形式がこのアプリケーションより新しい場合
+pub fn parse_data_file(content: &str) -> Result<(DataFile, u32), String> {
+    if content.trim().is_empty() {
+        return Ok((envelope(HashMap::new()), CURRENT_SCHEMA_VERSION));
+    }
+    let value: Value =
+        serde_json::from_str(content).map_err(|e| format!("Failed to parse JSON: {}", e))?;
+    let version = schema_version(&value)?;
+    let (value, _) = migrate(value)?;
+    let data_file =
+        serde_json::from_value(value).map_err(|e| format!("Failed to parse JSON: {}", e))?;
+    Ok((data_file, version))
+}
+
+/// ユーザーの一覧を現在の形式のファイルとして包みます。
+///
+/// # 引数
+/// * `users` - メールアドレスをキーとするユーザーの一覧
+///
+/// # 戻り値
+/// * `DataFile` - 現在の形式のファイル
+pub fn envelope(users: HashMap<String, User>) -> DataFile {
+    DataFile {
+        schema_version: CURRENT_SCHEMA_VERSION,
+        metadata: DataFileMetadata {
+            generator: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            updated_at: Some(Utc::now()),
+        },
+        users,
+    }
+}
+
+/// バージョン1のファイル（ユーザーの一覧）をバージョン2の形式に包みます。
+///
+/// # 引数
+/// * `value` - バージョン1のファイルの内容
+///
+/// # 戻り値
+/// * `Ok(Value)` - バージョン2のファイルの内容
+///
+/// # エラー
+/// * 内容がオブジェクトでない場合
+fn wrap_in_envelope(value: Value) -> Result<Value, String> {
+    let Value::Object(users) = value else {
+        return Err("user map must be a JSON object".to_string());
+    };
+    let mut file = Map::new();
+    file.insert("schema_version".to_string(), Value::from(2));
+    file.insert("metadata".to_string(), Value::Object(Map::new()));
+    file.insert("users".to_string(), Value::Object(users));
+    Ok(Value::Object(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_are_contiguous() {
+        for (index, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.from, index as u32 + 1);
+        }
+        assert_eq!(MIGRATIONS.len() as u32 + 1, CURRENT_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_parse_version_1_fixture() {
+        let content = include_str!("../../tests/fixtures/userdata_v1.json");
+        let (data_file, version) = parse_data_file(content).unwrap();
+
+        assert_eq!(version, 1);
+        assert_eq!(data_file.schema_version, CURRENT_SCHEMA_VERSION);
+        assert_eq!(data_file.users.len(), 2);
+        let legacy = &data_file.users["legacy@example.com"];
+        assert_eq!(legacy.phone, "09012345678");
+        assert_eq!(legacy.version, 0);
+        assert_eq!(legacy.updated_at, None);
+        let current = &data_file.users["current@example.com"];
+        assert_eq!(current.aliases, vec!["old@example.com".to_string()]);
+        assert_eq!(current.version, 3);
+    }
+
+    #[test]
+    fn test_parse_version_2_fixture() {
+        let content = include_str!("../../tests/fixtures/userdata_v2.json");
+        let (data_file, version) = parse_data_file(content).unwrap();
+
+        assert_eq!(version, 2);
+        assert_eq!(data_file.metadata.generator, "rust-learn 0.1.0");
+        assert_eq!(data_file.users.len(), 2);
+        // 現在の形式で書き込んだ内容は、そのまま読み戻せる
+        let written = serde_json::to_string(&data_file).unwrap();
+        assert_eq!(parse_data_file(&written).unwrap().0, data_file);
+    }
+
+    #[test]
+    fn test_newer_schema_version_is_rejected() {
+        let content = r#"{"schema_version": 99, "users": {}}"#;
+        let error = parse_data_file(content).unwrap_err();
+
+        assert!(error.contains("newer than the supported version"));
+        assert!(parse_data_file(r#"{"schema_version": "2"}"#).is_err());
+        assert!(parse_data_file("[]").is_err());
+    }
+}
@@ -3,11 +3,23 @@
 //! このモジュールは、JSONファイルを使用してユーザーデータを保存および読み込む機能を提供します。
 //! 保存先のファイルパスは環境変数`USER_DATA_FILE`で指定できます。
 
+use crate::models::data_file::DataFile;
 use crate::models::user::User;
+use crate::repositories::backup_repository::{BackupRepository, BackupRepositoryImpl};
+use crate::repositories::encryption::{
+    DataFileCipher, decrypt_if_encrypted, is_encrypted, read_sidecar, write_sidecar,
+};
+use crate::repositories::event_sourced_repository::{
+    EventSourcedUserRepository, snapshot_interval_from_env,
+};
+use crate::repositories::schema::{self, CURRENT_SCHEMA_VERSION};
+use chrono::Utc;
+use serde_json::{Deserializer, Map, Value};
 use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::path::Path;
+use std::str::FromStr;
 
 #[cfg(test)]
 use mockall::automock;
@@ -58,6 +70,26 @@ pub trait UserRepository {
     /// * JSONのデシリアライズに失敗した場合
     fn find_all(&self) -> Result<Vec<User>, String>;
 
+    /// 全てのユーザーを、保存時のキーとともに取得します。
+    ///
+    /// 手で編集されたファイルなどでは、キーとユーザーのメールアドレスが一致しない場合があります。
+    /// 既定の実装はメールアドレスをキーとして返します。
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<(String, User)>)` - 保存時のキーとユーザーの組のリスト
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * ファイルの読み込みに失敗した場合
+    /// * JSONのデシリアライズに失敗した場合
+    fn find_all_entries(&self) -> Result<Vec<(String, User)>, String> {
+        Ok(self
+            .find_all()?
+            .into_iter()
+            .map(|user| (user.email.clone(), user))
+            .collect())
+    }
+
     /// 指定されたメールアドレスのユーザーを削除します。
     ///
     /// # 引数
@@ -72,12 +104,149 @@ pub trait UserRepository {
     /// * ファイルの読み書きに失敗した場合
     /// * JSONのシリアライズ/デシリアライズに失敗した場合
     fn delete(&self, email: &str) -> Result<bool, String>;
+
+    /// ユーザーの保存と別のユーザーの削除を1回の書き込みで行います。
+    ///
+    /// ユーザーの統合やメールアドレスの付け替えのように、保存と削除の
+    /// 一方だけが反映された状態を残したくない場合に使用します。
+    /// 既定の実装は`apply_batch`を使用します。
+    ///
+    /// # 引数
+    /// * `user` - 保存するユーザー情報
+    /// * `removed_email` - 削除するユーザーのメールアドレス
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 保存と削除に成功した場合（削除対象が存在しない場合も含む）
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * ファイルの読み書きに失敗した場合
+    /// * JSONのシリアライズ/デシリアライズに失敗した場合
+    fn replace(&self, user: &User, removed_email: &str) -> Result<(), String> {
+        let mut operations = Vec::new();
+        if removed_email != user.email {
+            operations.push(BatchOperation::Delete(removed_email.to_string()));
+        }
+        operations.push(BatchOperation::Put(user.clone()));
+        self.apply_batch(&operations)
+    }
+
+    /// 複数の保存と削除をまとめて、全て反映するか全く反映しないかのどちらかで適用します。
+    ///
+    /// 操作は指定された順に適用されます。存在しないユーザーの削除は何もしません。
+    ///
+    /// # 引数
+    /// * `operations` - 適用する操作
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 全ての操作の適用に成功した場合
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します（いずれの操作も反映されません）：
+    /// * ファイルの読み書きに失敗した場合
+    /// * JSONのシリアライズ/デシリアライズに失敗した場合
+    fn apply_batch(&self, operations: &[BatchOperation]) -> Result<(), String>;
+}
+
+/// `UserRepository::apply_batch`で適用する1件の操作
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchOperation {
+    /// ユーザーを保存する（同じメールアドレスのユーザーがいれば置き換える）
+    Put(User),
+    /// 指定されたメールアドレスのユーザーを削除する
+    Delete(String),
+}
+
+impl<R: UserRepository + ?Sized> UserRepository for Box<R> {
+    fn save(&self, user: &User) -> Result<(), String> {
+        (**self).save(user)
+    }
+
+    fn find_by_email(&self, email: &str) -> Result<Option<User>, String> {
+        (**self).find_by_email(email)
+    }
+
+    fn find_all(&self) -> Result<Vec<User>, String> {
+        (**self).find_all()
+    }
+
+    fn find_all_entries(&self) -> Result<Vec<(String, User)>, String> {
+        (**self).find_all_entries()
+    }
+
+    fn delete(&self, email: &str) -> Result<bool, String> {
+        (**self).delete(email)
+    }
+
+    fn replace(&self, user: &User, removed_email: &str) -> Result<(), String> {
+        (**self).replace(user, removed_email)
+    }
+
+    fn apply_batch(&self, operations: &[BatchOperation]) -> Result<(), String> {
+        (**self).apply_batch(operations)
+    }
+}
+
+impl<R: UserRepository + ?Sized> UserRepository for &R {
+    fn save(&self, user: &User) -> Result<(), String> {
+        (**self).save(user)
+    }
+
+    fn find_by_email(&self, email: &str) -> Result<Option<User>, String> {
+        (**self).find_by_email(email)
+    }
+
+    fn find_all(&self) -> Result<Vec<User>, String> {
+        (**self).find_all()
+    }
+
+    fn find_all_entries(&self) -> Result<Vec<(String, User)>, String> {
+        (**self).find_all_entries()
+    }
+
+    fn delete(&self, email: &str) -> Result<bool, String> {
+        (**self).delete(email)
+    }
+
+    fn replace(&self, user: &User, removed_email: &str) -> Result<(), String> {
+        (**self).replace(user, removed_email)
+    }
+
+    fn apply_batch(&self, operations: &[BatchOperation]) -> Result<(), String> {
+        (**self).apply_batch(operations)
+    }
+}
+
+/// ユーザーデータのファイルの修復結果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RepairReport {
+    /// 読み込めたユーザーの件数
+    pub recovered: usize,
+    /// ユーザーとして読み込めず、隔離した断片
+    pub quarantined: Vec<String>,
+    /// ファイルを書き直したかどうか（元のファイルが正しく読み込めた場合は`false`）
+    pub rewritten: bool,
+}
+
+/// ユーザーデータのファイルの形式の変換結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationReport {
+    /// 変換前のファイル形式のバージョン
+    pub from_version: u32,
+    /// 変換後のファイル形式のバージョン
+    pub to_version: u32,
+    /// 適用した（ドライランの場合は適用する）移行の説明
+    pub migrations: Vec<&'static str>,
 }
 
 /// JSONファイルベースのユーザーリポジトリの実装
 pub struct UserRepositoryImpl {
     /// ユーザーデータを保存するJSONファイルのパス
     file_path: String,
+    /// 書き込みの前にバックアップを作成するリポジトリ（`None`の場合はバックアップしない）
+    backups: Option<Box<dyn BackupRepository>>,
+    /// ファイルの暗号化・復号に使用する鍵（`None`の場合は平文で書き込む）
+    cipher: Option<DataFileCipher>,
 }
 
 impl Default for UserRepositoryImpl {
@@ -91,16 +260,217 @@ impl UserRepositoryImpl {
     ///
     /// 環境変数`USER_DATA_FILE`が設定されている場合はその値を、
     /// 設定されていない場合は"userdata.json"をファイルパスとして使用します。
+    /// バックアップと暗号化は`configured`と同様に環境変数に従って設定します。
     ///
     /// # 戻り値
     /// * `Self` - 新しいUserRepositoryインスタンス
     pub fn new() -> Self {
-        let file_path = env::var("USER_DATA_FILE").unwrap_or_else(|_| "userdata.json".to_string());
-        Self { file_path }
+        Self::configured(&data_file_path())
+    }
+
+    /// 保存先のファイルパスを指定し、環境変数の設定を適用したUserRepositoryインスタンスを作成します。
+    ///
+    /// 書き込みの前には、環境変数で設定された件数・期間でバックアップを作成します。
+    /// 環境変数`USER_DATA_PASSPHRASE`または`USER_DATA_KEY_FILE`で鍵が設定されている場合は、
+    /// ファイルを暗号化して書き込みます。
+    ///
+    /// # 引数
+    /// * `file_path` - ユーザーデータを保存するJSONファイルのパス
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいUserRepositoryインスタンス
+    pub fn configured(file_path: &str) -> Self {
+        let repository =
+            Self::with_file_path(file_path).with_backups(BackupRepositoryImpl::new(file_path));
+        match DataFileCipher::from_env() {
+            Some(cipher) => repository.with_cipher(cipher),
+            None => repository,
+        }
+    }
+
+    /// 保存先のファイルパスを指定してUserRepositoryインスタンスを作成します。
+    ///
+    /// # 引数
+    /// * `file_path` - ユーザーデータを保存するJSONファイルのパス
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいUserRepositoryインスタンス
+    pub fn with_file_path(file_path: impl Into<String>) -> Self {
+        Self {
+            file_path: file_path.into(),
+            backups: None,
+            cipher: None,
+        }
+    }
+
+    /// 書き込みの前にバックアップを作成するリポジトリを設定します。
+    ///
+    /// # 引数
+    /// * `backups` - バックアップを作成するリポジトリ
+    ///
+    /// # 戻り値
+    /// * `Self` - 書き込みの前にバックアップを作成するUserRepositoryインスタンス
+    pub fn with_backups(mut self, backups: impl BackupRepository + 'static) -> Self {
+        self.backups = Some(Box::new(backups));
+        self
+    }
+
+    /// ファイルの暗号化・復号に使用する鍵を設定します。
+    ///
+    /// 鍵を設定したリポジトリは、暗号化されたファイルも平文のファイルも読み込めますが、
+    /// 書き込む際は常に暗号化します。
+    ///
+    /// # 引数
+    /// * `cipher` - 暗号化・復号に使用する鍵
+    ///
+    /// # 戻り値
+    /// * `Self` - ファイルを暗号化して書き込むUserRepositoryインスタンス
+    pub fn with_cipher(mut self, cipher: DataFileCipher) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// ファイルが暗号化されているかどうかを判定します。
+    ///
+    /// # 戻り値
+    /// * `Ok(bool)` - 暗号化されている場合は`true`（ファイルがない場合は`false`）
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * ファイルの読み込みに失敗した場合
+    pub fn is_encrypted(&self) -> Result<bool, String> {
+        Ok(is_encrypted(&self.read_raw()?))
+    }
+
+    /// ファイルを指定した鍵で暗号化して書き直します。
+    ///
+    /// 現在のファイルはこのリポジトリの鍵で読み込み、`cipher`が`None`の場合は平文で書き直します。
+    /// 暗号化されたファイルを書き直す場合は、書き直す前のファイルをバックアップします。
+    /// 平文のファイルを暗号化する場合は、平文の複製を残さないようバックアップしません。
+    ///
+    /// # 引数
+    /// * `cipher` - 書き直すファイルの暗号化に使用する鍵（`None`の場合は平文）
+    ///
+    /// # 戻り値
+    /// * `Ok(usize)` - 書き直したユーザーの件数
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * ファイルの読み書きに失敗した場合
+    /// * 現在のファイルを復号できない場合
+    /// * 暗号化に失敗した場合
+    pub fn set_encryption(&self, cipher: Option<&DataFileCipher>) -> Result<usize, String> {
+        let users = self.read_users()?;
+        if let (true, Some(backups)) = (self.is_encrypted()?, &self.backups) {
+            backups.create()?;
+        }
+        self.write_file(&users, cipher)?;
+        Ok(users.len())
+    }
+
+    /// 壊れたユーザーデータのファイルから、読み込めるユーザーを取り出して書き直します。
+    ///
+    /// ファイル全体をJSONとして解析できない場合（途中で切れている、手で編集して
+    /// 壊れたなど）は、ユーザーを1件ずつ解析します。ユーザーとして読み込めなかった
+    /// 断片は隔離ファイル（JSON Lines形式）に追記し、読み込めたユーザーだけで
+    /// ファイルを書き直します。バックアップが設定されている場合、書き直す前の
+    /// ファイルもバックアップされます。ファイルが正しく読み込める場合は何もしません。
+    ///
+    /// # 引数
+    /// * `quarantine_path` - 隔離した断片を追記するファイルのパス
+    ///
+    /// # 戻り値
+    /// * `Ok(RepairReport)` - 読み込めたユーザーの件数と隔離した断片
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * ファイルの読み書きに失敗した場合
+    pub fn repair(&self, quarantine_path: &str) -> Result<RepairReport, String> {
+        let content = self.read_content()?;
+        if content.trim().is_empty() {
+            return Ok(RepairReport::default());
+        }
+
+        let (users, quarantined, intact) = salvage_users(&content)?;
+        let report = RepairReport {
+            recovered: users.len(),
+            rewritten: !intact || !quarantined.is_empty(),
+            quarantined,
+        };
+        if !report.rewritten {
+            return Ok(report);
+        }
+
+        if !report.quarantined.is_empty() {
+            let mut lines = String::new();
+            let timestamp = Utc::now().to_rfc3339();
+            for fragment in &report.quarantined {
+                let line = serde_json::json!({
+                    "timestamp": timestamp,
+                    "source": self.file_path,
+                    "fragment": fragment,
+                });
+                lines.push_str(&line.to_string());
+                lines.push('\n');
+            }
+            let mut content = read_sidecar(quarantine_path, self.cipher.as_ref())
+                .map_err(|e| format!("Failed to read quarantine file: {}", e))?;
+            content.push_str(&lines);
+            write_sidecar(quarantine_path, &content, self.cipher.as_ref())
+                .map_err(|e| format!("Failed to write quarantine file: {}", e))?;
+        }
+        self.write_users(&users)?;
+        Ok(report)
+    }
+
+    /// ユーザーデータのファイルを現在のファイル形式に変換して書き直します。
+    ///
+    /// 古い形式のファイルは読み込みのたびにメモリ上で変換されますが、このメソッドで
+    /// 変換した内容をファイルに保存できます。バックアップが設定されている場合、
+    /// 書き直す前のファイルもバックアップされます。ファイルが既に現在の形式の場合は何もしません。
+    ///
+    /// # 引数
+    /// * `dry_run` - `true`の場合は書き込みを行わず、適用する移行だけを返す
+    ///
+    /// # 戻り値
+    /// * `Ok(MigrationReport)` - 変換前後のファイル形式のバージョンと、適用した移行
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * ファイルの読み書きに失敗した場合
+    /// * ファイルの内容がユーザーデータとして読み込めない場合
+    /// * ファイル形式がこのアプリケーションより新しい場合
+    pub fn migrate(&self, dry_run: bool) -> Result<MigrationReport, String> {
+        let content = self.read_content()?;
+        if content.trim().is_empty() {
+            return Ok(MigrationReport {
+                from_version: CURRENT_SCHEMA_VERSION,
+                to_version: CURRENT_SCHEMA_VERSION,
+                migrations: Vec::new(),
+            });
+        }
+
+        let value = parse_json(&content)?;
+        let from_version = schema::schema_version(&value)?;
+        let (value, applied) = schema::migrate(value)?;
+        let data_file = deserialize_data_file(value)?;
+        if !dry_run && !applied.is_empty() {
+            self.write_users(&data_file.users)?;
+        }
+        Ok(MigrationReport {
+            from_version,
+            to_version: data_file.schema_version,
+            migrations: applied
+                .iter()
+                .map(|migration| migration.description)
+                .collect(),
+        })
     }
 
     /// JSONファイルからユーザーデータを読み込みます。
     ///
+    /// 古い形式のファイルは、現在の形式に変換してから読み込みます。
+    ///
     /// # 戻り値
     /// * `Ok(HashMap<String, User>)` - ユーザーデータのマップ（メールアドレスをキーとする）
     ///
@@ -108,22 +478,21 @@ impl UserRepositoryImpl {
     /// * ファイルの読み込みに失敗した場合
     /// * JSONのデシリアライズに失敗した場合
     fn read_users(&self) -> Result<HashMap<String, User>, String> {
-        if !Path::new(&self.file_path).exists() {
-            return Ok(HashMap::new());
-        }
-
-        let content = fs::read_to_string(&self.file_path)
-            .map_err(|e| format!("Failed to read file: {}", e))?;
-
+        let content = self.read_content()?;
         if content.is_empty() {
             return Ok(HashMap::new());
         }
 
-        serde_json::from_str(&content).map_err(|e| format!("Failed to parse JSON: {}", e))
+        let (value, _) = schema::migrate(parse_json(&content)?)?;
+        Ok(deserialize_data_file(value)?.users)
     }
 
     /// ユーザーデータをJSONファイルに書き込みます。
     ///
+    /// 一時ファイルに書き込んでから置き換えるため、書き込みの途中で失敗しても
+    /// 元のファイルが中途半端な内容になることはありません。
+    /// バックアップが設定されている場合は、書き込む前に現在のファイルをバックアップします。
+    ///
     /// # 引数
     /// * `users` - 書き込むユーザーデータのマップ
     ///
@@ -132,12 +501,69 @@ impl UserRepositoryImpl {
     ///
     /// # エラー
     /// * JSONのシリアライズに失敗した場合
+    /// * バックアップの作成に失敗した場合
+    /// * 暗号化に失敗した場合
     /// * ファイルの書き込みに失敗した場合
     fn write_users(&self, users: &HashMap<String, User>) -> Result<(), String> {
-        let content = serde_json::to_string_pretty(users)
+        if let Some(backups) = &self.backups {
+            backups.create()?;
+        }
+        self.write_file(users, self.cipher.as_ref())
+    }
+
+    /// ファイルの内容を読み込みます。暗号化されている場合は復号します。
+    ///
+    /// # 戻り値
+    /// * `Ok(String)` - 平文のファイルの内容（ファイルがない場合は空文字列）
+    ///
+    /// # エラー
+    /// * ファイルの読み込みに失敗した場合
+    /// * ファイルが暗号化されているが、鍵が設定されていない、または誤っている場合
+    fn read_content(&self) -> Result<String, String> {
+        decrypt_if_encrypted(&self.read_raw()?, self.cipher.as_ref())
+    }
+
+    /// ファイルの内容をそのまま読み込みます。
+    ///
+    /// # 戻り値
+    /// * `Ok(String)` - ファイルの内容（ファイルがない場合は空文字列）
+    ///
+    /// # エラー
+    /// * ファイルの読み込みに失敗した場合
+    fn read_raw(&self) -> Result<String, String> {
+        if !Path::new(&self.file_path).exists() {
+            return Ok(String::new());
+        }
+        fs::read_to_string(&self.file_path).map_err(|e| format!("Failed to read file: {}", e))
+    }
+
+    /// ユーザーデータを、指定された鍵で暗号化して一時ファイル経由で書き込みます。
+    ///
+    /// # 引数
+    /// * `users` - 書き込むユーザーデータのマップ
+    /// * `cipher` - 暗号化に使用する鍵（`None`の場合は平文で書き込む）
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 書き込みに成功した場合
+    ///
+    /// # エラー
+    /// * JSONのシリアライズに失敗した場合
+    /// * 暗号化に失敗した場合
+    /// * ファイルの書き込みに失敗した場合
+    fn write_file(
+        &self,
+        users: &HashMap<String, User>,
+        cipher: Option<&DataFileCipher>,
+    ) -> Result<(), String> {
+        let mut content = serde_json::to_string_pretty(&schema::envelope(users.clone()))
             .map_err(|e| format!("Failed to serialize JSON: {}", e))?;
+        if let Some(cipher) = cipher {
+            content = cipher.encrypt(&content)?;
+        }
 
-        fs::write(&self.file_path, content).map_err(|e| format!("Failed to write file: {}", e))
+        let temp_path = format!("{}.tmp", self.file_path);
+        fs::write(&temp_path, content).map_err(|e| format!("Failed to write file: {}", e))?;
+        fs::rename(&temp_path, &self.file_path).map_err(|e| format!("Failed to write file: {}", e))
     }
 }
 
@@ -158,18 +584,417 @@ impl UserRepository for UserRepositoryImpl {
         Ok(users.values().cloned().collect())
     }
 
+    fn find_all_entries(&self) -> Result<Vec<(String, User)>, String> {
+        Ok(self.read_users()?.into_iter().collect())
+    }
+
     fn delete(&self, email: &str) -> Result<bool, String> {
         let mut users = self.read_users()?;
         let existed = users.remove(email).is_some();
         self.write_users(&users)?;
         Ok(existed)
     }
+
+    fn apply_batch(&self, operations: &[BatchOperation]) -> Result<(), String> {
+        let mut users = self.read_users()?;
+        for operation in operations {
+            match operation {
+                BatchOperation::Put(user) => {
+                    users.insert(user.email.clone(), user.clone());
+                }
+                BatchOperation::Delete(email) => {
+                    users.remove(email);
+                }
+            }
+        }
+        self.write_users(&users)
+    }
+}
+
+/// ファイルの内容をJSONとして解析します。
+///
+/// # 引数
+/// * `content` - ファイルの内容
+///
+/// # 戻り値
+/// * `Ok(Value)` - 解析した内容
+///
+/// # エラー
+/// * JSONとして解析できない場合
+fn parse_json(content: &str) -> Result<Value, String> {
+    serde_json::from_str(content)
+        .map_err(|e| format!("Failed to parse JSON: {} (run `repair` to recover)", e))
+}
+
+/// 現在の形式に変換した内容を、ユーザーデータのファイルとして読み込みます。
+///
+/// # 引数
+/// * `value` - 現在の形式に変換したファイルの内容
+///
+/// # 戻り値
+/// * `Ok(DataFile)` - ユーザーデータのファイル
+///
+/// # エラー
+/// * ユーザーデータとして読み込めない場合
+fn deserialize_data_file(value: Value) -> Result<DataFile, String> {
+    serde_json::from_value(value)
+        .map_err(|e| format!("Failed to parse JSON: {} (run `repair` to recover)", e))
+}
+
+/// 壊れたファイルから取り出した内容（読み込めたユーザー、読み込めなかった断片、
+/// ファイル全体をJSONとして解析できたかどうか）
+type Salvaged = (HashMap<String, User>, Vec<String>, bool);
+
+/// ユーザーデータのファイルの内容から、読み込めるユーザーを1件ずつ取り出します。
+///
+/// 解析できない箇所に出会った場合は、次にユーザーとして解析できる
+/// `"キー": { ... }`の位置まで読み飛ばし、読み飛ばした部分を1つの断片とします。
+/// 現在の形式のファイルでは、`users`の中のユーザーだけを対象とします。
+///
+/// # 引数
+/// * `content` - ファイルの内容
+///
+/// # 戻り値
+/// * `Ok(Salvaged)` - 読み込めたユーザー、読み込めなかった断片、
+///   ファイル全体をJSONとして解析できたかどうか
+///
+/// # エラー
+/// * ファイル形式がこのアプリケーションより新しい場合
+fn salvage_users(content: &str) -> Result<Salvaged, String> {
+    let mut users = HashMap::new();
+    let mut fragments = Vec::new();
+
+    if let Ok(value) = serde_json::from_str::<Value>(content) {
+        let (mut value, _) = schema::migrate(value)?;
+        let entries = match value.get_mut("users").map(Value::take) {
+            Some(Value::Object(entries)) => entries,
+            None | Some(Value::Null) => Map::new(),
+            Some(other) => {
+                fragments.push(format!("\"users\": {}", other));
+                Map::new()
+            }
+        };
+        for (key, value) in entries {
+            match serde_json::from_value::<User>(value.clone()) {
+                Ok(user) => {
+                    users.insert(key, user);
+                }
+                Err(_) => fragments.push(format!("{}: {}", Value::String(key), value)),
+            }
+        }
+        return Ok((users, fragments, true));
+    }
+
+    // 現在の形式では`users`の中から、古い形式ではファイルの先頭から読み込む
+    let start = content
+        .find("\"users\"")
+        .and_then(|index| content[index..].find('{').map(|offset| index + offset))
+        .or_else(|| content.find('{'));
+    let mut pos = start.map_or(content.len(), |index| index + 1);
+    let mut corrupt_from: Option<usize> = None;
+    while pos < content.len() {
+        let rest = &content[pos..];
+        let trimmed = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        pos += rest.len() - trimmed.len();
+        if corrupt_from.is_none() && trimmed.starts_with('}') {
+            break;
+        }
+
+        match parse_entry(trimmed).filter(|(_, value, _)| value.is_object()) {
+            Some((key, value, length)) => {
+                if let Some(start) = corrupt_from.take() {
+                    fragments.push(trim_fragment(&content[start..pos]));
+                }
+                let entry = &content[pos..pos + length];
+                match serde_json::from_value::<User>(value) {
+                    Ok(user) => {
+                        users.insert(key, user);
+                    }
+                    Err(_) => fragments.push(entry.trim().to_string()),
+                }
+                pos += length;
+            }
+            None => {
+                corrupt_from.get_or_insert(pos);
+                // 次のキーの候補（引用符）まで読み飛ばす
+                pos = content[pos + 1..]
+                    .find('"')
+                    .map_or(content.len(), |index| pos + 1 + index);
+            }
+        }
+    }
+    if let Some(start) = corrupt_from {
+        let fragment = trim_fragment(&content[start..]);
+        if !fragment.is_empty() {
+            fragments.push(fragment);
+        }
+    }
+    Ok((users, fragments, false))
+}
+
+/// `"キー": 値`の形式の1件を解析します。
+///
+/// # 引数
+/// * `text` - キーの引用符から始まる文字列
+///
+/// # 戻り値
+/// * `Some((String, Value, usize))` - キー、値、解析した部分のバイト数
+/// * `None` - 解析できない場合
+fn parse_entry(text: &str) -> Option<(String, Value, usize)> {
+    let mut keys = Deserializer::from_str(text).into_iter::<String>();
+    let key = keys.next()?.ok()?;
+    let after_key = &text[keys.byte_offset()..];
+    let after_colon = after_key.trim_start().strip_prefix(':')?;
+    let offset = text.len() - after_colon.len();
+    let mut values = Deserializer::from_str(after_colon).into_iter::<Value>();
+    let value = values.next()?.ok()?;
+    Some((key, value, offset + values.byte_offset()))
+}
+
+/// 読み飛ばした断片の前後の空白と区切りの`,`を取り除きます。
+///
+/// # 引数
+/// * `fragment` - 読み飛ばした断片
+///
+/// # 戻り値
+/// * `String` - 整えた断片
+fn trim_fragment(fragment: &str) -> String {
+    fragment.trim().trim_end_matches(',').trim_end().to_string()
+}
+
+/// ユーザーデータの保存方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StorageBackend {
+    /// 現在の状態をJSONファイルに保存する（`UserRepositoryImpl`）
+    #[default]
+    Json,
+    /// イベントログを正とし、射影から読み込む（`EventSourcedUserRepository`）
+    EventSourced,
+}
+
+impl FromStr for StorageBackend {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "json" => Ok(StorageBackend::Json),
+            "event" | "events" => Ok(StorageBackend::EventSourced),
+            _ => Err(format!(
+                "Invalid storage backend: {} (expected json or event)",
+                value
+            )),
+        }
+    }
+}
+
+impl StorageBackend {
+    /// 環境変数`USER_STORAGE_BACKEND`から保存方式を読み込みます。
+    ///
+    /// # 戻り値
+    /// * `Ok(Self)` - 指定された保存方式（設定されていない場合は`Json`）
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 設定された値が`json`・`event`のいずれでもない場合
+    pub fn from_env() -> Result<Self, String> {
+        env::var("USER_STORAGE_BACKEND").map_or(Ok(Self::default()), |value| value.parse())
+    }
+
+    /// 保存方式に応じて、ユーザーデータのファイルのほかに作成されるファイルのパスを返します。
+    ///
+    /// # 引数
+    /// * `data_file` - ユーザーデータのファイルパス
+    ///
+    /// # 戻り値
+    /// * `Vec<String>` - イベントログとスナップショットのパス（JSONファイルの場合は空）
+    pub fn storage_files(self, data_file: &str) -> Vec<String> {
+        match self {
+            StorageBackend::Json => Vec::new(),
+            StorageBackend::EventSourced => {
+                let repository = EventSourcedUserRepository::with_data_file(data_file);
+                vec![
+                    repository.log_path().to_string(),
+                    repository.snapshot_path().to_string(),
+                ]
+            }
+        }
+    }
+
+    /// 保存方式に応じたリポジトリを作成します。
+    ///
+    /// # 引数
+    /// * `data_file` - ユーザーデータのファイルパス
+    /// * `cipher` - ファイルの暗号化・復号に使用する鍵（`None`の場合は平文）
+    ///
+    /// # 戻り値
+    /// * `Box<dyn UserRepository>` - 作成したリポジトリ
+    pub fn open(self, data_file: &str, cipher: Option<DataFileCipher>) -> Box<dyn UserRepository> {
+        match self {
+            StorageBackend::Json => {
+                let repository = UserRepositoryImpl::with_file_path(data_file)
+                    .with_backups(BackupRepositoryImpl::new(data_file));
+                match cipher {
+                    Some(cipher) => Box::new(repository.with_cipher(cipher)),
+                    None => Box::new(repository),
+                }
+            }
+            StorageBackend::EventSourced => {
+                let mut repository = EventSourcedUserRepository::with_data_file(data_file);
+                if let Some(interval) = snapshot_interval_from_env() {
+                    repository = repository.with_snapshot_interval(interval);
+                }
+                match cipher {
+                    Some(cipher) => Box::new(repository.with_cipher(cipher)),
+                    None => Box::new(repository),
+                }
+            }
+        }
+    }
+}
+
+/// ユーザーデータを保存するファイルのパスを返します。
+///
+/// 環境変数`USER_DATA_FILE`が設定されている場合はその値を、
+/// 設定されていない場合は"userdata.json"を返します。
+///
+/// # 戻り値
+/// * `String` - ユーザーデータのファイルパス
+pub fn data_file_path() -> String {
+    env::var("USER_DATA_FILE").unwrap_or_else(|_| "userdata.json".to_string())
+}
+
+/// ユーザーデータのファイルと同じ場所に置く補助ファイルのパスを作成します。
+///
+/// # 引数
+/// * `data_file` - ユーザーデータのファイルパス
+/// * `suffix` - 付加する拡張子（例: "history.jsonl"）
+///
+/// # 戻り値
+/// * `String` - `.json`を取り除いたデータファイルのパスに`.{suffix}`を付けたパス
+///
+/// # Examples
+/// ```
+/// use rust_learn::repositories::user_repository::sibling_path;
+///
+/// assert_eq!(sibling_path("data/userdata.json", "history.jsonl"), "data/userdata.history.jsonl");
+/// ```
+pub fn sibling_path(data_file: &str, suffix: &str) -> String {
+    let stem = data_file.strip_suffix(".json").unwrap_or(data_file);
+    format!("{}.{}", stem, suffix)
+}
+
+/// 隔離ファイルから、指定されたメールアドレスを含む断片を探します。
+///
+/// # 引数
+/// * `quarantine_path` - 隔離ファイルのパス
+/// * `emails` - 対象のメールアドレス
+/// * `cipher` - 暗号化・復号に使用する鍵（鍵が設定されていない場合は`None`）
+///
+/// # 戻り値
+/// * `Ok(Vec<String>)` - いずれかのメールアドレスを含む断片（ファイルが存在しない場合は空）
+///
+/// # Errors
+/// 以下の場合にエラーを返します：
+/// * ファイルの読み込みに失敗した場合
+pub fn find_quarantined(
+    quarantine_path: &str,
+    emails: &[String],
+    cipher: Option<&DataFileCipher>,
+) -> Result<Vec<String>, String> {
+    Ok(read_quarantine(quarantine_path, cipher)?
+        .into_iter()
+        .filter(|line| mentions_any(line, emails))
+        .map(|line| {
+            serde_json::from_str::<Value>(&line)
+                .ok()
+                .and_then(|value| value["fragment"].as_str().map(str::to_string))
+                .unwrap_or(line)
+        })
+        .collect())
+}
+
+/// 隔離ファイルから、指定されたメールアドレスを含む断片を削除します。
+///
+/// # 引数
+/// * `quarantine_path` - 隔離ファイルのパス
+/// * `emails` - 対象のメールアドレス
+/// * `cipher` - 暗号化・復号に使用する鍵（鍵が設定されていない場合は`None`）
+///
+/// # 戻り値
+/// * `Ok(usize)` - 削除した断片の件数
+///
+/// # Errors
+/// 以下の場合にエラーを返します：
+/// * ファイルの読み書きに失敗した場合
+pub fn erase_quarantined(
+    quarantine_path: &str,
+    emails: &[String],
+    cipher: Option<&DataFileCipher>,
+) -> Result<usize, String> {
+    let (erased, kept): (Vec<String>, Vec<String>) = read_quarantine(quarantine_path, cipher)?
+        .into_iter()
+        .partition(|line| mentions_any(line, emails));
+    if erased.is_empty() {
+        return Ok(0);
+    }
+
+    let content: String = kept.iter().map(|line| format!("{}\n", line)).collect();
+    write_sidecar(quarantine_path, &content, cipher)
+        .map_err(|e| format!("Failed to write quarantine file: {}", e))?;
+    Ok(erased.len())
+}
+
+/// 隔離ファイルの内容を行ごとに読み込みます。
+///
+/// # 引数
+/// * `quarantine_path` - 隔離ファイルのパス
+/// * `cipher` - 復号に使用する鍵（鍵が設定されていない場合は`None`）
+///
+/// # 戻り値
+/// * `Ok(Vec<String>)` - 空行を除く全ての行（ファイルが存在しない場合は空）
+///
+/// # エラー
+/// * ファイルの読み込み、または復号に失敗した場合
+fn read_quarantine(
+    quarantine_path: &str,
+    cipher: Option<&DataFileCipher>,
+) -> Result<Vec<String>, String> {
+    let content = read_sidecar(quarantine_path, cipher)
+        .map_err(|e| format!("Failed to read quarantine file: {}", e))?;
+    Ok(content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// 文字列がいずれかのメールアドレスを含むかを判定します（大文字・小文字は区別しません）。
+///
+/// # 引数
+/// * `text` - 判定する文字列
+/// * `emails` - メールアドレス
+///
+/// # 戻り値
+/// * `bool` - いずれかのメールアドレスを含む場合は`true`
+fn mentions_any(text: &str, emails: &[String]) -> bool {
+    let text = text.to_lowercase();
+    emails
+        .iter()
+        .any(|email| text.contains(email.to_lowercase().as_str()))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tempfile::NamedTempFile;
+    use crate::repositories::encryption::KeySource;
+    use tempfile::TempDir;
+
+    fn setup_in_temp_dir() -> (TempDir, UserRepositoryImpl) {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_file = temp_dir.path().join("userdata.json");
+        let repo = UserRepositoryImpl::configured(data_file.to_str().unwrap());
+        (temp_dir, repo)
+    }
 
     fn create_test_user() -> User {
         User {
@@ -177,17 +1002,13 @@ mod tests {
             username: "testuser".to_string(),
             phone: "1234567890".to_string(),
             age: 25,
+            ..Default::default()
         }
     }
 
     #[test]
     fn test_save_and_find_user() {
-        let temp_file = NamedTempFile::new().unwrap();
-        unsafe {
-            env::set_var("USER_DATA_FILE", temp_file.path().to_str().unwrap());
-        }
-
-        let repo = UserRepositoryImpl::new();
+        let (_temp_dir, repo) = setup_in_temp_dir();
         let user = create_test_user();
 
         // Test save
@@ -201,12 +1022,7 @@ mod tests {
 
     #[test]
     fn test_find_all_users() {
-        let temp_file = NamedTempFile::new().unwrap();
-        unsafe {
-            env::set_var("USER_DATA_FILE", temp_file.path().to_str().unwrap());
-        }
-
-        let repo = UserRepositoryImpl::new();
+        let (_temp_dir, repo) = setup_in_temp_dir();
         let user1 = create_test_user();
         let mut user2 = create_test_user();
         user2.email = "test2@example.com".to_string();
@@ -220,16 +1036,197 @@ mod tests {
 
     #[test]
     fn test_delete_user() {
-        let temp_file = NamedTempFile::new().unwrap();
-        unsafe {
-            env::set_var("USER_DATA_FILE", temp_file.path().to_str().unwrap());
-        }
-
-        let repo = UserRepositoryImpl::new();
+        let (_temp_dir, repo) = setup_in_temp_dir();
         let user = create_test_user();
 
         repo.save(&user).unwrap();
         assert!(repo.delete(&user.email).unwrap());
         assert!(repo.find_by_email(&user.email).unwrap().is_none());
     }
+
+    #[test]
+    fn test_replace_user() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = UserRepositoryImpl::with_file_path(
+            temp_dir.path().join("userdata.json").to_str().unwrap(),
+        );
+        let old = create_test_user();
+        let mut new = create_test_user();
+        new.email = "new@example.com".to_string();
+        new.aliases = vec![old.email.clone()];
+
+        repo.save(&old).unwrap();
+        repo.replace(&new, &old.email).unwrap();
+
+        assert_eq!(repo.find_all().unwrap(), vec![new]);
+    }
+
+    #[test]
+    fn test_apply_batch_is_all_or_nothing() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("userdata.json");
+        let repo = UserRepositoryImpl::with_file_path(path.to_str().unwrap());
+        let mut a = create_test_user();
+        a.email = "a@example.com".to_string();
+        let mut b = create_test_user();
+        b.email = "b@example.com".to_string();
+        repo.save(&a).unwrap();
+
+        repo.apply_batch(&[
+            BatchOperation::Delete(a.email.clone()),
+            BatchOperation::Put(b.clone()),
+            BatchOperation::Delete("missing@example.com".to_string()),
+        ])
+        .unwrap();
+        assert_eq!(repo.find_all().unwrap(), vec![b.clone()]);
+
+        // 書き込めない場合は何も反映されない
+        let blocked = temp_dir.path().join("userdata.json.tmp");
+        fs::create_dir(&blocked).unwrap();
+        assert!(repo.apply_batch(&[BatchOperation::Put(a)]).is_err());
+        assert_eq!(repo.find_all().unwrap(), vec![b]);
+    }
+
+    #[test]
+    fn test_repair_salvages_valid_records() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("userdata.json");
+        let quarantine = temp_dir.path().join("userdata.quarantine.jsonl");
+        let repo = UserRepositoryImpl::with_file_path(path.to_str().unwrap());
+        let user = create_test_user();
+        let record = serde_json::to_string(&user).unwrap();
+        // 2件目が壊れ、最後のユーザーの途中でファイルが切れている
+        let content = format!(
+            r#"{{"a@example.com": {record}, "b@example.com": {{"email": "b@exa, "c@example.com": {record}, "d@example.com": {{"email""#
+        );
+        fs::write(&path, content).unwrap();
+        assert!(repo.find_all().unwrap_err().contains("repair"));
+
+        let report = repo.repair(quarantine.to_str().unwrap()).unwrap();
+        assert_eq!(report.recovered, 2);
+        assert_eq!(
+            report.quarantined,
+            vec![
+                r#""b@example.com": {"email": "b@exa"#.to_string(),
+                r#""d@example.com": {"email""#.to_string(),
+            ]
+        );
+        assert!(report.rewritten);
+        assert_eq!(repo.find_all().unwrap().len(), 2);
+        assert!(
+            fs::read_to_string(&quarantine)
+                .unwrap()
+                .contains("b@example.com")
+        );
+
+        // 正しく読み込めるファイルはそのまま
+        let report = repo.repair(quarantine.to_str().unwrap()).unwrap();
+        assert_eq!(report.recovered, 2);
+        assert!(!report.rewritten);
+    }
+
+    #[test]
+    fn test_repair_quarantines_invalid_users() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("userdata.json");
+        let quarantine = temp_dir.path().join("userdata.quarantine.jsonl");
+        let repo = UserRepositoryImpl::with_file_path(path.to_str().unwrap());
+        let record = serde_json::to_string(&create_test_user()).unwrap();
+        fs::write(
+            &path,
+            format!(r#"{{"a@example.com": {record}, "b@example.com": {{"age": "x"}}}}"#),
+        )
+        .unwrap();
+
+        let report = repo.repair(quarantine.to_str().unwrap()).unwrap();
+        assert_eq!(report.recovered, 1);
+        assert_eq!(
+            report.quarantined,
+            vec![r#""b@example.com": {"age":"x"}"#.to_string()]
+        );
+        assert!(report.rewritten);
+
+        // 隔離した断片もメールアドレスで探して削除できる
+        let quarantine = quarantine.to_str().unwrap();
+        let emails = vec!["b@example.com".to_string()];
+        assert_eq!(
+            find_quarantined(quarantine, &emails, None).unwrap(),
+            report.quarantined
+        );
+        assert_eq!(erase_quarantined(quarantine, &emails, None).unwrap(), 1);
+        assert!(
+            find_quarantined(quarantine, &emails, None)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_version_1_file_is_migrated() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("userdata.json");
+        let fixture = include_str!("../../tests/fixtures/userdata_v1.json");
+        fs::write(&path, fixture).unwrap();
+        let repo = UserRepositoryImpl::with_file_path(path.to_str().unwrap());
+
+        // 読み込みはメモリ上で変換し、ファイルは書き換えない
+        assert_eq!(repo.find_all().unwrap().len(), 2);
+        let report = repo.migrate(true).unwrap();
+        assert_eq!((report.from_version, report.to_version), (1, 2));
+        assert_eq!(report.migrations.len(), 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), fixture);
+
+        repo.migrate(false).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains(r#""schema_version": 2"#));
+        assert_eq!(repo.find_all().unwrap().len(), 2);
+        let report = repo.migrate(false).unwrap();
+        assert_eq!(report.from_version, CURRENT_SCHEMA_VERSION);
+        assert!(report.migrations.is_empty());
+    }
+
+    #[test]
+    fn test_encrypted_file_round_trip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("userdata.json");
+        let key_file = temp_dir.path().join("data.key");
+        fs::write(&key_file, "ab".repeat(32)).unwrap();
+        let cipher = || DataFileCipher::new(KeySource::KeyFile(key_file.clone()));
+        let plain = UserRepositoryImpl::with_file_path(path.to_str().unwrap());
+        let encrypted =
+            UserRepositoryImpl::with_file_path(path.to_str().unwrap()).with_cipher(cipher());
+
+        plain.save(&create_test_user()).unwrap();
+        assert!(!plain.is_encrypted().unwrap());
+        assert_eq!(encrypted.set_encryption(Some(&cipher())).unwrap(), 1);
+        assert!(encrypted.is_encrypted().unwrap());
+        assert!(
+            !fs::read_to_string(&path)
+                .unwrap()
+                .contains("test@example.com")
+        );
+        assert!(
+            encrypted
+                .find_by_email("test@example.com")
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            plain
+                .find_all()
+                .unwrap_err()
+                .contains("Data file is encrypted")
+        );
+
+        assert_eq!(encrypted.set_encryption(None).unwrap(), 1);
+        assert!(!plain.is_encrypted().unwrap());
+        assert_eq!(plain.find_all().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_parse_storage_backend() {
+        assert_eq!("json".parse(), Ok(StorageBackend::Json));
+        assert_eq!("event".parse(), Ok(StorageBackend::EventSourced));
+        assert!("sqlite".parse::<StorageBackend>().is_err());
+    }
 }
@@ -3,5 +3,32 @@
 //! このモジュールは、アプリケーションのビジネスロジックを実装し、
 //! データの検証やビジネスルールの適用を行います。
 
+/// 共有用のテストデータを作るため、値を仮名に置き換えるモジュール
+pub mod anonymizer;
+
+/// 重複登録の可能性があるユーザーを検出するモジュール
+pub mod duplicate_detector;
+
+/// メールアドレスの正規化を行うモジュール
+pub mod email_normalizer;
+
+/// 表示するメールアドレス・電話番号を伏せ字にするモジュール
+pub mod masking;
+
+/// 2つのユーザーレコードを統合するモジュール
+pub mod merge;
+
+/// 電話番号の正規化を行うモジュール
+pub mod phone_normalizer;
+
+/// 負荷試験やデモのため、架空のユーザーを決定的に生成するモジュール
+pub mod seeder;
+
+/// ユーザー名の正規化と検証を行うモジュール
+pub mod username_normalizer;
+
+/// ユーザー検索のための文字列の照合を行うモジュール
+pub mod search;
+
 /// ユーザー管理のビジネスロジックを実装するモジュール
 pub mod user_service;
@@ -0,0 +1,574 @@
+//! 共有用のテストデータを作るため、個人を特定できる値を仮名に置き換えるモジュール
+//!
+//! 仮名はソルトを鍵とするHMAC-SHA256から決定的に作るため、同じソルトであれば
+//! 同じ値は常に同じ仮名になります。値の分布が保たれるよう、文字の種類
+//! （英小文字・英大文字・数字・ひらがな・カタカナ・漢字）と長さ、記号の位置、
+//! 電話番号の国番号と先頭の桁、よく使われるメールアドレスのドメインはそのまま残します。
+//! ソルトは環境変数`USER_ANONYMIZE_SALT`で指定します。
+
+use crate::models::user::User;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::env;
+
+/// 仮名に置き換えずに残す、広く使われているメールアドレスのドメイン
+const PUBLIC_EMAIL_DOMAINS: &[&str] = &[
+    "gmail.com",
+    "yahoo.co.jp",
+    "outlook.com",
+    "hotmail.com",
+    "icloud.com",
+    "docomo.ne.jp",
+    "ezweb.ne.jp",
+    "softbank.ne.jp",
+    "example.com",
+];
+
+/// 国別のトップレベルドメインの下で、組織の種類を表す第2レベルのラベル
+const SECOND_LEVEL_LABELS: &[&str] = &["co", "ne", "or", "ac", "go", "ed", "com", "org"];
+
+/// 漢字を置き換える際に使用する、人名によく使われる漢字
+const NAME_KANJI: &str = "佐藤鈴木高橋田中伊渡辺山本村小林加吉松井上清水斎口森池阿部石川前野大原岡\
+                          和子美花太郎一二三健翔真由香奈恵智浩幸正明";
+
+/// 電話番号のうち、仮名に置き換える末尾の桁数
+const PHONE_RANDOM_DIGITS: usize = 8;
+
+/// 仮名が重複した、または規則に合わなかった場合に作り直す回数の上限
+const MAX_PSEUDONYM_ATTEMPTS: u32 = 64;
+
+/// ユーザーを仮名に置き換える際に従う、値の規則
+///
+/// 規則に従っている値は、規則に従う仮名に置き換えます。
+pub trait PseudonymRules {
+    /// メールアドレスを、同じアドレスとみなすもの同士で等しくなるキーに変換します。
+    ///
+    /// # 引数
+    /// * `email` - 保存されているメールアドレス
+    ///
+    /// # 戻り値
+    /// * `String` - 小文字の照合用のキー
+    fn email_key(&self, email: &str) -> String;
+
+    /// メールアドレスが規則に従っているかを判定します。
+    ///
+    /// # 引数
+    /// * `email` - メールアドレス
+    ///
+    /// # 戻り値
+    /// * `bool` - 規則に従っている場合は`true`
+    fn is_valid_email(&self, email: &str) -> bool;
+
+    /// ユーザー名が規則に従っているかを判定します。
+    ///
+    /// # 引数
+    /// * `username` - ユーザー名
+    ///
+    /// # 戻り値
+    /// * `bool` - 規則に従っている場合は`true`
+    fn is_valid_username(&self, username: &str) -> bool;
+
+    /// 電話番号が規則に従っているかを判定します。
+    ///
+    /// # 引数
+    /// * `phone` - 電話番号
+    ///
+    /// # 戻り値
+    /// * `bool` - 規則に従っている場合は`true`
+    fn is_valid_phone(&self, phone: &str) -> bool;
+}
+
+/// 値を決定的な仮名に置き換える
+pub struct Anonymizer {
+    /// HMACの鍵として使用するソルト
+    salt: Vec<u8>,
+}
+
+impl Anonymizer {
+    /// ソルトを指定してAnonymizerインスタンスを作成します。
+    ///
+    /// # 引数
+    /// * `salt` - 仮名の生成に使用するソルト
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいAnonymizerインスタンス
+    pub fn new(salt: impl Into<Vec<u8>>) -> Self {
+        Self { salt: salt.into() }
+    }
+
+    /// 環境変数`USER_ANONYMIZE_SALT`からソルトを読み込みます。
+    ///
+    /// # 戻り値
+    /// * `Option<Self>` - ソルトを設定したインスタンス（設定されていない、または空の場合は`None`）
+    pub fn from_env() -> Option<Self> {
+        env::var("USER_ANONYMIZE_SALT")
+            .ok()
+            .filter(|salt| !salt.is_empty())
+            .map(Self::new)
+    }
+
+    /// メールアドレスを仮名に置き換えます。
+    ///
+    /// ローカル部はメールアドレス全体から、ドメインはドメインだけから仮名を作るため、
+    /// 同じドメインのメールアドレスは同じドメインの仮名になります。
+    /// トップレベルドメイン（`co.jp`のような組織の種類を含む）と、広く使われている
+    /// ドメインはそのまま残します。
+    ///
+    /// # 引数
+    /// * `email` - 正規化後のメールアドレス
+    /// * `attempt` - 仮名が重複した、または規則に合わなかった場合に作り直す回数（最初は0）
+    ///
+    /// # 戻り値
+    /// * `String` - 仮名のメールアドレス
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_learn::services::anonymizer::Anonymizer;
+    ///
+    /// let anonymizer = Anonymizer::new("salt");
+    /// let email = anonymizer.email("taro.yamada@example.co.jp", 0);
+    /// assert_eq!(email, anonymizer.email("taro.yamada@example.co.jp", 0));
+    /// assert_ne!(email, "taro.yamada@example.co.jp");
+    /// assert!(email.ends_with(".co.jp"));
+    /// assert_eq!(email.find('.'), Some(4));
+    /// assert!(anonymizer.email("taro@gmail.com", 0).ends_with("@gmail.com"));
+    /// ```
+    pub fn email(&self, email: &str, attempt: u32) -> String {
+        let Some((local, domain)) = email.rsplit_once('@') else {
+            return self.substitute("email", email, email, attempt);
+        };
+        let local = self.substitute("email", email, local, attempt);
+        format!("{}@{}", local, self.domain(domain))
+    }
+
+    /// ユーザー名を仮名に置き換えます。
+    ///
+    /// 文字ごとに同じ種類（英小文字・英大文字・数字・ひらがな・カタカナ・漢字）の文字に置き換え、
+    /// それ以外の文字（空白や記号）はそのまま残します。
+    ///
+    /// # 引数
+    /// * `username` - 正規化後のユーザー名
+    /// * `attempt` - 仮名が規則に合わなかった場合に作り直す回数（最初は0）
+    ///
+    /// # 戻り値
+    /// * `String` - 仮名のユーザー名
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_learn::services::anonymizer::Anonymizer;
+    ///
+    /// let anonymizer = Anonymizer::new("salt");
+    /// let username = anonymizer.username("山田 たろう", 0);
+    /// assert_eq!(username.chars().count(), 6);
+    /// assert_eq!(username.chars().nth(2), Some(' '));
+    /// assert!(username.chars().skip(3).all(|c| ('ぁ'..='ん').contains(&c)));
+    /// ```
+    pub fn username(&self, username: &str, attempt: u32) -> String {
+        self.substitute("username", username, username, attempt)
+    }
+
+    /// 電話番号を仮名に置き換えます。
+    ///
+    /// 末尾の8桁（短い番号では先頭の2桁を除く桁）を置き換え、国番号や携帯電話・固定電話を
+    /// 表す先頭の桁と、`+`・区切り文字はそのまま残します。
+    ///
+    /// # 引数
+    /// * `phone` - 保存されている電話番号
+    /// * `attempt` - 仮名が規則に合わなかった場合に作り直す回数（最初は0）
+    ///
+    /// # 戻り値
+    /// * `String` - 仮名の電話番号
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_learn::services::anonymizer::Anonymizer;
+    ///
+    /// let phone = Anonymizer::new("salt").phone("+819012345678", 0);
+    /// assert!(phone.starts_with("+8190"));
+    /// assert_eq!(phone.len(), 13);
+    /// ```
+    pub fn phone(&self, phone: &str, attempt: u32) -> String {
+        let digits = phone.chars().filter(char::is_ascii_digit).count();
+        let kept = digits
+            .saturating_sub(PHONE_RANDOM_DIGITS)
+            .max(2.min(digits));
+        let mut bytes = self.stream("phone", phone, attempt, digits).into_iter();
+        let mut index = 0;
+        phone
+            .chars()
+            .map(|c| {
+                if !c.is_ascii_digit() {
+                    return c;
+                }
+                index += 1;
+                if index <= kept {
+                    c
+                } else {
+                    pick("0123456789", bytes.next().unwrap_or(0))
+                }
+            })
+            .collect()
+    }
+
+    /// ユーザーのメールアドレス・別名・ユーザー名・電話番号を仮名に置き換えます。
+    ///
+    /// 同じ値は常に同じ仮名に置き換えるため、別名と統合先の関係や重複の傾向は保たれます。
+    /// 規則に従っている値は規則に従う仮名に、メールアドレスと電話番号は互いに異なる仮名に置き換えます
+    /// （条件を満たすまで仮名を作り直します）。規則に従っていない値や、正規化されていない
+    /// メールアドレスの大文字・小文字の違いもそのまま再現します。年齢や日時などはそのまま残します。
+    ///
+    /// # 引数
+    /// * `users` - 置き換えるユーザー
+    /// * `rules` - 値の規則
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<User>)` - 仮名に置き換えたユーザー（`users`と同じ順）
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 条件を満たす仮名を作れなかった場合
+    pub fn anonymize_users(
+        &self,
+        users: Vec<User>,
+        rules: &impl PseudonymRules,
+    ) -> Result<Vec<User>, String> {
+        let emails = pseudonyms(
+            "email",
+            users
+                .iter()
+                .flat_map(|user| std::iter::once(&user.email).chain(&user.aliases))
+                .map(|email| rules.email_key(email))
+                .collect(),
+            |email, attempt| self.email(email, attempt),
+            |email| rules.is_valid_email(email),
+            true,
+        )?;
+        let usernames = pseudonyms(
+            "username",
+            users.iter().map(|user| user.username.clone()).collect(),
+            |username, attempt| self.username(username, attempt),
+            |username| rules.is_valid_username(username),
+            false,
+        )?;
+        let phones = pseudonyms(
+            "phone",
+            users.iter().map(|user| user.phone.clone()).collect(),
+            |phone, attempt| self.phone(phone, attempt),
+            |phone| rules.is_valid_phone(phone),
+            true,
+        )?;
+
+        Ok(users
+            .into_iter()
+            .map(|user| User {
+                email: apply_case(&user.email, &emails[&rules.email_key(&user.email)]),
+                aliases: user
+                    .aliases
+                    .iter()
+                    .map(|alias| apply_case(alias, &emails[&rules.email_key(alias)]))
+                    .collect(),
+                username: usernames[&user.username].clone(),
+                phone: phones[&user.phone].clone(),
+                ..user
+            })
+            .collect())
+    }
+
+    /// メールアドレスのドメインを仮名に置き換えます。
+    ///
+    /// # 引数
+    /// * `domain` - ドメイン
+    ///
+    /// # 戻り値
+    /// * `String` - 仮名のドメイン（広く使われているドメインはそのまま）
+    fn domain(&self, domain: &str) -> String {
+        if PUBLIC_EMAIL_DOMAINS.contains(&domain) {
+            return domain.to_string();
+        }
+        let labels: Vec<&str> = domain.split('.').collect();
+        let mut suffix_labels = 1;
+        if labels.len() > 2
+            && labels[labels.len() - 1].len() == 2
+            && SECOND_LEVEL_LABELS.contains(&labels[labels.len() - 2])
+        {
+            suffix_labels = 2;
+        }
+        let split = labels.len().saturating_sub(suffix_labels).max(1);
+        let name = labels[..split].join(".");
+        let suffix = labels[split..].join(".");
+        let name = self.substitute("domain", &name, &name, 0);
+        if suffix.is_empty() {
+            name
+        } else {
+            format!("{}.{}", name, suffix)
+        }
+    }
+
+    /// 文字ごとに同じ種類の文字に置き換えます。
+    ///
+    /// # 引数
+    /// * `field` - 置き換える値の種類（同じ値でも種類ごとに異なる仮名にする）
+    /// * `seed` - 仮名を決める値（置き換える値の一部だけを置き換える場合は値全体）
+    /// * `text` - 置き換える文字列
+    /// * `attempt` - 作り直す回数
+    ///
+    /// # 戻り値
+    /// * `String` - 置き換えた文字列
+    fn substitute(&self, field: &str, seed: &str, text: &str, attempt: u32) -> String {
+        let mut bytes = self
+            .stream(field, seed, attempt, text.chars().count())
+            .into_iter();
+        text.chars()
+            .map(|c| {
+                let byte = bytes.next().unwrap_or(0);
+                match c {
+                    'a'..='z' => pick("abcdefghijklmnopqrstuvwxyz", byte),
+                    'A'..='Z' => pick("ABCDEFGHIJKLMNOPQRSTUVWXYZ", byte),
+                    '0'..='9' => pick("0123456789", byte),
+                    'ぁ'..='ん' => shift('ぁ', 'ん', byte),
+                    'ァ'..='ン' => shift('ァ', 'ン', byte),
+                    '\u{4E00}'..='\u{9FFF}' => pick(NAME_KANJI, byte),
+                    _ => c,
+                }
+            })
+            .collect()
+    }
+
+    /// 値とソルトから、置き換えに使用するバイト列を作ります。
+    ///
+    /// # 引数
+    /// * `field` - 置き換える値の種類
+    /// * `value` - 置き換える値
+    /// * `attempt` - 作り直す回数
+    /// * `length` - 必要なバイト数
+    ///
+    /// # 戻り値
+    /// * `Vec<u8>` - `length`バイト以上のバイト列
+    fn stream(&self, field: &str, value: &str, attempt: u32, length: usize) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(length + 32);
+        let mut block: u32 = 0;
+        while bytes.len() < length {
+            let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.salt)
+                .expect("HMAC accepts keys of any length");
+            for part in [field.as_bytes(), value.as_bytes()] {
+                mac.update(&(part.len() as u64).to_be_bytes());
+                mac.update(part);
+            }
+            mac.update(&attempt.to_be_bytes());
+            mac.update(&block.to_be_bytes());
+            bytes.extend_from_slice(&mac.finalize().into_bytes());
+            block += 1;
+        }
+        bytes
+    }
+}
+
+/// 値の一覧を、それぞれの仮名に対応付けます。
+///
+/// 規則に従っている値には規則に従う仮名を選び、`unique`が`true`の場合は
+/// 異なる値に同じ仮名を割り当てないよう、条件を満たすまで仮名を作り直します。
+///
+/// # 引数
+/// * `field` - 値の種類（エラーメッセージに使用する）
+/// * `values` - 置き換える値（並び順に仮名を割り当てる）
+/// * `generate` - 値と作り直した回数から仮名を作る関数
+/// * `valid` - 値が規則に従っているかを判定する関数
+/// * `unique` - 異なる値に異なる仮名を割り当てるかどうか
+///
+/// # 戻り値
+/// * `Ok(HashMap<String, String>)` - 値から仮名への対応
+///
+/// # エラー
+/// * 条件を満たす仮名を作れなかった場合
+fn pseudonyms(
+    field: &str,
+    values: BTreeSet<String>,
+    generate: impl Fn(&str, u32) -> String,
+    valid: impl Fn(&str) -> bool,
+    unique: bool,
+) -> Result<HashMap<String, String>, String> {
+    let mut mapping = HashMap::new();
+    let mut taken = HashSet::new();
+    for value in values {
+        let must_be_valid = valid(&value);
+        let pseudonym = (0..MAX_PSEUDONYM_ATTEMPTS)
+            .map(|attempt| generate(&value, attempt))
+            .find(|candidate| {
+                (!must_be_valid || valid(candidate)) && !(unique && taken.contains(candidate))
+            })
+            .ok_or_else(|| {
+                format!(
+                    "Could not generate a pseudonym for a {} after {} attempts",
+                    field, MAX_PSEUDONYM_ATTEMPTS
+                )
+            })?;
+        taken.insert(pseudonym.clone());
+        mapping.insert(value, pseudonym);
+    }
+    Ok(mapping)
+}
+
+/// 元の値で大文字だった位置の文字を、仮名でも大文字にします。
+///
+/// # 引数
+/// * `original` - 元の値
+/// * `pseudonym` - 小文字の値から作った仮名
+///
+/// # 戻り値
+/// * `String` - 大文字・小文字を元の値に合わせた仮名（文字数が異なる場合は仮名のまま）
+fn apply_case(original: &str, pseudonym: &str) -> String {
+    let original = original.trim();
+    if original.chars().count() != pseudonym.chars().count() {
+        return pseudonym.to_string();
+    }
+    original
+        .chars()
+        .zip(pseudonym.chars())
+        .map(|(o, p)| {
+            if o.is_uppercase() {
+                p.to_ascii_uppercase()
+            } else {
+                p
+            }
+        })
+        .collect()
+}
+
+/// 文字の一覧から1文字を選びます。
+///
+/// # 引数
+/// * `chars` - 文字の一覧
+/// * `byte` - 選ぶ位置を決める値
+///
+/// # 戻り値
+/// * `char` - 選んだ文字
+fn pick(chars: &str, byte: u8) -> char {
+    let count = chars.chars().count();
+    chars.chars().nth(byte as usize % count).unwrap_or('a')
+}
+
+/// 連続した範囲の文字から1文字を選びます。
+///
+/// # 引数
+/// * `first` - 範囲の最初の文字
+/// * `last` - 範囲の最後の文字
+/// * `byte` - 選ぶ位置を決める値
+///
+/// # 戻り値
+/// * `char` - 選んだ文字
+fn shift(first: char, last: char, byte: u8) -> char {
+    let count = last as u32 - first as u32 + 1;
+    char::from_u32(first as u32 + u32::from(byte) % count).unwrap_or(first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pseudonyms_are_stable_per_salt() {
+        let anonymizer = Anonymizer::new("salt");
+        let other = Anonymizer::new("pepper");
+
+        let email = anonymizer.email("john@acme-corp.com", 0);
+        assert_eq!(email, anonymizer.email("john@acme-corp.com", 0));
+        assert_ne!(email, other.email("john@acme-corp.com", 0));
+        assert_ne!(email, anonymizer.email("john@acme-corp.com", 1));
+        // 同じドメインは同じ仮名のドメインになる
+        let domain = |email: &str| email.split_once('@').unwrap().1.to_string();
+        assert_eq!(
+            domain(&email),
+            domain(&anonymizer.email("jane@acme-corp.com", 0))
+        );
+        assert_ne!(domain(&email), "acme-corp.com");
+        assert_eq!(domain(&email).len(), "acme-corp.com".len());
+        assert_eq!(domain(&email).find('-'), Some(4));
+    }
+
+    #[test]
+    fn test_character_classes_are_preserved() {
+        let anonymizer = Anonymizer::new("salt");
+
+        let username = anonymizer.username("Taro_99 タナカ 田中", 0);
+        let chars: Vec<char> = username.chars().collect();
+        assert_eq!(chars.len(), 14);
+        assert!(chars[0].is_ascii_uppercase());
+        assert!(chars[1..4].iter().all(char::is_ascii_lowercase));
+        assert_eq!(chars[4], '_');
+        assert!(chars[5..7].iter().all(char::is_ascii_digit));
+        assert_eq!((chars[7], chars[11]), (' ', ' '));
+        assert!(chars[8..11].iter().all(|c| ('ァ'..='ン').contains(c)));
+        assert!(chars[12..].iter().all(|c| NAME_KANJI.contains(*c)));
+
+        assert_eq!(anonymizer.phone("090-1234-5678", 0)[..4], *"090-");
+        assert_eq!(anonymizer.phone("1234567", 0)[..2], *"12");
+    }
+
+    struct LowercaseRules;
+
+    impl PseudonymRules for LowercaseRules {
+        fn email_key(&self, email: &str) -> String {
+            email.trim().to_lowercase()
+        }
+
+        fn is_valid_email(&self, email: &str) -> bool {
+            email.contains('@')
+        }
+
+        fn is_valid_username(&self, username: &str) -> bool {
+            !username.is_empty()
+        }
+
+        fn is_valid_phone(&self, phone: &str) -> bool {
+            phone.starts_with('0')
+        }
+    }
+
+    fn user(email: &str, aliases: &[&str], phone: &str) -> User {
+        User {
+            email: email.to_string(),
+            username: "Taro".to_string(),
+            phone: phone.to_string(),
+            age: 30,
+            aliases: aliases.iter().map(|alias| alias.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_anonymize_users_keeps_links_and_case() {
+        let anonymizer = Anonymizer::new("salt");
+        let users = vec![
+            user("John@example.com", &["old@example.com"], "090-1234-5678"),
+            user("old@EXAMPLE.com", &[], "090-1234-5679"),
+        ];
+
+        let anonymized = anonymizer.anonymize_users(users, &LowercaseRules).unwrap();
+
+        assert_eq!(anonymized.len(), 2);
+        // 同じアドレスは大文字・小文字を元の値に合わせた同じ仮名になる
+        assert_eq!(anonymized[0].aliases[0], anonymized[1].email.to_lowercase());
+        assert_ne!(anonymized[1].email, anonymized[1].email.to_lowercase());
+        assert!(
+            anonymized[0]
+                .email
+                .starts_with(|c: char| c.is_ascii_uppercase())
+        );
+        assert_ne!(anonymized[0].email.to_lowercase(), "john@example.com");
+        // 電話番号は規則に従ったまま互いに異なる仮名になる
+        assert_ne!(anonymized[0].phone, anonymized[1].phone);
+        assert!(anonymized.iter().all(|u| u.phone.starts_with('0')));
+        assert_eq!(anonymized[0].username, anonymized[1].username);
+        assert_eq!(anonymized[0].age, 30);
+    }
+
+    #[test]
+    fn test_apply_case_follows_the_original() {
+        assert_eq!(
+            apply_case(" John@Example.com ", "abcd@efghijk.xyz"),
+            "Abcd@Efghijk.xyz"
+        );
+        assert_eq!(apply_case("John@example.com", "ab@c.d"), "ab@c.d");
+    }
+}
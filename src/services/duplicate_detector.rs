@@ -0,0 +1,416 @@
+//! 重複登録の可能性があるユーザーを検出するモジュール
+//!
+//! 同一人物が別のメールアドレスで登録されている可能性を、ユーザー名の編集距離、
+//! 正規化した電話番号の一致、メールアドレスのローカル部の類似度から評価します。
+
+use crate::models::user::User;
+use crate::services::search::fold_for_search;
+use std::collections::BTreeMap;
+
+/// 類似度の計算に用いるユーザー名の重み
+const USERNAME_WEIGHT: f64 = 0.4;
+
+/// 類似度の計算に用いる電話番号の重み
+const PHONE_WEIGHT: f64 = 0.4;
+
+/// 類似度の計算に用いるメールアドレスのローカル部の重み
+const EMAIL_LOCAL_WEIGHT: f64 = 0.2;
+
+/// 重複候補とみなすスコアの既定のしきい値
+pub const DEFAULT_DUPLICATE_THRESHOLD: f64 = 0.6;
+
+/// 2人のユーザーの類似度の評価結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicatePair {
+    /// 1人目のユーザーのメールアドレス
+    pub first: String,
+    /// 2人目のユーザーのメールアドレス
+    pub second: String,
+    /// 総合スコア（0.0〜1.0）
+    pub score: f64,
+    /// ユーザー名の類似度（0.0〜1.0）
+    pub username_similarity: f64,
+    /// 正規化した電話番号が一致しているかどうか
+    pub same_phone: bool,
+    /// メールアドレスのローカル部の類似度（0.0〜1.0）
+    pub email_local_similarity: f64,
+}
+
+/// 重複の可能性があるユーザーのまとまり
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateCluster {
+    /// まとまりに含まれるユーザー（メールアドレス順）
+    pub users: Vec<User>,
+    /// まとまりの中でしきい値を超えた組み合わせ（スコアの高い順）
+    pub pairs: Vec<DuplicatePair>,
+}
+
+impl DuplicateCluster {
+    /// まとまりの中で最も高いスコアを返します。
+    ///
+    /// # 戻り値
+    /// * `f64` - 最も高い組み合わせのスコア
+    pub fn max_score(&self) -> f64 {
+        self.pairs.iter().map(|pair| pair.score).fold(0.0, f64::max)
+    }
+}
+
+/// 比較用に整えたユーザーの値
+///
+/// 組み合わせごとに整え直さないよう、ユーザーごとに1回だけ作成します。
+struct FoldedUser {
+    /// 検索用に整えたユーザー名
+    username: String,
+    /// 整えたユーザー名の文字数
+    username_length: usize,
+    /// 比較用に正規化した電話番号
+    phone: String,
+    /// 比較用に整えたメールアドレスのローカル部
+    email_local: String,
+    /// 整えたローカル部の文字数
+    email_local_length: usize,
+}
+
+impl FoldedUser {
+    /// ユーザーの値を比較用に整えます。
+    ///
+    /// # 引数
+    /// * `user` - 対象のユーザー
+    /// * `phone_key` - 電話番号を比較用に正規化する関数
+    ///
+    /// # 戻り値
+    /// * `Self` - 比較用に整えた値
+    fn new(user: &User, phone_key: impl Fn(&User) -> String) -> Self {
+        let username = fold_for_search(&user.username);
+        let email_local = email_local_key(&user.email);
+        Self {
+            username_length: username.chars().count(),
+            email_local_length: email_local.chars().count(),
+            username,
+            phone: phone_key(user),
+            email_local,
+        }
+    }
+}
+
+/// 2人のユーザーの類似度を評価します。
+///
+/// # 引数
+/// * `a` - 1人目のユーザー
+/// * `b` - 2人目のユーザー
+/// * `phone_key` - 電話番号を比較用に正規化する関数
+///
+/// # 戻り値
+/// * `DuplicatePair` - 評価結果
+pub fn score_pair(a: &User, b: &User, phone_key: impl Fn(&User) -> String) -> DuplicatePair {
+    score_folded(
+        a,
+        &FoldedUser::new(a, &phone_key),
+        b,
+        &FoldedUser::new(b, &phone_key),
+    )
+}
+
+/// 比較用に整えた値から2人のユーザーの類似度を評価します。
+///
+/// # 引数
+/// * `a` - 1人目のユーザー
+/// * `folded_a` - 1人目のユーザーの比較用の値
+/// * `b` - 2人目のユーザー
+/// * `folded_b` - 2人目のユーザーの比較用の値
+///
+/// # 戻り値
+/// * `DuplicatePair` - 評価結果
+fn score_folded(a: &User, folded_a: &FoldedUser, b: &User, folded_b: &FoldedUser) -> DuplicatePair {
+    let username_similarity = similarity(
+        &folded_a.username,
+        folded_a.username_length,
+        &folded_b.username,
+        folded_b.username_length,
+    );
+    let same_phone = folded_a.phone == folded_b.phone;
+    let email_local_similarity = similarity(
+        &folded_a.email_local,
+        folded_a.email_local_length,
+        &folded_b.email_local,
+        folded_b.email_local_length,
+    );
+
+    let score = USERNAME_WEIGHT * username_similarity
+        + if same_phone { PHONE_WEIGHT } else { 0.0 }
+        + EMAIL_LOCAL_WEIGHT * email_local_similarity;
+
+    DuplicatePair {
+        first: a.email.clone(),
+        second: b.email.clone(),
+        score,
+        username_similarity,
+        same_phone,
+        email_local_similarity,
+    }
+}
+
+/// 文字数の差だけから、2人のユーザーのスコアの上限を求めます。
+///
+/// 編集距離は文字数の差以上になるため、編集距離を計算せずにスコアの上限が分かります。
+///
+/// # 引数
+/// * `a` - 1人目のユーザーの比較用の値
+/// * `b` - 2人目のユーザーの比較用の値
+///
+/// # 戻り値
+/// * `f64` - スコアの上限
+fn max_score(a: &FoldedUser, b: &FoldedUser) -> f64 {
+    USERNAME_WEIGHT * max_similarity(a.username_length, b.username_length)
+        + if a.phone == b.phone {
+            PHONE_WEIGHT
+        } else {
+            0.0
+        }
+        + EMAIL_LOCAL_WEIGHT * max_similarity(a.email_local_length, b.email_local_length)
+}
+
+/// 全ユーザーの組み合わせを評価し、重複の可能性があるまとまりを返します。
+///
+/// しきい値以上のスコアを持つ組み合わせでつながったユーザーを1つのまとまりとします。
+///
+/// # 引数
+/// * `users` - 評価するユーザーの一覧
+/// * `threshold` - 重複候補とみなすスコアの下限
+/// * `phone_key` - 電話番号を比較用に正規化する関数
+///
+/// # 戻り値
+/// * `Vec<DuplicateCluster>` - 最高スコアの高い順に並んだまとまり
+pub fn find_duplicate_clusters(
+    users: &[User],
+    threshold: f64,
+    phone_key: impl Fn(&User) -> String,
+) -> Vec<DuplicateCluster> {
+    let folded: Vec<FoldedUser> = users
+        .iter()
+        .map(|user| FoldedUser::new(user, &phone_key))
+        .collect();
+    let mut parents: Vec<usize> = (0..users.len()).collect();
+    let mut pairs = Vec::new();
+
+    for i in 0..users.len() {
+        for j in (i + 1)..users.len() {
+            // 文字数の差だけでしきい値に届かない組み合わせは、編集距離を計算しない
+            if max_score(&folded[i], &folded[j]) < threshold {
+                continue;
+            }
+            let pair = score_folded(&users[i], &folded[i], &users[j], &folded[j]);
+            if pair.score >= threshold {
+                let (root_i, root_j) = (find_root(&mut parents, i), find_root(&mut parents, j));
+                parents[root_j] = root_i;
+                pairs.push((i, pair));
+            }
+        }
+    }
+
+    let mut clusters: BTreeMap<usize, DuplicateCluster> = BTreeMap::new();
+    for (index, pair) in pairs {
+        let root = find_root(&mut parents, index);
+        clusters
+            .entry(root)
+            .or_insert_with(|| DuplicateCluster {
+                users: Vec::new(),
+                pairs: Vec::new(),
+            })
+            .pairs
+            .push(pair);
+    }
+    for (index, user) in users.iter().enumerate() {
+        let root = find_root(&mut parents, index);
+        if let Some(cluster) = clusters.get_mut(&root) {
+            cluster.users.push(user.clone());
+        }
+    }
+
+    let mut clusters: Vec<DuplicateCluster> = clusters
+        .into_values()
+        .map(|mut cluster| {
+            cluster.users.sort_by(|a, b| a.email.cmp(&b.email));
+            cluster.pairs.sort_by(|a, b| b.score.total_cmp(&a.score));
+            cluster
+        })
+        .collect();
+    clusters.sort_by(|a, b| b.max_score().total_cmp(&a.max_score()));
+    clusters
+}
+
+/// 2つの文字列の編集距離（レーベンシュタイン距離）を文字単位で計算します。
+///
+/// # 引数
+/// * `a` - 1つ目の文字列
+/// * `b` - 2つ目の文字列
+///
+/// # 戻り値
+/// * `usize` - 編集距離
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// 編集距離を文字数で割り、0.0〜1.0の類似度に変換します。
+///
+/// # 引数
+/// * `a` - 1つ目の文字列
+/// * `a_length` - 1つ目の文字列の文字数
+/// * `b` - 2つ目の文字列
+/// * `b_length` - 2つ目の文字列の文字数
+///
+/// # 戻り値
+/// * `f64` - 類似度（完全一致で1.0）
+fn similarity(a: &str, a_length: usize, b: &str, b_length: usize) -> f64 {
+    let length = a_length.max(b_length);
+    if length == 0 {
+        return 0.0;
+    }
+    1.0 - edit_distance(a, b) as f64 / length as f64
+}
+
+/// 文字数だけから類似度の上限を求めます。
+///
+/// # 引数
+/// * `a_length` - 1つ目の文字列の文字数
+/// * `b_length` - 2つ目の文字列の文字数
+///
+/// # 戻り値
+/// * `f64` - 類似度の上限（文字数が同じ場合は1.0）
+fn max_similarity(a_length: usize, b_length: usize) -> f64 {
+    let length = a_length.max(b_length);
+    if length == 0 {
+        return 0.0;
+    }
+    1.0 - a_length.abs_diff(b_length) as f64 / length as f64
+}
+
+/// メールアドレスのローカル部を比較用に整えます。
+///
+/// `+`以降のサブアドレスと、区切りとして使われるピリオドを取り除きます。
+///
+/// # 引数
+/// * `email` - メールアドレス
+///
+/// # 戻り値
+/// * `String` - 比較用のローカル部
+fn email_local_key(email: &str) -> String {
+    let local = email.split('@').next().unwrap_or_default();
+    let local = local.split('+').next().unwrap_or_default();
+    fold_for_search(local).replace('.', "")
+}
+
+/// Union-Findの根を経路圧縮しながら求めます。
+///
+/// # 引数
+/// * `parents` - 各要素の親の添字
+/// * `index` - 根を求める要素の添字
+///
+/// # 戻り値
+/// * `usize` - 根の添字
+fn find_root(parents: &mut [usize], index: usize) -> usize {
+    let mut root = index;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    let mut current = index;
+    while parents[current] != root {
+        let next = parents[current];
+        parents[current] = root;
+        current = next;
+    }
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(email: &str, username: &str, phone: &str) -> User {
+        User {
+            email: email.to_string(),
+            username: username.to_string(),
+            phone: phone.to_string(),
+            age: 25,
+            ..Default::default()
+        }
+    }
+
+    fn phone_key(user: &User) -> String {
+        user.phone.clone()
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("たなか", "たなべ"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_score_pair() {
+        let a = user("taro.tanaka@example.com", "Tanaka Taro", "+819012345678");
+        let b = user("tarotanaka+work@example.org", "ﾀﾅｶ Taro", "+819012345678");
+        let pair = score_pair(&a, &b, phone_key);
+
+        assert!(pair.same_phone);
+        assert_eq!(pair.email_local_similarity, 1.0);
+        assert!(pair.score >= DEFAULT_DUPLICATE_THRESHOLD);
+    }
+
+    #[test]
+    fn test_find_duplicate_clusters() {
+        let users = vec![
+            user("a@example.com", "Tanaka Taro", "+819012345678"),
+            user("b@example.com", "Tanaka Tar0", "+819012345678"),
+            user("c@example.com", "Tanaka Taro", "+819012345678"),
+            user("d@example.com", "Suzuki Hanako", "+818011112222"),
+        ];
+        let clusters = find_duplicate_clusters(&users, DEFAULT_DUPLICATE_THRESHOLD, phone_key);
+
+        assert_eq!(clusters.len(), 1);
+        let emails: Vec<&str> = clusters[0].users.iter().map(|u| u.email.as_str()).collect();
+        assert_eq!(
+            emails,
+            vec!["a@example.com", "b@example.com", "c@example.com"]
+        );
+        assert_eq!(clusters[0].pairs.len(), 3);
+    }
+
+    #[test]
+    fn test_max_score_bounds_actual_score() {
+        let users = [
+            user("taro@example.com", "Tanaka Taro", "+819012345678"),
+            user("t@example.com", "Taro", "+819012345678"),
+            user(
+                "hanako.suzuki@example.com",
+                "Suzuki Hanako Long Name",
+                "+818011112222",
+            ),
+            user("x@example.com", "", "+818011112222"),
+        ];
+        let folded: Vec<FoldedUser> = users
+            .iter()
+            .map(|u| FoldedUser::new(u, phone_key))
+            .collect();
+        for i in 0..users.len() {
+            for j in 0..users.len() {
+                let pair = score_folded(&users[i], &folded[i], &users[j], &folded[j]);
+                assert!(pair.score <= max_score(&folded[i], &folded[j]) + f64::EPSILON);
+            }
+        }
+        // 文字数の差が大きく電話番号も異なる組み合わせは、編集距離を計算せずに除外できる
+        assert!(max_score(&folded[0], &folded[2]) < DEFAULT_DUPLICATE_THRESHOLD);
+    }
+}
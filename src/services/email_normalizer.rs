@@ -0,0 +1,98 @@
+//! メールアドレスの正規化を行うモジュール
+//!
+//! 同じメールアドレスが表記揺れによって別ユーザーとして登録されないよう、
+//! 以下の規則で正規化します。
+//!
+//! 1. 前後の空白（全角スペースを含む）を取り除く
+//! 2. ドメイン部を小文字化し、国際化ドメイン名（IDN）はPunycodeに変換する
+//! 3. 設定によりローカル部も小文字化する
+
+use crate::services::user_service::UserError;
+use regex::Regex;
+
+/// メールアドレスを正規化します。
+///
+/// # 引数
+/// * `email` - 正規化するメールアドレス
+/// * `lowercase_local_part` - ローカル部（`@`より前）も小文字化する場合は`true`
+///
+/// # 戻り値
+/// * `Ok(String)` - 正規化されたメールアドレス
+///
+/// # Errors
+/// 以下の場合に`UserError::InvalidEmail`を返します：
+/// * `@`が含まれていない、またはローカル部が空の場合
+/// * ドメイン部をPunycodeに変換できない場合
+/// * 正規化後の値がメールアドレスの形式に一致しない場合
+///
+/// # Panics
+/// 内部の正規表現が不正な場合にパニックしますが、固定の正規表現のため発生しません。
+///
+/// # Examples
+/// ```
+/// use rust_learn::services::email_normalizer::normalize_email;
+///
+/// let email = normalize_email("  John@Example.COM ", true).unwrap();
+/// assert_eq!(email, "john@example.com");
+///
+/// let email = normalize_email("taro@例え.jp", true).unwrap();
+/// assert_eq!(email, "taro@xn--r8jz45g.jp");
+/// ```
+pub fn normalize_email(email: &str, lowercase_local_part: bool) -> Result<String, UserError> {
+    let invalid = || UserError::InvalidEmail(format!("Invalid email format: {}", email));
+
+    let trimmed = email.trim();
+    let (local, domain) = trimmed.rsplit_once('@').ok_or_else(invalid)?;
+    if local.is_empty() {
+        return Err(invalid());
+    }
+
+    let domain = idna::domain_to_ascii(domain).map_err(|_| invalid())?;
+    let local = if lowercase_local_part {
+        local.to_lowercase()
+    } else {
+        local.to_string()
+    };
+    let normalized = format!("{}@{}", local, domain);
+
+    let email_regex = Regex::new(r"^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}$").unwrap();
+    if !email_regex.is_match(&normalized) {
+        return Err(invalid());
+    }
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_trims_and_lowercases() {
+        let email = normalize_email("\u{3000}John.Doe@Example.COM\t", true).unwrap();
+        assert_eq!(email, "john.doe@example.com");
+    }
+
+    #[test]
+    fn test_normalize_preserves_local_part_case() {
+        let email = normalize_email("John.Doe@Example.COM", false).unwrap();
+        assert_eq!(email, "John.Doe@example.com");
+    }
+
+    #[test]
+    fn test_normalize_idn_domain() {
+        let email = normalize_email("user@Bücher.example", true).unwrap();
+        assert_eq!(email, "user@xn--bcher-kva.example");
+    }
+
+    #[test]
+    fn test_normalize_invalid_email() {
+        assert!(matches!(
+            normalize_email("invalid-email", true),
+            Err(UserError::InvalidEmail(_))
+        ));
+        assert!(matches!(
+            normalize_email("@example.com", true),
+            Err(UserError::InvalidEmail(_))
+        ));
+    }
+}
@@ -0,0 +1,371 @@
+//! 画面やログに表示するメールアドレス・電話番号を伏せ字にするモジュール
+//!
+//! 伏せ方は環境変数`USER_MASK_STRATEGY`で指定します（`partial`または`full`、既定値は`partial`）。
+
+use std::env;
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+
+/// 全て伏せる場合に表示する文字列
+const FULL_MASK: &str = "********";
+
+/// 文章の中で電話番号とみなす、数字の最小の桁数
+const MIN_PHONE_DIGITS: usize = 7;
+
+/// 値の伏せ方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MaskStrategy {
+    /// 一部を残して伏せる（例: `j***@example.com`、`090-****-5678`）
+    #[default]
+    Partial,
+    /// 全て伏せる（`********`）
+    Full,
+}
+
+impl FromStr for MaskStrategy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "partial" => Ok(MaskStrategy::Partial),
+            "full" => Ok(MaskStrategy::Full),
+            _ => Err(format!(
+                "Invalid mask strategy: {} (expected partial or full)",
+                value
+            )),
+        }
+    }
+}
+
+impl MaskStrategy {
+    /// 環境変数`USER_MASK_STRATEGY`から伏せ方を読み込みます。
+    ///
+    /// # 戻り値
+    /// * `Ok(Self)` - 指定された伏せ方（設定されていない場合は`Partial`）
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 設定された値が`partial`・`full`のいずれでもない場合
+    pub fn from_env() -> Result<Self, String> {
+        env::var("USER_MASK_STRATEGY").map_or(Ok(Self::default()), |value| value.parse())
+    }
+}
+
+/// 表示する値を伏せ字にする
+///
+/// 伏せ方が設定されていない場合（`Masker::reveal`）は、値をそのまま返します。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Masker {
+    /// 値の伏せ方（`None`の場合は伏せない）
+    strategy: Option<MaskStrategy>,
+}
+
+impl Masker {
+    /// 指定した伏せ方で値を伏せるインスタンスを作成します。
+    ///
+    /// # 引数
+    /// * `strategy` - 値の伏せ方
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいMaskerインスタンス
+    pub fn new(strategy: MaskStrategy) -> Self {
+        Self {
+            strategy: Some(strategy),
+        }
+    }
+
+    /// 値を伏せずにそのまま表示するインスタンスを作成します。
+    ///
+    /// # 戻り値
+    /// * `Self` - 値を伏せないMaskerインスタンス
+    pub fn reveal() -> Self {
+        Self { strategy: None }
+    }
+
+    /// エラーメッセージを表示するためのインスタンスを作成します。
+    ///
+    /// `reveal`が`true`の場合、または標準出力と標準エラー出力がどちらも端末の場合は伏せません。
+    /// それ以外の場合（ログに残る場合など）は、指定した伏せ方で伏せます。
+    ///
+    /// # 引数
+    /// * `strategy` - 値の伏せ方
+    /// * `reveal` - 伏せずに表示するかどうか（`--reveal`の指定）
+    ///
+    /// # 戻り値
+    /// * `Self` - エラーメッセージを伏せ字にするMaskerインスタンス
+    pub fn for_errors(strategy: MaskStrategy, reveal: bool) -> Self {
+        if reveal || (io::stdout().is_terminal() && io::stderr().is_terminal()) {
+            Self::reveal()
+        } else {
+            Self::new(strategy)
+        }
+    }
+
+    /// メールアドレスを伏せ字にします。
+    ///
+    /// # 引数
+    /// * `email` - メールアドレス
+    ///
+    /// # 戻り値
+    /// * `String` - 伏せ字にしたメールアドレス
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_learn::services::masking::{MaskStrategy, Masker};
+    ///
+    /// let masker = Masker::new(MaskStrategy::Partial);
+    /// assert_eq!(masker.email("john@example.com"), "j***@example.com");
+    /// assert_eq!(Masker::reveal().email("john@example.com"), "john@example.com");
+    /// ```
+    pub fn email(&self, email: &str) -> String {
+        match self.strategy {
+            None => email.to_string(),
+            Some(MaskStrategy::Full) => FULL_MASK.to_string(),
+            Some(MaskStrategy::Partial) => {
+                let (local, domain) = match email.rsplit_once('@') {
+                    Some((local, domain)) => (local, Some(domain)),
+                    None => (email, None),
+                };
+                let head: String = local.chars().take(1).collect();
+                match domain {
+                    Some(domain) => format!("{}***@{}", head, domain),
+                    None => format!("{}***", head),
+                }
+            }
+        }
+    }
+
+    /// 電話番号を伏せ字にします。
+    ///
+    /// 一部を残す場合は、先頭の3桁と末尾の4桁（8桁未満の番号では末尾の2桁）を残し、
+    /// 区切り文字と`+`はそのまま表示します。
+    ///
+    /// # 引数
+    /// * `phone` - 電話番号（保存されている形式、または表示用の形式）
+    ///
+    /// # 戻り値
+    /// * `String` - 伏せ字にした電話番号
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_learn::services::masking::{MaskStrategy, Masker};
+    ///
+    /// let masker = Masker::new(MaskStrategy::Partial);
+    /// assert_eq!(masker.phone("09012345678"), "090****5678");
+    /// assert_eq!(masker.phone("090-1234-5678"), "090-****-5678");
+    /// assert_eq!(Masker::new(MaskStrategy::Full).phone("090-1234-5678"), "********");
+    /// ```
+    pub fn phone(&self, phone: &str) -> String {
+        match self.strategy {
+            None => phone.to_string(),
+            Some(MaskStrategy::Full) => FULL_MASK.to_string(),
+            Some(MaskStrategy::Partial) => {
+                let digits = phone.chars().filter(char::is_ascii_digit).count();
+                let (head, tail) = if digits >= 8 { (3, 4) } else { (0, 2) };
+                let mut index = 0;
+                phone
+                    .chars()
+                    .map(|c| {
+                        if !c.is_ascii_digit() {
+                            return c;
+                        }
+                        index += 1;
+                        if index <= head || index + tail > digits {
+                            c
+                        } else {
+                            '*'
+                        }
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// 変更履歴などに表示するフィールドの値を、フィールドに応じて伏せ字にします。
+    ///
+    /// # 引数
+    /// * `field` - フィールド名（`email`・`aliases`・`phone`以外はそのまま表示する）
+    /// * `value` - フィールドの値（`aliases`の場合は`, `区切りのメールアドレス）
+    ///
+    /// # 戻り値
+    /// * `String` - 伏せ字にした値
+    pub fn field(&self, field: &str, value: &str) -> String {
+        match field {
+            "email" => self.email(value),
+            "aliases" => value
+                .split(", ")
+                .map(|email| self.email(email))
+                .collect::<Vec<_>>()
+                .join(", "),
+            "phone" => self.phone(value),
+            _ => value.to_string(),
+        }
+    }
+
+    /// 文字列に含まれるメールアドレスを伏せ字にします。
+    ///
+    /// エラーメッセージのように、メールアドレスを含む文章を表示する場合に使用します。
+    ///
+    /// # 引数
+    /// * `text` - 文字列
+    /// * `email` - 伏せるメールアドレス
+    ///
+    /// # 戻り値
+    /// * `String` - メールアドレスを伏せ字にした文字列
+    pub fn text(&self, text: &str, email: &str) -> String {
+        if self.strategy.is_none() || email.is_empty() {
+            return text.to_string();
+        }
+        text.replace(email, &self.email(email))
+    }
+
+    /// 文章に含まれる全てのメールアドレスと電話番号を伏せ字にします。
+    ///
+    /// どの値が含まれるかわからないエラーメッセージを表示する場合に使用します。
+    /// `@`を挟んだ語をメールアドレス、区切り文字（`-`・空白・括弧）を1文字ずつ挟んで
+    /// 7桁以上続く数字を電話番号とみなします。
+    ///
+    /// # 引数
+    /// * `text` - 文章
+    ///
+    /// # 戻り値
+    /// * `String` - メールアドレスと電話番号を伏せ字にした文章
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_learn::services::masking::{MaskStrategy, Masker};
+    ///
+    /// let masker = Masker::new(MaskStrategy::Partial);
+    /// assert_eq!(
+    ///     masker.message("UserAlreadyExists(\"User with email john@example.com already exists\")"),
+    ///     "UserAlreadyExists(\"User with email j***@example.com already exists\")"
+    /// );
+    /// assert_eq!(
+    ///     masker.message("Invalid phone number: 090-1234-5678"),
+    ///     "Invalid phone number: 090-****-5678"
+    /// );
+    /// ```
+    pub fn message(&self, text: &str) -> String {
+        if self.strategy.is_none() {
+            return text.to_string();
+        }
+
+        let mut emails_masked = String::new();
+        let mut word = String::new();
+        for c in text.chars() {
+            if c.is_alphanumeric() || "._%+-@'".contains(c) {
+                word.push(c);
+            } else {
+                emails_masked.push_str(&self.email_word(&word));
+                word.clear();
+                emails_masked.push(c);
+            }
+        }
+        emails_masked.push_str(&self.email_word(&word));
+
+        let chars: Vec<char> = emails_masked.chars().collect();
+        let mut masked = String::new();
+        let mut index = 0;
+        while index < chars.len() {
+            let starts_number = chars[index].is_ascii_digit()
+                || (chars[index] == '+' && chars.get(index + 1).is_some_and(char::is_ascii_digit));
+            if !starts_number || (index > 0 && chars[index - 1].is_alphanumeric()) {
+                masked.push(chars[index]);
+                index += 1;
+                continue;
+            }
+            let mut end = index + 1;
+            loop {
+                match chars.get(end) {
+                    Some(c) if c.is_ascii_digit() => end += 1,
+                    Some('-' | ' ' | '(' | ')')
+                        if chars.get(end + 1).is_some_and(char::is_ascii_digit) =>
+                    {
+                        end += 1
+                    }
+                    _ => break,
+                }
+            }
+            let run: String = chars[index..end].iter().collect();
+            if run.chars().filter(char::is_ascii_digit).count() >= MIN_PHONE_DIGITS {
+                masked.push_str(&self.phone(&run));
+            } else {
+                masked.push_str(&run);
+            }
+            index = end;
+        }
+        masked
+    }
+
+    /// 語がメールアドレスの場合に伏せ字にします。
+    ///
+    /// # 引数
+    /// * `word` - 空白や記号で区切られた語
+    ///
+    /// # 戻り値
+    /// * `String` - メールアドレスの場合は伏せ字にした語（文末の`.`は残す）、それ以外はそのままの語
+    fn email_word(&self, word: &str) -> String {
+        let address = word.trim_end_matches('.');
+        match address.split_once('@') {
+            Some((local, domain)) if !local.is_empty() && !domain.is_empty() => {
+                format!("{}{}", self.email(address), &word[address.len()..])
+            }
+            _ => word.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_mask() {
+        let masker = Masker::new(MaskStrategy::Partial);
+
+        assert_eq!(masker.email("yamada@example.co.jp"), "y***@example.co.jp");
+        assert_eq!(masker.email("invalid"), "i***");
+        assert_eq!(masker.phone("+819012345678"), "+819*****5678");
+        assert_eq!(masker.phone("1234567"), "*****67");
+        assert_eq!(
+            masker.field("aliases", "a@example.com, b@example.com"),
+            "a***@example.com, b***@example.com"
+        );
+        assert_eq!(masker.field("age", "25"), "25");
+        assert_eq!(
+            masker.text("User with email a@example.com not found", "a@example.com"),
+            "User with email a***@example.com not found"
+        );
+    }
+
+    #[test]
+    fn test_message_masks_every_email_and_phone() {
+        let masker = Masker::new(MaskStrategy::Partial);
+
+        assert_eq!(
+            masker.message(
+                "Cannot undo merge of a@example.com: b@example.com has been created since."
+            ),
+            "Cannot undo merge of a***@example.com: b***@example.com has been created since."
+        );
+        assert_eq!(
+            masker.message("Duplicate phone +819012345678 (version 3 of 10)"),
+            "Duplicate phone +819*****5678 (version 3 of 10)"
+        );
+        assert_eq!(
+            Masker::new(MaskStrategy::Full).message("InvalidPhone(\"1234567\")"),
+            "InvalidPhone(\"********\")"
+        );
+        assert_eq!(
+            Masker::reveal().message("a@example.com 09012345678"),
+            "a@example.com 09012345678"
+        );
+    }
+
+    #[test]
+    fn test_parse_mask_strategy() {
+        assert_eq!("full".parse(), Ok(MaskStrategy::Full));
+        assert_eq!("partial".parse(), Ok(MaskStrategy::Partial));
+        assert!("none".parse::<MaskStrategy>().is_err());
+    }
+}
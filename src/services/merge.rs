@@ -0,0 +1,249 @@
+//! 2つのユーザーレコードを統合するモジュール
+//!
+//! 重複して登録されたユーザーを1つにまとめる際に、フィールドごとに
+//! どちらの値を採用するかを戦略に従って決定します。
+
+use crate::models::user::User;
+use std::fmt;
+use std::str::FromStr;
+
+/// 統合時に値を選択するフィールド
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeField {
+    /// ユーザー名
+    Username,
+    /// 電話番号
+    Phone,
+    /// 年齢
+    Age,
+}
+
+impl fmt::Display for MergeField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MergeField::Username => "username",
+            MergeField::Phone => "phone",
+            MergeField::Age => "age",
+        };
+        f.write_str(name)
+    }
+}
+
+/// フィールドの値を選択する戦略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeStrategy {
+    /// 残す側のユーザーの値を採用する
+    #[default]
+    PreferKeep,
+    /// 更新日時が新しい側のユーザーの値を採用する
+    PreferNewest,
+    /// 値が異なる場合に利用者に選択させる
+    Ask,
+}
+
+impl FromStr for MergeStrategy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "keep" => Ok(MergeStrategy::PreferKeep),
+            "newest" => Ok(MergeStrategy::PreferNewest),
+            "ask" => Ok(MergeStrategy::Ask),
+            _ => Err(format!(
+                "Invalid merge strategy: {} (expected keep, newest or ask)",
+                value
+            )),
+        }
+    }
+}
+
+/// 統合時に選択された値の提供元
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeChoice {
+    /// 残す側のユーザーの値
+    Keep,
+    /// 取り込む側のユーザーの値
+    Drop,
+}
+
+/// フィールドごとの統合戦略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MergeOptions {
+    /// ユーザー名の統合戦略
+    pub username: MergeStrategy,
+    /// 電話番号の統合戦略
+    pub phone: MergeStrategy,
+    /// 年齢の統合戦略
+    pub age: MergeStrategy,
+}
+
+impl MergeOptions {
+    /// 全てのフィールドに同じ戦略を適用する設定を作成します。
+    ///
+    /// # 引数
+    /// * `strategy` - 全てのフィールドに適用する戦略
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいMergeOptionsインスタンス
+    pub fn all(strategy: MergeStrategy) -> Self {
+        Self {
+            username: strategy,
+            phone: strategy,
+            age: strategy,
+        }
+    }
+}
+
+/// 2つのユーザーレコードを統合した結果を作成します。
+///
+/// メールアドレスは残す側のものを使用し、取り込む側のメールアドレスと
+/// 両者が持っていた別名は統合後のユーザーの別名として記録します。
+///
+/// # 引数
+/// * `keep` - 残す側のユーザー
+/// * `drop` - 取り込まれて削除される側のユーザー
+/// * `options` - フィールドごとの統合戦略
+/// * `ask` - `MergeStrategy::Ask`で値が異なる場合に呼び出され、採用する値を返す関数。
+///   引数はフィールド、残す側の値、取り込む側の値です。
+///
+/// # 戻り値
+/// * `User` - 統合後のユーザー
+pub fn merge_records(
+    keep: &User,
+    drop: &User,
+    options: &MergeOptions,
+    ask: &mut dyn FnMut(MergeField, &str, &str) -> MergeChoice,
+) -> User {
+    let drop_is_newer = match (keep.updated_at, drop.updated_at) {
+        (Some(keep_at), Some(drop_at)) => drop_at > keep_at,
+        (None, Some(_)) => true,
+        _ => false,
+    };
+    let mut choose =
+        |field: MergeField, strategy: MergeStrategy, keep_value: &str, drop_value: &str| {
+            if keep_value == drop_value {
+                return MergeChoice::Keep;
+            }
+            match strategy {
+                MergeStrategy::PreferKeep => MergeChoice::Keep,
+                MergeStrategy::PreferNewest if drop_is_newer => MergeChoice::Drop,
+                MergeStrategy::PreferNewest => MergeChoice::Keep,
+                MergeStrategy::Ask => ask(field, keep_value, drop_value),
+            }
+        };
+
+    let mut merged = keep.clone();
+    if choose(
+        MergeField::Username,
+        options.username,
+        &keep.username,
+        &drop.username,
+    ) == MergeChoice::Drop
+    {
+        merged.username = drop.username.clone();
+    }
+    if choose(MergeField::Phone, options.phone, &keep.phone, &drop.phone) == MergeChoice::Drop {
+        merged.phone = drop.phone.clone();
+    }
+    if choose(
+        MergeField::Age,
+        options.age,
+        &keep.age.to_string(),
+        &drop.age.to_string(),
+    ) == MergeChoice::Drop
+    {
+        merged.age = drop.age;
+    }
+
+    for alias in std::iter::once(&drop.email).chain(&drop.aliases) {
+        if *alias != merged.email && !merged.aliases.contains(alias) {
+            merged.aliases.push(alias.clone());
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn users() -> (User, User) {
+        let keep = User {
+            email: "keep@example.com".to_string(),
+            username: "Tanaka Taro".to_string(),
+            phone: "+819012345678".to_string(),
+            age: 30,
+            updated_at: Some(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()),
+            aliases: vec!["old@example.com".to_string()],
+            ..Default::default()
+        };
+        let drop = User {
+            email: "drop@example.com".to_string(),
+            username: "田中太郎".to_string(),
+            phone: "+818012345678".to_string(),
+            age: 31,
+            updated_at: Some(Utc.with_ymd_and_hms(2025, 6, 1, 0, 0, 0).unwrap()),
+            aliases: vec!["older@example.com".to_string()],
+            ..Default::default()
+        };
+        (keep, drop)
+    }
+
+    fn never_ask(_: MergeField, _: &str, _: &str) -> MergeChoice {
+        panic!("unexpected prompt");
+    }
+
+    #[test]
+    fn test_merge_prefer_keep_records_aliases() {
+        let (keep, drop) = users();
+        let merged = merge_records(&keep, &drop, &MergeOptions::default(), &mut never_ask);
+
+        assert_eq!(merged.email, "keep@example.com");
+        assert_eq!(merged.username, keep.username);
+        assert_eq!(merged.age, 30);
+        assert_eq!(
+            merged.aliases,
+            vec!["old@example.com", "drop@example.com", "older@example.com"]
+        );
+    }
+
+    #[test]
+    fn test_merge_prefer_newest() {
+        let (keep, drop) = users();
+        let options = MergeOptions {
+            phone: MergeStrategy::PreferNewest,
+            ..MergeOptions::default()
+        };
+        let merged = merge_records(&keep, &drop, &options, &mut never_ask);
+
+        assert_eq!(merged.username, keep.username);
+        assert_eq!(merged.phone, drop.phone);
+    }
+
+    #[test]
+    fn test_merge_ask_only_for_differing_fields() {
+        let (keep, mut drop) = users();
+        drop.age = keep.age;
+        let mut asked = Vec::new();
+        let merged = merge_records(
+            &keep,
+            &drop,
+            &MergeOptions::all(MergeStrategy::Ask),
+            &mut |field, _, _| {
+                asked.push(field);
+                MergeChoice::Drop
+            },
+        );
+
+        assert_eq!(asked, vec![MergeField::Username, MergeField::Phone]);
+        assert_eq!(merged.username, drop.username);
+        assert_eq!(merged.phone, drop.phone);
+    }
+
+    #[test]
+    fn test_parse_strategy() {
+        assert_eq!("newest".parse(), Ok(MergeStrategy::PreferNewest));
+        assert!("latest".parse::<MergeStrategy>().is_err());
+    }
+}
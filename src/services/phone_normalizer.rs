@@ -0,0 +1,401 @@
+//! 電話番号の正規化を行うモジュール
+//!
+//! `090-1234-5678`、`+81 90 1234 5678`、全角の`０９０１２３４５６７８`のような
+//! 様々な表記の電話番号を解析し、E.164形式（例: `+819012345678`）に正規化します。
+//! 国番号を含まない番号は、既定の国（通常は日本）の番号として解釈します。
+
+use crate::services::user_service::UserError;
+
+/// 国ごとの電話番号の規則
+#[derive(Debug, PartialEq)]
+pub struct CountryPlan {
+    /// ISO 3166-1 alpha-2 の国コード（例: "JP"）
+    pub region: &'static str,
+    /// 国際電話の国番号（例: "81"）
+    pub calling_code: &'static str,
+    /// 国内で番号の先頭に付けるプレフィックス（例: 日本の"0"）
+    pub trunk_prefix: &'static str,
+    /// 国番号を含まない番号に国内プレフィックスが必須かどうか
+    pub trunk_required: bool,
+    /// 国内プレフィックスを除いた番号（国内番号）の最小桁数
+    pub min_digits: usize,
+    /// 国内プレフィックスを除いた番号（国内番号）の最大桁数
+    pub max_digits: usize,
+}
+
+/// 対応している国の一覧
+const COUNTRY_PLANS: &[CountryPlan] = &[
+    CountryPlan {
+        region: "JP",
+        calling_code: "81",
+        trunk_prefix: "0",
+        trunk_required: true,
+        min_digits: 9,
+        max_digits: 10,
+    },
+    CountryPlan {
+        region: "US",
+        calling_code: "1",
+        trunk_prefix: "1",
+        trunk_required: false,
+        min_digits: 10,
+        max_digits: 10,
+    },
+    CountryPlan {
+        region: "GB",
+        calling_code: "44",
+        trunk_prefix: "0",
+        trunk_required: true,
+        min_digits: 9,
+        max_digits: 10,
+    },
+    CountryPlan {
+        region: "CN",
+        calling_code: "86",
+        trunk_prefix: "0",
+        trunk_required: true,
+        min_digits: 10,
+        max_digits: 11,
+    },
+    CountryPlan {
+        region: "KR",
+        calling_code: "82",
+        trunk_prefix: "0",
+        trunk_required: true,
+        min_digits: 8,
+        max_digits: 10,
+    },
+    CountryPlan {
+        region: "TW",
+        calling_code: "886",
+        trunk_prefix: "0",
+        trunk_required: true,
+        min_digits: 8,
+        max_digits: 9,
+    },
+];
+
+/// E.164形式で許可される国番号を含めた最小桁数（未対応の国番号に適用）
+const E164_MIN_DIGITS: usize = 8;
+
+/// E.164形式で許可される国番号を含めた最大桁数
+const E164_MAX_DIGITS: usize = 15;
+
+/// 正規化の導入前に受け付けていた電話番号の最小桁数
+const LEGACY_MIN_DIGITS: usize = 10;
+
+/// 国コードから電話番号の規則を取得します。
+///
+/// # 引数
+/// * `region` - ISO 3166-1 alpha-2 の国コード（大文字・小文字は区別しません）
+///
+/// # 戻り値
+/// * `Some(&CountryPlan)` - 対応している国の場合
+/// * `None` - 未対応の国の場合
+pub fn country_plan(region: &str) -> Option<&'static CountryPlan> {
+    COUNTRY_PLANS
+        .iter()
+        .find(|plan| plan.region.eq_ignore_ascii_case(region))
+}
+
+/// 電話番号をE.164形式に正規化します。
+///
+/// 全角数字・全角記号は半角として扱い、空白・ハイフン・括弧・ピリオドは区切り文字として無視します。
+/// `+`で始まる番号は国番号付きの番号として、それ以外は`default_region`の国内番号として解釈します。
+/// 国番号の後に国内プレフィックスを付けた表記（例: `+81 090-1234-5678`）は、国内プレフィックスを
+/// 取り除いて解釈します。国内プレフィックスが必須の国では、国内プレフィックスのない国内番号
+/// （正規化の導入前に保存された`9012345678`など）は推測で補わずにエラーとします。
+///
+/// # 引数
+/// * `phone` - 正規化する電話番号
+/// * `default_region` - 国番号を含まない番号に適用する国コード（例: "JP"）
+///
+/// # 戻り値
+/// * `Ok(String)` - E.164形式の電話番号
+///
+/// # Errors
+/// 以下の場合に`UserError::InvalidPhone`を返します：
+/// * 数字と区切り文字以外の文字が含まれている場合
+/// * 桁数がその国の規則に合わない場合
+/// * 国内プレフィックスが必須の国で、国内番号が国内プレフィックスで始まらない場合
+/// * `default_region`が未対応の国コードの場合
+///
+/// # Examples
+/// ```
+/// use rust_learn::services::phone_normalizer::normalize_phone;
+///
+/// assert_eq!(normalize_phone("090-1234-5678", "JP").unwrap(), "+819012345678");
+/// assert_eq!(normalize_phone("+81 90 1234 5678", "JP").unwrap(), "+819012345678");
+/// assert_eq!(normalize_phone("０９０１２３４５６７８", "JP").unwrap(), "+819012345678");
+/// assert_eq!(normalize_phone("+81 090-1234-5678", "JP").unwrap(), "+819012345678");
+/// assert!(normalize_phone("9012345678", "JP").is_err());
+/// ```
+pub fn normalize_phone(phone: &str, default_region: &str) -> Result<String, UserError> {
+    let invalid = |reason: &str| UserError::InvalidPhone(format!("{}: {}", reason, phone));
+
+    let (international, digits) =
+        clean_phone(phone).ok_or_else(|| invalid("Phone number contains invalid characters"))?;
+    if digits.is_empty() {
+        return Err(invalid("Phone number is empty"));
+    }
+
+    if international {
+        return match split_calling_code(&digits) {
+            Some((plan, national)) => {
+                // 国番号の後に国内プレフィックスを付けた表記（例: +81 090...）
+                let national = national
+                    .strip_prefix(plan.trunk_prefix)
+                    .filter(|rest| valid_length(plan, rest))
+                    .unwrap_or(national);
+                if !valid_length(plan, national) {
+                    return Err(invalid("Invalid phone number length"));
+                }
+                if plan.trunk_required && national.starts_with(plan.trunk_prefix) {
+                    return Err(invalid("Invalid national number"));
+                }
+                Ok(format!("+{}{}", plan.calling_code, national))
+            }
+            None if (E164_MIN_DIGITS..=E164_MAX_DIGITS).contains(&digits.len()) => {
+                Ok(format!("+{}", digits))
+            }
+            None => Err(invalid("Invalid phone number length")),
+        };
+    }
+
+    let plan = country_plan(default_region).ok_or_else(|| {
+        UserError::InvalidPhone(format!("Unsupported default country: {}", default_region))
+    })?;
+    let national = match digits.strip_prefix(plan.trunk_prefix) {
+        Some(rest) if plan.trunk_required && rest.starts_with(plan.trunk_prefix) => {
+            return Err(invalid("Invalid national number"));
+        }
+        Some(rest) if valid_length(plan, rest) => rest,
+        None if plan.trunk_required => {
+            return Err(invalid(&format!(
+                "Phone number must start with {} or a country code",
+                plan.trunk_prefix
+            )));
+        }
+        _ if !plan.trunk_required && valid_length(plan, &digits) => digits.as_str(),
+        _ => return Err(invalid("Invalid phone number length")),
+    };
+    Ok(format!("+{}{}", plan.calling_code, national))
+}
+
+/// 正規化の導入前の規則（10桁以上の半角数字のみ）に従った電話番号かどうかを判定します。
+///
+/// 導入前に保存された値には、`1234567890`のように国内番号か判断できないものが含まれます。
+/// このような値は推測で変換せず、入力どおりに扱うために使用します。
+///
+/// # 引数
+/// * `phone` - 判定する電話番号
+///
+/// # 戻り値
+/// * `bool` - 導入前の規則に従っている場合は`true`
+///
+/// # Examples
+/// ```
+/// use rust_learn::services::phone_normalizer::is_legacy_phone;
+///
+/// assert!(is_legacy_phone("1234567890"));
+/// assert!(!is_legacy_phone("090-1234-5678"));
+/// assert!(!is_legacy_phone("123456789"));
+/// ```
+pub fn is_legacy_phone(phone: &str) -> bool {
+    phone.len() >= LEGACY_MIN_DIGITS && phone.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+/// E.164形式の電話番号を表示用の形式に変換します。
+///
+/// `default_region`の国の番号は国内形式（例: `090-1234-5678`）で、
+/// それ以外の国の番号はE.164形式のまま返します。
+/// E.164形式として解釈できない値（正規化の導入前に保存された値など）はそのまま返します。
+///
+/// # 引数
+/// * `phone` - E.164形式の電話番号
+/// * `default_region` - 国内形式で表示する国コード
+///
+/// # 戻り値
+/// * `String` - 表示用の電話番号
+///
+/// # Examples
+/// ```
+/// use rust_learn::services::phone_normalizer::format_phone;
+///
+/// assert_eq!(format_phone("+819012345678", "JP"), "090-1234-5678");
+/// assert_eq!(format_phone("+12025550123", "JP"), "+12025550123");
+/// ```
+pub fn format_phone(phone: &str, default_region: &str) -> String {
+    let Some(digits) = phone.strip_prefix('+') else {
+        return phone.to_string();
+    };
+    match split_calling_code(digits) {
+        Some((plan, national)) if plan.region.eq_ignore_ascii_case(default_region) => {
+            format_national(plan, national)
+        }
+        _ => phone.to_string(),
+    }
+}
+
+/// 入力から区切り文字を取り除き、半角数字のみの文字列にします。
+///
+/// # 引数
+/// * `phone` - 入力された電話番号
+///
+/// # 戻り値
+/// * `Some((bool, String))` - 先頭に`+`があったかどうかと、数字のみの文字列
+/// * `None` - 数字・区切り文字以外の文字が含まれていた場合
+fn clean_phone(phone: &str) -> Option<(bool, String)> {
+    let mut international = false;
+    let mut digits = String::new();
+
+    for (index, c) in phone.trim().chars().enumerate() {
+        match c {
+            '0'..='9' => digits.push(c),
+            // 全角数字
+            '０'..='９' => digits.push(char::from(b'0' + (c as u32 - '０' as u32) as u8)),
+            '+' | '＋' if index == 0 => international = true,
+            ' ' | '\u{3000}' | '-' | '‐' | '－' | '−' | 'ー' | '(' | ')' | '（' | '）' | '.' =>
+                {}
+            _ => return None,
+        }
+    }
+    Some((international, digits))
+}
+
+/// 国番号付きの数字列を国番号と国内番号に分割します。
+///
+/// # 引数
+/// * `digits` - `+`を除いたE.164形式の数字列
+///
+/// # 戻り値
+/// * `Some((&CountryPlan, &str))` - 対応している国番号で始まる場合
+/// * `None` - 未対応の国番号の場合
+fn split_calling_code(digits: &str) -> Option<(&'static CountryPlan, &str)> {
+    COUNTRY_PLANS.iter().find_map(|plan| {
+        digits
+            .strip_prefix(plan.calling_code)
+            .map(|national| (plan, national))
+    })
+}
+
+/// 国内番号の桁数がその国の規則に合っているかを判定します。
+///
+/// # 引数
+/// * `plan` - 国ごとの電話番号の規則
+/// * `national` - 国内番号
+///
+/// # 戻り値
+/// * `bool` - 規則に合っている場合は`true`
+fn valid_length(plan: &CountryPlan, national: &str) -> bool {
+    (plan.min_digits..=plan.max_digits).contains(&national.len())
+}
+
+/// 国内番号を国内形式に整形します。
+///
+/// # 引数
+/// * `plan` - 国ごとの電話番号の規則
+/// * `national` - 国内番号
+///
+/// # 戻り値
+/// * `String` - 国内形式の電話番号
+fn format_national(plan: &CountryPlan, national: &str) -> String {
+    let number = format!("{}{}", plan.trunk_prefix, national);
+    let groups: &[usize] = match (plan.region, number.len()) {
+        // 携帯電話・IP電話など（例: 090-1234-5678）
+        ("JP", 11) => &[3, 4, 4],
+        // 東京・大阪（例: 03-1234-5678）
+        ("JP", 10) if number.starts_with("03") || number.starts_with("06") => &[2, 4, 4],
+        // その他の固定電話（例: 011-234-5678）
+        ("JP", 10) => &[3, 3, 4],
+        // 北米（例: 1-202-555-0123）
+        ("US", 11) => &[1, 3, 3, 4],
+        _ => return number,
+    };
+
+    let mut parts = Vec::with_capacity(groups.len());
+    let mut rest = number.as_str();
+    for &size in groups {
+        let (head, tail) = rest.split_at(size);
+        parts.push(head);
+        rest = tail;
+    }
+    parts.join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_japanese_formats() {
+        for input in [
+            "090-1234-5678",
+            "09012345678",
+            "+81 90 1234 5678",
+            "+81-90-1234-5678",
+            "+81 090-1234-5678",
+            "+81 (0)90 1234 5678",
+            "０９０１２３４５６７８",
+            "０９０－１２３４－５６７８",
+            "(090) 1234 5678",
+        ] {
+            assert_eq!(normalize_phone(input, "JP").unwrap(), "+819012345678");
+        }
+        assert_eq!(
+            normalize_phone("03-1234-5678", "JP").unwrap(),
+            "+81312345678"
+        );
+    }
+
+    #[test]
+    fn test_normalize_with_other_default_country() {
+        assert_eq!(
+            normalize_phone("202-555-0123", "US").unwrap(),
+            "+12025550123"
+        );
+        assert_eq!(
+            normalize_phone("1 202 555 0123", "us").unwrap(),
+            "+12025550123"
+        );
+        assert_eq!(
+            normalize_phone("+33 1 23 45 67 89", "JP").unwrap(),
+            "+33123456789"
+        );
+    }
+
+    #[test]
+    fn test_normalize_rejects_invalid_numbers() {
+        for input in [
+            "090-1234",
+            "+81 90 1234 5678 9",
+            "090-abcd-5678",
+            "",
+            "12+345",
+            // 国内プレフィックスのない値は国内番号か判断できない
+            "9012345678",
+            "1234567890",
+            "0090-1234-5678",
+            "+81 0012345678",
+        ] {
+            assert!(matches!(
+                normalize_phone(input, "JP"),
+                Err(UserError::InvalidPhone(_))
+            ));
+        }
+        assert!(matches!(
+            normalize_phone("09012345678", "XX"),
+            Err(UserError::InvalidPhone(_))
+        ));
+    }
+
+    #[test]
+    fn test_format_phone() {
+        assert_eq!(format_phone("+819012345678", "JP"), "090-1234-5678");
+        assert_eq!(format_phone("+81312345678", "JP"), "03-1234-5678");
+        assert_eq!(format_phone("+81112345678", "JP"), "011-234-5678");
+        assert_eq!(format_phone("+12025550123", "US"), "1-202-555-0123");
+        assert_eq!(format_phone("+12025550123", "JP"), "+12025550123");
+        assert_eq!(format_phone("1234567890", "JP"), "1234567890");
+    }
+}
@@ -0,0 +1,194 @@
+//! ユーザー検索のための文字列の照合を行うモジュール
+//!
+//! 全角・半角、大文字・小文字、カタカナ・ひらがなの違いを無視して照合できるよう、
+//! 検索語と検索対象の双方を同じ規則で畳み込んでから比較します。
+
+use crate::models::user::User;
+use unicode_normalization::UnicodeNormalization;
+
+/// 検索語に一致したフィールド
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchField {
+    /// ユーザー名に一致した場合
+    Username,
+    /// メールアドレスに一致した場合
+    Email,
+}
+
+/// 検索結果の1件
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    /// 一致したユーザー
+    pub user: User,
+    /// 一致の度合いを表すスコア（大きいほど良く一致している）
+    pub score: u32,
+    /// 最も良く一致したフィールド
+    pub field: SearchField,
+}
+
+/// 文字列を検索用に畳み込みます。
+///
+/// NFKC正規化により全角英数字を半角に、半角カナを全角に揃え、
+/// さらに小文字化とカタカナからひらがなへの変換を行います。
+///
+/// # 引数
+/// * `text` - 畳み込む文字列
+///
+/// # 戻り値
+/// * `String` - 検索用に畳み込まれた文字列
+///
+/// # Examples
+/// ```
+/// use rust_learn::services::search::fold_for_search;
+///
+/// assert_eq!(fold_for_search("ＴＡＮＡＫＡ"), "tanaka");
+/// assert_eq!(fold_for_search("ﾀﾅｶ"), fold_for_search("たなか"));
+/// ```
+pub fn fold_for_search(text: &str) -> String {
+    text.trim()
+        .nfkc()
+        .flat_map(char::to_lowercase)
+        .map(katakana_to_hiragana)
+        .collect()
+}
+
+/// ユーザーが検索語にどの程度一致するかを評価します。
+///
+/// ユーザー名とメールアドレスのそれぞれについて、完全一致・前方一致・
+/// 単語の先頭での一致・部分一致の順に高いスコアを付け、最も高いものを返します。
+/// メールアドレスの一致は、完全一致を除いてユーザー名の一致よりやや低く評価します。
+///
+/// # 引数
+/// * `user` - 評価するユーザー
+/// * `folded_term` - `fold_for_search`で畳み込んだ検索語
+///
+/// # 戻り値
+/// * `Some(SearchResult)` - 一致した場合
+/// * `None` - 一致しなかった場合
+pub fn score_user(user: &User, folded_term: &str) -> Option<SearchResult> {
+    if folded_term.is_empty() {
+        return None;
+    }
+
+    let username_score = match_score(&fold_for_search(&user.username), folded_term);
+
+    let email = fold_for_search(&user.email);
+    let email_score = if email == folded_term {
+        Some(100)
+    } else {
+        let local = email.split('@').next().unwrap_or_default();
+        match_score(local, folded_term)
+            .or_else(|| match_score(&email, folded_term).map(|_| 40))
+            .map(|score| score - 10)
+    };
+
+    let (score, field) = match (username_score, email_score) {
+        (Some(u), Some(e)) if e > u => (e, SearchField::Email),
+        (Some(u), _) => (u, SearchField::Username),
+        (None, Some(e)) => (e, SearchField::Email),
+        (None, None) => return None,
+    };
+    Some(SearchResult {
+        user: user.clone(),
+        score,
+        field,
+    })
+}
+
+/// 畳み込み済みの文字列同士の一致度を計算します。
+///
+/// # 引数
+/// * `text` - 検索対象の文字列
+/// * `term` - 検索語
+///
+/// # 戻り値
+/// * `Some(u32)` - 一致した場合のスコア（完全一致100、前方一致80、単語の先頭60、部分一致40）
+/// * `None` - 一致しなかった場合
+fn match_score(text: &str, term: &str) -> Option<u32> {
+    if text == term {
+        Some(100)
+    } else if text.starts_with(term) {
+        Some(80)
+    } else if text
+        .match_indices(term)
+        .any(|(index, _)| text[..index].ends_with([' ', '.', '_', '-', '+']))
+    {
+        Some(60)
+    } else if text.contains(term) {
+        Some(40)
+    } else {
+        None
+    }
+}
+
+/// カタカナをひらがなに変換します。カタカナ以外の文字はそのまま返します。
+///
+/// # 引数
+/// * `c` - 変換する文字
+///
+/// # 戻り値
+/// * `char` - 変換後の文字
+fn katakana_to_hiragana(c: char) -> char {
+    match c {
+        // ァ〜ヶ、および踊り字ヽヾ
+        '\u{30A1}'..='\u{30F6}' | '\u{30FD}' | '\u{30FE}' => {
+            char::from_u32(c as u32 - 0x60).unwrap_or(c)
+        }
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(email: &str, username: &str) -> User {
+        User {
+            email: email.to_string(),
+            username: username.to_string(),
+            phone: "+819012345678".to_string(),
+            age: 25,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fold_width_case_and_kana() {
+        assert_eq!(fold_for_search("ｔａｎａｋａ"), "tanaka");
+        assert_eq!(fold_for_search("Tanaka"), "tanaka");
+        assert_eq!(fold_for_search("タナカ"), "たなか");
+        assert_eq!(fold_for_search("ﾀﾞﾅｶ"), "だなか");
+    }
+
+    #[test]
+    fn test_score_prefers_better_matches() {
+        let term = fold_for_search("tanaka");
+        let exact = score_user(&user("t@example.com", "Tanaka"), &term).unwrap();
+        let prefix = score_user(&user("t@example.com", "tanaka taro"), &term).unwrap();
+        let word = score_user(&user("t@example.com", "taro tanaka"), &term).unwrap();
+        let partial = score_user(&user("t@example.com", "yamatanaka"), &term).unwrap();
+
+        assert!(exact.score > prefix.score);
+        assert!(prefix.score > word.score);
+        assert!(word.score > partial.score);
+        assert!(score_user(&user("t@example.com", "suzuki"), &term).is_none());
+    }
+
+    #[test]
+    fn test_score_matches_email() {
+        let result = score_user(
+            &user("tanaka@example.com", "たろう"),
+            &fold_for_search("ＴＡＮＡＫＡ"),
+        )
+        .unwrap();
+
+        assert_eq!(result.field, SearchField::Email);
+        assert_eq!(result.score, 90);
+    }
+
+    #[test]
+    fn test_score_matches_kana_variants() {
+        let result = score_user(&user("t@example.com", "たなか"), &fold_for_search("ﾀﾅｶ"));
+        assert_eq!(result.unwrap().score, 100);
+    }
+}
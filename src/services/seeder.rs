@@ -0,0 +1,264 @@
+//! 負荷試験やデモのため、架空のユーザーを決定的に生成するモジュール
+//!
+//! 生成は64ビットのシードだけから決まるため、同じシードと件数からは常に同じユーザーが
+//! 生成されます。ユーザー名は日本人の姓名（漢字・ひらがな・ローマ字）、メールアドレスは
+//! 文書用に予約された`example`ドメイン、電話番号は日本の携帯電話・固定電話の番号で、
+//! いずれも`UserService`の規則に従う値になります。
+
+use crate::models::user::User;
+use std::collections::HashSet;
+
+/// 姓（漢字・ひらがな・ローマ字）
+const FAMILY_NAMES: &[(&str, &str, &str)] = &[
+    ("佐藤", "さとう", "sato"),
+    ("鈴木", "すずき", "suzuki"),
+    ("高橋", "たかはし", "takahashi"),
+    ("田中", "たなか", "tanaka"),
+    ("伊藤", "いとう", "ito"),
+    ("渡辺", "わたなべ", "watanabe"),
+    ("山本", "やまもと", "yamamoto"),
+    ("中村", "なかむら", "nakamura"),
+    ("小林", "こばやし", "kobayashi"),
+    ("加藤", "かとう", "kato"),
+    ("吉田", "よしだ", "yoshida"),
+    ("山田", "やまだ", "yamada"),
+    ("佐々木", "ささき", "sasaki"),
+    ("松本", "まつもと", "matsumoto"),
+    ("井上", "いのうえ", "inoue"),
+    ("木村", "きむら", "kimura"),
+    ("林", "はやし", "hayashi"),
+    ("清水", "しみず", "shimizu"),
+    ("山口", "やまぐち", "yamaguchi"),
+    ("森", "もり", "mori"),
+];
+
+/// 名（漢字・ひらがな・ローマ字）
+const GIVEN_NAMES: &[(&str, &str, &str)] = &[
+    ("太郎", "たろう", "taro"),
+    ("翔太", "しょうた", "shota"),
+    ("蓮", "れん", "ren"),
+    ("大翔", "ひろと", "hiroto"),
+    ("健一", "けんいち", "kenichi"),
+    ("誠", "まこと", "makoto"),
+    ("悠斗", "ゆうと", "yuto"),
+    ("拓也", "たくや", "takuya"),
+    ("直樹", "なおき", "naoki"),
+    ("和也", "かずや", "kazuya"),
+    ("花子", "はなこ", "hanako"),
+    ("陽菜", "ひな", "hina"),
+    ("結衣", "ゆい", "yui"),
+    ("さくら", "さくら", "sakura"),
+    ("美咲", "みさき", "misaki"),
+    ("愛", "あい", "ai"),
+    ("由美子", "ゆみこ", "yumiko"),
+    ("葵", "あおい", "aoi"),
+    ("恵", "めぐみ", "megumi"),
+    ("彩", "あや", "aya"),
+];
+
+/// メールアドレスのドメイン（実在の利用者に届かないよう、文書用に予約されたもの）
+const EMAIL_DOMAINS: &[&str] = &[
+    "example.com",
+    "example.jp",
+    "example.co.jp",
+    "example.ne.jp",
+    "example.org",
+];
+
+/// 電話番号の国番号より後の先頭の桁（携帯電話の`90`・`80`・`70`と、東京・大阪の市外局番）
+const PHONE_PREFIXES: &[&str] = &["90", "90", "90", "80", "80", "70", "3", "6"];
+
+/// 生成するユーザーの年齢の下限
+const MIN_AGE: u32 = 18;
+
+/// 生成するユーザーの年齢の上限
+const MAX_AGE: u32 = 85;
+
+/// シードから架空のユーザーを生成する
+///
+/// 乱数にはSplitMix64を使用します。
+pub struct Seeder {
+    /// 乱数の内部状態
+    state: u64,
+}
+
+impl Seeder {
+    /// シードを指定してSeederインスタンスを作成します。
+    ///
+    /// # 引数
+    /// * `seed` - 乱数のシード
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいSeederインスタンス
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// 指定した件数のユーザーを生成します。
+    ///
+    /// メールアドレスには通し番号を含めるため、1回の生成の中では重複しません。
+    /// 電話番号も重複しないように選びます。作成日時は設定せず、バージョンは1とします。
+    ///
+    /// # 引数
+    /// * `count` - 生成するユーザーの件数
+    ///
+    /// # 戻り値
+    /// * `Vec<User>` - 生成したユーザー
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_learn::services::seeder::Seeder;
+    ///
+    /// let users = Seeder::new(42).generate(3);
+    /// assert_eq!(users.len(), 3);
+    /// assert_eq!(users, Seeder::new(42).generate(3));
+    /// assert_ne!(users, Seeder::new(43).generate(3));
+    /// assert!(users.iter().all(|user| user.phone.starts_with("+81")));
+    /// ```
+    pub fn generate(&mut self, count: usize) -> Vec<User> {
+        let mut phones = HashSet::new();
+        (1..=count)
+            .map(|number| {
+                let family = FAMILY_NAMES[self.below(FAMILY_NAMES.len())];
+                let given = GIVEN_NAMES[self.below(GIVEN_NAMES.len())];
+                let email = self.email(family.2, given.2, number);
+                let username = self.username(family, given);
+                let mut phone = self.phone();
+                while !phones.insert(phone.clone()) {
+                    phone = self.phone();
+                }
+                let age = MIN_AGE + self.below((MAX_AGE - MIN_AGE + 1) as usize) as u32;
+                User {
+                    email,
+                    username,
+                    phone,
+                    age,
+                    version: 1,
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    /// 姓名と通し番号からメールアドレスを作ります。
+    ///
+    /// # 引数
+    /// * `family` - 姓のローマ字
+    /// * `given` - 名のローマ字
+    /// * `number` - 通し番号
+    ///
+    /// # 戻り値
+    /// * `String` - 正規化後の形式のメールアドレス
+    fn email(&mut self, family: &str, given: &str, number: usize) -> String {
+        let local = match self.below(3) {
+            0 => format!("{}.{}{}", given, family, number),
+            1 => format!("{}{}{}", given, family, number),
+            _ => format!("{}_{}{}", family, given, number),
+        };
+        format!(
+            "{}@{}",
+            local,
+            EMAIL_DOMAINS[self.below(EMAIL_DOMAINS.len())]
+        )
+    }
+
+    /// 姓名からユーザー名を作ります。
+    ///
+    /// 漢字・ひらがなの姓名（姓と名の間は空白）と、ローマ字のいくつかの書き方から選びます。
+    ///
+    /// # 引数
+    /// * `family` - 姓（漢字・ひらがな・ローマ字）
+    /// * `given` - 名（漢字・ひらがな・ローマ字）
+    ///
+    /// # 戻り値
+    /// * `String` - 3文字以上32文字以下のユーザー名
+    fn username(&mut self, family: (&str, &str, &str), given: (&str, &str, &str)) -> String {
+        match self.below(10) {
+            0..=3 => format!("{} {}", family.0, given.0),
+            4 | 5 => format!("{} {}", family.1, given.1),
+            6 | 7 => format!("{}_{}", given.2, family.2),
+            8 => format!("{}{}{:02}", &given.2[..1], family.2, self.below(100)),
+            _ => format!("{}{}", capitalize(given.2), capitalize(family.2)),
+        }
+    }
+
+    /// 日本の電話番号をE.164形式で作ります。
+    ///
+    /// # 戻り値
+    /// * `String` - 国番号を除いて9桁（固定電話）または10桁（携帯電話）の電話番号
+    fn phone(&mut self) -> String {
+        let prefix = PHONE_PREFIXES[self.below(PHONE_PREFIXES.len())];
+        let subscriber = self.below(100_000_000);
+        format!("+81{}{:08}", prefix, subscriber)
+    }
+
+    /// `0`以上`bound`未満の値を選びます。
+    ///
+    /// # 引数
+    /// * `bound` - 選ぶ値の上限（この値は含まない、1以上）
+    ///
+    /// # 戻り値
+    /// * `usize` - 選んだ値
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    /// SplitMix64で次の乱数を求めます。
+    ///
+    /// # 戻り値
+    /// * `u64` - 次の乱数
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// ローマ字の先頭を大文字にします。
+///
+/// # 引数
+/// * `word` - ASCIIの英小文字からなる文字列
+///
+/// # 戻り値
+/// * `String` - 先頭を大文字にした文字列
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_users_are_unique() {
+        let users = Seeder::new(7).generate(2000);
+
+        let emails: HashSet<&str> = users.iter().map(|user| user.email.as_str()).collect();
+        let phones: HashSet<&str> = users.iter().map(|user| user.phone.as_str()).collect();
+        assert_eq!(emails.len(), users.len());
+        assert_eq!(phones.len(), users.len());
+        assert!(
+            users
+                .iter()
+                .all(|user| (MIN_AGE..=MAX_AGE).contains(&user.age))
+        );
+        // 漢字・ひらがなの名前も含まれる
+        assert!(
+            users
+                .iter()
+                .any(|user| user.username.chars().any(|c| ('ぁ'..='ん').contains(&c)))
+        );
+        assert!(users.iter().any(|user| {
+            user.username
+                .chars()
+                .any(|c| ('\u{4E00}'..='\u{9FFF}').contains(&c))
+        }));
+        assert_eq!(capitalize("taro"), "Taro");
+    }
+}
@@ -1,11 +1,147 @@
+use crate::models::audit::{AuditEntry, AuditOutcome, AuditVerification};
+use crate::models::integrity::{IntegrityIssue, IntegrityProblem, IntegrityReport};
+use crate::models::journal::{Journal, JournalChange, JournalEntry};
+use crate::models::privacy::{ErasureReceipt, SubjectExport};
+use crate::models::revision::{Revision, RevisionOperation};
 use crate::models::user::User;
-use crate::repositories::user_repository::UserRepository;
-use regex::Regex;
+use crate::repositories::audit_repository::AuditRepository;
+use crate::repositories::dry_run_repository::DryRunUserRepository;
+use crate::repositories::field_encryption::{
+    FieldAccess, FieldCipher, FieldEncryptedUserRepository,
+};
+use crate::repositories::history_repository::HistoryRepository;
+use crate::repositories::journal_repository::JournalRepository;
+use crate::repositories::user_repository::{BatchOperation, UserRepository};
+use crate::services::anonymizer::{Anonymizer, PseudonymRules};
+use crate::services::duplicate_detector::{DuplicateCluster, find_duplicate_clusters};
+use crate::services::email_normalizer::normalize_email;
+use crate::services::merge::{MergeChoice, MergeField, MergeOptions, merge_records};
+use crate::services::phone_normalizer::{format_phone, is_legacy_phone, normalize_phone};
+use crate::services::search::{SearchResult, fold_for_search, score_user};
+use crate::services::username_normalizer::{
+    DEFAULT_MAX_USERNAME_LENGTH, DEFAULT_RESERVED_USERNAMES, normalize_username,
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
 
 /// ユーザー管理のビジネスロジックを実装するサービス
 pub struct UserService<T: UserRepository> {
     /// ユーザーデータの永続化を担当するリポジトリ
     repository: T,
+    /// 入力値の正規化・検証に関する設定
+    config: UserServiceConfig,
+    /// 変更履歴を記録するリポジトリ（`None`の場合は履歴を記録しない）
+    history: Option<Box<dyn HistoryRepository>>,
+    /// 監査ログを記録するリポジトリ（`None`の場合は監査ログを記録しない）
+    audit: Option<Box<dyn AuditRepository>>,
+    /// 取り消し・やり直しのための操作ジャーナル（`None`の場合は記録しない）
+    journal: Option<Box<dyn JournalRepository>>,
+    /// 実行中の操作で行われた、変更履歴・監査ログ・操作ジャーナルに記録する前の変更
+    pending_changes: RefCell<Vec<PendingChange>>,
+    /// ユーザーの一部のフィールドの暗号化に使用する鍵（`None`の場合は暗号化しない）
+    field_cipher: Option<FieldCipher>,
+    /// 暗号化したフィールドを伏せ字で読み込むかどうか
+    redacted: bool,
+}
+
+/// 実行中の操作で行われた、まだ記録していない1件のレコードに対する変更
+struct PendingChange {
+    /// 記録に使用するメールアドレス（正規化できる場合は正規化後のもの）
+    email: String,
+    /// 変更を行った操作の種類
+    operation: RevisionOperation,
+    /// 暗号化するフィールドを暗号化した、変更前後のレコード
+    change: JournalChange,
+}
+
+/// 操作ジャーナルに保持する、取り消しできる操作の上限
+pub const JOURNAL_LIMIT: usize = 100;
+
+/// `UserService`の動作を調整する設定
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserServiceConfig {
+    /// メールアドレスのローカル部（`@`より前）も小文字化するかどうか
+    ///
+    /// 環境変数`USER_EMAIL_PRESERVE_LOCAL_CASE`に`1`または`true`を指定すると無効になります。
+    pub lowercase_email_local_part: bool,
+
+    /// 国番号を含まない電話番号に適用する国コード（ISO 3166-1 alpha-2）
+    ///
+    /// 環境変数`USER_DEFAULT_COUNTRY`で指定できます。既定値は`JP`です。
+    pub default_country: String,
+
+    /// ユーザー名の最大文字数（書記素クラスタ数）
+    ///
+    /// 環境変数`USER_USERNAME_MAX_LENGTH`で指定できます。既定値は32です。
+    pub max_username_length: usize,
+
+    /// ユーザー名として使用できない予約語の一覧
+    ///
+    /// 環境変数`USER_RESERVED_NAMES`にカンマ区切りで指定すると、既定の一覧を置き換えます。
+    pub reserved_usernames: Vec<String>,
+
+    /// 変更履歴と監査ログに記録する操作者の名前
+    ///
+    /// 環境変数`USER_ACTOR`で指定できます。指定がない場合はOSのユーザー名
+    /// （`USER`または`USERNAME`）を使用し、それもない場合は`unknown`になります。
+    pub actor: String,
+}
+
+impl Default for UserServiceConfig {
+    fn default() -> Self {
+        Self {
+            lowercase_email_local_part: true,
+            default_country: "JP".to_string(),
+            max_username_length: DEFAULT_MAX_USERNAME_LENGTH,
+            reserved_usernames: DEFAULT_RESERVED_USERNAMES
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            actor: "unknown".to_string(),
+        }
+    }
+}
+
+impl UserServiceConfig {
+    /// 環境変数から設定を読み込みます。
+    ///
+    /// 設定されていない項目には既定値が使用されます。
+    ///
+    /// # 戻り値
+    /// * `Self` - 環境変数を反映した設定
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Ok(value) = env::var("USER_EMAIL_PRESERVE_LOCAL_CASE") {
+            config.lowercase_email_local_part = !matches!(value.as_str(), "1" | "true");
+        }
+        if let Ok(value) = env::var("USER_DEFAULT_COUNTRY") {
+            config.default_country = value.trim().to_uppercase();
+        }
+        if let Some(length) = env::var("USER_USERNAME_MAX_LENGTH")
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+        {
+            config.max_username_length = length;
+        }
+        if let Ok(value) = env::var("USER_RESERVED_NAMES") {
+            config.reserved_usernames = value
+                .split(',')
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .collect();
+        }
+        if let Some(actor) = ["USER_ACTOR", "USER", "USERNAME"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .map(|value| value.trim().to_string())
+            .find(|value| !value.is_empty())
+        {
+            config.actor = actor;
+        }
+        config
+    }
 }
 
 /// ユーザー操作に関連するエラー
@@ -26,6 +162,12 @@ pub enum UserError {
     RepositoryError(String),
     /// 既に存在するユーザーを作成しようとした場合のエラー
     UserAlreadyExists(String),
+    /// 要求された操作が現在のデータの状態と矛盾する場合のエラー
+    InvalidOperation(String),
+    /// ゴミ箱にあるユーザーと同じメールアドレスでユーザーを作成しようとした場合のエラー
+    UserInTrash(String),
+    /// 想定したバージョンの後にレコードが変更されていた場合のエラー
+    Conflict(String),
 }
 
 impl From<String> for UserError {
@@ -34,6 +176,21 @@ impl From<String> for UserError {
     }
 }
 
+/// 監査ログの検索条件
+///
+/// 指定されていない条件は全ての記録に一致します。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditQuery {
+    /// 対象ユーザーのメールアドレス
+    pub email: Option<String>,
+    /// 操作者の名前
+    pub actor: Option<String>,
+    /// この日時以降の記録に限定する
+    pub since: Option<DateTime<Utc>>,
+    /// この日時以前の記録に限定する
+    pub until: Option<DateTime<Utc>>,
+}
+
 impl<T: UserRepository> UserService<T> {
     /// 新しいUserServiceインスタンスを作成します。
     ///
@@ -43,15 +200,122 @@ impl<T: UserRepository> UserService<T> {
     /// # 戻り値
     /// * `Self` - 新しいUserServiceインスタンス
     pub fn new(repository: T) -> Self {
-        Self { repository }
+        Self::with_config(repository, UserServiceConfig::default())
+    }
+
+    /// 設定を指定してUserServiceインスタンスを作成します。
+    ///
+    /// # 引数
+    /// * `repository` - ユーザーデータの永続化を担当するリポジトリ
+    /// * `config` - 入力値の正規化・検証に関する設定
+    ///
+    /// # 戻り値
+    /// * `Self` - 新しいUserServiceインスタンス
+    pub fn with_config(repository: T, config: UserServiceConfig) -> Self {
+        Self {
+            repository,
+            config,
+            history: None,
+            audit: None,
+            journal: None,
+            pending_changes: RefCell::new(Vec::new()),
+            field_cipher: None,
+            redacted: false,
+        }
+    }
+
+    /// 変更履歴を記録するリポジトリを設定します。
+    ///
+    /// 設定すると、ユーザーを変更する操作のたびに変更後の状態がリビジョンとして記録され、
+    /// `user_history`・`get_user_at`・`revert_user`が使用できるようになります。
+    ///
+    /// # 引数
+    /// * `history` - 変更履歴を記録するリポジトリ
+    ///
+    /// # 戻り値
+    /// * `Self` - 変更履歴を記録するUserServiceインスタンス
+    pub fn with_history(mut self, history: impl HistoryRepository + 'static) -> Self {
+        self.history = Some(Box::new(history));
+        self
+    }
+
+    /// 監査ログを記録するリポジトリを設定します。
+    ///
+    /// 設定すると、ユーザーを変更する操作のたびに、成功・失敗を問わず
+    /// 操作者・操作・変更前後の値・結果が記録されます。
+    ///
+    /// # 引数
+    /// * `audit` - 監査ログを記録するリポジトリ
+    ///
+    /// # 戻り値
+    /// * `Self` - 監査ログを記録するUserServiceインスタンス
+    pub fn with_audit(mut self, audit: impl AuditRepository + 'static) -> Self {
+        self.audit = Some(Box::new(audit));
+        self
+    }
+
+    /// 取り消し・やり直しのための操作ジャーナルを設定します。
+    ///
+    /// 設定すると、ユーザーを変更する操作のたびに変更前後の状態が記録され、
+    /// `undo`・`redo`が使用できるようになります。
+    ///
+    /// # 引数
+    /// * `journal` - 操作ジャーナルのリポジトリ
+    ///
+    /// # 戻り値
+    /// * `Self` - 操作ジャーナルを記録するUserServiceインスタンス
+    pub fn with_journal(mut self, journal: impl JournalRepository + 'static) -> Self {
+        self.journal = Some(Box::new(journal));
+        self
+    }
+
+    /// ユーザーの一部のフィールドを暗号化する鍵を設定します。
+    ///
+    /// 設定すると、書き込むユーザーの指定されたフィールドを暗号化して保存し、
+    /// 読み込んだユーザーの暗号化されたフィールドを復号して返します。
+    /// 設定しない場合、暗号化されたフィールドを持つユーザーの読み込みはエラーになります。
+    ///
+    /// # 引数
+    /// * `cipher` - フィールドの暗号化に使用する鍵
+    ///
+    /// # 戻り値
+    /// * `Self` - フィールドを暗号化するUserServiceインスタンス
+    pub fn with_field_encryption(mut self, cipher: FieldCipher) -> Self {
+        self.field_cipher = Some(cipher);
+        self
+    }
+
+    /// 暗号化されたフィールドを伏せ字で読み込むサービスを作成します。
+    ///
+    /// 鍵の有無にかかわらず、暗号化されたフィールドの値は伏せ字（`********`）に置き換えられます。
+    /// 作成したサービスでユーザーを変更する操作はエラーになります。
+    ///
+    /// # 戻り値
+    /// * `UserService<&T>` - 読み込み専用のUserServiceインスタンス
+    pub fn redacted(&self) -> UserService<&T> {
+        let mut service = UserService::with_config(&self.repository, self.config.clone());
+        service.redacted = true;
+        service
+    }
+
+    /// 変更履歴と監査ログに記録する操作者を設定します。
+    ///
+    /// # 引数
+    /// * `actor` - 操作者の名前
+    ///
+    /// # 戻り値
+    /// * `Self` - 操作者を設定したUserServiceインスタンス
+    pub fn with_actor(mut self, actor: impl Into<String>) -> Self {
+        self.config.actor = actor.into();
+        self
     }
 
     /// 新しいユーザーを作成します。
     ///
     /// # 引数
     /// * `email` - ユーザーのメールアドレス
-    /// * `username` - ユーザー名（3文字以上）
-    /// * `phone` - 電話番号（10桁以上の数字）
+    /// * `username` - ユーザー名（3文字以上、NFKC正規化して保存）
+    /// * `phone` - 電話番号（国内形式または国番号付きの形式）
     /// * `age` - 年齢（0-150の範囲）
     ///
     /// # 戻り値
@@ -78,10 +342,11 @@ impl<T: UserRepository> UserService<T> {
     /// # Errors
     /// 以下の場合にエラーを返します：
     /// * `UserError::InvalidEmail` - メールアドレスの形式が不正な場合
-    /// * `UserError::InvalidUsername` - ユーザー名が3文字未満の場合
-    /// * `UserError::InvalidPhone` - 電話番号が10桁未満の場合
+    /// * `UserError::InvalidUsername` - ユーザー名の文字数が範囲外、予約語、または不可視文字を含む場合
+    /// * `UserError::InvalidPhone` - 電話番号の形式や桁数が不正な場合
     /// * `UserError::InvalidAge` - 年齢が150歳を超える場合
     /// * `UserError::UserAlreadyExists` - 同じメールアドレスのユーザーが既に存在する場合
+    /// * `UserError::UserInTrash` - 同じメールアドレスのユーザーがゴミ箱にある場合
     /// * `UserError::RepositoryError` - データの永続化に失敗した場合
     pub fn create_user(
         &self,
@@ -90,39 +355,54 @@ impl<T: UserRepository> UserService<T> {
         phone: String,
         age: u32,
     ) -> Result<User, UserError> {
-        self.validate_email(&email)?;
-        self.validate_username(&username)?;
-        self.validate_phone(&phone)?;
-        self.validate_age(age)?;
-
-        // Check if user already exists
-        if let Ok(Some(_)) = self.repository.find_by_email(&email) {
-            return Err(UserError::UserAlreadyExists(format!(
-                "User with email {} already exists",
-                email
-            )));
-        }
+        self.audited(RevisionOperation::Create, Some(&email), || {
+            let email = self.normalize_email(&email)?;
+            let username = self.normalize_username(&username)?;
+            let phone = self.normalize_phone(&phone)?;
+            self.validate_age(age)?;
 
-        let user = User {
-            email,
-            username,
-            phone,
-            age,
-        };
+            // Check if user already exists
+            match self.find_existing(&email) {
+                Ok(Some(existing)) if existing.deleted_at.is_some() => {
+                    return Err(UserError::UserInTrash(format!(
+                        "User with email {} is in the trash; restore or purge it first",
+                        email
+                    )));
+                }
+                Ok(Some(_)) => {
+                    return Err(UserError::UserAlreadyExists(format!(
+                        "User with email {} already exists",
+                        email
+                    )));
+                }
+                _ => {}
+            }
 
-        self.repository
-            .save(&user)
-            .map_err(UserError::RepositoryError)?;
+            let user = User {
+                email,
+                username,
+                phone,
+                age,
+                updated_at: Some(Utc::now()),
+                version: 1,
+                ..Default::default()
+            };
 
-        Ok(user)
+            self.storage()
+                .save(&user)
+                .map_err(UserError::RepositoryError)?;
+            self.record_change(&user.email, RevisionOperation::Create, None, Some(&user))?;
+
+            Ok(user)
+        })
     }
 
     /// 既存のユーザー情報を更新します。
     ///
     /// # 引数
     /// * `email` - 更新するユーザーのメールアドレス
-    /// * `username` - 新しいユーザー名（3文字以上）
-    /// * `phone` - 新しい電話番号（10桁以上の数字）
+    /// * `username` - 新しいユーザー名（3文字以上、NFKC正規化して保存）
+    /// * `phone` - 新しい電話番号（国内形式または国番号付きの形式）
     /// * `age` - 新しい年齢（0-150の範囲）
     ///
     /// # 戻り値
@@ -147,8 +427,9 @@ impl<T: UserRepository> UserService<T> {
     ///
     /// # Errors
     /// 以下の場合にエラーを返します：
-    /// * `UserError::InvalidUsername` - ユーザー名が3文字未満の場合
-    /// * `UserError::InvalidPhone` - 電話番号が10桁未満の場合
+    /// * `UserError::InvalidEmail` - メールアドレスの形式が不正な場合
+    /// * `UserError::InvalidUsername` - ユーザー名の文字数が範囲外、予約語、または不可視文字を含む場合
+    /// * `UserError::InvalidPhone` - 電話番号の形式や桁数が不正な場合
     /// * `UserError::InvalidAge` - 年齢が150歳を超える場合
     /// * `UserError::UserNotFound` - 指定されたメールアドレスのユーザーが存在しない場合
     /// * `UserError::RepositoryError` - データの永続化に失敗した場合
@@ -159,30 +440,111 @@ impl<T: UserRepository> UserService<T> {
         phone: String,
         age: u32,
     ) -> Result<User, UserError> {
-        self.validate_username(&username)?;
-        self.validate_phone(&phone)?;
-        self.validate_age(age)?;
+        self.update_user_checked(email, username, phone, age, None)
+    }
 
-        // Check if user exists
-        if self.repository.find_by_email(&email)?.is_none() {
-            return Err(UserError::UserNotFound(format!(
-                "User with email {} not found",
-                email
-            )));
-        }
+    /// レコードのバージョンが想定どおりの場合だけ、既存のユーザー情報を更新します。
+    ///
+    /// 読み込んだ後に他の操作で変更されたレコードを上書きしないために使用します。
+    ///
+    /// # 引数
+    /// * `email` - 更新対象のユーザーのメールアドレス（変更不可）
+    /// * `username` - 新しいユーザー名
+    /// * `phone` - 新しい電話番号
+    /// * `age` - 新しい年齢
+    /// * `expected_version` - 想定するレコードのバージョン
+    ///
+    /// # 戻り値
+    /// * `Ok(User)` - 更新されたユーザー情報
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::Conflict` - レコードのバージョンが`expected_version`と異なる場合
+    /// * そのほか`update_user`と同じ場合
+    pub fn update_user_if_version(
+        &self,
+        email: String,
+        username: String,
+        phone: String,
+        age: u32,
+        expected_version: u64,
+    ) -> Result<User, UserError> {
+        self.update_user_checked(email, username, phone, age, Some(expected_version))
+    }
 
-        let user = User {
-            email,
-            username,
-            phone,
-            age,
-        };
+    /// 既存のユーザー情報を更新します。
+    ///
+    /// # 引数
+    /// * `email` - 更新対象のユーザーのメールアドレス
+    /// * `username` - 新しいユーザー名
+    /// * `phone` - 新しい電話番号
+    /// * `age` - 新しい年齢
+    /// * `expected_version` - 想定するレコードのバージョン（`None`の場合は確認しない）
+    ///
+    /// # 戻り値
+    /// * `Ok(User)` - 更新されたユーザー情報
+    ///
+    /// # エラー
+    /// * `update_user`・`update_user_if_version`と同じ場合
+    fn update_user_checked(
+        &self,
+        email: String,
+        username: String,
+        phone: String,
+        age: u32,
+        expected_version: Option<u64>,
+    ) -> Result<User, UserError> {
+        self.audited(RevisionOperation::Update, Some(&email), || {
+            let email = self.normalize_email(&email)?;
+            let username = self.normalize_username(&username)?;
+            let phone = self.normalize_phone(&phone)?;
+            self.validate_age(age)?;
 
-        self.repository
-            .save(&user)
-            .map_err(UserError::RepositoryError)?;
+            // Check if user exists
+            let Some(existing) = self
+                .find_existing(&email)?
+                .filter(|user| user.deleted_at.is_none())
+            else {
+                return Err(UserError::UserNotFound(format!(
+                    "User with email {} not found",
+                    email
+                )));
+            };
+            self.check_version(&existing, expected_version)?;
 
-        Ok(user)
+            // 別名で指定された場合は統合先のユーザーを更新する
+            let key = self
+                .normalize_email(&existing.email)
+                .unwrap_or_else(|_| existing.email.clone());
+            let user = User {
+                email: key,
+                username,
+                phone,
+                age,
+                updated_at: Some(Utc::now()),
+                version: existing.version + 1,
+                ..existing.clone()
+            };
+
+            // 正規化前のキーで保存されている既存レコードは正規化後のキーへ移し替える
+            if existing.email != user.email {
+                self.storage()
+                    .replace(&user, &existing.email)
+                    .map_err(UserError::RepositoryError)?;
+            } else {
+                self.storage()
+                    .save(&user)
+                    .map_err(UserError::RepositoryError)?;
+            }
+            self.record_change(
+                &user.email,
+                RevisionOperation::Update,
+                Some(&existing),
+                Some(&user),
+            )?;
+
+            Ok(user)
+        })
     }
 
     /// 指定されたメールアドレスのユーザー情報を取得します。
@@ -206,11 +568,13 @@ impl<T: UserRepository> UserService<T> {
     ///
     /// # Errors
     /// 以下の場合にエラーを返します：
+    /// * `UserError::InvalidEmail` - メールアドレスの形式が不正な場合
     /// * `UserError::UserNotFound` - 指定されたメールアドレスのユーザーが存在しない場合
     /// * `UserError::RepositoryError` - データの取得に失敗した場合
     pub fn get_user(&self, email: &str) -> Result<User, UserError> {
-        self.repository
-            .find_by_email(email)?
+        let email = self.normalize_email(email)?;
+        self.find_existing(&email)?
+            .filter(|user| user.deleted_at.is_none())
             .ok_or_else(|| UserError::UserNotFound(format!("User with email {} not found", email)))
     }
 
@@ -224,15 +588,117 @@ impl<T: UserRepository> UserService<T> {
     ///   全てのユーザー情報を取得します。
     ///
     /// # 戻り値
-    /// * `Ok(Vec<User>)` - 全てのユーザー情報
+    /// * `Ok(Vec<User>)` - ゴミ箱にあるユーザーを除く全てのユーザー情報
     ///
     /// # Errors
     /// 以下の場合にエラーを返します：
-    /// * `UserError::RepositoryError` - データの取得に失敗した場合
+    /// * `UserError::RepositoryError` - データの取得、または暗号化されたフィールドの復号に失敗した場合
     pub fn list_users(&self) -> Result<Vec<User>, UserError> {
-        self.repository
-            .find_all()
-            .map_err(UserError::RepositoryError)
+        let users = self.storage().find_all()?;
+        Ok(users
+            .into_iter()
+            .filter(|user| user.deleted_at.is_none())
+            .collect())
+    }
+
+    /// ゴミ箱にあるユーザーの一覧を取得します。
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<User>)` - 削除日時の古い順に並んだ、ゴミ箱にあるユーザー
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::RepositoryError` - データの取得、または暗号化されたフィールドの復号に失敗した場合
+    pub fn list_trashed_users(&self) -> Result<Vec<User>, UserError> {
+        let mut users: Vec<User> = self
+            .storage()
+            .find_all()?
+            .into_iter()
+            .filter(|user| user.deleted_at.is_some())
+            .collect();
+        users.sort_by_key(|user| user.deleted_at);
+        Ok(users)
+    }
+
+    /// ゴミ箱にあるユーザーを元に戻します。
+    ///
+    /// # 引数
+    /// * `email` - 元に戻すユーザーのメールアドレス
+    ///
+    /// # 戻り値
+    /// * `Ok(User)` - 元に戻したユーザー情報
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::InvalidEmail` - メールアドレスの形式が不正な場合
+    /// * `UserError::UserNotFound` - 指定されたメールアドレスのユーザーがゴミ箱にない場合
+    /// * `UserError::RepositoryError` - データの永続化に失敗した場合
+    pub fn restore_user(&self, email: &str) -> Result<User, UserError> {
+        self.audited(RevisionOperation::Restore, Some(email), || {
+            let email = self.normalize_email(email)?;
+            let Some(trashed) = self
+                .find_existing(&email)?
+                .filter(|user| user.deleted_at.is_some())
+            else {
+                return Err(UserError::UserNotFound(format!(
+                    "User with email {} not found in the trash",
+                    email
+                )));
+            };
+
+            let user = User {
+                deleted_at: None,
+                updated_at: Some(Utc::now()),
+                version: trashed.version + 1,
+                ..trashed.clone()
+            };
+            self.storage()
+                .save(&user)
+                .map_err(UserError::RepositoryError)?;
+            self.record_change(
+                &user.email,
+                RevisionOperation::Restore,
+                Some(&trashed),
+                Some(&user),
+            )?;
+            Ok(user)
+        })
+    }
+
+    /// ゴミ箱にあるユーザーのうち、削除から指定期間以上経過したものを完全に削除します。
+    ///
+    /// # 引数
+    /// * `older_than` - 完全に削除する対象とする、削除からの経過期間
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<User>)` - 完全に削除したユーザー
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::RepositoryError` - データの削除に失敗した場合
+    pub fn purge_trash(&self, older_than: TimeDelta) -> Result<Vec<User>, UserError> {
+        self.audited(RevisionOperation::Purge, None, || {
+            let cutoff = Utc::now() - older_than;
+            let purged: Vec<User> = self
+                .list_trashed_users()?
+                .into_iter()
+                .filter(|user| user.deleted_at.is_some_and(|at| at <= cutoff))
+                .collect();
+            // 途中で失敗して一部だけが削除された状態にならないよう、1回の書き込みで削除する
+            let operations: Vec<BatchOperation> = purged
+                .iter()
+                .map(|user| BatchOperation::Delete(user.email.clone()))
+                .collect();
+            if !operations.is_empty() {
+                self.storage()
+                    .apply_batch(&operations)
+                    .map_err(UserError::RepositoryError)?;
+            }
+            for user in &purged {
+                self.record_change(&user.email, RevisionOperation::Purge, Some(user), None)?;
+            }
+            Ok(purged)
+        })
     }
 
     /// 指定されたメールアドレスのユーザーを削除します。
@@ -251,171 +717,3560 @@ impl<T: UserRepository> UserService<T> {
     /// # 引数
     /// * `email` - 削除するユーザーのメールアドレス
     ///
+    /// ユーザーはすぐには消去されず、削除日時を記録してゴミ箱に移動します。
+    /// ゴミ箱にあるユーザーは`restore_user`で元に戻すか、`purge_trash`で完全に削除できます。
+    ///
     /// # 戻り値
     /// * `Ok(())` - 削除成功
     ///
     /// # Errors
     /// 以下の場合にエラーを返します：
+    /// * `UserError::InvalidEmail` - メールアドレスの形式が不正な場合
     /// * `UserError::UserNotFound` - 指定されたメールアドレスのユーザーが存在しない場合
+    /// * `UserError::InvalidOperation` - 統合によって記録された別名が指定された場合
     /// * `UserError::RepositoryError` - データの削除に失敗した場合
     pub fn delete_user(&self, email: &str) -> Result<(), UserError> {
-        if !self
-            .repository
-            .delete(email)
-            .map_err(UserError::RepositoryError)?
-        {
-            return Err(UserError::UserNotFound(format!(
-                "User with email {} not found",
-                email
-            )));
-        }
-        Ok(())
+        self.delete_user_checked(email, None)
     }
 
-    /// メールアドレスの形式を検証します。
+    /// レコードのバージョンが想定どおりの場合だけ、ユーザーを削除します。
     ///
     /// # 引数
-    /// * `email` - 検証するメールアドレス
+    /// * `email` - 削除するユーザーのメールアドレス
+    /// * `expected_version` - 想定するレコードのバージョン
     ///
     /// # 戻り値
-    /// * `Ok(())` - 検証に成功した場合
+    /// * `Ok(())` - 削除成功
     ///
-    /// # エラー
-    /// * `UserError::InvalidEmail` - メールアドレスの形式が不正な場合
-    fn validate_email(&self, email: &str) -> Result<(), UserError> {
-        let email_regex = Regex::new(r"^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}$").unwrap();
-        if !email_regex.is_match(email) {
-            return Err(UserError::InvalidEmail(format!(
-                "Invalid email format: {}",
-                email
-            )));
-        }
-        Ok(())
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::Conflict` - レコードのバージョンが`expected_version`と異なる場合
+    /// * そのほか`delete_user`と同じ場合
+    pub fn delete_user_if_version(
+        &self,
+        email: &str,
+        expected_version: u64,
+    ) -> Result<(), UserError> {
+        self.delete_user_checked(email, Some(expected_version))
     }
 
-    /// ユーザー名の長さを検証します。
+    /// ユーザーをゴミ箱に移動します。
     ///
     /// # 引数
-    /// * `username` - 検証するユーザー名
+    /// * `email` - 削除するユーザーのメールアドレス
+    /// * `expected_version` - 想定するレコードのバージョン（`None`の場合は確認しない）
     ///
     /// # 戻り値
-    /// * `Ok(())` - 検証に成功した場合
+    /// * `Ok(())` - 削除成功
     ///
     /// # エラー
-    /// * `UserError::InvalidUsername` - ユーザー名が3文字未満の場合
-    fn validate_username(&self, username: &str) -> Result<(), UserError> {
-        if username.trim().is_empty() || username.len() < 3 {
-            return Err(UserError::InvalidUsername(
-                "Username must be at least 3 characters long".to_string(),
-            ));
-        }
-        Ok(())
+    /// * `delete_user`・`delete_user_if_version`と同じ場合
+    fn delete_user_checked(
+        &self,
+        email: &str,
+        expected_version: Option<u64>,
+    ) -> Result<(), UserError> {
+        self.audited(RevisionOperation::Delete, Some(email), || {
+            let user = self.get_user(email)?;
+            // 別名で指定された場合に統合先のユーザーごと削除しないよう、拒否する
+            if self.audit_key(email) != self.audit_key(&user.email) {
+                return Err(UserError::InvalidOperation(format!(
+                    "{} is an alias of {}; delete the user by its primary email",
+                    email.trim(),
+                    user.email
+                )));
+            }
+            self.check_version(&user, expected_version)?;
+            let trashed = User {
+                deleted_at: Some(Utc::now()),
+                version: user.version + 1,
+                ..user.clone()
+            };
+            self.storage()
+                .save(&trashed)
+                .map_err(UserError::RepositoryError)?;
+            self.record_change(
+                &trashed.email,
+                RevisionOperation::Delete,
+                Some(&user),
+                Some(&trashed),
+            )?;
+            Ok(())
+        })
     }
 
-    /// 電話番号の形式を検証します。
+    /// 2人のユーザーを1つのレコードに統合します。
+    ///
+    /// フィールドごとの戦略に従って値を選び、統合後のユーザーの保存と
+    /// 取り込まれる側のユーザーの削除を1回の書き込みで行います。
+    /// 取り込まれる側のメールアドレスは別名として記録され、以後このアドレスでの
+    /// 参照は統合後のユーザーに解決されます。
     ///
     /// # 引数
-    /// * `phone` - 検証する電話番号
+    /// * `keep_email` - 残す側のユーザーのメールアドレス
+    /// * `drop_email` - 取り込まれて削除される側のユーザーのメールアドレス
+    /// * `options` - フィールドごとの統合戦略
+    /// * `ask` - `MergeStrategy::Ask`で値が異なる場合に呼び出され、採用する値を返す関数
     ///
     /// # 戻り値
-    /// * `Ok(())` - 検証に成功した場合
+    /// * `Ok(User)` - 統合後のユーザー
     ///
-    /// # エラー
-    /// * `UserError::InvalidPhone` - 電話番号が10桁未満の場合
-    fn validate_phone(&self, phone: &str) -> Result<(), UserError> {
-        let phone_regex = Regex::new(r"^\d{10,}$").unwrap();
-        if !phone_regex.is_match(phone) {
-            return Err(UserError::InvalidPhone(
-                "Phone number must be at least 10 digits".to_string(),
-            ));
-        }
-        Ok(())
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::InvalidEmail` - メールアドレスの形式が不正な場合
+    /// * `UserError::UserNotFound` - どちらかのユーザーが存在しない場合
+    /// * `UserError::InvalidOperation` - 2つのメールアドレスが同じユーザーを指している場合
+    /// * `UserError::RepositoryError` - データの永続化に失敗した場合
+    pub fn merge_users(
+        &self,
+        keep_email: &str,
+        drop_email: &str,
+        options: &MergeOptions,
+        ask: &mut dyn FnMut(MergeField, &str, &str) -> MergeChoice,
+    ) -> Result<User, UserError> {
+        self.audited(RevisionOperation::Merge, Some(keep_email), || {
+            let keep = self.get_user(keep_email)?;
+            let drop = self.get_user(drop_email)?;
+            if keep.email == drop.email {
+                return Err(UserError::InvalidOperation(format!(
+                    "{} and {} refer to the same user",
+                    keep_email, drop_email
+                )));
+            }
+
+            let mut merged = merge_records(&keep, &drop, options, ask);
+            merged.updated_at = Some(Utc::now());
+            merged.version = keep.version + 1;
+            self.storage()
+                .replace(&merged, &drop.email)
+                .map_err(UserError::RepositoryError)?;
+            self.record_change(
+                &merged.email,
+                RevisionOperation::Merge,
+                Some(&keep),
+                Some(&merged),
+            )?;
+            self.record_change(&drop.email, RevisionOperation::Merge, Some(&drop), None)?;
+            Ok(merged)
+        })
     }
 
-    /// 年齢の範囲を検証します。
+    /// ユーザーの変更履歴を取得します。
     ///
     /// # 引数
-    /// * `age` - 検証する年齢
+    /// * `email` - ユーザーのメールアドレス（統合によって記録された別名も可）
     ///
     /// # 戻り値
-    /// * `Ok(())` - 検証に成功した場合
+    /// * `Ok(Vec<Revision>)` - リビジョン番号の昇順に並んだ変更履歴
     ///
-    /// # エラー
-    /// * `UserError::InvalidAge` - 年齢が150歳を超える場合
-    fn validate_age(&self, age: u32) -> Result<(), UserError> {
-        if age > 150 {
-            return Err(UserError::InvalidAge(
-                "Age must be between 0 and 150".to_string(),
-            ));
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::InvalidEmail` - メールアドレスの形式が不正な場合
+    /// * `UserError::InvalidOperation` - 変更履歴の記録が有効になっていない場合
+    /// * `UserError::UserNotFound` - 指定されたユーザーの変更履歴が存在しない場合
+    /// * `UserError::RepositoryError` - データの取得に失敗した場合
+    pub fn user_history(&self, email: &str) -> Result<Vec<Revision>, UserError> {
+        let history = self.history()?;
+        let key = self.history_key(email)?;
+        let revisions = history
+            .find_by_email(&key)?
+            .into_iter()
+            .map(|revision| self.open_revision(revision))
+            .collect::<Result<Vec<_>, _>>()?;
+        if revisions.is_empty() {
+            return Err(UserError::UserNotFound(format!(
+                "No history recorded for {}",
+                key
+            )));
         }
-        Ok(())
+        Ok(revisions)
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::repositories::user_repository::MockUserRepository;
 
-    fn create_mock_repository() -> MockUserRepository {
-        MockUserRepository::new()
+    /// 指定された日時におけるユーザーの状態を取得します。
+    ///
+    /// # 引数
+    /// * `email` - ユーザーのメールアドレス（統合によって記録された別名も可）
+    /// * `at` - 状態を取得する日時
+    ///
+    /// # 戻り値
+    /// * `Ok(User)` - 指定された日時以前で最後のリビジョンが記録した状態
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::InvalidEmail` - メールアドレスの形式が不正な場合
+    /// * `UserError::InvalidOperation` - 変更履歴の記録が有効になっていない場合
+    /// * `UserError::UserNotFound` - 指定された日時にユーザーが存在しなかった場合
+    /// * `UserError::RepositoryError` - データの取得に失敗した場合
+    pub fn get_user_at(&self, email: &str, at: DateTime<Utc>) -> Result<User, UserError> {
+        let not_found = || {
+            UserError::UserNotFound(format!("User with email {} did not exist at {}", email, at))
+        };
+        self.user_history(email)
+            .or_else(|e| match e {
+                UserError::UserNotFound(_) => Ok(Vec::new()),
+                e => Err(e),
+            })?
+            .into_iter()
+            .rev()
+            .find(|revision| revision.timestamp <= at)
+            .and_then(|revision| revision.user)
+            .filter(|user| user.deleted_at.is_none())
+            .ok_or_else(not_found)
     }
 
-    #[test]
-    fn test_create_user_success() {
-        let mut mock_repo = create_mock_repository();
-        mock_repo.expect_find_by_email().return_once(|_| Ok(None));
-        mock_repo.expect_save().return_once(|_| Ok(()));
+    /// ユーザー名・電話番号・年齢を過去のリビジョンの値に戻します。
+    ///
+    /// 巻き戻しも新しいリビジョンとして記録されるため、巻き戻し自体を
+    /// さらに巻き戻すこともできます。
+    ///
+    /// # 引数
+    /// * `email` - ユーザーのメールアドレス（統合によって記録された別名も可）
+    /// * `revision` - 戻す先のリビジョン番号
+    ///
+    /// # 戻り値
+    /// * `Ok(User)` - 巻き戻した後のユーザー情報
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::InvalidEmail` - メールアドレスの形式が不正な場合
+    /// * `UserError::InvalidOperation` - 変更履歴の記録が有効になっていない場合、
+    ///   または指定されたリビジョンにユーザーの状態が記録されていない場合
+    /// * `UserError::UserNotFound` - ユーザーまたは指定されたリビジョンが存在しない場合
+    /// * `UserError::RepositoryError` - データの永続化に失敗した場合
+    pub fn revert_user(&self, email: &str, revision: u64) -> Result<User, UserError> {
+        self.audited(RevisionOperation::Revert, Some(email), || {
+            let revisions = self.user_history(email)?;
+            let target = revisions
+                .iter()
+                .find(|r| r.revision == revision)
+                .ok_or_else(|| {
+                    UserError::UserNotFound(format!("Revision {} of {} not found", revision, email))
+                })?;
+            let Some(snapshot) = &target.user else {
+                return Err(UserError::InvalidOperation(format!(
+                    "Revision {} of {} has no user state to revert to",
+                    revision, email
+                )));
+            };
+            let current = self.get_user(email)?;
 
-        let service = UserService::new(mock_repo);
-        let result = service.create_user(
-            "test@example.com".to_string(),
+            let user = User {
+                username: snapshot.username.clone(),
+                phone: snapshot.phone.clone(),
+                age: snapshot.age,
+                updated_at: Some(Utc::now()),
+                version: current.version + 1,
+                ..current.clone()
+            };
+            self.storage()
+                .save(&user)
+                .map_err(UserError::RepositoryError)?;
+            self.record_change(
+                &user.email,
+                RevisionOperation::Revert,
+                Some(&current),
+                Some(&user),
+            )?;
+            Ok(user)
+        })
+    }
+
+    /// 直前の操作を新しいものから順に取り消します。
+    ///
+    /// 操作の後に対象のレコードが変更されている場合は、他の変更を上書きしないよう
+    /// その操作の取り消しを拒否し、それ以上は取り消しません。
+    /// 取り消した操作は`redo`でやり直せます。
+    ///
+    /// # 引数
+    /// * `count` - 取り消す操作の数
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<JournalEntry>)` - 取り消した操作（取り消した順）
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::InvalidOperation` - 操作ジャーナルの記録が有効になっていない場合、
+    ///   取り消せる操作がない場合、または操作の後に対象のレコードが変更されている場合
+    /// * `UserError::RepositoryError` - データの永続化に失敗した場合
+    pub fn undo(&self, count: usize) -> Result<Vec<JournalEntry>, UserError> {
+        self.audited(RevisionOperation::Undo, None, || {
+            self.replay_journal(RevisionOperation::Undo, count)
+        })
+    }
+
+    /// 取り消した操作を、最後に取り消したものから順にやり直します。
+    ///
+    /// 取り消しの後に対象のレコードが変更されている場合は、他の変更を上書きしないよう
+    /// その操作のやり直しを拒否し、それ以上はやり直しません。
+    /// 取り消しの後に新しい操作を行うと、それまでに取り消した操作はやり直せなくなります。
+    ///
+    /// # 引数
+    /// * `count` - やり直す操作の数
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<JournalEntry>)` - やり直した操作（やり直した順）
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::InvalidOperation` - 操作ジャーナルの記録が有効になっていない場合、
+    ///   やり直せる操作がない場合、または取り消しの後に対象のレコードが変更されている場合
+    /// * `UserError::RepositoryError` - データの永続化に失敗した場合
+    pub fn redo(&self, count: usize) -> Result<Vec<JournalEntry>, UserError> {
+        self.audited(RevisionOperation::Redo, None, || {
+            self.replay_journal(RevisionOperation::Redo, count)
+        })
+    }
+
+    /// 書き込みを行わずに操作を実行し、行われるはずだった変更を返します。
+    ///
+    /// 操作は現在のデータを読み込むサービス上で、入力値の検証や既存のユーザーとの
+    /// 競合の確認を含めて通常どおり実行されますが、書き込みはメモリ上にのみ行われます。
+    /// 変更履歴・監査ログ・操作ジャーナルにも記録されません。
+    ///
+    /// # 引数
+    /// * `action` - 書き込みを行わないサービスを受け取って実行する操作
+    ///
+    /// # 戻り値
+    /// * `Ok((R, Vec<JournalChange>))` - 操作の結果と、メールアドレス順に並んだレコードごとの変更
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * 操作が返したエラー
+    /// * `UserError::RepositoryError` - データの取得に失敗した場合
+    ///
+    /// # Examples
+    /// ```
+    /// use rust_learn::repositories::user_repository::UserRepositoryImpl;
+    /// use rust_learn::services::user_service::UserService;
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let path = dir.path().join("userdata.json");
+    /// let service = UserService::new(UserRepositoryImpl::with_file_path(path.to_str().unwrap()));
+    /// let (user, changes) = service
+    ///     .preview(|service| {
+    ///         service.create_user(
+    ///             "test@example.com".to_string(),
+    ///             "testuser".to_string(),
+    ///             "09012345678".to_string(),
+    ///             25,
+    ///         )
+    ///     })
+    ///     .unwrap();
+    ///
+    /// assert_eq!(changes[0].after.as_ref(), Some(&user));
+    /// assert!(!path.exists());
+    /// ```
+    pub fn preview<R>(
+        &self,
+        action: impl FnOnce(&UserService<Box<dyn UserRepository + '_>>) -> Result<R, UserError>,
+    ) -> Result<(R, Vec<JournalChange>), UserError> {
+        let repository = DryRunUserRepository::new(self.storage());
+        let result = {
+            let service: UserService<Box<dyn UserRepository + '_>> =
+                UserService::with_config(Box::new(&repository), self.config.clone());
+            action(&service)?
+        };
+        Ok((result, repository.planned_changes()?))
+    }
+
+    /// 監査ログから条件に一致する記録を取得します。
+    ///
+    /// # 引数
+    /// * `query` - 検索条件
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<AuditEntry>)` - 記録された順に並んだ、条件に一致する記録
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::InvalidOperation` - 監査ログの記録が有効になっていない場合
+    /// * `UserError::RepositoryError` - データの取得に失敗した場合
+    pub fn audit_log(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>, UserError> {
+        let audit = self
+            .audit
+            .as_deref()
+            .ok_or_else(|| UserError::InvalidOperation("Audit log is not enabled".to_string()))?;
+        let email = query.email.as_deref().map(|email| self.audit_key(email));
+        audit
+            .find_all()?
+            .into_iter()
+            .filter(|entry| {
+                email.as_ref().is_none_or(|email| {
+                    entry.email.as_deref().map(|e| self.audit_key(e)).as_ref() == Some(email)
+                })
+            })
+            .filter(|entry| {
+                query
+                    .actor
+                    .as_ref()
+                    .is_none_or(|actor| entry.actor == *actor)
+            })
+            .filter(|entry| query.since.is_none_or(|since| entry.timestamp >= since))
+            .filter(|entry| query.until.is_none_or(|until| entry.timestamp <= until))
+            .map(|entry| self.open_audit_entry(entry))
+            .collect()
+    }
+
+    /// 監査ログのハッシュチェーンとHMACを検証します。
+    ///
+    /// # 戻り値
+    /// * `Ok(AuditVerification)` - 検証結果（チェーンの途切れは`broken`に設定されます）
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::InvalidOperation` - 監査ログの記録が有効になっていない場合
+    /// * `UserError::RepositoryError` - データの取得に失敗した場合
+    pub fn verify_audit_log(&self) -> Result<AuditVerification, UserError> {
+        Ok(self.audit()?.verify()?)
+    }
+
+    /// ユーザー名とメールアドレスからユーザーを検索します。
+    ///
+    /// 全角・半角、大文字・小文字、カタカナ・ひらがなの違いを無視して照合し、
+    /// 一致の度合いが高い順に並べて返します。
+    ///
+    /// # 引数
+    /// * `term` - 検索語
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<SearchResult>)` - スコアの高い順に並んだ検索結果（検索語が空の場合は空）
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::RepositoryError` - データの取得に失敗した場合
+    pub fn search_users(&self, term: &str) -> Result<Vec<SearchResult>, UserError> {
+        let term = fold_for_search(term);
+        let mut results: Vec<SearchResult> = self
+            .list_users()?
+            .iter()
+            .filter_map(|user| score_user(user, &term))
+            .collect();
+        results.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| a.user.username.cmp(&b.user.username))
+                .then_with(|| a.user.email.cmp(&b.user.email))
+        });
+        Ok(results)
+    }
+
+    /// 同一人物が別のメールアドレスで登録されている可能性があるユーザーを検出します。
+    ///
+    /// ユーザー名の編集距離、正規化した電話番号の一致、メールアドレスのローカル部の
+    /// 類似度から組み合わせごとにスコアを計算し、しきい値以上の組み合わせで
+    /// つながったユーザーをまとめて返します。
+    ///
+    /// # 引数
+    /// * `threshold` - 重複候補とみなすスコアの下限（0.0〜1.0）
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<DuplicateCluster>)` - 最高スコアの高い順に並んだ重複候補のまとまり
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::InvalidOperation` - 暗号化されたフィールドを伏せ字で読み込むサービスの場合
+    /// * `UserError::RepositoryError` - データの取得、または暗号化されたフィールドの復号に失敗した場合
+    pub fn find_probable_duplicates(
+        &self,
+        threshold: f64,
+    ) -> Result<Vec<DuplicateCluster>, UserError> {
+        // 伏せ字は全員で同じ値になるため、比較すると全てのユーザーが重複候補になってしまう
+        if self.redacted {
+            return Err(UserError::InvalidOperation(
+                "Cannot detect duplicates from redacted fields; set USER_FIELD_KEY_FILE"
+                    .to_string(),
+            ));
+        }
+        let mut users = self.list_users()?;
+        users.sort_by(|a, b| a.email.cmp(&b.email));
+        Ok(find_duplicate_clusters(&users, threshold, |user| {
+            self.normalize_phone(&user.phone)
+                .unwrap_or_else(|_| user.phone.clone())
+        }))
+    }
+
+    /// 正規化後のメールアドレスが重複している既存レコードを検出します。
+    ///
+    /// 正規化が導入される前に登録されたデータには、`John@Example.com`と
+    /// `john@example.com`のように同一人物を指すレコードが別々に存在する場合があります。
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<(String, Vec<User>)>)` - 正規化後のメールアドレスと、それに該当する複数のユーザー
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::RepositoryError` - データの取得に失敗した場合
+    pub fn find_email_duplicates(&self) -> Result<Vec<(String, Vec<User>)>, UserError> {
+        let mut groups: BTreeMap<String, Vec<User>> = BTreeMap::new();
+        for user in self.storage().find_all()? {
+            let key = self
+                .normalize_email(&user.email)
+                .unwrap_or_else(|_| user.email.clone());
+            groups.entry(key).or_default().push(user);
+        }
+
+        Ok(groups
+            .into_iter()
+            .filter(|(_, users)| users.len() > 1)
+            .map(|(email, mut users)| {
+                users.sort_by(|a, b| a.email.cmp(&b.email));
+                (email, users)
+            })
+            .collect())
+    }
+
+    /// 保存されている全てのレコードを現在の規則に照らして検査し、必要に応じて修正します。
+    ///
+    /// キーとメールアドレスの不一致、規則に違反する値、正規化されていない値、
+    /// 正規化後のメールアドレス・電話番号の重複を検出します（ゴミ箱にあるユーザーも含みます）。
+    /// `fix`が`true`の場合は、安全に修正できるもの（正規化されていない値と、
+    /// 移動先のキーが空いている場合のキーの不一致）を1回の書き込みで修正します。
+    /// 規則に違反する値と重複は修正せず、報告のみ行います。
+    ///
+    /// # 引数
+    /// * `fix` - 安全に修正できる問題を修正するかどうか
+    ///
+    /// # 戻り値
+    /// * `Ok(IntegrityReport)` - 検査したレコードの件数と見つかった問題
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::RepositoryError` - データの取得・保存に失敗した場合
+    pub fn check_integrity(&self, fix: bool) -> Result<IntegrityReport, UserError> {
+        if !fix {
+            return self.inspect_integrity(false);
+        }
+        self.audited(RevisionOperation::Fix, None, || {
+            self.inspect_integrity(true)
+        })
+    }
+
+    /// 全てのユーザーを書き直し、フィールドの暗号化の設定を保存されている値に反映します。
+    ///
+    /// 暗号化するフィールドに指定された値は暗号化し、指定から外された値は復号して保存します。
+    /// 値そのものは変わらないため、変更履歴・監査ログ・操作ジャーナルには記録しません。
+    /// キーとメールアドレスが一致しないレコードは書き直しません（`check --fix`で修正してください）。
+    ///
+    /// # 戻り値
+    /// * `Ok(usize)` - 書き直したユーザーの件数
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::InvalidOperation` - フィールドの暗号化の鍵が設定されていない場合
+    /// * `UserError::RepositoryError` - データの取得・保存、または復号に失敗した場合
+    pub fn rewrite_fields(&self) -> Result<usize, UserError> {
+        if self.field_cipher.is_none() {
+            return Err(UserError::InvalidOperation(
+                "Field encryption is not configured; set USER_FIELD_KEY_FILE".to_string(),
+            ));
+        }
+        let operations: Vec<BatchOperation> = self
+            .storage()
+            .find_all_entries()?
+            .into_iter()
+            .filter(|(key, user)| *key == user.email)
+            .map(|(_, user)| BatchOperation::Put(user))
+            .collect();
+        self.storage().apply_batch(&operations)?;
+        Ok(operations.len())
+    }
+
+    /// 開示請求・消去請求の対象として扱うメールアドレスを求めます。
+    ///
+    /// 指定されたメールアドレスを正規化したものに加え、該当するユーザー（別名で
+    /// 参照された場合を含む）のメールアドレスと別名を対象とします。
+    ///
+    /// # 引数
+    /// * `email` - 請求を行ったユーザーのメールアドレス
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<String>)` - 重複を除いて並べた、正規化後のメールアドレス
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::InvalidEmail` - メールアドレスの形式が不正な場合
+    /// * `UserError::RepositoryError` - データの取得に失敗した場合
+    pub fn subject_emails(&self, email: &str) -> Result<Vec<String>, UserError> {
+        let email = self.normalize_email(email)?;
+        let mut emails = vec![email.clone()];
+        if let Some(user) = self.find_existing(&email)? {
+            emails.extend(
+                std::iter::once(&user.email)
+                    .chain(&user.aliases)
+                    .map(|email| self.audit_key(email)),
+            );
+        }
+        emails.sort();
+        emails.dedup();
+        Ok(emails)
+    }
+
+    /// 指定されたユーザーについて保存されている全てのデータを集めます。
+    ///
+    /// 現在のレコード（ゴミ箱にあるものを含む）、変更履歴、監査ログ、操作ジャーナルを
+    /// 対象とします。記録が有効になっていないものは空になります。
+    /// バックアップと隔離ファイルはサービスの管理外のため、呼び出し元で追加します。
+    ///
+    /// # 引数
+    /// * `email` - 請求を行ったユーザーのメールアドレス
+    ///
+    /// # 戻り値
+    /// * `Ok(SubjectExport)` - 集めたデータ（`backups`と`quarantine`は空）
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::InvalidEmail` - メールアドレスの形式が不正な場合
+    /// * `UserError::RepositoryError` - データの取得、または復号に失敗した場合
+    pub fn export_subject(&self, email: &str) -> Result<SubjectExport, UserError> {
+        let emails = self.subject_emails(email)?;
+        let user = self.find_existing(&self.normalize_email(email)?)?;
+        let mut history = Vec::new();
+        if let Some(repository) = &self.history {
+            for email in &emails {
+                for revision in repository.find_by_email(email)? {
+                    history.push(self.open_revision(revision)?);
+                }
+            }
+        }
+        let audit = match &self.audit {
+            Some(audit) => audit
+                .find_all()?
+                .into_iter()
+                .filter(|entry| self.audit_mentions(entry, &emails))
+                .map(|entry| self.open_audit_entry(entry))
+                .collect::<Result<_, _>>()?,
+            None => Vec::new(),
+        };
+        let journal = match &self.journal {
+            Some(journal) => {
+                let journal = journal.load()?;
+                journal
+                    .undo
+                    .into_iter()
+                    .chain(journal.redo)
+                    .filter(|entry| self.journal_mentions(entry, &emails))
+                    .map(|entry| self.open_journal_entry(entry))
+                    .collect::<Result<_, _>>()?
+            }
+            None => Vec::new(),
+        };
+        Ok(SubjectExport {
+            exported_at: Utc::now(),
+            emails,
+            user,
+            history,
+            audit,
+            journal,
+            backups: Vec::new(),
+            quarantine: Vec::new(),
+        })
+    }
+
+    /// 消去請求の受領証の`subject`に記録する、メールアドレスのHMACを求めます。
+    ///
+    /// # 引数
+    /// * `email` - ユーザーのメールアドレス（正規化して計算する）
+    /// * `signing_key` - 受領証の署名に使用する鍵
+    ///
+    /// # 戻り値
+    /// * `Ok(String)` - 受領証の`subject`と比較できる値
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::InvalidEmail` - メールアドレスの形式が不正な場合
+    /// * `UserError::InvalidOperation` - 鍵をHMACの鍵として使用できない場合
+    pub fn erasure_subject(&self, email: &str, signing_key: &[u8]) -> Result<String, UserError> {
+        ErasureReceipt::subject_of(&self.normalize_email(email)?, signing_key)
+            .map_err(UserError::InvalidOperation)
+    }
+
+    /// 指定されたユーザーのデータを消去します。
+    ///
+    /// レコードをゴミ箱を経由せずに削除し、変更履歴のリビジョンと、対象のユーザーを含む
+    /// 操作ジャーナルの操作を削除します。監査ログは操作の記録を残したまま、
+    /// ユーザーを特定できる値だけを消去します（ハッシュチェーンは計算し直され、
+    /// 前後の最後の記録のハッシュを受領証に記録します）。
+    /// 消去そのものは、値が残らないよう変更履歴・監査ログ・操作ジャーナルに記録しません。
+    /// バックアップと隔離ファイルはサービスの管理外のため、呼び出し元で消去します。
+    ///
+    /// # 引数
+    /// * `email` - 請求を行ったユーザーのメールアドレス
+    /// * `signing_key` - 受領証の署名に使用する鍵（受領証の`subject`の計算にも使用する）
+    ///
+    /// # 戻り値
+    /// * `Ok(ErasureReceipt)` - 消去した内容を記録した署名前の受領証
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::InvalidEmail` - メールアドレスの形式が不正な場合
+    /// * `UserError::InvalidOperation` - 監査ログのハッシュチェーンが途切れている場合、
+    ///   または鍵をHMACの鍵として使用できない場合
+    /// * `UserError::RepositoryError` - データの取得・保存に失敗した場合
+    pub fn erase_subject(
+        &self,
+        email: &str,
+        signing_key: &[u8],
+    ) -> Result<ErasureReceipt, UserError> {
+        let email = self.normalize_email(email)?;
+        let emails = self.subject_emails(&email)?;
+        let mut receipt = ErasureReceipt {
+            subject: self.erasure_subject(&email, signing_key)?,
+            erased_at: Utc::now(),
+            actor: self.config.actor.clone(),
+            ..Default::default()
+        };
+
+        // 途切れたチェーンを計算し直すと改ざんを隠してしまうため、先に検証する
+        if let Some(audit) = &self.audit {
+            let verification = audit.verify()?;
+            if let Some(broken) = verification.broken {
+                return Err(UserError::InvalidOperation(format!(
+                    "Audit log is broken at line {}: {}; resolve it before erasing",
+                    broken.line, broken.reason
+                )));
+            }
+            receipt.audit_head_before = verification.last_hash;
+        }
+
+        if let Some(user) = self.find_existing(&email)? {
+            let keys: Vec<BatchOperation> = self
+                .storage()
+                .find_all_entries()?
+                .into_iter()
+                .filter(|(_, stored)| stored.email == user.email)
+                .map(|(key, _)| BatchOperation::Delete(key))
+                .collect();
+            self.storage().apply_batch(&keys)?;
+            receipt.record_deleted = true;
+        }
+        if let Some(history) = &self.history {
+            for email in &emails {
+                receipt.history_revisions += history.erase(email)?;
+            }
+        }
+        if let Some(audit) = &self.audit {
+            let positions: Vec<usize> = audit
+                .find_all()?
+                .iter()
+                .enumerate()
+                .filter(|(_, entry)| self.audit_mentions(entry, &emails))
+                .map(|(index, _)| index)
+                .collect();
+            receipt.audit_entries = audit.erase(&positions)?;
+            receipt.audit_head_after = audit.verify()?.last_hash;
+        }
+        if let Some(journal) = &self.journal {
+            let mut current = journal.load()?;
+            let count = current.undo.len() + current.redo.len();
+            current
+                .undo
+                .retain(|entry| !self.journal_mentions(entry, &emails));
+            current
+                .redo
+                .retain(|entry| !self.journal_mentions(entry, &emails));
+            receipt.journal_entries = count - current.undo.len() - current.redo.len();
+            if receipt.journal_entries > 0 {
+                journal.store(&current)?;
+            }
+        }
+        Ok(receipt)
+    }
+
+    /// 全てのユーザーのメールアドレス・別名・ユーザー名・電話番号を仮名に置き換えて、別のリポジトリに書き込みます。
+    ///
+    /// 置き換えは`Anonymizer::anonymize_users`が、このサービスの正規化の規則に従って行います。
+    ///
+    /// # 引数
+    /// * `anonymizer` - 仮名を作るインスタンス
+    /// * `target` - 仮名に置き換えたユーザーを書き込むリポジトリ
+    ///
+    /// # 戻り値
+    /// * `Ok(usize)` - 書き込んだユーザーの件数
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::InvalidOperation` - 条件を満たす仮名を作れなかった場合
+    /// * `UserError::RepositoryError` - データの取得・書き込み、または復号に失敗した場合
+    pub fn anonymize_into(
+        &self,
+        anonymizer: &Anonymizer,
+        target: &impl UserRepository,
+    ) -> Result<usize, UserError> {
+        let mut users = self.storage().find_all()?;
+        users.sort_by(|a, b| a.email.cmp(&b.email));
+
+        let users = anonymizer
+            .anonymize_users(users, self)
+            .map_err(UserError::InvalidOperation)?;
+        let operations: Vec<BatchOperation> = users.into_iter().map(BatchOperation::Put).collect();
+        target.apply_batch(&operations)?;
+        Ok(operations.len())
+    }
+
+    /// 複数のユーザーを検証した上で、1回の書き込みでまとめて作成します。
+    ///
+    /// 各ユーザーは`create_user`と同じ規則で正規化・検証し、既存のユーザー（ゴミ箱にあるものや
+    /// 別名を含む）や一覧の中でメールアドレスが重複する場合は、何も書き込まずに失敗します。
+    /// 作成は`seed`の操作として、操作ジャーナルには1件の操作（`undo`で取り消せる）、
+    /// 監査ログには`details`を内容とする1件の記録として残します。大量の生成データの投入を
+    /// 想定しているため、ユーザーごとの変更履歴と監査ログの記録は行いません。
+    ///
+    /// # 引数
+    /// * `users` - 作成するユーザー（メールアドレス・ユーザー名・電話番号・年齢を使用）
+    /// * `details` - 監査ログに記録する内容（生成に使用したシードなど）
+    ///
+    /// # 戻り値
+    /// * `Ok(usize)` - 作成したユーザーの件数
+    ///
+    /// # Errors
+    /// 以下の場合にエラーを返します：
+    /// * `UserError::InvalidEmail` - メールアドレスの形式が不正な場合
+    /// * `UserError::InvalidUsername` - ユーザー名が不正な場合
+    /// * `UserError::InvalidPhone` - 電話番号の形式や桁数が不正な場合
+    /// * `UserError::InvalidAge` - 年齢が範囲外の場合
+    /// * `UserError::UserAlreadyExists` - 同じメールアドレスのユーザーが既に存在する、または一覧の中で重複する場合
+    /// * `UserError::RepositoryError` - データの取得・書き込みに失敗した場合
+    pub fn insert_users(&self, users: &[User], details: &str) -> Result<usize, UserError> {
+        self.audited_as(RevisionOperation::Seed, None, Some(details), || {
+            let mut emails: HashSet<String> = self
+                .storage()
+                .find_all()?
+                .iter()
+                .flat_map(|user| std::iter::once(&user.email).chain(&user.aliases))
+                .map(|email| self.audit_key(email))
+                .collect();
+            let now = Utc::now();
+            let mut created = Vec::with_capacity(users.len());
+            for user in users {
+                let email = self.normalize_email(&user.email)?;
+                let username = self.normalize_username(&user.username)?;
+                let phone = self.normalize_phone(&user.phone)?;
+                self.validate_age(user.age)?;
+                if !emails.insert(email.clone()) {
+                    return Err(UserError::UserAlreadyExists(format!(
+                        "User with email {} already exists",
+                        email
+                    )));
+                }
+                created.push(User {
+                    email,
+                    username,
+                    phone,
+                    age: user.age,
+                    updated_at: Some(now),
+                    version: 1,
+                    ..Default::default()
+                });
+            }
+            let operations: Vec<BatchOperation> =
+                created.iter().cloned().map(BatchOperation::Put).collect();
+            self.storage().apply_batch(&operations)?;
+            for user in &created {
+                self.record_change(&user.email, RevisionOperation::Seed, None, Some(user))?;
+            }
+            Ok(created.len())
+        })
+    }
+
+    /// 保存されている電話番号を表示用の形式に変換します。
+    ///
+    /// 既定の国の番号は国内形式（例: `090-1234-5678`）で、それ以外はE.164形式で返します。
+    ///
+    /// # 引数
+    /// * `phone` - 保存されている電話番号
+    ///
+    /// # 戻り値
+    /// * `String` - 表示用の電話番号
+    pub fn display_phone(&self, phone: &str) -> String {
+        format_phone(phone, &self.config.default_country)
+    }
+
+    /// 書き込んだ変更を、操作の完了後に記録する変更として保持します。
+    ///
+    /// 変更履歴・監査ログ・操作ジャーナルへの記録は、操作が成功した後に`commit_changes`で行います。
+    /// 操作が失敗した場合は、保持した変更を使って`audited`が書き込みを戻します。
+    ///
+    /// # 引数
+    /// * `email` - 対象ユーザーのメールアドレス
+    /// * `operation` - 変更を行った操作の種類
+    /// * `before` - 変更前のユーザーの状態
+    /// * `after` - 変更後のユーザーの状態（完全に削除された場合は`None`）
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 保持に成功した場合
+    ///
+    /// # エラー
+    /// * `UserError::RepositoryError` - フィールドの暗号化に失敗した場合
+    fn record_change(
+        &self,
+        email: &str,
+        operation: RevisionOperation,
+        before: Option<&User>,
+        after: Option<&User>,
+    ) -> Result<(), UserError> {
+        // 正規化前のキーで保存されているレコードも正規化後のキーで記録する
+        let email = self
+            .normalize_email(email)
+            .unwrap_or_else(|_| email.to_string());
+        // 暗号化するフィールドは、データと同じく暗号化してから記録する
+        let change = JournalChange {
+            before: self.seal(before)?,
+            after: self.seal(after)?,
+        };
+        self.pending_changes.borrow_mut().push(PendingChange {
+            email,
+            operation,
+            change,
+        });
+        Ok(())
+    }
+
+    /// 書き込みを終えた変更を、操作ジャーナル・変更履歴・監査ログの順に記録します。
+    ///
+    /// 監査ログの成功の記録は、他の記録が全て成功した後に行います。途中で失敗した場合は、
+    /// 操作ジャーナルを元の状態に戻し、追記したリビジョンは変更前の状態に戻すリビジョンで
+    /// 打ち消してからエラーを返します（書き込みは呼び出し元が戻します）。
+    /// `details`を指定した場合は、変更履歴には記録せず、監査ログには全ての変更をまとめた
+    /// 1件の記録だけを残します（大量の生成データの投入とその取り消し・やり直しで使用します）。
+    ///
+    /// # 引数
+    /// * `changes` - 記録する変更
+    /// * `update_journal` - 操作ジャーナルを更新する関数（操作ジャーナルが有効な場合のみ呼び出す）
+    /// * `details` - まとめて記録する場合の、監査ログに記録する内容
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 記録に成功した場合
+    ///
+    /// # エラー
+    /// * `UserError::RepositoryError` - 変更履歴・監査ログ・操作ジャーナルの読み書きに失敗した場合
+    fn commit_changes(
+        &self,
+        changes: &[PendingChange],
+        update_journal: impl FnOnce(&mut Journal),
+        details: Option<&str>,
+    ) -> Result<(), UserError> {
+        let previous = match &self.journal {
+            Some(journal) => {
+                let previous = journal.load()?;
+                let mut next = previous.clone();
+                update_journal(&mut next);
+                journal.store(&next)?;
+                Some(previous)
+            }
+            None => None,
+        };
+
+        let mut recorded = 0;
+        let result = match details {
+            Some(details) => self.append_summary(changes, details),
+            None => self
+                .append_revisions(changes, &mut recorded)
+                .and_then(|()| self.append_successes(changes)),
+        };
+        if result.is_err() {
+            // 打ち消しに失敗しても、呼び出し元には元のエラーを返す
+            if let (Some(journal), Some(previous)) = (&self.journal, &previous) {
+                let _ = journal.store(previous);
+            }
+            if let Some(history) = &self.history {
+                for pending in changes[..recorded].iter().rev() {
+                    let _ = history.find_by_email(&pending.email).and_then(|revisions| {
+                        history.append(&Revision {
+                            email: pending.email.clone(),
+                            revision: revisions.last().map_or(1, |revision| revision.revision + 1),
+                            timestamp: Utc::now(),
+                            actor: self.config.actor.clone(),
+                            operation: RevisionOperation::Revert,
+                            user: pending.change.before.clone(),
+                        })
+                    });
+                }
+            }
+        }
+        result
+    }
+
+    /// 変更後のユーザーの状態をリビジョンとして記録します。
+    ///
+    /// 変更履歴の記録が無効な場合は何もしません。対象のユーザーにまだ履歴がなく、
+    /// 変更前の状態がある場合（履歴の記録を始める前から存在したユーザーなど）は、
+    /// 先に変更前の状態をベースラインとして記録します。
+    ///
+    /// # 引数
+    /// * `changes` - 記録する変更
+    /// * `recorded` - 変更後の状態を記録できた変更の件数
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 記録に成功した場合
+    ///
+    /// # エラー
+    /// * `UserError::RepositoryError` - 変更履歴の読み書きに失敗した場合
+    fn append_revisions(
+        &self,
+        changes: &[PendingChange],
+        recorded: &mut usize,
+    ) -> Result<(), UserError> {
+        let Some(history) = &self.history else {
+            return Ok(());
+        };
+        for pending in changes {
+            let mut next = history
+                .find_by_email(&pending.email)?
+                .last()
+                .map_or(1, |revision| revision.revision + 1);
+            let now = Utc::now();
+            if let (1, Some(before)) = (next, &pending.change.before) {
+                history.append(&Revision {
+                    email: pending.email.clone(),
+                    revision: next,
+                    timestamp: before.updated_at.unwrap_or(now).min(now),
+                    actor: self.config.actor.clone(),
+                    operation: RevisionOperation::Baseline,
+                    user: Some(before.clone()),
+                })?;
+                next += 1;
+            }
+
+            history.append(&Revision {
+                email: pending.email.clone(),
+                revision: next,
+                timestamp: now,
+                actor: self.config.actor.clone(),
+                operation: pending.operation,
+                user: pending.change.after.clone(),
+            })?;
+            *recorded += 1;
+        }
+        Ok(())
+    }
+
+    /// 成功した変更を、変更前後の値とともに監査ログに記録します。
+    ///
+    /// 監査ログの記録が無効な場合は何もしません。
+    ///
+    /// # 引数
+    /// * `changes` - 記録する変更
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 記録に成功した場合
+    ///
+    /// # エラー
+    /// * `UserError::RepositoryError` - 監査ログの読み書きに失敗した場合
+    fn append_successes(&self, changes: &[PendingChange]) -> Result<(), UserError> {
+        let Some(audit) = &self.audit else {
+            return Ok(());
+        };
+        for pending in changes {
+            audit.append(&AuditEntry {
+                timestamp: Utc::now(),
+                actor: self.config.actor.clone(),
+                operation: pending.operation,
+                email: Some(pending.email.clone()),
+                before: pending.change.before.clone(),
+                after: pending.change.after.clone(),
+                details: None,
+                outcome: AuditOutcome::Success,
+                prev_hash: None,
+                hmac: None,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// 複数のユーザーへの変更を、まとめて1件の成功として監査ログに記録します。
+    ///
+    /// 監査ログの記録が無効な場合は何もしません。
+    ///
+    /// # 引数
+    /// * `changes` - 記録する変更（操作の種類は先頭の変更のものを使用する）
+    /// * `details` - 記録する内容
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 記録に成功した場合
+    ///
+    /// # エラー
+    /// * `UserError::RepositoryError` - 監査ログの読み書きに失敗した場合
+    fn append_summary(&self, changes: &[PendingChange], details: &str) -> Result<(), UserError> {
+        let (Some(audit), Some(first)) = (&self.audit, changes.first()) else {
+            return Ok(());
+        };
+        audit.append(&AuditEntry {
+            timestamp: Utc::now(),
+            actor: self.config.actor.clone(),
+            operation: first.operation,
+            email: None,
+            before: None,
+            after: None,
+            details: Some(details.to_string()),
+            outcome: AuditOutcome::Success,
+            prev_hash: None,
+            hmac: None,
+        })?;
+        Ok(())
+    }
+
+    /// 操作を実行し、成功した場合は変更を記録し、失敗した場合はその試みを監査ログに記録します。
+    ///
+    /// 成功した場合は、`record_change`で保持した変更を`commit_changes`で記録します。
+    /// 操作ジャーナルが有効な場合は、成功した操作で行われた変更をまとめて
+    /// 1件の操作として記録します（取り消し・やり直しは`replay_journal`が1件ずつ記録します）。
+    /// 書き込みの後で失敗した場合（変更の記録に失敗した場合など）は、保持した変更を
+    /// 逆順に戻してから失敗を返します。
+    ///
+    /// # 引数
+    /// * `operation` - 操作の種類
+    /// * `email` - 操作の対象として指定されたメールアドレス
+    /// * `action` - 実行する操作
+    ///
+    /// # 戻り値
+    /// * `Ok(R)` - 操作の結果
+    ///
+    /// # エラー
+    /// * 操作が返したエラー
+    /// * `UserError::RepositoryError` - 変更の記録に失敗した場合
+    fn audited<R>(
+        &self,
+        operation: RevisionOperation,
+        email: Option<&str>,
+        action: impl FnOnce() -> Result<R, UserError>,
+    ) -> Result<R, UserError> {
+        self.audited_as(operation, email, None, action)
+    }
+
+    /// `audited`と同様に操作を実行し、成功した場合の変更を`details`に応じて記録します。
+    ///
+    /// # 引数
+    /// * `operation` - 操作の種類
+    /// * `email` - 操作の対象として指定されたメールアドレス
+    /// * `details` - 変更をまとめて1件で記録する場合の、監査ログに記録する内容
+    /// * `action` - 実行する操作
+    ///
+    /// # 戻り値
+    /// * `Ok(R)` - 操作の結果
+    ///
+    /// # エラー
+    /// * 操作が返したエラー
+    /// * `UserError::RepositoryError` - 変更の記録に失敗した場合
+    fn audited_as<R>(
+        &self,
+        operation: RevisionOperation,
+        email: Option<&str>,
+        details: Option<&str>,
+        action: impl FnOnce() -> Result<R, UserError>,
+    ) -> Result<R, UserError> {
+        self.pending_changes.borrow_mut().clear();
+        let result = action();
+        let changes = self.pending_changes.take();
+        let result = match result {
+            Ok(value) if !changes.is_empty() => self
+                .commit_changes(
+                    &changes,
+                    |journal| {
+                        journal.record(
+                            JournalEntry {
+                                timestamp: Utc::now(),
+                                actor: self.config.actor.clone(),
+                                operation,
+                                changes: changes
+                                    .iter()
+                                    .map(|pending| pending.change.clone())
+                                    .collect(),
+                            },
+                            JOURNAL_LIMIT,
+                        )
+                    },
+                    details,
+                )
+                .map(|()| value),
+            result => result,
+        };
+        // 書き込み後に失敗した場合は、記録のない変更が残らないよう書き込みを戻す
+        let result = match result {
+            Err(error) if !changes.is_empty() => Err(match self.roll_back(&changes) {
+                Ok(()) => error,
+                Err(rollback) => UserError::RepositoryError(format!(
+                    "{:?}; failed to roll back the change: {:?}",
+                    error, rollback
+                )),
+            }),
+            result => result,
+        };
+        if let (Err(error), Some(audit)) = (&result, &self.audit) {
+            // 失敗の記録に失敗しても、呼び出し元には元のエラーを返す
+            let _ = audit.append(&AuditEntry {
+                timestamp: Utc::now(),
+                actor: self.config.actor.clone(),
+                operation,
+                email: email.map(str::to_string),
+                before: None,
+                after: None,
+                details: None,
+                outcome: AuditOutcome::Failure(format!("{:?}", error)),
+                prev_hash: None,
+                hmac: None,
+            });
+        }
+        result
+    }
+
+    /// 書き込んだ変更を逆順に戻し、変更前の状態を1回の書き込みで復元します。
+    ///
+    /// # 引数
+    /// * `changes` - `record_change`で保持した変更
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 復元に成功した場合
+    ///
+    /// # エラー
+    /// * `UserError::RepositoryError` - データの書き込みに失敗した場合
+    fn roll_back(&self, changes: &[PendingChange]) -> Result<(), UserError> {
+        let mut operations = Vec::new();
+        for pending in changes.iter().rev() {
+            match (&pending.change.before, &pending.change.after) {
+                (Some(before), Some(after)) if before.email != after.email => {
+                    operations.push(BatchOperation::Delete(after.email.clone()));
+                    operations.push(BatchOperation::Put(before.clone()));
+                }
+                (Some(before), _) => operations.push(BatchOperation::Put(before.clone())),
+                (None, Some(after)) => operations.push(BatchOperation::Delete(after.email.clone())),
+                (None, None) => {}
+            }
+        }
+        self.storage().apply_batch(&operations)?;
+        Ok(())
+    }
+
+    /// 全てのレコードを検査し、`fix`が`true`の場合は安全に修正できる問題を修正します。
+    ///
+    /// # 引数
+    /// * `fix` - 安全に修正できる問題を修正するかどうか
+    ///
+    /// # 戻り値
+    /// * `Ok(IntegrityReport)` - 検査したレコードの件数と見つかった問題
+    ///
+    /// # エラー
+    /// * `UserError::RepositoryError` - データの取得・保存に失敗した場合
+    fn inspect_integrity(&self, fix: bool) -> Result<IntegrityReport, UserError> {
+        let mut entries = self.storage().find_all_entries()?;
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut taken: HashSet<String> = entries.iter().map(|(key, _)| key.clone()).collect();
+        let mut email_groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let mut phone_groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let mut issues = Vec::new();
+        let mut operations = Vec::new();
+        let mut changes = Vec::new();
+
+        for (key, user) in &entries {
+            let mut problems = Vec::new();
+            let mut fixed = user.clone();
+            if *key != user.email {
+                problems.push(IntegrityProblem::KeyMismatch {
+                    email: user.email.clone(),
+                });
+            }
+
+            let email = match self.normalize_email(&user.email) {
+                Ok(normalized) => {
+                    if normalized != user.email {
+                        problems.push(IntegrityProblem::NotNormalized {
+                            field: "email",
+                            value: user.email.clone(),
+                            normalized: normalized.clone(),
+                        });
+                    }
+                    normalized
+                }
+                Err(error) => {
+                    problems.push(invalid_field("email", error));
+                    user.email.clone()
+                }
+            };
+            email_groups
+                .entry(email.clone())
+                .or_default()
+                .push(key.clone());
+
+            match self.normalize_username(&user.username) {
+                Ok(normalized) if normalized != user.username => {
+                    problems.push(IntegrityProblem::NotNormalized {
+                        field: "username",
+                        value: user.username.clone(),
+                        normalized: normalized.clone(),
+                    });
+                    fixed.username = normalized;
+                }
+                Ok(_) => {}
+                Err(error) => problems.push(invalid_field("username", error)),
+            }
+
+            match self.normalize_phone(&user.phone) {
+                Ok(normalized) => {
+                    if normalized != user.phone {
+                        problems.push(IntegrityProblem::NotNormalized {
+                            field: "phone",
+                            value: user.phone.clone(),
+                            normalized: normalized.clone(),
+                        });
+                        fixed.phone = normalized.clone();
+                    }
+                    phone_groups
+                        .entry(normalized)
+                        .or_default()
+                        .push(key.clone());
+                }
+                Err(error) => problems.push(invalid_field("phone", error)),
+            }
+
+            if let Err(error) = self.validate_age(user.age) {
+                problems.push(invalid_field("age", error));
+            }
+
+            // キーの付け替えは、付け替え先のキーが空いている場合にだけ行う
+            if email == *key || !taken.contains(&email) {
+                fixed.email = email;
+            } else if user.email != *key {
+                fixed = user.clone();
+            }
+            let write = fix && fixed != *user;
+            if write {
+                if fixed.email != *key {
+                    taken.remove(key);
+                    taken.insert(fixed.email.clone());
+                    operations.push(BatchOperation::Delete(key.clone()));
+                }
+                fixed.version = user.version + 1;
+                operations.push(BatchOperation::Put(fixed.clone()));
+                changes.push((user, fixed.clone()));
+            }
+
+            for problem in problems {
+                let was_fixed = write
+                    && match &problem {
+                        IntegrityProblem::KeyMismatch { .. } => true,
+                        IntegrityProblem::NotNormalized {
+                            field, normalized, ..
+                        } => {
+                            let value = match *field {
+                                "email" => &fixed.email,
+                                "username" => &fixed.username,
+                                _ => &fixed.phone,
+                            };
+                            value == normalized
+                        }
+                        _ => false,
+                    };
+                issues.push(IntegrityIssue {
+                    key: key.clone(),
+                    problem,
+                    fixed: was_fixed,
+                });
+            }
+        }
+
+        for group in email_groups.values().filter(|keys| keys.len() > 1) {
+            for key in group {
+                issues.push(IntegrityIssue {
+                    key: key.clone(),
+                    problem: IntegrityProblem::DuplicateEmail {
+                        others: group
+                            .iter()
+                            .filter(|other| *other != key)
+                            .cloned()
+                            .collect(),
+                    },
+                    fixed: false,
+                });
+            }
+        }
+        for (phone, group) in phone_groups.iter().filter(|(_, keys)| keys.len() > 1) {
+            for key in group {
+                issues.push(IntegrityIssue {
+                    key: key.clone(),
+                    problem: IntegrityProblem::DuplicatePhone {
+                        phone: phone.clone(),
+                        others: group
+                            .iter()
+                            .filter(|other| *other != key)
+                            .cloned()
+                            .collect(),
+                    },
+                    fixed: false,
+                });
+            }
+        }
+        issues.sort_by(|a, b| a.key.cmp(&b.key));
+
+        if !operations.is_empty() {
+            self.storage().apply_batch(&operations)?;
+        }
+        for (before, after) in changes {
+            self.record_change(
+                &after.email,
+                RevisionOperation::Fix,
+                Some(before),
+                Some(&after),
+            )?;
+        }
+        Ok(IntegrityReport {
+            checked: entries.len(),
+            issues,
+        })
+    }
+
+    /// 操作ジャーナルに記録された操作を取り消し、またはやり直します。
+    ///
+    /// # 引数
+    /// * `direction` - `RevisionOperation::Undo`（取り消し）または`RevisionOperation::Redo`（やり直し）
+    /// * `count` - 取り消す・やり直す操作の数
+    ///
+    /// # 戻り値
+    /// * `Ok(Vec<JournalEntry>)` - 取り消した・やり直した操作
+    ///
+    /// # エラー
+    /// * `UserError::InvalidOperation` - 操作ジャーナルの記録が有効になっていない場合、
+    ///   対象の操作がない場合、または対象のレコードが変更されている場合
+    /// * `UserError::RepositoryError` - データの永続化に失敗した場合
+    fn replay_journal(
+        &self,
+        direction: RevisionOperation,
+        count: usize,
+    ) -> Result<Vec<JournalEntry>, UserError> {
+        let journal = self.journal()?;
+        let undo = direction == RevisionOperation::Undo;
+        let verb = if undo { "undo" } else { "redo" };
+        let mut state = journal.load()?;
+        let mut replayed = Vec::new();
+
+        for _ in 0..count {
+            let stack = if undo { &state.undo } else { &state.redo };
+            let Some(sealed) = stack.last().cloned() else {
+                break;
+            };
+            let entry = self.open_journal_entry(sealed.clone())?;
+            // 取り消しは変更後から変更前へ逆順に、やり直しは変更前から変更後へ順に適用する
+            let steps: Vec<(Option<&User>, Option<&User>)> = if undo {
+                entry
+                    .changes
+                    .iter()
+                    .rev()
+                    .map(|change| (change.after.as_ref(), change.before.as_ref()))
+                    .collect()
+            } else {
+                entry
+                    .changes
+                    .iter()
+                    .map(|change| (change.before.as_ref(), change.after.as_ref()))
+                    .collect()
+            };
+
+            // 大量の変更を含む操作でもファイルを何度も読み込まないよう、現在のレコードはまとめて取得する
+            // （フィールドの復号は、対象のレコードだけに行う）
+            let stored: HashMap<String, User> =
+                self.repository.find_all_entries()?.into_iter().collect();
+            for &(expected, target) in &steps {
+                if let Err(reason) = self.check_unchanged(&stored, expected, target)? {
+                    let email = entry.changes.first().map_or("", JournalChange::email);
+                    let done = if replayed.is_empty() {
+                        String::new()
+                    } else {
+                        format!("Stopped after {} operation(s): ", replayed.len())
+                    };
+                    return Err(UserError::InvalidOperation(format!(
+                        "{}Cannot {} {} of {}: {}",
+                        done, verb, entry.operation, email, reason
+                    )));
+                }
+            }
+            // 1つの操作による変更は、1回の書き込みでまとめて適用する
+            let mut planned = Vec::new();
+            let mut operations = Vec::new();
+            for &(expected, target) in &steps {
+                let (current, applied) = self.plan_journal_change(&stored, expected, target)?;
+                match (&current, &applied) {
+                    (Some(current), Some(applied)) if current.email != applied.email => {
+                        operations.push(BatchOperation::Delete(current.email.clone()));
+                        operations.push(BatchOperation::Put(applied.clone()));
+                    }
+                    (_, Some(applied)) => operations.push(BatchOperation::Put(applied.clone())),
+                    (Some(current), None) => {
+                        operations.push(BatchOperation::Delete(current.email.clone()))
+                    }
+                    (None, None) => {}
+                }
+                planned.push((current, applied));
+            }
+            self.storage().apply_batch(&operations)?;
+            for ((expected, target), (current, applied)) in steps.iter().zip(&planned) {
+                let email = target.or(*expected).map_or("", |user| user.email.as_str());
+                self.record_change(email, direction, current.as_ref(), applied.as_ref())?;
+            }
+
+            if undo {
+                state.undo.pop();
+                state.redo.push(sealed);
+            } else {
+                state.redo.pop();
+                state.undo.push(sealed);
+            }
+            // 一括作成の取り消し・やり直しは、一括作成と同じくまとめて1件で記録する
+            let details = (entry.operation == RevisionOperation::Seed)
+                .then(|| format!("{} of {} user(s)", entry.operation, entry.changes.len()));
+            // 記録を終えた取り消し・やり直しは、後の失敗で書き込みを戻さない
+            let changes = self.pending_changes.take();
+            let committed = self.commit_changes(
+                &changes,
+                |current| *current = state.clone(),
+                details.as_deref(),
+            );
+            if let Err(error) = committed {
+                self.pending_changes.replace(changes);
+                return Err(error);
+            }
+            replayed.push(entry);
+        }
+
+        if replayed.is_empty() && count > 0 {
+            return Err(UserError::InvalidOperation(format!("Nothing to {}", verb)));
+        }
+        Ok(replayed)
+    }
+
+    /// レコードが操作ジャーナルに記録された状態から変更されていないことを確認します。
+    ///
+    /// `expected`のキーのレコードがバージョンを除いて`expected`と一致し、キーが変わる場合は
+    /// `target`のキーが空いていることを確認します。取り消し・やり直しのたびにバージョンは
+    /// 増えるため、バージョンは比較しません（他の変更では更新日時なども変わります）。
+    ///
+    /// # 引数
+    /// * `stored` - 保存時のキーごとの、現在のレコード（フィールドは暗号化されたまま）
+    /// * `expected` - 現在あるはずのレコード（ないはずの場合は`None`）
+    /// * `target` - 適用後のレコード（削除する場合は`None`）
+    ///
+    /// # 戻り値
+    /// * `Ok(Ok(()))` - 変更されていない場合
+    /// * `Ok(Err(String))` - 変更されている場合（理由を保持する）
+    ///
+    /// # エラー
+    /// * `UserError::RepositoryError` - フィールドの復号に失敗した場合
+    fn check_unchanged(
+        &self,
+        stored: &HashMap<String, User>,
+        expected: Option<&User>,
+        target: Option<&User>,
+    ) -> Result<Result<(), String>, UserError> {
+        let mut keys: Vec<&str> = Vec::new();
+        for user in [expected, target].into_iter().flatten() {
+            if !keys.contains(&user.email.as_str()) {
+                keys.push(&user.email);
+            }
+        }
+        let content = |user: Option<&User>| {
+            user.map(|user| User {
+                version: 0,
+                ..user.clone()
+            })
+        };
+        for key in keys {
+            let wanted = expected.filter(|user| user.email == key);
+            let current = self.open(stored.get(key).cloned())?;
+            if content(current.as_ref()) != content(wanted) {
+                return Ok(Err(match (current, wanted) {
+                    (Some(_), None) => format!("{} has been created since", key),
+                    (None, Some(_)) => format!("{} has been removed since", key),
+                    _ => format!("{} has been changed since", key),
+                }));
+            }
+        }
+        Ok(Ok(()))
+    }
+
+    /// 操作ジャーナルに記録された変更を適用した後のレコードを求めます。
+    ///
+    /// 適用後のレコードのバージョンは、現在のバージョンと記録されたバージョンの
+    /// 大きい方より1大きい値になり、バージョンが戻ることはありません。
+    ///
+    /// # 引数
+    /// * `stored` - 保存時のキーごとの、現在のレコード（フィールドは暗号化されたまま）
+    /// * `expected` - 現在あるはずのレコード（ない場合は`None`）
+    /// * `target` - 適用後のレコード（削除する場合は`None`）
+    ///
+    /// # 戻り値
+    /// * `Ok((Option<User>, Option<User>))` - 現在保存されているレコードと、保存するレコード
+    ///
+    /// # エラー
+    /// * `UserError::RepositoryError` - フィールドの復号に失敗した場合
+    fn plan_journal_change(
+        &self,
+        stored: &HashMap<String, User>,
+        expected: Option<&User>,
+        target: Option<&User>,
+    ) -> Result<(Option<User>, Option<User>), UserError> {
+        let current =
+            self.open(expected.and_then(|expected| stored.get(&expected.email).cloned()))?;
+        let applied = target.map(|target| User {
+            version: current
+                .as_ref()
+                .map_or(0, |user| user.version)
+                .max(target.version)
+                + 1,
+            ..target.clone()
+        });
+        Ok((current, applied))
+    }
+
+    /// レコードのバージョンが想定したものと一致することを確認します。
+    ///
+    /// # 引数
+    /// * `user` - 保存されているユーザー
+    /// * `expected_version` - 想定するバージョン（`None`の場合は確認しない）
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 一致する場合、または想定するバージョンが指定されていない場合
+    ///
+    /// # エラー
+    /// * `UserError::Conflict` - バージョンが一致しない場合
+    fn check_version(&self, user: &User, expected_version: Option<u64>) -> Result<(), UserError> {
+        match expected_version {
+            Some(expected) if expected != user.version => Err(UserError::Conflict(format!(
+                "User {} is at version {}, but version {} was expected; \
+                 it has been changed since it was read",
+                user.email, user.version, expected
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// 監査ログの記録が、指定されたメールアドレスのユーザーに関するものかを判定します。
+    ///
+    /// # 引数
+    /// * `entry` - 監査ログの記録
+    /// * `emails` - 正規化後のメールアドレス
+    ///
+    /// # 戻り値
+    /// * `bool` - 対象のメールアドレス、または変更前後の状態がいずれかに該当する場合は`true`
+    fn audit_mentions(&self, entry: &AuditEntry, emails: &[String]) -> bool {
+        entry
+            .email
+            .as_deref()
+            .is_some_and(|email| emails.contains(&self.audit_key(email)))
+            || [&entry.before, &entry.after]
+                .into_iter()
+                .flatten()
+                .any(|user| self.user_mentions(user, emails))
+    }
+
+    /// 操作ジャーナルの操作が、指定されたメールアドレスのユーザーを含むかを判定します。
+    ///
+    /// # 引数
+    /// * `entry` - 操作ジャーナルの操作
+    /// * `emails` - 正規化後のメールアドレス
+    ///
+    /// # 戻り値
+    /// * `bool` - いずれかの変更の前後の状態が該当する場合は`true`
+    fn journal_mentions(&self, entry: &JournalEntry, emails: &[String]) -> bool {
+        entry.changes.iter().any(|change| {
+            [&change.before, &change.after]
+                .into_iter()
+                .flatten()
+                .any(|user| self.user_mentions(user, emails))
+        })
+    }
+
+    /// ユーザーのメールアドレスまたは別名が、指定されたメールアドレスのいずれかに該当するかを判定します。
+    ///
+    /// # 引数
+    /// * `user` - ユーザー
+    /// * `emails` - 正規化後のメールアドレス
+    ///
+    /// # 戻り値
+    /// * `bool` - いずれかに該当する場合は`true`
+    fn user_mentions(&self, user: &User, emails: &[String]) -> bool {
+        std::iter::once(&user.email)
+            .chain(&user.aliases)
+            .any(|email| emails.contains(&self.audit_key(email)))
+    }
+
+    /// フィールドの暗号化の設定に従ってユーザーを読み書きするリポジトリを返します。
+    ///
+    /// # 戻り値
+    /// * `FieldEncryptedUserRepository<&T>` - 暗号化されたフィールドを復号（または伏せ字に）して読み込むリポジトリ
+    fn storage(&self) -> FieldEncryptedUserRepository<'_, &T> {
+        let access = match (&self.field_cipher, self.redacted) {
+            (_, true) => FieldAccess::Redacted,
+            (Some(cipher), false) => FieldAccess::Key(cipher),
+            (None, false) => FieldAccess::NoKey,
+        };
+        FieldEncryptedUserRepository::new(&self.repository, access)
+    }
+
+    /// 変更履歴・監査ログ・操作ジャーナルに記録するユーザーの状態を、フィールドの暗号化の設定に従って暗号化します。
+    ///
+    /// # 引数
+    /// * `user` - 記録するユーザーの状態
+    ///
+    /// # 戻り値
+    /// * `Ok(Option<User>)` - 暗号化したユーザーの状態（鍵がない場合はそのまま）
+    ///
+    /// # エラー
+    /// * `UserError::RepositoryError` - 伏せ字で読み込んでいる場合、または暗号化に失敗した場合
+    fn seal(&self, user: Option<&User>) -> Result<Option<User>, UserError> {
+        Ok(user.map(|user| self.storage().seal(user)).transpose()?)
+    }
+
+    /// 変更履歴・監査ログ・操作ジャーナルから読み込んだユーザーの状態を、データと同じように復号（または伏せ字に）します。
+    ///
+    /// # 引数
+    /// * `user` - 記録されていたユーザーの状態
+    ///
+    /// # 戻り値
+    /// * `Ok(Option<User>)` - 復号した、または伏せ字に置き換えたユーザーの状態
+    ///
+    /// # エラー
+    /// * `UserError::RepositoryError` - 鍵がないのに暗号化した値がある場合、または復号に失敗した場合
+    fn open(&self, user: Option<User>) -> Result<Option<User>, UserError> {
+        Ok(user.map(|user| self.storage().open(user)).transpose()?)
+    }
+
+    /// 変更履歴のリビジョンが記録したユーザーの状態を復号します。
+    ///
+    /// # 引数
+    /// * `revision` - 読み込んだリビジョン
+    ///
+    /// # 戻り値
+    /// * `Ok(Revision)` - ユーザーの状態を復号したリビジョン
+    ///
+    /// # エラー
+    /// * `UserError::RepositoryError` - 復号に失敗した場合
+    fn open_revision(&self, revision: Revision) -> Result<Revision, UserError> {
+        Ok(Revision {
+            user: self.open(revision.user)?,
+            ..revision
+        })
+    }
+
+    /// 監査ログの記録が保持する変更前後のユーザーの状態を復号します。
+    ///
+    /// # 引数
+    /// * `entry` - 読み込んだ記録
+    ///
+    /// # 戻り値
+    /// * `Ok(AuditEntry)` - ユーザーの状態を復号した記録
+    ///
+    /// # エラー
+    /// * `UserError::RepositoryError` - 復号に失敗した場合
+    fn open_audit_entry(&self, entry: AuditEntry) -> Result<AuditEntry, UserError> {
+        Ok(AuditEntry {
+            before: self.open(entry.before)?,
+            after: self.open(entry.after)?,
+            ..entry
+        })
+    }
+
+    /// 操作ジャーナルの操作が保持する変更前後のレコードを復号します。
+    ///
+    /// # 引数
+    /// * `entry` - 読み込んだ操作
+    ///
+    /// # 戻り値
+    /// * `Ok(JournalEntry)` - レコードを復号した操作
+    ///
+    /// # エラー
+    /// * `UserError::RepositoryError` - 復号に失敗した場合
+    fn open_journal_entry(&self, entry: JournalEntry) -> Result<JournalEntry, UserError> {
+        let changes = entry
+            .changes
+            .into_iter()
+            .map(|change| {
+                Ok(JournalChange {
+                    before: self.open(change.before)?,
+                    after: self.open(change.after)?,
+                })
+            })
+            .collect::<Result<_, UserError>>()?;
+        Ok(JournalEntry { changes, ..entry })
+    }
+
+    /// 操作ジャーナルのリポジトリを返します。
+    ///
+    /// # 戻り値
+    /// * `Ok(&dyn JournalRepository)` - 操作ジャーナルのリポジトリ
+    ///
+    /// # エラー
+    /// * `UserError::InvalidOperation` - 操作ジャーナルの記録が有効になっていない場合
+    fn journal(&self) -> Result<&dyn JournalRepository, UserError> {
+        self.journal.as_deref().ok_or_else(|| {
+            UserError::InvalidOperation("Operation journal is not enabled".to_string())
+        })
+    }
+
+    /// 監査ログの記録とメールアドレスを照合するためのキーを求めます。
+    ///
+    /// 失敗した操作では入力されたままのメールアドレスが記録されるため、
+    /// 正規化できない場合は前後の空白を除いて小文字化した値を使用します。
+    ///
+    /// # 引数
+    /// * `email` - メールアドレス
+    ///
+    /// # 戻り値
+    /// * `String` - 照合用のキー
+    fn audit_key(&self, email: &str) -> String {
+        self.normalize_email(email)
+            .unwrap_or_else(|_| email.trim().to_lowercase())
+    }
+
+    /// 監査ログのリポジトリを返します。
+    ///
+    /// # 戻り値
+    /// * `Ok(&dyn AuditRepository)` - 監査ログのリポジトリ
+    ///
+    /// # エラー
+    /// * `UserError::InvalidOperation` - 監査ログの記録が有効になっていない場合
+    fn audit(&self) -> Result<&dyn AuditRepository, UserError> {
+        self.audit
+            .as_deref()
+            .ok_or_else(|| UserError::InvalidOperation("Audit log is not enabled".to_string()))
+    }
+
+    /// 変更履歴のリポジトリを返します。
+    ///
+    /// # 戻り値
+    /// * `Ok(&dyn HistoryRepository)` - 変更履歴のリポジトリ
+    ///
+    /// # エラー
+    /// * `UserError::InvalidOperation` - 変更履歴の記録が有効になっていない場合
+    fn history(&self) -> Result<&dyn HistoryRepository, UserError> {
+        self.history.as_deref().ok_or_else(|| {
+            UserError::InvalidOperation("Revision history is not enabled".to_string())
+        })
+    }
+
+    /// 変更履歴を検索するためのキーを求めます。
+    ///
+    /// 保存済みのユーザー（別名を含む）に該当する場合はそのユーザーの正規化後の
+    /// メールアドレスを、該当しない場合（完全に削除された場合など）は指定された
+    /// メールアドレスを正規化したものを返します。
+    ///
+    /// # 引数
+    /// * `email` - ユーザーのメールアドレス
+    ///
+    /// # 戻り値
+    /// * `Ok(String)` - 変更履歴のキー
+    ///
+    /// # エラー
+    /// * `UserError::InvalidEmail` - メールアドレスの形式が不正な場合
+    /// * `UserError::RepositoryError` - データの取得に失敗した場合
+    fn history_key(&self, email: &str) -> Result<String, UserError> {
+        let email = self.normalize_email(email)?;
+        Ok(match self.find_existing(&email)? {
+            Some(user) => self.normalize_email(&user.email).unwrap_or(user.email),
+            None => email,
+        })
+    }
+
+    /// 正規化済みのメールアドレスに該当する保存済みユーザーを検索します。
+    ///
+    /// まず正規化後のキーで検索し、見つからない場合は正規化前の表記で
+    /// 保存されているレコードを全件から探します。それでも見つからない場合は、
+    /// 統合によって別名として記録されたメールアドレスから探します。
+    ///
+    /// # 引数
+    /// * `email` - 正規化済みのメールアドレス
+    ///
+    /// # 戻り値
+    /// * `Ok(Some(User))` - 該当するユーザーが見つかった場合
+    /// * `Ok(None)` - 該当するユーザーが存在しない場合
+    ///
+    /// # エラー
+    /// * `UserError::RepositoryError` - データの取得に失敗した場合
+    fn find_existing(&self, email: &str) -> Result<Option<User>, UserError> {
+        if let Some(user) = self.storage().find_by_email(email)? {
+            return Ok(Some(user));
+        }
+
+        let mut users = self.storage().find_all()?;
+        users.sort_by(|a, b| a.email.cmp(&b.email));
+        let by_email = users.iter().position(|user| {
+            self.normalize_email(&user.email)
+                .is_ok_and(|normalized| normalized == email)
+        });
+        let by_alias = || {
+            users.iter().position(|user| {
+                user.aliases.iter().any(|alias| {
+                    self.normalize_email(alias)
+                        .is_ok_and(|normalized| normalized == email)
+                })
+            })
+        };
+        Ok(by_email
+            .or_else(by_alias)
+            .map(|index| users.swap_remove(index)))
+    }
+
+    /// メールアドレスを設定に従って正規化し、形式を検証します。
+    ///
+    /// # 引数
+    /// * `email` - 正規化するメールアドレス
+    ///
+    /// # 戻り値
+    /// * `Ok(String)` - 正規化されたメールアドレス
+    ///
+    /// # エラー
+    /// * `UserError::InvalidEmail` - メールアドレスの形式が不正な場合
+    fn normalize_email(&self, email: &str) -> Result<String, UserError> {
+        normalize_email(email, self.config.lowercase_email_local_part)
+    }
+
+    /// ユーザー名を正規化し、文字数・使用文字・予約語を検証します。
+    ///
+    /// # 引数
+    /// * `username` - 正規化するユーザー名
+    ///
+    /// # 戻り値
+    /// * `Ok(String)` - 正規化されたユーザー名
+    ///
+    /// # エラー
+    /// * `UserError::InvalidUsername` - ユーザー名が規則に従っていない場合
+    fn normalize_username(&self, username: &str) -> Result<String, UserError> {
+        normalize_username(
+            username,
+            self.config.max_username_length,
+            &self.config.reserved_usernames,
+        )
+    }
+
+    /// 電話番号を設定された国の規則に従ってE.164形式に正規化します。
+    ///
+    /// 国の規則では解釈できないものの、正規化の導入前の規則（10桁以上の数字）に従った値は、
+    /// 既存のデータを読み書きできるよう入力どおりに返します。
+    ///
+    /// # 引数
+    /// * `phone` - 正規化する電話番号
+    ///
+    /// # 戻り値
+    /// * `Ok(String)` - E.164形式の電話番号、または導入前の形式の電話番号
+    ///
+    /// # エラー
+    /// * `UserError::InvalidPhone` - 電話番号の形式や桁数が不正な場合
+    fn normalize_phone(&self, phone: &str) -> Result<String, UserError> {
+        match normalize_phone(phone, &self.config.default_country) {
+            Err(_) if is_legacy_phone(phone) => Ok(phone.to_string()),
+            result => result,
+        }
+    }
+
+    /// 年齢の範囲を検証します。
+    ///
+    /// # 引数
+    /// * `age` - 検証する年齢
+    ///
+    /// # 戻り値
+    /// * `Ok(())` - 検証に成功した場合
+    ///
+    /// # エラー
+    /// * `UserError::InvalidAge` - 年齢が150歳を超える場合
+    fn validate_age(&self, age: u32) -> Result<(), UserError> {
+        if age > 150 {
+            return Err(UserError::InvalidAge(
+                "Age must be between 0 and 150".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<T: UserRepository> PseudonymRules for UserService<T> {
+    fn email_key(&self, email: &str) -> String {
+        self.audit_key(email)
+    }
+
+    fn is_valid_email(&self, email: &str) -> bool {
+        self.normalize_email(email).is_ok()
+    }
+
+    fn is_valid_username(&self, username: &str) -> bool {
+        self.normalize_username(username).is_ok()
+    }
+
+    fn is_valid_phone(&self, phone: &str) -> bool {
+        self.normalize_phone(phone).is_ok()
+    }
+}
+
+/// 検証エラーを、規則に違反する値として報告する問題に変換します。
+///
+/// # 引数
+/// * `field` - フィールド名
+/// * `error` - 検証エラー
+///
+/// # 戻り値
+/// * `IntegrityProblem` - 規則に違反する値を表す問題
+fn invalid_field(field: &'static str, error: UserError) -> IntegrityProblem {
+    let reason = match error {
+        UserError::InvalidEmail(reason)
+        | UserError::InvalidUsername(reason)
+        | UserError::InvalidPhone(reason)
+        | UserError::InvalidAge(reason) => reason,
+        other => format!("{:?}", other),
+    };
+    IntegrityProblem::InvalidField { field, reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::audit_repository::{AuditRepositoryImpl, MockAuditRepository};
+    use crate::repositories::field_encryption::{EncryptedField, REDACTED_VALUE};
+    use crate::repositories::history_repository::HistoryRepositoryImpl;
+    use crate::repositories::journal_repository::JournalRepositoryImpl;
+    use crate::repositories::user_repository::{MockUserRepository, UserRepositoryImpl};
+    use crate::services::seeder::Seeder;
+
+    fn create_mock_repository() -> MockUserRepository {
+        MockUserRepository::new()
+    }
+
+    #[test]
+    fn test_create_user_success() {
+        let mut mock_repo = create_mock_repository();
+        mock_repo.expect_find_by_email().return_once(|_| Ok(None));
+        mock_repo.expect_find_all().return_once(|| Ok(vec![]));
+        mock_repo.expect_save().return_once(|_| Ok(()));
+
+        let service = UserService::new(mock_repo);
+        let result = service.create_user(
+            "test@example.com".to_string(),
+            "testuser".to_string(),
+            "1234567890".to_string(),
+            25,
+        );
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_create_user_invalid_email() {
+        let mock_repo = create_mock_repository();
+        let service = UserService::new(mock_repo);
+        let result = service.create_user(
+            "invalid-email".to_string(),
+            "testuser".to_string(),
+            "1234567890".to_string(),
+            25,
+        );
+
+        assert!(matches!(result, Err(UserError::InvalidEmail(_))));
+    }
+
+    #[test]
+    fn test_update_user_not_found() {
+        let mut mock_repo = create_mock_repository();
+        mock_repo.expect_find_by_email().return_once(|_| Ok(None));
+        mock_repo.expect_find_all().return_once(|| Ok(vec![]));
+
+        let service = UserService::new(mock_repo);
+        let result = service.update_user(
+            "test@example.com".to_string(),
+            "testuser".to_string(),
+            "1234567890".to_string(),
+            25,
+        );
+
+        assert!(matches!(result, Err(UserError::UserNotFound(_))));
+    }
+
+    #[test]
+    fn test_delete_user_success() {
+        let mut mock_repo = create_mock_repository();
+        mock_repo
+            .expect_find_by_email()
+            .return_once(|_| Ok(Some(legacy_user("test@example.com"))));
+        mock_repo
+            .expect_save()
+            .withf(|user| user.deleted_at.is_some())
+            .return_once(|_| Ok(()));
+
+        let service = UserService::new(mock_repo);
+        let result = service.delete_user("test@example.com");
+
+        assert!(result.is_ok());
+    }
+
+    fn legacy_user(email: &str) -> User {
+        User {
+            email: email.to_string(),
+            username: "testuser".to_string(),
+            phone: "1234567890".to_string(),
+            age: 25,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_create_user_conflicts_with_unnormalized_email() {
+        let mut mock_repo = create_mock_repository();
+        mock_repo.expect_find_by_email().return_once(|_| Ok(None));
+        mock_repo
+            .expect_find_all()
+            .return_once(|| Ok(vec![legacy_user("John@Example.com")]));
+
+        let service = UserService::new(mock_repo);
+        let result = service.create_user(
+            " john@EXAMPLE.com ".to_string(),
+            "testuser".to_string(),
+            "1234567890".to_string(),
+            25,
+        );
+
+        assert!(matches!(result, Err(UserError::UserAlreadyExists(_))));
+    }
+
+    #[test]
+    fn test_get_user_is_case_insensitive() {
+        let mut mock_repo = create_mock_repository();
+        mock_repo
+            .expect_find_by_email()
+            .withf(|email| email == "john@example.com")
+            .return_once(|_| Ok(Some(legacy_user("john@example.com"))));
+
+        let service = UserService::new(mock_repo);
+        let user = service.get_user("John@Example.COM").unwrap();
+
+        assert_eq!(user.email, "john@example.com");
+    }
+
+    #[test]
+    fn test_update_user_rekeys_unnormalized_record() {
+        let mut mock_repo = create_mock_repository();
+        mock_repo.expect_find_by_email().return_once(|_| Ok(None));
+        mock_repo
+            .expect_find_all()
+            .return_once(|| Ok(vec![legacy_user("John@Example.com")]));
+        mock_repo
+            .expect_replace()
+            .withf(|user, removed| {
+                user.email == "john@example.com" && removed == "John@Example.com"
+            })
+            .return_once(|_, _| Ok(()));
+
+        let service = UserService::new(mock_repo);
+        let result = service.update_user(
+            "john@example.com".to_string(),
+            "newuser".to_string(),
+            "1234567890".to_string(),
+            30,
+        );
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_find_email_duplicates() {
+        let mut mock_repo = create_mock_repository();
+        mock_repo.expect_find_all().return_once(|| {
+            Ok(vec![
+                legacy_user("John@Example.com"),
+                legacy_user("john@example.com"),
+                legacy_user("alice@example.com"),
+            ])
+        });
+
+        let service = UserService::new(mock_repo);
+        let duplicates = service.find_email_duplicates().unwrap();
+
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].0, "john@example.com");
+        assert_eq!(duplicates[0].1.len(), 2);
+    }
+
+    #[test]
+    fn test_create_user_normalizes_phone() {
+        let mut mock_repo = create_mock_repository();
+        mock_repo.expect_find_by_email().return_once(|_| Ok(None));
+        mock_repo.expect_find_all().return_once(|| Ok(vec![]));
+        mock_repo
+            .expect_save()
+            .withf(|user| user.phone == "+819012345678")
+            .return_once(|_| Ok(()));
+
+        let service = UserService::new(mock_repo);
+        let user = service
+            .create_user(
+                "test@example.com".to_string(),
+                "testuser".to_string(),
+                "０９０－１２３４－５６７８".to_string(),
+                25,
+            )
+            .unwrap();
+
+        assert_eq!(user.phone, "+819012345678");
+        assert_eq!(service.display_phone(&user.phone), "090-1234-5678");
+    }
+
+    #[test]
+    fn test_create_user_invalid_phone() {
+        let mock_repo = create_mock_repository();
+        let service = UserService::new(mock_repo);
+        let result = service.create_user(
+            "test@example.com".to_string(),
             "testuser".to_string(),
+            "090-1234".to_string(),
+            25,
+        );
+
+        assert!(matches!(result, Err(UserError::InvalidPhone(_))));
+    }
+
+    #[test]
+    fn test_create_user_counts_username_characters() {
+        let mock_repo = create_mock_repository();
+        let service = UserService::new(mock_repo);
+        let result = service.create_user(
+            "test@example.com".to_string(),
+            "山".to_string(),
             "1234567890".to_string(),
             25,
         );
 
-        assert!(result.is_ok());
+        assert!(matches!(result, Err(UserError::InvalidUsername(_))));
     }
 
     #[test]
-    fn test_create_user_invalid_email() {
+    fn test_create_user_rejects_configured_reserved_name() {
         let mock_repo = create_mock_repository();
-        let service = UserService::new(mock_repo);
+        let config = UserServiceConfig {
+            reserved_usernames: vec!["operator".to_string()],
+            ..UserServiceConfig::default()
+        };
+        let service = UserService::with_config(mock_repo, config);
         let result = service.create_user(
-            "invalid-email".to_string(),
-            "testuser".to_string(),
+            "test@example.com".to_string(),
+            "Operator".to_string(),
             "1234567890".to_string(),
             25,
         );
 
-        assert!(matches!(result, Err(UserError::InvalidEmail(_))));
+        assert!(matches!(result, Err(UserError::InvalidUsername(_))));
     }
 
     #[test]
-    fn test_update_user_not_found() {
+    fn test_search_users_ranks_results() {
+        let mut mock_repo = create_mock_repository();
+        mock_repo.expect_find_all().return_once(|| {
+            let mut partial = legacy_user("b@example.com");
+            partial.username = "やまたなか".to_string();
+            let mut exact = legacy_user("a@example.com");
+            exact.username = "タナカ".to_string();
+            let mut other = legacy_user("c@example.com");
+            other.username = "suzuki".to_string();
+            Ok(vec![partial, exact, other])
+        });
+
+        let service = UserService::new(mock_repo);
+        let results = service.search_users("ﾀﾅｶ").unwrap();
+
+        let emails: Vec<&str> = results.iter().map(|r| r.user.email.as_str()).collect();
+        assert_eq!(emails, vec!["a@example.com", "b@example.com"]);
+    }
+
+    #[test]
+    fn test_find_probable_duplicates_normalizes_phone() {
+        let mut mock_repo = create_mock_repository();
+        mock_repo.expect_find_all().return_once(|| {
+            let mut first = legacy_user("taro@example.com");
+            first.username = "Tanaka Taro".to_string();
+            first.phone = "+819012345678".to_string();
+            let mut second = legacy_user("t.tanaka@example.org");
+            second.username = "tanaka taro".to_string();
+            second.phone = "090-1234-5678".to_string();
+            Ok(vec![first, second, legacy_user("other@example.com")])
+        });
+
+        let service = UserService::new(mock_repo);
+        let clusters = service.find_probable_duplicates(0.6).unwrap();
+
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].users.len(), 2);
+        assert!(clusters[0].pairs[0].same_phone);
+    }
+
+    #[test]
+    fn test_merge_users_writes_once_and_resolves_alias() {
+        let mut mock_repo = create_mock_repository();
+        mock_repo.expect_find_by_email().returning(|email| {
+            Ok(match email {
+                "keep@example.com" => Some(legacy_user("keep@example.com")),
+                "drop@example.com" => Some(legacy_user("drop@example.com")),
+                _ => None,
+            })
+        });
+        mock_repo
+            .expect_replace()
+            .times(1)
+            .withf(|user, removed| {
+                user.email == "keep@example.com"
+                    && user.aliases == vec!["drop@example.com".to_string()]
+                    && removed == "drop@example.com"
+            })
+            .return_once(|_, _| Ok(()));
+
+        let service = UserService::new(mock_repo);
+        let merged = service
+            .merge_users(
+                "keep@example.com",
+                "Drop@Example.com",
+                &MergeOptions::default(),
+                &mut |_, _, _| MergeChoice::Keep,
+            )
+            .unwrap();
+
+        assert_eq!(merged.aliases, vec!["drop@example.com"]);
+    }
+
+    #[test]
+    fn test_delete_user_rejects_alias() {
+        let mut mock_repo = create_mock_repository();
+        mock_repo.expect_find_by_email().returning(|email| {
+            Ok((email == "keep@example.com").then(|| {
+                let mut keeper = legacy_user("keep@example.com");
+                keeper.aliases = vec!["drop@example.com".to_string()];
+                keeper
+            }))
+        });
+        mock_repo.expect_find_all().returning(|| {
+            let mut keeper = legacy_user("keep@example.com");
+            keeper.aliases = vec!["drop@example.com".to_string()];
+            Ok(vec![keeper])
+        });
+        mock_repo.expect_save().times(1).returning(|_| Ok(()));
+
+        let service = UserService::new(mock_repo);
+        assert!(matches!(
+            service.delete_user("Drop@Example.com"),
+            Err(UserError::InvalidOperation(_))
+        ));
+        assert!(service.delete_user("Keep@Example.com").is_ok());
+    }
+
+    #[test]
+    fn test_get_user_by_alias() {
         let mut mock_repo = create_mock_repository();
         mock_repo.expect_find_by_email().return_once(|_| Ok(None));
+        mock_repo.expect_find_all().return_once(|| {
+            let mut keeper = legacy_user("keep@example.com");
+            keeper.aliases = vec!["drop@example.com".to_string()];
+            Ok(vec![legacy_user("other@example.com"), keeper])
+        });
+
+        let service = UserService::new(mock_repo);
+        let user = service.get_user("drop@example.com").unwrap();
+
+        assert_eq!(user.email, "keep@example.com");
+    }
+
+    fn trashed_user(email: &str, days_ago: i64) -> User {
+        User {
+            deleted_at: Some(Utc::now() - TimeDelta::days(days_ago)),
+            ..legacy_user(email)
+        }
+    }
+
+    #[test]
+    fn test_trashed_user_is_hidden_and_blocks_create() {
+        let mut mock_repo = create_mock_repository();
+        mock_repo
+            .expect_find_by_email()
+            .returning(|_| Ok(Some(trashed_user("test@example.com", 1))));
+        mock_repo
+            .expect_find_all()
+            .returning(|| Ok(vec![trashed_user("test@example.com", 1)]));
+
+        let service = UserService::new(mock_repo);
+
+        assert!(matches!(
+            service.get_user("test@example.com"),
+            Err(UserError::UserNotFound(_))
+        ));
+        assert!(service.list_users().unwrap().is_empty());
+        assert_eq!(service.list_trashed_users().unwrap().len(), 1);
+        assert!(matches!(
+            service.create_user(
+                "test@example.com".to_string(),
+                "testuser".to_string(),
+                "1234567890".to_string(),
+                25,
+            ),
+            Err(UserError::UserInTrash(_))
+        ));
+    }
+
+    #[test]
+    fn test_restore_user() {
+        let mut mock_repo = create_mock_repository();
+        mock_repo
+            .expect_find_by_email()
+            .return_once(|_| Ok(Some(trashed_user("test@example.com", 1))));
+        mock_repo
+            .expect_save()
+            .withf(|user| user.deleted_at.is_none())
+            .return_once(|_| Ok(()));
+
+        let service = UserService::new(mock_repo);
+        let user = service.restore_user("test@example.com").unwrap();
+
+        assert_eq!(user.deleted_at, None);
+    }
+
+    #[test]
+    fn test_purge_trash_older_than() {
+        let mut mock_repo = create_mock_repository();
+        mock_repo.expect_find_all().return_once(|| {
+            Ok(vec![
+                trashed_user("old@example.com", 40),
+                trashed_user("recent@example.com", 5),
+                legacy_user("active@example.com"),
+            ])
+        });
+        mock_repo
+            .expect_apply_batch()
+            .times(1)
+            .withf(|operations| {
+                operations == [BatchOperation::Delete("old@example.com".to_string())]
+            })
+            .return_once(|_| Ok(()));
 
         let service = UserService::new(mock_repo);
+        let purged = service.purge_trash(TimeDelta::days(30)).unwrap();
+
+        assert_eq!(purged.len(), 1);
+        assert_eq!(purged[0].email, "old@example.com");
+    }
+
+    fn history_service(dir: &tempfile::TempDir) -> UserService<UserRepositoryImpl> {
+        let repository =
+            UserRepositoryImpl::with_file_path(dir.path().join("userdata.json").to_str().unwrap());
+        let history = HistoryRepositoryImpl::with_file_path(
+            dir.path().join("userdata.history.jsonl").to_str().unwrap(),
+        );
+        let config = UserServiceConfig {
+            actor: "tester".to_string(),
+            ..UserServiceConfig::default()
+        };
+        UserService::with_config(repository, config).with_history(history)
+    }
+
+    #[test]
+    fn test_failed_history_append_rolls_back_the_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = history_service(&dir);
+        service
+            .create_user(
+                "test@example.com".to_string(),
+                "testuser".to_string(),
+                "09012345678".to_string(),
+                25,
+            )
+            .unwrap();
+        // 変更履歴のファイルを読み書きできない状態にする
+        let history_file = dir.path().join("userdata.history.jsonl");
+        std::fs::remove_file(&history_file).unwrap();
+        std::fs::create_dir(&history_file).unwrap();
+
+        assert!(
+            service
+                .update_user(
+                    "test@example.com".to_string(),
+                    "newname".to_string(),
+                    "09012345678".to_string(),
+                    30,
+                )
+                .is_err()
+        );
+        let user = service.get_user("test@example.com").unwrap();
+        assert_eq!((user.username.as_str(), user.age), ("testuser", 25));
+
+        assert!(
+            service
+                .create_user(
+                    "other@example.com".to_string(),
+                    "otheruser".to_string(),
+                    "08012345678".to_string(),
+                    30,
+                )
+                .is_err()
+        );
+        assert_eq!(service.list_users().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_rolled_back_write_leaves_no_success_records() {
+        let dir = tempfile::tempdir().unwrap();
+        history_service(&dir)
+            .create_user(
+                "test@example.com".to_string(),
+                "testuser".to_string(),
+                "09012345678".to_string(),
+                25,
+            )
+            .unwrap();
+        let history_file = dir.path().join("userdata.history.jsonl");
+        std::fs::remove_file(&history_file).unwrap();
+        std::fs::create_dir(&history_file).unwrap();
+        // 変更履歴の記録に失敗した変更は、監査ログに失敗としてだけ記録される
+        let mut mock_audit = MockAuditRepository::new();
+        mock_audit
+            .expect_append()
+            .times(1)
+            .withf(|entry| matches!(entry.outcome, AuditOutcome::Failure(_)))
+            .return_once(|_| Ok(()));
+        let service = history_service(&dir).with_audit(mock_audit);
+
+        let result = service.update_user(
+            "test@example.com".to_string(),
+            "newname".to_string(),
+            "09012345678".to_string(),
+            30,
+        );
+        assert!(result.is_err());
+        assert_eq!(service.get_user("test@example.com").unwrap().age, 25);
+    }
+
+    #[test]
+    fn test_failed_audit_append_reverts_the_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let audit_file = dir.path().join("userdata.audit.jsonl");
+        let service = history_service(&dir).with_audit(AuditRepositoryImpl::with_file_path(
+            audit_file.to_str().unwrap(),
+        ));
+        service
+            .create_user(
+                "test@example.com".to_string(),
+                "testuser".to_string(),
+                "09012345678".to_string(),
+                25,
+            )
+            .unwrap();
+        // 監査ログのファイルを読み書きできない状態にする
+        std::fs::remove_file(&audit_file).unwrap();
+        std::fs::create_dir(&audit_file).unwrap();
+
         let result = service.update_user(
             "test@example.com".to_string(),
+            "newname".to_string(),
+            "09012345678".to_string(),
+            30,
+        );
+        assert!(result.is_err());
+        assert_eq!(service.get_user("test@example.com").unwrap().age, 25);
+
+        // 追記したリビジョンは、変更前の状態に戻すリビジョンで打ち消される
+        let history = service.user_history("test@example.com").unwrap();
+        let operations: Vec<RevisionOperation> = history.iter().map(|r| r.operation).collect();
+        assert_eq!(
+            operations,
+            vec![
+                RevisionOperation::Create,
+                RevisionOperation::Update,
+                RevisionOperation::Revert
+            ]
+        );
+        assert_eq!(history[2].user.as_ref().unwrap().age, 25);
+    }
+
+    #[test]
+    fn test_history_records_revisions_and_time_travel() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = history_service(&dir);
+        service
+            .create_user(
+                "test@example.com".to_string(),
+                "testuser".to_string(),
+                "09012345678".to_string(),
+                25,
+            )
+            .unwrap();
+        let before_update = Utc::now();
+        service
+            .update_user(
+                "Test@Example.com".to_string(),
+                "testuser".to_string(),
+                "08012345678".to_string(),
+                26,
+            )
+            .unwrap();
+        service.delete_user("test@example.com").unwrap();
+
+        let history = service.user_history("test@example.com").unwrap();
+        let operations: Vec<RevisionOperation> = history.iter().map(|r| r.operation).collect();
+        assert_eq!(
+            operations,
+            vec![
+                RevisionOperation::Create,
+                RevisionOperation::Update,
+                RevisionOperation::Delete
+            ]
+        );
+        assert!(history.iter().all(|r| r.actor == "tester"));
+
+        let past = service
+            .get_user_at("test@example.com", before_update)
+            .unwrap();
+        assert_eq!(past.phone, "+819012345678");
+        assert!(matches!(
+            service.get_user_at("test@example.com", Utc::now()),
+            Err(UserError::UserNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_revert_user_records_new_revision() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = history_service(&dir);
+        service
+            .create_user(
+                "test@example.com".to_string(),
+                "testuser".to_string(),
+                "09012345678".to_string(),
+                25,
+            )
+            .unwrap();
+        service
+            .update_user(
+                "test@example.com".to_string(),
+                "newuser".to_string(),
+                "08012345678".to_string(),
+                30,
+            )
+            .unwrap();
+
+        let reverted = service.revert_user("test@example.com", 1).unwrap();
+        assert_eq!(reverted.username, "testuser");
+        assert_eq!(reverted.age, 25);
+
+        let history = service.user_history("test@example.com").unwrap();
+        assert_eq!(history.last().unwrap().operation, RevisionOperation::Revert);
+        assert_eq!(history.last().unwrap().revision, 3);
+        assert!(matches!(
+            service.revert_user("test@example.com", 9),
+            Err(UserError::UserNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_history_records_baseline_for_existing_user() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = history_service(&dir);
+        service
+            .repository
+            .save(&legacy_user("Legacy@Example.com"))
+            .unwrap();
+
+        service.delete_user("legacy@example.com").unwrap();
+
+        let history = service.user_history("legacy@example.com").unwrap();
+        assert_eq!(history[0].operation, RevisionOperation::Baseline);
+        assert_eq!(history[0].user.as_ref().unwrap().deleted_at, None);
+        assert_eq!(history[1].operation, RevisionOperation::Delete);
+    }
+
+    #[test]
+    fn test_history_requires_history_repository() {
+        let service = UserService::new(create_mock_repository());
+        assert!(matches!(
+            service.user_history("test@example.com"),
+            Err(UserError::InvalidOperation(_))
+        ));
+    }
+
+    #[test]
+    fn test_audit_records_success_with_before_and_after() {
+        let mut mock_repo = create_mock_repository();
+        mock_repo
+            .expect_find_by_email()
+            .return_once(|_| Ok(Some(legacy_user("test@example.com"))));
+        mock_repo.expect_save().return_once(|_| Ok(()));
+        let mut mock_audit = MockAuditRepository::new();
+        mock_audit
+            .expect_append()
+            .times(1)
+            .withf(|entry| {
+                entry.operation == RevisionOperation::Delete
+                    && entry.actor == "alice"
+                    && entry.outcome == AuditOutcome::Success
+                    && entry
+                        .before
+                        .as_ref()
+                        .is_some_and(|u| u.deleted_at.is_none())
+                    && entry.after.as_ref().is_some_and(|u| u.deleted_at.is_some())
+            })
+            .return_once(|_| Ok(()));
+
+        let service = UserService::new(mock_repo)
+            .with_audit(mock_audit)
+            .with_actor("alice");
+        service.delete_user("test@example.com").unwrap();
+    }
+
+    #[test]
+    fn test_audit_records_failed_attempt() {
+        let mut mock_repo = create_mock_repository();
+        mock_repo.expect_find_by_email().return_once(|_| Ok(None));
+        mock_repo.expect_find_all().return_once(|| Ok(vec![]));
+        let mut mock_audit = MockAuditRepository::new();
+        mock_audit
+            .expect_append()
+            .times(1)
+            .withf(|entry| {
+                entry.operation == RevisionOperation::Update
+                    && entry.email.as_deref() == Some("Nobody@Example.com")
+                    && matches!(&entry.outcome, AuditOutcome::Failure(e) if e.contains("UserNotFound"))
+            })
+            .return_once(|_| Ok(()));
+
+        let service = UserService::new(mock_repo).with_audit(mock_audit);
+        let result = service.update_user(
+            "Nobody@Example.com".to_string(),
             "testuser".to_string(),
-            "1234567890".to_string(),
+            "09012345678".to_string(),
             25,
         );
-
         assert!(matches!(result, Err(UserError::UserNotFound(_))));
     }
 
     #[test]
-    fn test_delete_user_success() {
-        let mut mock_repo = create_mock_repository();
-        mock_repo.expect_delete().return_once(|_| Ok(true));
+    fn test_audit_log_filters_entries() {
+        let entry = |email: &str, actor: &str, days_ago: i64| AuditEntry {
+            timestamp: Utc::now() - TimeDelta::days(days_ago),
+            actor: actor.to_string(),
+            operation: RevisionOperation::Create,
+            email: Some(email.to_string()),
+            before: None,
+            after: None,
+            details: None,
+            outcome: AuditOutcome::Success,
+            prev_hash: None,
+            hmac: None,
+        };
+        let entries = vec![
+            entry("a@example.com", "alice", 10),
+            entry("A@Example.com", "bob", 5),
+            entry("b@example.com", "alice", 1),
+        ];
+        let mut mock_audit = MockAuditRepository::new();
+        mock_audit
+            .expect_find_all()
+            .returning(move || Ok(entries.clone()));
+        let service = UserService::new(create_mock_repository()).with_audit(mock_audit);
 
-        let service = UserService::new(mock_repo);
-        let result = service.delete_user("test@example.com");
+        let by_user = AuditQuery {
+            email: Some("a@example.com".to_string()),
+            ..AuditQuery::default()
+        };
+        assert_eq!(service.audit_log(&by_user).unwrap().len(), 2);
 
-        assert!(result.is_ok());
+        let by_actor_and_time = AuditQuery {
+            actor: Some("alice".to_string()),
+            since: Some(Utc::now() - TimeDelta::days(3)),
+            ..AuditQuery::default()
+        };
+        let found = service.audit_log(&by_actor_and_time).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].email.as_deref(), Some("b@example.com"));
+    }
+
+    fn journal_service(dir: &tempfile::TempDir) -> UserService<UserRepositoryImpl> {
+        let journal = JournalRepositoryImpl::with_file_path(
+            dir.path().join("userdata.journal.json").to_str().unwrap(),
+        );
+        history_service(dir).with_journal(journal)
+    }
+
+    #[test]
+    fn test_undo_and_redo_update() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = journal_service(&dir);
+        service
+            .create_user(
+                "test@example.com".to_string(),
+                "testuser".to_string(),
+                "09012345678".to_string(),
+                25,
+            )
+            .unwrap();
+        service
+            .update_user(
+                "test@example.com".to_string(),
+                "newuser".to_string(),
+                "08012345678".to_string(),
+                30,
+            )
+            .unwrap();
+
+        let undone = service.undo(1).unwrap();
+        assert_eq!(undone[0].operation, RevisionOperation::Update);
+        assert_eq!(service.get_user("test@example.com").unwrap().age, 25);
+
+        let redone = service.redo(1).unwrap();
+        assert_eq!(redone[0].operation, RevisionOperation::Update);
+        assert_eq!(service.get_user("test@example.com").unwrap().age, 30);
+
+        assert_eq!(service.undo(5).unwrap().len(), 2);
+        assert!(matches!(
+            service.get_user("test@example.com"),
+            Err(UserError::UserNotFound(_))
+        ));
+        assert!(matches!(
+            service.undo(1),
+            Err(UserError::InvalidOperation(_))
+        ));
+
+        let history = service.user_history("test@example.com").unwrap();
+        assert_eq!(history.last().unwrap().operation, RevisionOperation::Undo);
+    }
+
+    #[test]
+    fn test_undo_refuses_when_record_changed_since() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = journal_service(&dir);
+        service
+            .create_user(
+                "test@example.com".to_string(),
+                "testuser".to_string(),
+                "09012345678".to_string(),
+                25,
+            )
+            .unwrap();
+        service.delete_user("test@example.com").unwrap();
+        // 操作ジャーナルを使用しない別の利用者による変更
+        history_service(&dir)
+            .restore_user("test@example.com")
+            .unwrap();
+
+        let result = service.undo(1);
+        assert!(
+            matches!(&result, Err(UserError::InvalidOperation(message)) if message.contains("changed since"))
+        );
+        assert!(service.get_user("test@example.com").is_ok());
+    }
+
+    #[test]
+    fn test_partial_undo_keeps_data_and_journal_in_sync() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = journal_service(&dir);
+        for email in ["a@example.com", "b@example.com"] {
+            service
+                .create_user(
+                    email.to_string(),
+                    "testuser".to_string(),
+                    "09012345678".to_string(),
+                    25,
+                )
+                .unwrap();
+        }
+        // 操作ジャーナルを使用しない別の利用者による変更
+        history_service(&dir)
+            .update_user(
+                "a@example.com".to_string(),
+                "newuser".to_string(),
+                "09012345678".to_string(),
+                30,
+            )
+            .unwrap();
+
+        let result = service.undo(2);
+        assert!(
+            matches!(&result, Err(UserError::InvalidOperation(message)) if message.starts_with("Stopped after 1 operation(s)"))
+        );
+        // 1件目の取り消しは書き込みとジャーナルの両方に残る
+        assert!(matches!(
+            service.get_user("b@example.com"),
+            Err(UserError::UserNotFound(_))
+        ));
+        assert_eq!(service.get_user("a@example.com").unwrap().age, 30);
+
+        assert_eq!(service.redo(1).unwrap().len(), 1);
+        assert!(service.get_user("b@example.com").is_ok());
+        assert_eq!(service.undo(1).unwrap().len(), 1);
+        assert!(matches!(
+            service.get_user("b@example.com"),
+            Err(UserError::UserNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_new_operation_clears_redo() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = journal_service(&dir);
+        service
+            .create_user(
+                "a@example.com".to_string(),
+                "testuser".to_string(),
+                "09012345678".to_string(),
+                25,
+            )
+            .unwrap();
+        service.undo(1).unwrap();
+        service
+            .create_user(
+                "b@example.com".to_string(),
+                "testuser".to_string(),
+                "09012345678".to_string(),
+                25,
+            )
+            .unwrap();
+
+        assert!(matches!(
+            service.redo(1),
+            Err(UserError::InvalidOperation(_))
+        ));
+    }
+
+    #[test]
+    fn test_preview_validates_without_writing() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = journal_service(&dir);
+        service
+            .create_user(
+                "test@example.com".to_string(),
+                "testuser".to_string(),
+                "09012345678".to_string(),
+                25,
+            )
+            .unwrap();
+
+        let (_, changes) = service
+            .preview(|service| {
+                service.update_user(
+                    "TEST@example.com".to_string(),
+                    "newuser".to_string(),
+                    "09012345678".to_string(),
+                    30,
+                )
+            })
+            .unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].before.as_ref().unwrap().age, 25);
+        assert_eq!(changes[0].after.as_ref().unwrap().age, 30);
+        assert_eq!(service.get_user("test@example.com").unwrap().age, 25);
+        assert_eq!(service.user_history("test@example.com").unwrap().len(), 1);
+
+        let result = service.preview(|service| {
+            service.create_user(
+                "test@example.com".to_string(),
+                "other".to_string(),
+                "09012345678".to_string(),
+                25,
+            )
+        });
+        assert!(matches!(result, Err(UserError::UserAlreadyExists(_))));
+        assert_eq!(
+            service.undo(1).unwrap()[0].operation,
+            RevisionOperation::Create
+        );
+    }
+
+    #[test]
+    fn test_update_and_delete_check_expected_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = journal_service(&dir);
+        let created = service
+            .create_user(
+                "test@example.com".to_string(),
+                "testuser".to_string(),
+                "09012345678".to_string(),
+                25,
+            )
+            .unwrap();
+        assert_eq!(created.version, 1);
+
+        let updated = service
+            .update_user_if_version(
+                "test@example.com".to_string(),
+                "newuser".to_string(),
+                "09012345678".to_string(),
+                30,
+                1,
+            )
+            .unwrap();
+        assert_eq!(updated.version, 2);
+
+        // 別の操作者がバージョン1を読んだ後に更新しようとした場合
+        let result = service.update_user_if_version(
+            "test@example.com".to_string(),
+            "stale".to_string(),
+            "09012345678".to_string(),
+            40,
+            1,
+        );
+        assert!(matches!(result, Err(UserError::Conflict(_))));
+        assert!(matches!(
+            service.delete_user_if_version("test@example.com", 1),
+            Err(UserError::Conflict(_))
+        ));
+        assert_eq!(
+            service.get_user("test@example.com").unwrap().username,
+            "newuser"
+        );
+
+        service
+            .delete_user_if_version("test@example.com", 2)
+            .unwrap();
+        // 取り消してもバージョンは戻らない
+        service.undo(1).unwrap();
+        assert_eq!(service.get_user("test@example.com").unwrap().version, 4);
+        assert!(matches!(
+            service.delete_user_if_version("test@example.com", 2),
+            Err(UserError::Conflict(_))
+        ));
+    }
+
+    #[test]
+    fn test_legacy_phone_from_pre_normalization_file_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = history_service(&dir);
+        // 電話番号の正規化を導入する前の形式のファイル
+        std::fs::write(
+            dir.path().join("userdata.json"),
+            r#"{"test@example.com":{"email":"test@example.com","username":"testuser","phone":"1234567890","age":25}}"#,
+        )
+        .unwrap();
+
+        let report = service.check_integrity(false).unwrap();
+        assert_eq!(report.checked, 1);
+        assert!(report.issues.is_empty());
+
+        let updated = service
+            .update_user(
+                "test@example.com".to_string(),
+                "newuser".to_string(),
+                "1234567890".to_string(),
+                30,
+            )
+            .unwrap();
+        assert_eq!(updated.phone, "1234567890");
+        let updated = service
+            .update_user(
+                "test@example.com".to_string(),
+                "newuser".to_string(),
+                "090-1234-5678".to_string(),
+                30,
+            )
+            .unwrap();
+        assert_eq!(updated.phone, "+819012345678");
+        // 導入前の規則にも従わない値は受け付けない
+        assert!(matches!(
+            service.update_user(
+                "test@example.com".to_string(),
+                "newuser".to_string(),
+                "123456789".to_string(),
+                30,
+            ),
+            Err(UserError::InvalidPhone(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_integrity_reports_and_fixes() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = history_service(&dir);
+        let mut users = std::collections::HashMap::new();
+        users.insert(
+            "a@example.com".to_string(),
+            User {
+                phone: "090-1234-5678".to_string(),
+                ..legacy_user("a@Example.com")
+            },
+        );
+        users.insert(
+            "b@example.com".to_string(),
+            User {
+                phone: "+819012345678".to_string(),
+                age: 200,
+                ..legacy_user("b@example.com")
+            },
+        );
+        std::fs::write(
+            dir.path().join("userdata.json"),
+            serde_json::to_string(&users).unwrap(),
+        )
+        .unwrap();
+
+        let report = service.check_integrity(false).unwrap();
+        assert_eq!(report.checked, 2);
+        let problems: Vec<(&str, String)> = report
+            .issues
+            .iter()
+            .map(|issue| (issue.key.as_str(), issue.problem.to_string()))
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                (
+                    "a@example.com",
+                    "key does not match email a@Example.com".to_string()
+                ),
+                (
+                    "a@example.com",
+                    "email a@Example.com is not normalized (a@example.com)".to_string()
+                ),
+                (
+                    "a@example.com",
+                    "phone 090-1234-5678 is not normalized (+819012345678)".to_string()
+                ),
+                (
+                    "a@example.com",
+                    "same phone +819012345678 as b@example.com".to_string()
+                ),
+                (
+                    "b@example.com",
+                    "invalid age: Age must be between 0 and 150".to_string()
+                ),
+                (
+                    "b@example.com",
+                    "same phone +819012345678 as a@example.com".to_string()
+                ),
+            ]
+        );
+        assert_eq!(report.remaining(), 6);
+        assert_eq!(service.get_user("a@example.com").unwrap().version, 0);
+
+        let report = service.check_integrity(true).unwrap();
+        assert_eq!(report.remaining(), 3);
+        let fixed = service.get_user("a@example.com").unwrap();
+        assert_eq!(fixed.email, "a@example.com");
+        assert_eq!(fixed.phone, "+819012345678");
+        assert_eq!(fixed.version, 1);
+        assert_eq!(
+            service.user_history("a@example.com").unwrap()[1].operation,
+            RevisionOperation::Fix
+        );
+        assert_eq!(service.check_integrity(false).unwrap().issues.len(), 3);
+    }
+
+    #[test]
+    fn test_check_integrity_keeps_key_when_normalized_email_is_taken() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = history_service(&dir);
+        let repository =
+            UserRepositoryImpl::with_file_path(dir.path().join("userdata.json").to_str().unwrap());
+        repository.save(&legacy_user("test@Example.com")).unwrap();
+        repository.save(&legacy_user("test@example.com")).unwrap();
+
+        let report = service.check_integrity(true).unwrap();
+        let email_issues: Vec<&IntegrityIssue> = report
+            .issues
+            .iter()
+            .filter(|issue| issue.key == "test@Example.com")
+            .collect();
+        assert!(matches!(
+            email_issues[0].problem,
+            IntegrityProblem::NotNormalized { field: "email", .. }
+        ));
+        assert!(!email_issues[0].fixed);
+        assert!(
+            email_issues
+                .iter()
+                .any(|issue| matches!(issue.problem, IntegrityProblem::DuplicateEmail { .. }))
+        );
+        assert_eq!(repository.find_all().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_listing_reads_encrypted_fields_through_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("userdata.json");
+        let repository = UserRepositoryImpl::with_file_path(path.to_str().unwrap());
+        let cipher = FieldCipher::new(
+            [4; 32],
+            vec![EncryptedField::Phone, EncryptedField::Username],
+        );
+        let service = UserService::new(&repository).with_field_encryption(cipher);
+        for (email, username) in [("a@example.com", "tanaka"), ("b@example.com", "tanaka")] {
+            service
+                .create_user(
+                    email.to_string(),
+                    username.to_string(),
+                    "09012345678".to_string(),
+                    25,
+                )
+                .unwrap();
+        }
+        service.delete_user("b@example.com").unwrap();
+        service
+            .create_user(
+                "c@example.com".to_string(),
+                "tanaka".to_string(),
+                "09012345678".to_string(),
+                30,
+            )
+            .unwrap();
+
+        // 鍵がある場合は復号した値で一覧・検索・重複候補の検出を行う
+        let users = service.list_users().unwrap();
+        assert_eq!(users.len(), 2);
+        assert!(users.iter().all(|user| user.phone == "+819012345678"));
+        assert_eq!(service.list_trashed_users().unwrap()[0].username, "tanaka");
+        assert_eq!(service.search_users("tanaka").unwrap().len(), 2);
+        assert_eq!(service.find_probable_duplicates(0.5).unwrap().len(), 1);
+
+        // 鍵がない場合は、暗号文を返さずにエラーにする
+        let without_key = UserService::new(&repository);
+        assert!(without_key.list_users().is_err());
+        assert!(without_key.list_trashed_users().is_err());
+        assert!(without_key.search_users("tanaka").is_err());
+        assert!(without_key.find_probable_duplicates(0.5).is_err());
+
+        // 伏せ字の場合は、暗号化されたフィールドを伏せ字にして返す
+        let redacted = without_key.redacted();
+        let users = redacted.list_users().unwrap();
+        assert!(users.iter().all(|user| user.phone == REDACTED_VALUE));
+        assert!(users.iter().all(|user| user.username == REDACTED_VALUE));
+        assert_eq!(
+            redacted.list_trashed_users().unwrap()[0].phone,
+            REDACTED_VALUE
+        );
+        assert!(redacted.search_users("tanaka").unwrap().is_empty());
+        assert_eq!(redacted.search_users("c@example").unwrap().len(), 1);
+        assert!(matches!(
+            redacted.find_probable_duplicates(0.5),
+            Err(UserError::InvalidOperation(_))
+        ));
+    }
+
+    #[test]
+    fn test_sidecars_store_encrypted_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let audit = AuditRepositoryImpl::with_file_path(
+            dir.path().join("userdata.audit.jsonl").to_str().unwrap(),
+        );
+        let service = journal_service(&dir)
+            .with_audit(audit)
+            .with_field_encryption(FieldCipher::new([6; 32], vec![EncryptedField::Phone]));
+        service
+            .create_user(
+                "test@example.com".to_string(),
+                "testuser".to_string(),
+                "09012345678".to_string(),
+                25,
+            )
+            .unwrap();
+        service
+            .update_user(
+                "test@example.com".to_string(),
+                "testuser".to_string(),
+                "08012345678".to_string(),
+                26,
+            )
+            .unwrap();
+
+        for suffix in ["history.jsonl", "audit.jsonl", "journal.json"] {
+            let content =
+                std::fs::read_to_string(dir.path().join(format!("userdata.{}", suffix))).unwrap();
+            assert!(!content.contains("+819012345678"), "{}", suffix);
+            assert!(!content.contains("+818012345678"), "{}", suffix);
+        }
+
+        // 読み込むときは復号する
+        let history = service.user_history("test@example.com").unwrap();
+        assert_eq!(history[0].user.as_ref().unwrap().phone, "+819012345678");
+        let audit = service.audit_log(&AuditQuery::default()).unwrap();
+        assert_eq!(audit[1].after.as_ref().unwrap().phone, "+818012345678");
+        let export = service.export_subject("test@example.com").unwrap();
+        assert_eq!(
+            export.journal[0].changes[0].after.as_ref().unwrap().phone,
+            "+819012345678"
+        );
+        service.undo(1).unwrap();
+        assert_eq!(
+            service.get_user("test@example.com").unwrap().phone,
+            "+819012345678"
+        );
+        service.redo(1).unwrap();
+        assert_eq!(
+            service.get_user("test@example.com").unwrap().phone,
+            "+818012345678"
+        );
+    }
+
+    #[test]
+    fn test_field_encryption_is_transparent() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("userdata.json");
+        let repository = UserRepositoryImpl::with_file_path(path.to_str().unwrap());
+        repository.save(&legacy_user("old@example.com")).unwrap();
+        let cipher = FieldCipher::new([3; 32], vec![EncryptedField::Phone]);
+        let service = UserService::new(&repository).with_field_encryption(cipher);
+
+        let user = service
+            .create_user(
+                "test@example.com".to_string(),
+                "testuser".to_string(),
+                "09012345678".to_string(),
+                25,
+            )
+            .unwrap();
+        assert_eq!(service.get_user("test@example.com").unwrap(), user);
+        assert!(
+            !std::fs::read_to_string(&path)
+                .unwrap()
+                .contains("+819012345678")
+        );
+        let without_key = UserService::new(&repository);
+        assert!(without_key.get_user("test@example.com").is_err());
+        let redacted = without_key.redacted();
+        assert_eq!(
+            redacted.get_user("test@example.com").unwrap().phone,
+            REDACTED_VALUE
+        );
+        assert!(
+            redacted
+                .update_user(
+                    "test@example.com".to_string(),
+                    "renamed".to_string(),
+                    "09012345678".to_string(),
+                    26,
+                )
+                .is_err()
+        );
+
+        assert_eq!(service.rewrite_fields().unwrap(), 2);
+        assert!(without_key.get_user("old@example.com").is_err());
+        assert!(matches!(
+            without_key.rewrite_fields(),
+            Err(UserError::InvalidOperation(_))
+        ));
+    }
+
+    #[test]
+    fn test_export_and_erase_subject() {
+        let dir = tempfile::tempdir().unwrap();
+        let audit = AuditRepositoryImpl::with_file_path(
+            dir.path().join("userdata.audit.jsonl").to_str().unwrap(),
+        )
+        .with_hmac_key(Some(b"secret".to_vec()));
+        let service = journal_service(&dir).with_audit(audit);
+        for email in ["a@example.com", "b@example.com"] {
+            service
+                .create_user(
+                    email.to_string(),
+                    "testuser".to_string(),
+                    "09012345678".to_string(),
+                    25,
+                )
+                .unwrap();
+        }
+        service
+            .update_user(
+                "a@example.com".to_string(),
+                "renamed".to_string(),
+                "09012345678".to_string(),
+                26,
+            )
+            .unwrap();
+        let _ = service.delete_user_if_version("A@Example.com", 99);
+
+        let export = service.export_subject("a@example.com").unwrap();
+        assert_eq!(export.emails, vec!["a@example.com".to_string()]);
+        assert_eq!(export.user.unwrap().username, "renamed");
+        assert_eq!(export.history.len(), 2);
+        // 失敗した操作も、入力されたままのメールアドレスで照合される
+        assert_eq!(export.audit.len(), 3);
+        assert_eq!(export.journal.len(), 2);
+
+        let receipt = service.erase_subject("a@example.com", b"secret").unwrap();
+        assert!(receipt.record_deleted);
+        assert_eq!(
+            receipt.subject,
+            service.erasure_subject("A@example.com", b"secret").unwrap()
+        );
+        assert_ne!(
+            receipt.subject,
+            service.erasure_subject("a@example.com", b"other").unwrap()
+        );
+        assert_eq!(receipt.history_revisions, 2);
+        assert_eq!(receipt.audit_entries, 3);
+        assert_eq!(receipt.journal_entries, 2);
+        assert_ne!(receipt.audit_head_before, receipt.audit_head_after);
+        assert!(matches!(
+            service.get_user("a@example.com"),
+            Err(UserError::UserNotFound(_))
+        ));
+        assert!(service.verify_audit_log().unwrap().broken.is_none());
+        let export = service.export_subject("a@example.com").unwrap();
+        assert!(export.user.is_none() && export.history.is_empty());
+        assert!(export.audit.is_empty() && export.journal.is_empty());
+        // 他のユーザーのデータは残る
+        assert_eq!(
+            service.export_subject("b@example.com").unwrap().audit.len(),
+            1
+        );
+        assert!(
+            service
+                .erase_subject("a@example.com", b"secret")
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_anonymize_into_preserves_validity_and_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let source =
+            UserRepositoryImpl::with_file_path(dir.path().join("source.json").to_str().unwrap());
+        let user = |email: &str, username: &str, phone: &str| User {
+            email: email.to_string(),
+            username: username.to_string(),
+            phone: phone.to_string(),
+            age: 30,
+            version: 1,
+            ..Default::default()
+        };
+        source
+            .save(&User {
+                aliases: vec!["old@example.co.jp".to_string()],
+                ..user("taro@example.co.jp", "山田 太郎", "+819012345678")
+            })
+            .unwrap();
+        source
+            .save(&user("hanako@example.co.jp", "はなこ", "+819012345678"))
+            .unwrap();
+        source
+            .save(&user("Legacy@Example.com", "x", "03-1234-5678"))
+            .unwrap();
+        let service = UserService::new(&source);
+        let anonymize = |name: &str| {
+            let target =
+                UserRepositoryImpl::with_file_path(dir.path().join(name).to_str().unwrap());
+            assert_eq!(
+                service
+                    .anonymize_into(&Anonymizer::new("salt"), &target)
+                    .unwrap(),
+                3
+            );
+            let mut users = target.find_all().unwrap();
+            users.sort_by(|a, b| a.username.cmp(&b.username));
+            users
+        };
+
+        let users = anonymize("a.json");
+        assert_eq!(users, anonymize("b.json"));
+        let originals = source.find_all().unwrap();
+        for user in &users {
+            assert!(
+                originals
+                    .iter()
+                    .all(|original| original.email != user.email)
+            );
+        }
+        let taro = users.iter().find(|user| !user.aliases.is_empty()).unwrap();
+        let domain = |email: &str| email.split_once('@').unwrap().1.to_string();
+        assert!(taro.email.ends_with(".co.jp"));
+        assert!(users.iter().any(
+            |user| user.username != taro.username && domain(&user.email) == domain(&taro.email)
+        ));
+        assert!(domain(&taro.aliases[0]) == domain(&taro.email));
+        assert_eq!(taro.username.chars().nth(2), Some(' '));
+        // 同じ電話番号は同じ仮名になり、重複の傾向が保たれる
+        assert_eq!(users.iter().filter(|u| u.phone == taro.phone).count(), 2);
+        assert!(taro.phone.starts_with("+8190"));
+        let legacy = users
+            .iter()
+            .find(|user| user.phone.starts_with("03-"))
+            .unwrap();
+        assert!(legacy.email.starts_with(char::is_uppercase));
+
+        // 規則に従っていた値は仮名でも規則に従い、元のデータにあった問題だけが残る
+        let anonymized = UserService::new(UserRepositoryImpl::with_file_path(
+            dir.path().join("a.json").to_str().unwrap(),
+        ));
+        let report = anonymized.check_integrity(false).unwrap();
+        assert!(report.issues.iter().all(|issue| {
+            issue.key == legacy.email
+                || matches!(issue.problem, IntegrityProblem::DuplicatePhone { .. })
+        }));
+    }
+
+    #[test]
+    fn test_insert_seeded_users() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = UserService::new(UserRepositoryImpl::with_file_path(
+            dir.path().join("users.json").to_str().unwrap(),
+        ));
+        let users = Seeder::new(42).generate(500);
+        assert_eq!(
+            service.insert_users(&users, "count=500 seed=42").unwrap(),
+            500
+        );
+
+        // 生成したユーザーは全ての規則に従い、正規化後の値のまま保存される
+        let report = service.check_integrity(false).unwrap();
+        assert!(report.issues.is_empty());
+        let stored = service.get_user(&users[0].email).unwrap();
+        assert_eq!(
+            (stored.username, stored.phone, stored.version),
+            (users[0].username.clone(), users[0].phone.clone(), 1)
+        );
+
+        // 既存のユーザーと重複する場合は何も書き込まない
+        let more = Seeder::new(1).generate(10);
+        let mut overlapping = more.clone();
+        overlapping.push(users[0].clone());
+        assert!(matches!(
+            service.insert_users(&overlapping, "count=11"),
+            Err(UserError::UserAlreadyExists(_))
+        ));
+        let invalid = vec![User {
+            age: 200,
+            ..more[0].clone()
+        }];
+        assert!(matches!(
+            service.insert_users(&invalid, "count=1"),
+            Err(UserError::InvalidAge(_))
+        ));
+        assert_eq!(service.list_users().unwrap().len(), 500);
+    }
+
+    #[test]
+    fn test_seed_is_audited_once_and_can_be_undone() {
+        let dir = tempfile::tempdir().unwrap();
+        let audit_file = dir.path().join("userdata.audit.jsonl");
+        let service = journal_service(&dir).with_audit(AuditRepositoryImpl::with_file_path(
+            audit_file.to_str().unwrap(),
+        ));
+        let users = Seeder::new(42).generate(50);
+        assert_eq!(
+            service.insert_users(&users, "count=50 seed=42").unwrap(),
+            50
+        );
+
+        // 監査ログには件数とシードを1件で記録し、ユーザーごとの変更履歴は記録しない
+        let entries = service.audit_log(&AuditQuery::default()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].operation, RevisionOperation::Seed);
+        assert_eq!(entries[0].details.as_deref(), Some("count=50 seed=42"));
+        assert!(matches!(
+            service.user_history(&users[0].email),
+            Err(UserError::UserNotFound(_))
+        ));
+
+        let undone = service.undo(1).unwrap();
+        assert_eq!(undone[0].operation, RevisionOperation::Seed);
+        assert!(service.list_users().unwrap().is_empty());
+        service.redo(1).unwrap();
+        assert_eq!(service.list_users().unwrap().len(), 50);
+
+        let entries = service.audit_log(&AuditQuery::default()).unwrap();
+        let details: Vec<Option<&str>> = entries.iter().map(|e| e.details.as_deref()).collect();
+        assert_eq!(
+            details,
+            vec![
+                Some("count=50 seed=42"),
+                Some("seed of 50 user(s)"),
+                Some("seed of 50 user(s)")
+            ]
+        );
+        assert!(matches!(
+            service.user_history(&users[0].email),
+            Err(UserError::UserNotFound(_))
+        ));
     }
 }
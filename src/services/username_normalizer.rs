@@ -0,0 +1,219 @@
+//! ユーザー名の正規化と検証を行うモジュール
+//!
+//! ユーザー名はUnicode NFKC正規化を行ったうえで、バイト数ではなく
+//! 書記素クラスタ（利用者が1文字として認識する単位）で長さを数えます。
+
+use crate::services::user_service::UserError;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// ユーザー名の最小文字数
+pub const MIN_USERNAME_LENGTH: usize = 3;
+
+/// ユーザー名の最大文字数の既定値
+pub const DEFAULT_MAX_USERNAME_LENGTH: usize = 32;
+
+/// 絵文字をつなぐゼロ幅接合子
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+
+/// 既定の予約語（ユーザー名として使用できない名前）
+pub const DEFAULT_RESERVED_USERNAMES: &[&str] = &[
+    "admin",
+    "administrator",
+    "root",
+    "system",
+    "support",
+    "null",
+];
+
+/// ユーザー名を正規化し、規則に従っているかを検証します。
+///
+/// # 引数
+/// * `username` - 正規化するユーザー名
+/// * `max_length` - 許可する最大文字数（書記素クラスタ数）
+/// * `reserved_names` - 使用を禁止する名前の一覧（大文字・小文字は区別しません）
+///
+/// # 戻り値
+/// * `Ok(String)` - NFKC正規化され、前後の空白が取り除かれたユーザー名
+///
+/// # Errors
+/// 以下の場合に`UserError::InvalidUsername`を返します：
+/// * 制御文字や不可視文字が含まれている場合
+/// * 文字数が3文字未満、または`max_length`を超える場合
+/// * 予約語に該当する場合
+///
+/// # Examples
+/// ```
+/// use rust_learn::services::username_normalizer::normalize_username;
+///
+/// let reserved = vec!["admin".to_string()];
+/// assert_eq!(normalize_username(" ﾀﾅｶ ", 32, &reserved).unwrap(), "タナカ");
+/// assert!(normalize_username("山", 32, &reserved).is_err());
+/// assert!(normalize_username("Admin", 32, &reserved).is_err());
+/// ```
+pub fn normalize_username(
+    username: &str,
+    max_length: usize,
+    reserved_names: &[String],
+) -> Result<String, UserError> {
+    let normalized: String = username.nfkc().collect();
+    let normalized = normalized.trim();
+
+    if normalized.graphemes(true).any(has_invisible) {
+        return Err(UserError::InvalidUsername(
+            "Username must not contain control or invisible characters".to_string(),
+        ));
+    }
+
+    let length = normalized.graphemes(true).count();
+    if length < MIN_USERNAME_LENGTH {
+        return Err(UserError::InvalidUsername(
+            "Username must be at least 3 characters long".to_string(),
+        ));
+    }
+    if length > max_length {
+        return Err(UserError::InvalidUsername(format!(
+            "Username must be at most {} characters long",
+            max_length
+        )));
+    }
+
+    let folded = normalized.to_lowercase();
+    if reserved_names
+        .iter()
+        .any(|name| name.nfkc().collect::<String>().to_lowercase() == folded)
+    {
+        return Err(UserError::InvalidUsername(format!(
+            "Username is reserved: {}",
+            normalized
+        )));
+    }
+
+    Ok(normalized.to_string())
+}
+
+/// 書記素クラスタに制御文字や表示されない書式文字が含まれているかを判定します。
+///
+/// ゼロ幅接合子（U+200D）は、家族や職業の絵文字のように書記素クラスタの内側で
+/// 文字をつないでいる場合に限り許可し、単独やクラスタの先頭・末尾にある場合は拒否します。
+///
+/// # 引数
+/// * `grapheme` - 判定する書記素クラスタ
+///
+/// # 戻り値
+/// * `bool` - 許可されない文字が含まれている場合は`true`
+fn has_invisible(grapheme: &str) -> bool {
+    let count = grapheme.chars().count();
+    grapheme.chars().enumerate().any(|(index, c)| match c {
+        ZERO_WIDTH_JOINER => index == 0 || index + 1 == count,
+        _ => is_invisible(c),
+    })
+}
+
+/// 制御文字、または表示されない書式文字かどうかを判定します。
+///
+/// 絵文字の異体字セレクタ（U+FE00〜U+FE0F）は表示に影響するため許可します。
+/// ゼロ幅接合子は`has_invisible`で位置を確認するため、ここでは不可視文字として扱います。
+///
+/// # 引数
+/// * `c` - 判定する文字
+///
+/// # 戻り値
+/// * `bool` - 制御文字・不可視文字の場合は`true`
+fn is_invisible(c: char) -> bool {
+    c.is_control()
+        || matches!(
+            c,
+            '\u{00AD}'
+                | '\u{034F}'
+                | '\u{061C}'
+                | '\u{115F}'
+                | '\u{1160}'
+                | '\u{17B4}'
+                | '\u{17B5}'
+                | '\u{180B}'..='\u{180F}'
+                | '\u{200B}'..='\u{200F}'
+                | '\u{202A}'..='\u{202E}'
+                | '\u{2060}'..='\u{206F}'
+                | '\u{3164}'
+                | '\u{FEFF}'
+                | '\u{FFA0}'
+                | '\u{FFF0}'..='\u{FFFB}'
+                | '\u{E0000}'..='\u{E0FFF}'
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reserved() -> Vec<String> {
+        DEFAULT_RESERVED_USERNAMES
+            .iter()
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_length_counts_characters_not_bytes() {
+        assert!(normalize_username("山", 32, &reserved()).is_err());
+        assert!(normalize_username("山田", 32, &reserved()).is_err());
+        assert_eq!(
+            normalize_username("山田太", 32, &reserved()).unwrap(),
+            "山田太"
+        );
+        // 結合文字を含む「が」（か + 濁点）は1文字として数える
+        assert!(normalize_username("か\u{3099}き", 32, &reserved()).is_err());
+    }
+
+    #[test]
+    fn test_nfkc_normalization() {
+        assert_eq!(
+            normalize_username("ｔａｎａｋａ", 32, &reserved()).unwrap(),
+            "tanaka"
+        );
+        assert_eq!(
+            normalize_username("\u{3000}ﾀﾅｶ\u{3000}", 32, &reserved()).unwrap(),
+            "タナカ"
+        );
+    }
+
+    #[test]
+    fn test_rejects_invisible_characters() {
+        for username in ["test\u{200B}user", "test\u{0007}user", "\u{202E}testuser"] {
+            assert!(matches!(
+                normalize_username(username, 32, &reserved()),
+                Err(UserError::InvalidUsername(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_zero_width_joiner_inside_emoji() {
+        // 家族・職業の絵文字は1文字として数え、許可する
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        let cook = "\u{1F469}\u{200D}\u{1F373}";
+        let username = format!("{}{}ab", family, cook);
+        assert_eq!(
+            normalize_username(&username, 32, &reserved()).unwrap(),
+            username
+        );
+        assert!(normalize_username(&format!("{}a", family), 32, &reserved()).is_err());
+
+        for username in ["test\u{200D}user", "\u{200D}testuser", "testuser\u{200D}"] {
+            assert!(matches!(
+                normalize_username(username, 32, &reserved()),
+                Err(UserError::InvalidUsername(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_max_length_and_reserved_names() {
+        assert!(normalize_username("abcdef", 5, &reserved()).is_err());
+        assert!(normalize_username("abcde", 5, &reserved()).is_ok());
+        assert!(normalize_username("ROOT", 32, &reserved()).is_err());
+        assert!(normalize_username("ａｄｍｉｎ", 32, &reserved()).is_err());
+        assert!(normalize_username("rooted", 32, &reserved()).is_ok());
+    }
+}